@@ -1,13 +1,24 @@
 use actix_web::web::ServiceConfig;
+use shared::audit_models::AuditEventResponse;
 use shared::livekit_models::{
     CreateRoomRequest, LivekitRoom, RoomOptions, TokenRequest, TokenResponse, VideoGrantsWrapper,
 };
 use shared::response_models::Response;
 use shared::user_models::{
-    LoginRequest, ProjectInfo, ProjectRequest, RefreshTokenRequest, SignUpRequest,
+    AccountDeletionRequest, EmailVerificationConfirmRequest, LoginRequest, LoginResponse,
+    LoginSessionResponse, MfaChallengeResponse, MfaCodeRequest, MfaLoginRequest, MfaStatusResponse,
+    OidcLoginRequest, PasswordChangeRequest, PasswordResetConfirmRequest, PasswordResetRequest,
+    ProfileUpdateRequest, ProjectInfo, ProjectRequest, ProjectSecretsRequest, ProjectUpdateRequest,
+    RecoveryCodesResponse, RefreshTokenRequest, SignUpRequest, TotpEnrollmentResponse, UserProfile,
 };
 
-use shared::project_models::ProjectsSummary;
+use shared::deletion_models::ScheduledDeletionResponse;
+use shared::project_models::{
+    ProjectValidationReport, ProjectsSummary, ValidationCheck, ValidationStatus,
+};
+use shared::recording_models::{
+    RetentionPolicyRequest, RetentionPolicyResponse, SessionStorageUsage, StorageUsageResponse,
+};
 use utoipa::OpenApi;
 use utoipa_rapidoc::RapiDoc;
 use utoipa_redoc::{Redoc, Servable};
@@ -18,18 +29,47 @@ pub fn init_api_doc(config: &mut ServiceConfig) {
     #[openapi(
         paths(
             crate::login_handlers::login,
+            crate::login_handlers::login_mfa,
             crate::login_handlers::logout,
             crate::login_handlers::refresh_login_token,
             crate::login_handlers::signup,
             crate::login_handlers::me,
+            crate::login_handlers::update_profile,
+            crate::login_handlers::delete_account,
+            crate::login_handlers::change_password,
+            crate::login_handlers::request_password_reset,
+            crate::login_handlers::confirm_password_reset,
+            crate::login_handlers::request_email_verification,
+            crate::login_handlers::confirm_email_verification,
+            crate::login_handlers::list_login_sessions,
+            crate::login_handlers::revoke_login_sessions,
+            crate::login_handlers::revoke_login_session,
+            crate::login_handlers::mfa_status,
+            crate::login_handlers::enroll_totp,
+            crate::login_handlers::confirm_totp,
+            crate::login_handlers::disable_totp,
+            crate::login_handlers::regenerate_recovery_codes,
+            crate::login_handlers::jwks,
+            crate::oauth_handlers::login_with_google,
+            crate::oauth_handlers::login_with_oidc,
             crate::project_handlers::create_project,
+            crate::project_handlers::validate_project,
             crate::project_handlers::get_project,
             crate::project_handlers::list_projects,
             crate::project_handlers::delete_project,
+            crate::project_handlers::restore_project,
+            crate::project_handlers::list_deletions,
+            crate::project_handlers::update_project,
+            crate::project_handlers::rotate_project_secrets,
+            crate::project_handlers::list_audit_events,
+            crate::project_handlers::export_audit_events,
+            crate::project_handlers::get_storage_usage,
+            crate::project_handlers::get_retention_policy,
+            crate::project_handlers::set_retention_policy,
             // crate::project_handlers::summarize_projects
         ),
         components(
-            schemas(Response, LoginRequest, RefreshTokenRequest, SignUpRequest, ProjectRequest, ProjectInfo, ProjectsSummary,
+            schemas(Response, LoginRequest, RefreshTokenRequest, SignUpRequest, OidcLoginRequest, UserProfile, ProfileUpdateRequest, PasswordChangeRequest, PasswordResetRequest, PasswordResetConfirmRequest, EmailVerificationConfirmRequest, AccountDeletionRequest, LoginSessionResponse, LoginResponse, MfaChallengeResponse, MfaLoginRequest, MfaCodeRequest, TotpEnrollmentResponse, RecoveryCodesResponse, MfaStatusResponse, ProjectRequest, ProjectUpdateRequest, ProjectSecretsRequest, ProjectInfo, ProjectsSummary, ProjectValidationReport, ValidationCheck, ValidationStatus, AuditEventResponse, RetentionPolicyRequest, RetentionPolicyResponse, StorageUsageResponse, SessionStorageUsage, ScheduledDeletionResponse,
             TokenRequest, TokenResponse, VideoGrantsWrapper, CreateRoomRequest, RoomOptions, LivekitRoom)
         ),
         tags(
@@ -1,18 +1,30 @@
 use actix_web::{web, App, HttpResponse, HttpServer};
 use api::apidoc::init_api_doc;
 use api::login_handlers::init_routes as login_init_routes;
-use api::oauth_handlers::init_github_oauth_routes;
+use api::oauth_handlers::init_oauth_routes;
 use api::project_handlers::init_routes as project_init_routes;
-use api::{auth_middleware, rmq_handlers};
+use api::{
+    auth_middleware, organization_handlers, rate_limit_middleware, rmq_handlers, scope_middleware,
+};
 
+use application::audit::audit_service::AuditService;
+use application::notifier::DeviceNotifier;
+use application::organizations::organization_service::OrganizationService;
+use application::project::deletions::{deletion_service::DeletionService, purger::DeletionPurger};
 use application::project::devices::device_service;
+use application::project::members::member_service::MemberService;
 use application::project::session_service::SessionService;
+use application::rate_limit::RateLimits;
+use application::recordings::recording_service::RecordingService;
+use application::recordings::retention::RetentionWorker;
 use application::rmq::auth::RMQAuthService;
-use application::rmq::session_notifier::SessionNotifier;
+use application::rmq::outbox::OutboxWorker;
 use application::users::account_service::AccountService;
+use application::users::key_provider;
+use application::users::secret::Keyring;
 
 use infrastructure::establish_connection_pool;
-use log::info;
+use log::{error, info};
 use shared::deployment_config::DeploymentConfig;
 use shared::response_models::Response;
 use shared::utils::load_env;
@@ -37,6 +49,13 @@ async fn main() -> std::io::Result<()> {
     );
     env_logger::init();
     let config = DeploymentConfig::load();
+    if let Err(e) = Keyring::parse(&config.encryption_key) {
+        panic!("Failed to load the encryption keys: {}", e);
+    }
+    match key_provider::install_from_config(&config) {
+        Ok(name) => info!("Wrapping data keys with the {} key provider", name),
+        Err(e) => panic!("Failed to set up the key provider: {}", e),
+    }
 
     let app_host = config.app_host.clone();
     let app_port = config.app_port;
@@ -56,29 +75,43 @@ async fn main() -> std::io::Result<()> {
         info!("Root user created: {:?}", user);
     }
 
-    let session_service = SessionService::new(&config.encryption_key, pool.clone());
+    let session_service = SessionService::new(&config, pool.clone());
     let device_service = device_service::DeviceService::new(&config, pool.clone());
     let rmq_auth_service = RMQAuthService::new(auth_service.clone(), config.clone(), pool.clone());
-    let session_notifier_service = SessionNotifier::create(config.rabbitmq_config.clone())
-        .await
-        .expect("Failed to create session notifier");
+    let member_service = MemberService::new(pool.clone());
+    let audit_service = AuditService::new(pool.clone());
+    let recording_service = RecordingService::new(&config.encryption_key, pool.clone());
+    let deletion_service = DeletionService::new(&config.encryption_key, pool.clone());
+    let organization_service = OrganizationService::new(&config.encryption_key, pool.clone());
+    let device_notifier = DeviceNotifier::from_config(&config);
+    // Shared by all workers so that the buckets are per process
+    let rate_limits = web::Data::new(RateLimits::from_config(&config));
 
-    let queue_name = session_notifier_service
-        .initialize()
-        .await
-        .unwrap_or_else(|e| {
-            panic!("Failed to initialize session notifier: {}", e);
-        });
+    match device_notifier.initialize().await {
+        Ok(destination) => info!("Device notifier initialized with: {:?}", destination),
+        Err(e) => error!(
+            "Failed to initialize device notifier, notifications will be delivered once it is reachable: {}",
+            e
+        ),
+    }
 
-    info!("Session notifier initialized with queue: {:?}", queue_name);
+    let outbox_worker = OutboxWorker::new(&config, pool.clone(), device_notifier.clone());
+    tokio::spawn(outbox_worker.run());
+    tokio::spawn(RetentionWorker::new(&config, pool.clone()).run());
+    tokio::spawn(DeletionPurger::new(&config, pool.clone()).run());
 
     HttpServer::new(move || {
-        let mut app = App::new()
+        App::new()
+            // Registered before Authentication so that it runs after the token is verified
+            .wrap(scope_middleware::ApiKeyScopes)
+            .wrap(rate_limit_middleware::RateLimiting::Client)
             .wrap(auth_middleware::Authentication) // Comment this line if you want to integrate with yew-address-book-frontend
             .default_service(web::route().to(not_found))
             .wrap(actix_web::middleware::Logger::default())
             .app_data(web::Data::new(auth_service.clone()))
+            .app_data(web::Data::new(member_service.clone()))
             .app_data(web::Data::new(config.clone()))
+            .app_data(rate_limits.clone())
             .configure(login_init_routes)
             .configure(init_api_doc)
             .configure(|cfg| {
@@ -86,18 +119,23 @@ async fn main() -> std::io::Result<()> {
                     cfg,
                     web::Data::new(session_service.clone()),
                     web::Data::new(device_service.clone()),
-                    web::Data::new(session_notifier_service.clone()),
+                    web::Data::new(device_notifier.clone()),
+                    web::Data::new(rmq_auth_service.clone()),
+                    web::Data::new(audit_service.clone()),
+                    web::Data::new(recording_service.clone()),
+                    web::Data::new(deletion_service.clone()),
                 )
             })
             .configure(|cfg| {
                 rmq_handlers::init_routes(cfg, web::Data::new(rmq_auth_service.clone()))
-            });
-
-        if config.github_client_id.is_some() && config.github_client_secret.is_some() {
-            app = app.configure(init_github_oauth_routes);
-        }
-
-        app
+            })
+            .configure(|cfg| {
+                organization_handlers::init_routes(
+                    cfg,
+                    web::Data::new(organization_service.clone()),
+                )
+            })
+            .configure(|cfg| init_oauth_routes(cfg, &config))
     })
     .workers(num_workers)
     .bind(server_addr)?
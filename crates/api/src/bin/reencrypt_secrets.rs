@@ -0,0 +1,46 @@
+//! Re-encrypts every stored secret with a data key wrapped by the configured
+//! key provider, using the primary key of `ENCRYPTION_KEY` for the local one.
+//!
+//! To rotate keys, prepend a new versioned key (`2:<new key>,1:<old key>`),
+//! restart the API, run this command and drop the old key once
+//! `reencrypt_secrets --dry-run` reports no stale rows. Switching to another
+//! key provider works the same way, keeping `ENCRYPTION_KEY` until then.
+
+use std::error::Error;
+
+use application::users::key_provider;
+use application::users::key_rotation::reencrypt_all;
+use application::users::secret::Keyring;
+use infrastructure::establish_connection_pool;
+use shared::deployment_config::DeploymentConfig;
+
+fn main() -> Result<(), Box<dyn Error>> {
+    let dry_run = std::env::args().any(|arg| arg == "--dry-run");
+
+    let config = DeploymentConfig::load();
+    let keyring = Keyring::parse(&config.encryption_key)?;
+    let provider = key_provider::install_from_config(&config)?;
+    let pool = establish_connection_pool(&config.database_url);
+
+    println!(
+        "Re-encrypting with the {} key provider, keyring version {} (configured versions: {:?}){}",
+        provider,
+        keyring.primary_version(),
+        keyring.versions(),
+        if dry_run { ", dry run" } else { "" }
+    );
+
+    let summaries = reencrypt_all(&config.encryption_key, dry_run, &mut pool.get()?)?;
+
+    for summary in &summaries {
+        println!(
+            "{}: {} rows, {} {}",
+            summary.table,
+            summary.rows,
+            summary.stale,
+            if dry_run { "stale" } else { "re-encrypted" }
+        );
+    }
+
+    Ok(())
+}
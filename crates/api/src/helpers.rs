@@ -1,4 +1,14 @@
-use actix_web::HttpResponse;
+use std::net::{IpAddr, SocketAddr};
+use std::time::Duration;
+
+use actix_web::dev::ServiceRequest;
+use actix_web::http::header;
+use actix_web::web::Data;
+use actix_web::{HttpRequest, HttpResponse};
+use application::audit::AuditActor;
+use application::rate_limit::RateLimits;
+use application::users::tokens_manager::TokenInfo;
+use application::users::user::ClientInfo;
 use shared::response_models::Response;
 
 pub fn json_ok_response<T: serde::Serialize>(data: T) -> HttpResponse {
@@ -8,3 +18,54 @@ pub fn json_ok_response<T: serde::Serialize>(data: T) -> HttpResponse {
 pub fn error_response(e: impl Into<Response>) -> HttpResponse {
     e.into().into()
 }
+
+/// A `429` telling the client when to retry, rounded up to whole seconds.
+pub fn too_many_requests(retry_after: Duration) -> HttpResponse {
+    let seconds = retry_after.as_secs() + u64::from(retry_after.subsec_nanos() > 0);
+    HttpResponse::TooManyRequests()
+        .insert_header((header::RETRY_AFTER, seconds.max(1).to_string()))
+        .json(Response {
+            status: 429,
+            message: format!("Too many requests, retry in {} seconds", seconds.max(1)),
+        })
+}
+
+/// The address of the client, for rate limits, API key allow-lists and the
+/// audit log. The `Forwarded` headers are only honoured when the deployment
+/// trusts its reverse proxy, otherwise the address of the connection is used.
+pub fn client_ip(req: &ServiceRequest) -> Option<IpAddr> {
+    request_ip(req.request())
+}
+
+fn request_ip(req: &HttpRequest) -> Option<IpAddr> {
+    if req
+        .app_data::<Data<RateLimits>>()
+        .is_some_and(|limits| limits.trust_proxy_headers)
+    {
+        let address = req.connection_info().realip_remote_addr()?.to_string();
+        return address
+            .parse::<IpAddr>()
+            .or_else(|_| address.parse::<SocketAddr>().map(|address| address.ip()))
+            .ok();
+    }
+
+    req.peer_addr().map(|address| address.ip())
+}
+
+/// Describes the client of a request for the login session list and the
+/// login events of the audit log.
+pub fn client_info(req: &HttpRequest) -> ClientInfo {
+    ClientInfo {
+        user_agent: req
+            .headers()
+            .get(header::USER_AGENT)
+            .and_then(|agent| agent.to_str().ok())
+            .map(str::to_string),
+        ip_address: request_ip(req).map(|address| address.to_string()),
+    }
+}
+
+/// Who made a request, for the audit log.
+pub fn audit_actor(req: &HttpRequest, token_info: &TokenInfo) -> AuditActor {
+    AuditActor::new(token_info, client_info(req).ip_address)
+}
@@ -3,6 +3,9 @@ pub mod auth_middleware;
 pub(crate) mod helpers;
 pub mod login_handlers;
 pub mod oauth_handlers;
+pub mod organization_handlers;
 pub mod ownership_middleware;
 pub mod project_handlers;
+pub mod rate_limit_middleware;
 pub mod rmq_handlers;
+pub mod scope_middleware;
@@ -1,32 +1,83 @@
-use crate::helpers::{error_response, json_ok_response};
+use std::time::Duration;
+
+use crate::helpers::{
+    audit_actor, client_info, error_response, json_ok_response, too_many_requests,
+};
 use actix_web::web::{Json, ReqData};
-use actix_web::{delete, get, post, web, HttpRequest, HttpResponse};
+use actix_web::{delete, get, patch, post, web, HttpRequest, HttpResponse};
+use application::rate_limit::RateLimits;
 use application::users::account_service::AccountService;
 use application::users::tokens_manager::TokenInfo;
+use application::users::user::UserError;
 use shared::constants;
 use shared::response_models::Response;
-use shared::user_models::{ApiKeyRequest, LoginRequest, RefreshTokenRequest, SignUpRequest};
+use shared::user_models::{
+    AccountDeletionRequest, ApiKeyRequest, EmailVerificationConfirmRequest, LoginRequest,
+    LoginResponse, LoginSessionResponse, MfaCodeRequest, MfaLoginRequest, MfaStatusResponse,
+    PasswordChangeRequest, PasswordResetConfirmRequest, PasswordResetRequest, ProfileUpdateRequest,
+    RecoveryCodesResponse, RefreshTokenRequest, SessionRevocationQuery, SignUpRequest,
+    TokenResponse, TotpEnrollmentResponse,
+};
 
 #[utoipa::path(
     post,
     path = "/users/login",
     request_body = LoginRequest,
     responses(
-        (status = 200, description = "User logged in successfully", body = bool),
+        (status = 200, description = "Tokens, or an mfa token when the user has two-factor authentication", body = LoginResponse),
         (status = 404, description = "User not found"),
         (status = 401, description = "Password mismatch"),
+        (status = 429, description = "Too many attempts or account locked, see Retry-After"),
         (status = 500, description = "Internal Server Error/DatabaseError")
     )
 )]
 #[post("/login")]
 pub async fn login(
+    req: HttpRequest,
     user_auth: web::Data<AccountService>,
+    rate_limits: web::Data<RateLimits>,
     login_request: Json<LoginRequest>,
 ) -> HttpResponse {
-    user_auth
-        .login(login_request.into_inner())
-        .map(json_ok_response)
-        .unwrap_or_else(error_response)
+    if let Err(retry_after) = rate_limits
+        .auth_per_account
+        .check(&account_key(&login_request.username_or_email))
+    {
+        return too_many_requests(retry_after);
+    }
+
+    match user_auth.login(login_request.into_inner(), &client_info(&req)) {
+        Ok(tokens) => json_ok_response(tokens),
+        Err(UserError::AccountLocked(seconds)) => too_many_requests(Duration::from_secs(seconds)),
+        Err(e) => error_response(e),
+    }
+}
+
+#[utoipa::path(
+    post,
+    path = "/users/login/mfa",
+    request_body = MfaLoginRequest,
+    responses(
+        (status = 200, description = "User logged in successfully", body = TokenResponse),
+        (status = 401, description = "Invalid mfa token or code"),
+        (status = 429, description = "Too many attempts or account locked, see Retry-After")
+    )
+)]
+#[post("/login/mfa")]
+pub async fn login_mfa(
+    req: HttpRequest,
+    user_auth: web::Data<AccountService>,
+    mfa_request: Json<MfaLoginRequest>,
+) -> HttpResponse {
+    match user_auth.verify_login_mfa(&mfa_request, &client_info(&req)) {
+        Ok(tokens) => json_ok_response(tokens),
+        Err(UserError::AccountLocked(seconds)) => too_many_requests(Duration::from_secs(seconds)),
+        Err(e) => error_response(e),
+    }
+}
+
+/// Bucket key of the per account limit, shared by logins and password resets.
+fn account_key(username_or_email: &str) -> String {
+    format!("account:{}", username_or_email.trim().to_lowercase())
 }
 
 #[utoipa::path(
@@ -46,6 +97,7 @@ pub async fn signup(
 ) -> HttpResponse {
     user_auth
         .signup(signup_request.into_inner())
+        .await
         .map(json_ok_response)
         .unwrap_or_else(error_response)
 }
@@ -89,29 +141,33 @@ pub async fn logout(req: HttpRequest, user_auth: web::Data<AccountService>) -> H
     request_body = RefreshTokenRequest,
     responses(
         (status = 200, description = "User logged in successfully", body = bool),
+        (status = 401, description = "Invalid, expired or already used refresh token"),
         (status = 404, description = "User not found"),
         (status = 500, description = "Internal Server Error/DatabaseError")
     )
 )]
 #[post("/refresh-token")]
 pub async fn refresh_login_token(
+    req: HttpRequest,
     user_auth: web::Data<AccountService>,
     refresh_request: Json<RefreshTokenRequest>,
 ) -> HttpResponse {
     user_auth
-        .refresh_token(refresh_request.into_inner())
+        .refresh_token(refresh_request.into_inner(), &client_info(&req))
         .map(json_ok_response)
         .unwrap_or_else(error_response)
 }
 
 #[post("/api-key")]
 pub async fn create_api_key(
+    req: HttpRequest,
     api_token_request: Json<ApiKeyRequest>,
     user_data: ReqData<TokenInfo>,
     user_auth: web::Data<AccountService>,
 ) -> HttpResponse {
     user_auth
         .generate_api_keys(
+            &audit_actor(&req, &user_data),
             user_data.into_inner().user_id,
             &api_token_request.into_inner(),
         )
@@ -121,12 +177,17 @@ pub async fn create_api_key(
 
 #[delete("/api-key/{key_id}")]
 pub async fn delete_api_key(
+    req: HttpRequest,
     account_service: web::Data<AccountService>,
     key_id: web::Path<String>,
     user_data: ReqData<TokenInfo>,
 ) -> HttpResponse {
     account_service
-        .delete_api_key(user_data.into_inner().user_id, &key_id)
+        .delete_api_key(
+            &audit_actor(&req, &user_data),
+            user_data.into_inner().user_id,
+            &key_id,
+        )
         .map(json_ok_response)
         .unwrap_or_else(error_response)
 }
@@ -169,15 +230,390 @@ pub async fn me(
         .unwrap_or_else(error_response)
 }
 
+#[utoipa::path(
+    patch,
+    path = "/users/me",
+    request_body = ProfileUpdateRequest,
+    responses(
+        (status = 200, description = "Profile updated", body = UserProfile),
+        (status = 401, description = "Invalid Token")
+    )
+)]
+#[patch("/me")]
+pub async fn update_profile(
+    user_data: ReqData<TokenInfo>,
+    user_auth: web::Data<AccountService>,
+    update_request: Json<ProfileUpdateRequest>,
+) -> HttpResponse {
+    user_auth
+        .update_profile(user_data.into_inner().user_id, &update_request)
+        .map(json_ok_response)
+        .unwrap_or_else(error_response)
+}
+
+#[utoipa::path(
+    delete,
+    path = "/users/me",
+    request_body = AccountDeletionRequest,
+    responses(
+        (status = 200, description = "Account deleted"),
+        (status = 400, description = "Password mismatch"),
+        (status = 401, description = "Invalid Token"),
+        (status = 409, description = "The user still owns projects or organizations")
+    )
+)]
+#[delete("/me")]
+pub async fn delete_account(
+    req: HttpRequest,
+    user_data: ReqData<TokenInfo>,
+    user_auth: web::Data<AccountService>,
+    deletion_request: Option<Json<AccountDeletionRequest>>,
+) -> HttpResponse {
+    let deletion_request = deletion_request.map(Json::into_inner).unwrap_or_default();
+    user_auth
+        .delete_account(
+            &audit_actor(&req, &user_data),
+            user_data.into_inner().user_id,
+            &deletion_request,
+        )
+        .map(json_ok_response)
+        .unwrap_or_else(error_response)
+}
+
+#[utoipa::path(
+    post,
+    path = "/users/password",
+    request_body = PasswordChangeRequest,
+    responses(
+        (status = 200, description = "Password changed, other sessions logged out"),
+        (status = 400, description = "Wrong current password or weak new password"),
+        (status = 401, description = "Invalid Token")
+    )
+)]
+#[post("/password")]
+pub async fn change_password(
+    req: HttpRequest,
+    user_data: ReqData<TokenInfo>,
+    user_auth: web::Data<AccountService>,
+    change_request: Json<PasswordChangeRequest>,
+) -> HttpResponse {
+    user_auth
+        .change_password(
+            &audit_actor(&req, &user_data),
+            &user_data.into_inner(),
+            &change_request,
+        )
+        .map(json_ok_response)
+        .unwrap_or_else(error_response)
+}
+
+#[utoipa::path(
+    post,
+    path = "/users/password-reset/request",
+    request_body = PasswordResetRequest,
+    responses(
+        (status = 200, description = "A reset link was mailed if the email belongs to an account"),
+        (status = 429, description = "Too many requests, see Retry-After")
+    )
+)]
+#[post("/password-reset/request")]
+pub async fn request_password_reset(
+    user_auth: web::Data<AccountService>,
+    rate_limits: web::Data<RateLimits>,
+    reset_request: Json<PasswordResetRequest>,
+) -> HttpResponse {
+    if let Err(retry_after) = rate_limits
+        .auth_per_account
+        .check(&account_key(&reset_request.email))
+    {
+        return too_many_requests(retry_after);
+    }
+
+    user_auth
+        .request_password_reset(&reset_request)
+        .await
+        .map(json_ok_response)
+        .unwrap_or_else(error_response)
+}
+
+#[utoipa::path(
+    post,
+    path = "/users/password-reset/confirm",
+    request_body = PasswordResetConfirmRequest,
+    responses(
+        (status = 200, description = "Password reset, all sessions logged out"),
+        (status = 400, description = "Invalid or expired token, or weak password")
+    )
+)]
+#[post("/password-reset/confirm")]
+pub async fn confirm_password_reset(
+    user_auth: web::Data<AccountService>,
+    confirm_request: Json<PasswordResetConfirmRequest>,
+) -> HttpResponse {
+    user_auth
+        .confirm_password_reset(&confirm_request)
+        .map(json_ok_response)
+        .unwrap_or_else(error_response)
+}
+
+#[utoipa::path(
+    post,
+    path = "/users/email-verification/request",
+    responses(
+        (status = 200, description = "A verification link was mailed"),
+        (status = 401, description = "Invalid Token"),
+        (status = 409, description = "The email is already verified"),
+        (status = 502, description = "The email could not be sent")
+    )
+)]
+#[post("/email-verification/request")]
+pub async fn request_email_verification(
+    user_data: ReqData<TokenInfo>,
+    user_auth: web::Data<AccountService>,
+) -> HttpResponse {
+    user_auth
+        .request_email_verification(user_data.into_inner().user_id)
+        .await
+        .map(json_ok_response)
+        .unwrap_or_else(error_response)
+}
+
+#[utoipa::path(
+    post,
+    path = "/users/email-verification/confirm",
+    request_body = EmailVerificationConfirmRequest,
+    responses(
+        (status = 200, description = "Email verified", body = UserProfile),
+        (status = 400, description = "Invalid or expired token")
+    )
+)]
+#[post("/email-verification/confirm")]
+pub async fn confirm_email_verification(
+    user_auth: web::Data<AccountService>,
+    confirm_request: Json<EmailVerificationConfirmRequest>,
+) -> HttpResponse {
+    user_auth
+        .confirm_email_verification(&confirm_request)
+        .map(json_ok_response)
+        .unwrap_or_else(error_response)
+}
+
+#[utoipa::path(
+    get,
+    path = "/users/sessions",
+    responses(
+        (status = 200, description = "Active login sessions", body = Vec<LoginSessionResponse>),
+        (status = 401, description = "Invalid Token")
+    )
+)]
+#[get("/sessions")]
+pub async fn list_login_sessions(
+    user_data: ReqData<TokenInfo>,
+    user_auth: web::Data<AccountService>,
+) -> HttpResponse {
+    user_auth
+        .list_login_sessions(&user_data.into_inner())
+        .map(json_ok_response)
+        .unwrap_or_else(error_response)
+}
+
+#[utoipa::path(
+    delete,
+    path = "/users/sessions",
+    responses(
+        (status = 200, description = "Number of revoked sessions", body = usize),
+        (status = 401, description = "Invalid Token")
+    )
+)]
+#[delete("/sessions")]
+pub async fn revoke_login_sessions(
+    req: HttpRequest,
+    user_data: ReqData<TokenInfo>,
+    user_auth: web::Data<AccountService>,
+    query: web::Query<SessionRevocationQuery>,
+) -> HttpResponse {
+    user_auth
+        .revoke_login_sessions(
+            &audit_actor(&req, &user_data),
+            &user_data.into_inner(),
+            query.include_current,
+        )
+        .map(json_ok_response)
+        .unwrap_or_else(error_response)
+}
+
+#[utoipa::path(
+    delete,
+    path = "/users/sessions/{session_id}",
+    responses(
+        (status = 200, description = "Session revoked"),
+        (status = 401, description = "Invalid Token"),
+        (status = 404, description = "Session not found")
+    )
+)]
+#[delete("/sessions/{session_id}")]
+pub async fn revoke_login_session(
+    req: HttpRequest,
+    user_data: ReqData<TokenInfo>,
+    user_auth: web::Data<AccountService>,
+    session_id: web::Path<String>,
+) -> HttpResponse {
+    user_auth
+        .revoke_login_session(
+            &audit_actor(&req, &user_data),
+            user_data.into_inner().user_id,
+            &session_id,
+        )
+        .map(json_ok_response)
+        .unwrap_or_else(error_response)
+}
+
+#[utoipa::path(
+    get,
+    path = "/users/mfa",
+    responses(
+        (status = 200, description = "Two-factor authentication status", body = MfaStatusResponse),
+        (status = 401, description = "Invalid Token")
+    )
+)]
+#[get("/mfa")]
+pub async fn mfa_status(
+    user_data: ReqData<TokenInfo>,
+    user_auth: web::Data<AccountService>,
+) -> HttpResponse {
+    user_auth
+        .mfa_status(user_data.into_inner().user_id)
+        .map(json_ok_response)
+        .unwrap_or_else(error_response)
+}
+
+#[utoipa::path(
+    post,
+    path = "/users/mfa/totp/enroll",
+    responses(
+        (status = 200, description = "Secret to add to an authenticator app", body = TotpEnrollmentResponse),
+        (status = 401, description = "Invalid Token"),
+        (status = 409, description = "Two-factor authentication is already enabled")
+    )
+)]
+#[post("/mfa/totp/enroll")]
+pub async fn enroll_totp(
+    user_data: ReqData<TokenInfo>,
+    user_auth: web::Data<AccountService>,
+) -> HttpResponse {
+    user_auth
+        .enroll_totp(user_data.into_inner().user_id)
+        .map(json_ok_response)
+        .unwrap_or_else(error_response)
+}
+
+#[utoipa::path(
+    post,
+    path = "/users/mfa/totp/confirm",
+    request_body = MfaCodeRequest,
+    responses(
+        (status = 200, description = "Two-factor authentication enabled", body = RecoveryCodesResponse),
+        (status = 401, description = "Invalid Token or code"),
+        (status = 409, description = "No enrollment is pending")
+    )
+)]
+#[post("/mfa/totp/confirm")]
+pub async fn confirm_totp(
+    user_data: ReqData<TokenInfo>,
+    user_auth: web::Data<AccountService>,
+    code_request: Json<MfaCodeRequest>,
+) -> HttpResponse {
+    user_auth
+        .confirm_totp(user_data.into_inner().user_id, &code_request.code)
+        .map(json_ok_response)
+        .unwrap_or_else(error_response)
+}
+
+#[utoipa::path(
+    delete,
+    path = "/users/mfa/totp",
+    request_body = MfaCodeRequest,
+    responses(
+        (status = 200, description = "Two-factor authentication disabled"),
+        (status = 401, description = "Invalid Token or code"),
+        (status = 409, description = "Two-factor authentication is not enabled")
+    )
+)]
+#[delete("/mfa/totp")]
+pub async fn disable_totp(
+    user_data: ReqData<TokenInfo>,
+    user_auth: web::Data<AccountService>,
+    code_request: Json<MfaCodeRequest>,
+) -> HttpResponse {
+    user_auth
+        .disable_totp(user_data.into_inner().user_id, &code_request.code)
+        .map(json_ok_response)
+        .unwrap_or_else(error_response)
+}
+
+#[utoipa::path(
+    post,
+    path = "/users/mfa/recovery-codes",
+    request_body = MfaCodeRequest,
+    responses(
+        (status = 200, description = "New recovery codes, the old ones stop working", body = RecoveryCodesResponse),
+        (status = 401, description = "Invalid Token or code"),
+        (status = 409, description = "Two-factor authentication is not enabled")
+    )
+)]
+#[post("/mfa/recovery-codes")]
+pub async fn regenerate_recovery_codes(
+    user_data: ReqData<TokenInfo>,
+    user_auth: web::Data<AccountService>,
+    code_request: Json<MfaCodeRequest>,
+) -> HttpResponse {
+    user_auth
+        .regenerate_recovery_codes(user_data.into_inner().user_id, &code_request.code)
+        .map(json_ok_response)
+        .unwrap_or_else(error_response)
+}
+
+#[utoipa::path(
+    get,
+    path = "/.well-known/jwks.json",
+    responses(
+        (status = 200, description = "Public keys of the server-managed token signing key pairs, as a JWK set"),
+        (status = 500, description = "Internal Server Error/DatabaseError")
+    )
+)]
+#[get("/.well-known/jwks.json")]
+pub async fn jwks(user_auth: web::Data<AccountService>) -> HttpResponse {
+    user_auth
+        .jwks()
+        .map(json_ok_response)
+        .unwrap_or_else(error_response)
+}
+
 pub fn init_routes(cfg: &mut web::ServiceConfig) {
     let users_scope = web::scope("/users")
         .service(login)
+        .service(login_mfa)
         .service(logout)
         .service(refresh_login_token)
         .service(create_api_key)
         .service(delete_api_key)
         .service(list_all_api_keys)
         .service(signup)
-        .service(me);
-    cfg.service(users_scope);
+        .service(me)
+        .service(update_profile)
+        .service(delete_account)
+        .service(change_password)
+        .service(request_password_reset)
+        .service(confirm_password_reset)
+        .service(request_email_verification)
+        .service(confirm_email_verification)
+        .service(list_login_sessions)
+        .service(revoke_login_sessions)
+        .service(revoke_login_session)
+        .service(mfa_status)
+        .service(enroll_totp)
+        .service(confirm_totp)
+        .service(disable_totp)
+        .service(regenerate_recovery_codes);
+    cfg.service(users_scope).service(jwks);
 }
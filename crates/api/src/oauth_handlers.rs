@@ -1,11 +1,14 @@
+use crate::helpers::{client_info, error_response, json_ok_response};
 use actix_web::{
     post,
     web::{self, Json},
     HttpRequest, HttpResponse,
 };
-use application::users::{account_service::AccountService, oauth::github::GithubUser};
-use shared::response_models::Response;
-use shared::user_models::TokenResponse;
+use application::users::{
+    account_service::AccountService, oauth::github::GithubUser, user::UserError,
+};
+use shared::deployment_config::DeploymentConfig;
+use shared::user_models::{LoginResponse, OidcLoginRequest};
 
 #[post("/github/login")]
 async fn login_with_github(
@@ -21,26 +24,84 @@ async fn login_with_github(
     let user_data = user.into_inner();
     if let Ok(auth_string) = auth_header.to_str() {
         let github_token = auth_string[6..auth_string.len()].trim();
-        let token_result = account_service
-            .login_with_github(github_token, &user_data)
-            .await;
-
-        match token_result {
-            Ok((access_token, refresh_token)) => {
-                HttpResponse::Ok().json(TokenResponse::bearer(access_token, refresh_token))
-            }
-            Err(e) => {
-                let response: Response = e.into();
-                response.into()
-            }
-        }
+        login_response(
+            account_service
+                .login_with_github(github_token, &user_data, &client_info(&req))
+                .await,
+        )
     } else {
         HttpResponse::Unauthorized().body("Invalid Authorization Header")
     }
 }
 
-pub fn init_github_oauth_routes(cfg: &mut web::ServiceConfig) {
-    // Deployment Config load
-    let livekit_scope = web::scope("/oauth").service(login_with_github);
-    cfg.service(livekit_scope);
+fn login_response(login_result: Result<LoginResponse, UserError>) -> HttpResponse {
+    match login_result {
+        Ok(response) => json_ok_response(response),
+        Err(e) => error_response(e),
+    }
+}
+
+#[utoipa::path(
+    post,
+    path = "/oauth/google/login",
+    request_body = OidcLoginRequest,
+    responses(
+        (status = 200, description = "Tokens, or an mfa token when the user has two-factor authentication", body = LoginResponse),
+        (status = 400, description = "Neither an ID token nor a code was provided"),
+        (status = 401, description = "The ID token is invalid or the code was rejected"),
+        (status = 409, description = "An account with the unverified email already exists"),
+        (status = 502, description = "The provider could not be reached")
+    )
+)]
+#[post("/google/login")]
+pub async fn login_with_google(
+    req: HttpRequest,
+    account_service: web::Data<AccountService>,
+    request: Json<OidcLoginRequest>,
+) -> HttpResponse {
+    login_response(
+        account_service
+            .login_with_google(&request, &client_info(&req))
+            .await,
+    )
+}
+
+#[utoipa::path(
+    post,
+    path = "/oauth/oidc/login",
+    request_body = OidcLoginRequest,
+    responses(
+        (status = 200, description = "Tokens, or an mfa token when the user has two-factor authentication", body = LoginResponse),
+        (status = 400, description = "Neither an ID token nor a code was provided"),
+        (status = 401, description = "The ID token is invalid or the code was rejected"),
+        (status = 409, description = "An account with the unverified email already exists"),
+        (status = 502, description = "The provider could not be reached")
+    )
+)]
+#[post("/oidc/login")]
+pub async fn login_with_oidc(
+    req: HttpRequest,
+    account_service: web::Data<AccountService>,
+    request: Json<OidcLoginRequest>,
+) -> HttpResponse {
+    login_response(
+        account_service
+            .login_with_oidc(&request, &client_info(&req))
+            .await,
+    )
+}
+
+/// Registers the login routes of the configured identity providers.
+pub fn init_oauth_routes(cfg: &mut web::ServiceConfig, config: &DeploymentConfig) {
+    let mut oauth_scope = web::scope("/oauth");
+    if config.github_client_id.is_some() && config.github_client_secret.is_some() {
+        oauth_scope = oauth_scope.service(login_with_github);
+    }
+    if config.google_client_id.is_some() {
+        oauth_scope = oauth_scope.service(login_with_google);
+    }
+    if config.oidc_config.is_some() {
+        oauth_scope = oauth_scope.service(login_with_oidc);
+    }
+    cfg.service(oauth_scope);
 }
@@ -0,0 +1,356 @@
+use crate::helpers::{error_response, json_ok_response};
+use actix_web::{
+    delete, get, patch, post,
+    web::{self, ReqData},
+    HttpResponse,
+};
+use application::{
+    organizations::organization_service::OrganizationService, users::tokens_manager::TokenInfo,
+};
+use shared::{
+    member_models::MemberRoleUpdateRequest,
+    organization_models::{
+        LivekitCredentialRequest, LivekitCredentialUpdateRequest, OrganizationMemberRequest,
+        OrganizationRequest, OrganizationUpdateRequest, StorageCredentialRequest,
+        StorageCredentialUpdateRequest,
+    },
+};
+
+#[post("")]
+async fn create_organization(
+    user_info: ReqData<TokenInfo>,
+    request: web::Json<OrganizationRequest>,
+    organization_service: web::Data<OrganizationService>,
+) -> HttpResponse {
+    organization_service
+        .create_organization(user_info.into_inner().user_id, &request.into_inner())
+        .map(json_ok_response)
+        .unwrap_or_else(error_response)
+}
+
+#[get("")]
+async fn list_organizations(
+    user_info: ReqData<TokenInfo>,
+    organization_service: web::Data<OrganizationService>,
+) -> HttpResponse {
+    organization_service
+        .list_organizations(user_info.into_inner().user_id)
+        .map(json_ok_response)
+        .unwrap_or_else(error_response)
+}
+
+#[get("/{org_id}")]
+async fn get_organization(
+    org_id: web::Path<String>,
+    user_info: ReqData<TokenInfo>,
+    organization_service: web::Data<OrganizationService>,
+) -> HttpResponse {
+    organization_service
+        .get_organization(&org_id, user_info.into_inner().user_id)
+        .map(json_ok_response)
+        .unwrap_or_else(error_response)
+}
+
+#[patch("/{org_id}")]
+async fn update_organization(
+    org_id: web::Path<String>,
+    user_info: ReqData<TokenInfo>,
+    request: web::Json<OrganizationUpdateRequest>,
+    organization_service: web::Data<OrganizationService>,
+) -> HttpResponse {
+    organization_service
+        .update_organization(
+            &org_id,
+            user_info.into_inner().user_id,
+            &request.into_inner(),
+        )
+        .map(json_ok_response)
+        .unwrap_or_else(error_response)
+}
+
+#[delete("/{org_id}")]
+async fn delete_organization(
+    org_id: web::Path<String>,
+    user_info: ReqData<TokenInfo>,
+    organization_service: web::Data<OrganizationService>,
+) -> HttpResponse {
+    organization_service
+        .delete_organization(&org_id, user_info.into_inner().user_id)
+        .map(json_ok_response)
+        .unwrap_or_else(error_response)
+}
+
+#[get("/{org_id}/members")]
+async fn list_organization_members(
+    org_id: web::Path<String>,
+    user_info: ReqData<TokenInfo>,
+    organization_service: web::Data<OrganizationService>,
+) -> HttpResponse {
+    organization_service
+        .list_members(&org_id, user_info.into_inner().user_id)
+        .map(json_ok_response)
+        .unwrap_or_else(error_response)
+}
+
+#[post("/{org_id}/members/invite")]
+async fn invite_organization_member(
+    org_id: web::Path<String>,
+    user_info: ReqData<TokenInfo>,
+    request: web::Json<OrganizationMemberRequest>,
+    organization_service: web::Data<OrganizationService>,
+) -> HttpResponse {
+    organization_service
+        .invite_member(
+            &org_id,
+            user_info.into_inner().user_id,
+            &request.into_inner(),
+        )
+        .map(json_ok_response)
+        .unwrap_or_else(error_response)
+}
+
+#[get("/{org_id}/members/invitations")]
+async fn list_organization_invitations(
+    org_id: web::Path<String>,
+    user_info: ReqData<TokenInfo>,
+    organization_service: web::Data<OrganizationService>,
+) -> HttpResponse {
+    organization_service
+        .list_invitations(&org_id, user_info.into_inner().user_id)
+        .map(json_ok_response)
+        .unwrap_or_else(error_response)
+}
+
+#[delete("/{org_id}/members/invitations/{invitation_id}")]
+async fn revoke_organization_invitation(
+    path: web::Path<(String, String)>,
+    user_info: ReqData<TokenInfo>,
+    organization_service: web::Data<OrganizationService>,
+) -> HttpResponse {
+    let (org_id, invitation_id) = path.into_inner();
+    organization_service
+        .revoke_invitation(&org_id, user_info.into_inner().user_id, &invitation_id)
+        .map(json_ok_response)
+        .unwrap_or_else(error_response)
+}
+
+#[get("/invitations")]
+async fn list_my_organization_invitations(
+    user_info: ReqData<TokenInfo>,
+    organization_service: web::Data<OrganizationService>,
+) -> HttpResponse {
+    organization_service
+        .list_user_invitations(user_info.into_inner().user_id)
+        .map(json_ok_response)
+        .unwrap_or_else(error_response)
+}
+
+#[post("/invitations/{invitation_id}/accept")]
+async fn accept_organization_invitation(
+    invitation_id: web::Path<String>,
+    user_info: ReqData<TokenInfo>,
+    organization_service: web::Data<OrganizationService>,
+) -> HttpResponse {
+    organization_service
+        .accept_invitation(user_info.into_inner().user_id, &invitation_id)
+        .map(json_ok_response)
+        .unwrap_or_else(error_response)
+}
+
+#[post("/invitations/{invitation_id}/decline")]
+async fn decline_organization_invitation(
+    invitation_id: web::Path<String>,
+    user_info: ReqData<TokenInfo>,
+    organization_service: web::Data<OrganizationService>,
+) -> HttpResponse {
+    organization_service
+        .decline_invitation(user_info.into_inner().user_id, &invitation_id)
+        .map(json_ok_response)
+        .unwrap_or_else(error_response)
+}
+
+#[patch("/{org_id}/members/{user_id}")]
+async fn update_organization_member(
+    path: web::Path<(String, i32)>,
+    user_info: ReqData<TokenInfo>,
+    request: web::Json<MemberRoleUpdateRequest>,
+    organization_service: web::Data<OrganizationService>,
+) -> HttpResponse {
+    let (org_id, member_user_id) = path.into_inner();
+    organization_service
+        .update_member_role(
+            &org_id,
+            user_info.into_inner().user_id,
+            member_user_id,
+            &request.into_inner(),
+        )
+        .map(json_ok_response)
+        .unwrap_or_else(error_response)
+}
+
+#[delete("/{org_id}/members/{user_id}")]
+async fn remove_organization_member(
+    path: web::Path<(String, i32)>,
+    user_info: ReqData<TokenInfo>,
+    organization_service: web::Data<OrganizationService>,
+) -> HttpResponse {
+    let (org_id, member_user_id) = path.into_inner();
+    organization_service
+        .remove_member(&org_id, user_info.into_inner().user_id, member_user_id)
+        .map(json_ok_response)
+        .unwrap_or_else(error_response)
+}
+
+#[get("/{org_id}/credentials/livekit")]
+async fn list_livekit_credentials(
+    org_id: web::Path<String>,
+    user_info: ReqData<TokenInfo>,
+    organization_service: web::Data<OrganizationService>,
+) -> HttpResponse {
+    organization_service
+        .list_livekit_credentials(&org_id, user_info.into_inner().user_id)
+        .map(json_ok_response)
+        .unwrap_or_else(error_response)
+}
+
+#[post("/{org_id}/credentials/livekit")]
+async fn create_livekit_credential(
+    org_id: web::Path<String>,
+    user_info: ReqData<TokenInfo>,
+    request: web::Json<LivekitCredentialRequest>,
+    organization_service: web::Data<OrganizationService>,
+) -> HttpResponse {
+    organization_service
+        .create_livekit_credential(
+            &org_id,
+            user_info.into_inner().user_id,
+            &request.into_inner(),
+        )
+        .map(json_ok_response)
+        .unwrap_or_else(error_response)
+}
+
+#[patch("/{org_id}/credentials/livekit/{credential_id}")]
+async fn update_livekit_credential(
+    path: web::Path<(String, String)>,
+    user_info: ReqData<TokenInfo>,
+    request: web::Json<LivekitCredentialUpdateRequest>,
+    organization_service: web::Data<OrganizationService>,
+) -> HttpResponse {
+    let (org_id, credential_id) = path.into_inner();
+    organization_service
+        .update_livekit_credential(
+            &org_id,
+            user_info.into_inner().user_id,
+            &credential_id,
+            &request.into_inner(),
+        )
+        .map(json_ok_response)
+        .unwrap_or_else(error_response)
+}
+
+#[delete("/{org_id}/credentials/livekit/{credential_id}")]
+async fn delete_livekit_credential(
+    path: web::Path<(String, String)>,
+    user_info: ReqData<TokenInfo>,
+    organization_service: web::Data<OrganizationService>,
+) -> HttpResponse {
+    let (org_id, credential_id) = path.into_inner();
+    organization_service
+        .delete_livekit_credential(&org_id, user_info.into_inner().user_id, &credential_id)
+        .map(json_ok_response)
+        .unwrap_or_else(error_response)
+}
+
+#[get("/{org_id}/credentials/storage")]
+async fn list_storage_credentials(
+    org_id: web::Path<String>,
+    user_info: ReqData<TokenInfo>,
+    organization_service: web::Data<OrganizationService>,
+) -> HttpResponse {
+    organization_service
+        .list_storage_credentials(&org_id, user_info.into_inner().user_id)
+        .map(json_ok_response)
+        .unwrap_or_else(error_response)
+}
+
+#[post("/{org_id}/credentials/storage")]
+async fn create_storage_credential(
+    org_id: web::Path<String>,
+    user_info: ReqData<TokenInfo>,
+    request: web::Json<StorageCredentialRequest>,
+    organization_service: web::Data<OrganizationService>,
+) -> HttpResponse {
+    organization_service
+        .create_storage_credential(
+            &org_id,
+            user_info.into_inner().user_id,
+            &request.into_inner(),
+        )
+        .map(json_ok_response)
+        .unwrap_or_else(error_response)
+}
+
+#[patch("/{org_id}/credentials/storage/{credential_id}")]
+async fn update_storage_credential(
+    path: web::Path<(String, String)>,
+    user_info: ReqData<TokenInfo>,
+    request: web::Json<StorageCredentialUpdateRequest>,
+    organization_service: web::Data<OrganizationService>,
+) -> HttpResponse {
+    let (org_id, credential_id) = path.into_inner();
+    organization_service
+        .update_storage_credential(
+            &org_id,
+            user_info.into_inner().user_id,
+            &credential_id,
+            &request.into_inner(),
+        )
+        .map(json_ok_response)
+        .unwrap_or_else(error_response)
+}
+
+#[delete("/{org_id}/credentials/storage/{credential_id}")]
+async fn delete_storage_credential(
+    path: web::Path<(String, String)>,
+    user_info: ReqData<TokenInfo>,
+    organization_service: web::Data<OrganizationService>,
+) -> HttpResponse {
+    let (org_id, credential_id) = path.into_inner();
+    organization_service
+        .delete_storage_credential(&org_id, user_info.into_inner().user_id, &credential_id)
+        .map(json_ok_response)
+        .unwrap_or_else(error_response)
+}
+
+pub fn init_routes(
+    cfg: &mut web::ServiceConfig,
+    organization_service: web::Data<OrganizationService>,
+) {
+    let organizations_scope = web::scope("/organizations")
+        .app_data(organization_service.clone())
+        .service(create_organization)
+        .service(list_organizations)
+        .service(list_my_organization_invitations)
+        .service(accept_organization_invitation)
+        .service(decline_organization_invitation)
+        .service(get_organization)
+        .service(update_organization)
+        .service(delete_organization)
+        .service(list_organization_members)
+        .service(invite_organization_member)
+        .service(list_organization_invitations)
+        .service(revoke_organization_invitation)
+        .service(update_organization_member)
+        .service(remove_organization_member)
+        .service(list_livekit_credentials)
+        .service(create_livekit_credential)
+        .service(update_livekit_credential)
+        .service(delete_livekit_credential)
+        .service(list_storage_credentials)
+        .service(create_storage_credential)
+        .service(update_storage_credential)
+        .service(delete_storage_credential);
+
+    cfg.service(organizations_scope);
+}
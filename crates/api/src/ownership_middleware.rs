@@ -7,7 +7,8 @@ use actix_web::{
     web::Data,
     Error, HttpMessage, HttpResponse,
 };
-use application::users::account_service::AccountService;
+use application::project::members::member_service::MemberService;
+use application::project::members::ProjectRole;
 use application::users::tokens_manager::TokenInfo;
 use futures_util::future::LocalBoxFuture;
 use shared::constants;
@@ -23,11 +24,45 @@ fn extract_project_id(req: &ServiceRequest) -> Option<String> {
         .map(|s| s.to_string())
 }
 
+/// Minimum project role needed for a route, keyed by the method and the path
+/// segments that follow the project id.
+fn required_role(method: &Method, segments: &[&str]) -> ProjectRole {
+    match segments {
+        [] if method == Method::DELETE => ProjectRole::Owner,
+        ["restore"] if method == Method::POST => ProjectRole::Owner,
+        ["settings", ..] => ProjectRole::Admin,
+        // Members may leave a project themselves, the service checks the rest
+        ["members", _] if method == Method::DELETE => ProjectRole::Viewer,
+        ["members"] if method == Method::GET => ProjectRole::Viewer,
+        ["members", ..] => ProjectRole::Admin,
+        ["sessions", _] if method == Method::DELETE => ProjectRole::Admin,
+        ["sessions", _, "restore"] if method == Method::POST => ProjectRole::Admin,
+        ["create-session"] | ["sessions", _, "token" | "stop"] if method == Method::POST => {
+            ProjectRole::Operator
+        }
+        ["sessions", _, "get-media-url"] if method == Method::POST => ProjectRole::Viewer,
+        ["sessions", _, "consents", ..] if method == Method::POST => ProjectRole::Operator,
+        ["devices" | "device-groups", ..] if method != Method::GET => ProjectRole::Operator,
+        ["audit-events", ..] => ProjectRole::Admin,
+        _ if method == Method::GET => ProjectRole::Viewer,
+        _ => ProjectRole::Admin,
+    }
+}
+
+fn route_segments(req: &ServiceRequest) -> Vec<&str> {
+    req.path()
+        .trim_start_matches("/projects/")
+        .split('/')
+        .skip(1)
+        .filter(|segment| !segment.is_empty())
+        .collect()
+}
+
 #[derive(Debug)]
 enum OwnershipStatus {
     Ignored,
     ApplicationError(HttpResponse),
-    Authorized,
+    Authorized(ProjectRole),
 }
 
 impl<S, B> Transform<S, ServiceRequest> for Ownership
@@ -70,17 +105,32 @@ where
                 .any(|ignore_route| req.path().starts_with(ignore_route))
         {
             OwnershipStatus::Ignored
-        } else if let Some(user_info) = req.extensions().get::<TokenInfo>() {
-            let account_service = req.app_data::<Data<AccountService>>();
+        } else if let Some(user_id) = req.extensions().get::<TokenInfo>().map(|u| u.user_id) {
+            let member_service = req.app_data::<Data<MemberService>>();
 
-            account_service
+            member_service
                 .map(|svc| {
                     let project_id = extract_project_id(&req);
                     if let Some(project_id) = project_id {
-                        let user_id = user_info.user_id;
-                        svc.get_project(user_id, &project_id)
-                            .map(|_| OwnershipStatus::Authorized)
-                            .unwrap_or_else(|e: application::users::user::UserError| {
+                        svc.get_role(&project_id, user_id)
+                            .map(|role| {
+                                let required = required_role(req.method(), &route_segments(&req));
+                                if role.includes(required) {
+                                    OwnershipStatus::Authorized(role)
+                                } else {
+                                    OwnershipStatus::ApplicationError(
+                                        Response {
+                                            status: 403,
+                                            message: format!(
+                                                "This action requires the {} role",
+                                                required.as_str()
+                                            ),
+                                        }
+                                        .into(),
+                                    )
+                                }
+                            })
+                            .unwrap_or_else(|e| {
                                 OwnershipStatus::ApplicationError(Response::from(e).into())
                             })
                     } else {
@@ -91,7 +141,7 @@ where
                 })
                 .unwrap_or_else(|| {
                     OwnershipStatus::ApplicationError(
-                        HttpResponse::Unauthorized().body("Account Service Not Found"),
+                        HttpResponse::Unauthorized().body("Member Service Not Found"),
                     )
                 })
         } else {
@@ -100,8 +150,12 @@ where
             )
         };
 
+        if let OwnershipStatus::Authorized(role) = status {
+            req.extensions_mut().insert(role);
+        }
+
         match status {
-            OwnershipStatus::Ignored | OwnershipStatus::Authorized => {
+            OwnershipStatus::Ignored | OwnershipStatus::Authorized(_) => {
                 let fut = self.service.call(req);
                 Box::pin(async move {
                     let res = fut.await?;
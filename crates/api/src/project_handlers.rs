@@ -1,22 +1,43 @@
 use crate::{
-    helpers::{error_response, json_ok_response},
-    ownership_middleware,
+    helpers::{audit_actor, error_response, json_ok_response},
+    ownership_middleware, rate_limit_middleware,
 };
 use actix_web::{
-    delete, get, post,
+    delete, get, patch, post, put,
     web::{self, ReqData},
-    HttpResponse,
+    HttpRequest, HttpResponse,
 };
 use application::{
-    project::{devices::device_service::DeviceService, session_service::SessionService},
-    rmq::session_notifier::SessionNotifier,
-    users::{account_service::AccountService, tokens_manager::TokenInfo},
+    audit::audit_service::AuditService,
+    notifier::DeviceNotifier,
+    project::{
+        deletions::deletion_service::DeletionService,
+        devices::{
+            device_crud::DeviceError,
+            device_service::{self, DeviceService},
+        },
+        members::{member_service::MemberService, ProjectRole},
+        project_crud::ProjectError,
+        session_service::SessionService,
+    },
+    recordings::recording_service::RecordingService,
+    rmq::auth::RMQAuthService,
+    users::{account_service::AccountService, tokens_manager::TokenInfo, user::UserError},
 };
 use shared::{
-    device_models::DeviceRegisterRequest,
+    audit_models::AuditEventQuery,
+    deletion_models::DeletionQuery,
+    device_models::{
+        DeviceGroupRequest, DeviceGroupUpdateRequest, DeviceListQuery, DeviceRegisterRequest,
+        DeviceUpdateRequest,
+    },
     livekit_models::TokenRequest,
-    project_models::{EgressMediaPath, NewSessionRequest},
-    user_models::{ApiKeyRequest, ProjectRequest},
+    member_models::{MemberRoleUpdateRequest, ProjectInviteRequest},
+    project_models::{EgressMediaPath, NewSessionRequest, ProjectValidationReport},
+    recording_models::{ConsentRequest, RetentionPolicyRequest},
+    user_models::{
+        ProjectApiKeyRequest, ProjectRequest, ProjectSecretsRequest, ProjectUpdateRequest,
+    },
 };
 
 #[utoipa::path(
@@ -99,21 +120,160 @@ async fn get_project(
         (status = 500, description = "Internal Server Error/DatabaseError")
     ),
     params(
-        ("project_id", description = "The ID of the project to get details")
+        ("project_id", description = "The ID of the project to get details"),
+        ("media" = Option<String>, Query, description = "What happens to the recordings once the grace period ends: keep (default), delete or archive"),
+        ("archivePrefix" = Option<String>, Query, description = "Prefix the recordings are moved under by archive, archive when unset")
     )
 )]
 #[delete("/{project_id}")]
 async fn delete_project(
+    req: HttpRequest,
     user_data: ReqData<TokenInfo>,
     project_id: web::Path<String>,
+    query: web::Query<DeletionQuery>,
     account_service: web::Data<AccountService>,
+    rmq_auth_service: web::Data<RMQAuthService>,
 ) -> HttpResponse {
     account_service
-        .delete_project(user_data.into_inner().user_id, &project_id)
+        .delete_project(
+            &audit_actor(&req, &user_data),
+            user_data.into_inner().user_id,
+            &project_id,
+            &query,
+        )
+        .inspect(|_| rmq_auth_service.invalidate_project(&project_id))
         .map(json_ok_response)
         .unwrap_or_else(error_response)
 }
 
+#[utoipa::path(
+    post,
+    path = "/projects/{project_id}/restore",
+    responses(
+        (status = 200, description = "The restored project", body = ProjectInfo),
+        (status = 400, description = "Bad Request"),
+        (status = 401, description = "Unauthorized"),
+        (status = 403, description = "Forbidden"),
+        (status = 404, description = "Project not found or not pending deletion"),
+        (status = 500, description = "Internal Server Error/DatabaseError")
+    ),
+    params(
+        ("project_id", description = "The ID of the deleted project to restore")
+    )
+)]
+#[post("/{project_id}/restore")]
+async fn restore_project(
+    req: HttpRequest,
+    user_data: ReqData<TokenInfo>,
+    project_id: web::Path<String>,
+    deletion_service: web::Data<DeletionService>,
+) -> HttpResponse {
+    deletion_service
+        .restore_project(&audit_actor(&req, &user_data), &project_id)
+        .map(json_ok_response)
+        .unwrap_or_else(error_response)
+}
+
+#[utoipa::path(
+    get,
+    path = "/projects/{project_id}/deletions",
+    responses(
+        (status = 200, description = "Pending deletions of the project and its sessions", body = Vec<ScheduledDeletionResponse>),
+        (status = 400, description = "Bad Request"),
+        (status = 401, description = "Unauthorized"),
+        (status = 403, description = "Forbidden"),
+        (status = 404, description = "Project not found"),
+        (status = 500, description = "Internal Server Error/DatabaseError")
+    ),
+    params(
+        ("project_id", description = "The ID of the project to list the pending deletions of")
+    )
+)]
+#[get("/{project_id}/deletions")]
+async fn list_deletions(
+    project_id: web::Path<String>,
+    deletion_service: web::Data<DeletionService>,
+) -> HttpResponse {
+    deletion_service
+        .list_deletions(&project_id)
+        .map(json_ok_response)
+        .unwrap_or_else(error_response)
+}
+
+#[utoipa::path(
+    patch,
+    path = "/projects/{project_id}",
+    request_body = ProjectUpdateRequest,
+    responses(
+        (status = 200, description = "Project Updated Successfully", body = ProjectInfo),
+        (status = 400, description = "Bad Request"),
+        (status = 422, description = "Validation Failed", body = ProjectValidationReport),
+        (status = 401, description = "Unauthorized"),
+        (status = 403, description = "Forbidden"),
+        (status = 404, description = "Project not found"),
+        (status = 500, description = "Internal Server Error/DatabaseError")
+    ),
+    params(
+        ("project_id", description = "The ID of the project to update")
+    )
+)]
+#[patch("/{project_id}")]
+async fn update_project(
+    req: HttpRequest,
+    user_data: ReqData<TokenInfo>,
+    project_id: web::Path<String>,
+    account_service: web::Data<AccountService>,
+    request: web::Json<ProjectUpdateRequest>,
+) -> HttpResponse {
+    account_service
+        .patch_project(
+            &audit_actor(&req, &user_data),
+            user_data.into_inner().user_id,
+            &project_id,
+            &request,
+        )
+        .await
+        .map(json_ok_response)
+        .unwrap_or_else(validation_error_response)
+}
+
+#[utoipa::path(
+    post,
+    path = "/projects/{project_id}/settings/rotate-secrets",
+    request_body = ProjectSecretsRequest,
+    responses(
+        (status = 200, description = "Secrets Rotated Successfully", body = ProjectInfo),
+        (status = 400, description = "Bad Request"),
+        (status = 422, description = "Validation Failed", body = ProjectValidationReport),
+        (status = 401, description = "Unauthorized"),
+        (status = 403, description = "Forbidden"),
+        (status = 404, description = "Project not found"),
+        (status = 500, description = "Internal Server Error/DatabaseError")
+    ),
+    params(
+        ("project_id", description = "The ID of the project to rotate the secrets of")
+    )
+)]
+#[post("/{project_id}/settings/rotate-secrets")]
+async fn rotate_project_secrets(
+    req: HttpRequest,
+    user_data: ReqData<TokenInfo>,
+    project_id: web::Path<String>,
+    account_service: web::Data<AccountService>,
+    request: web::Json<ProjectSecretsRequest>,
+) -> HttpResponse {
+    account_service
+        .rotate_project_secrets(
+            &audit_actor(&req, &user_data),
+            user_data.into_inner().user_id,
+            &project_id,
+            &request,
+        )
+        .await
+        .map(json_ok_response)
+        .unwrap_or_else(validation_error_response)
+}
+
 #[utoipa::path(
     post,
     path = "/projects/create",
@@ -121,6 +281,7 @@ async fn delete_project(
     responses(
         (status = 200, description = "Project Created Successfully", body = ProjectInfo),
         (status = 400, description = "Bad Request"),
+        (status = 422, description = "Validation Failed", body = ProjectValidationReport),
         (status = 401, description = "Unauthorized"),
         (status = 404, description = "Project not found"),
         (status = 500, description = "Internal Server Error/DatabaseError")
@@ -128,19 +289,57 @@ async fn delete_project(
 )]
 #[post("/create")]
 async fn create_project(
+    req: HttpRequest,
     user_data: ReqData<TokenInfo>,
     project_request: web::Json<ProjectRequest>,
     account_service: web::Data<AccountService>,
 ) -> HttpResponse {
     account_service
         .create_project(
+            &audit_actor(&req, &user_data),
             user_data.into_inner().user_id,
             &project_request.into_inner(),
         )
+        .await
+        .map(json_ok_response)
+        .unwrap_or_else(validation_error_response)
+}
+
+#[utoipa::path(
+    post,
+    path = "/projects/validate",
+    request_body = ProjectRequest,
+    responses(
+        (status = 200, description = "Validation Report", body = ProjectValidationReport),
+        (status = 400, description = "Bad Request"),
+        (status = 401, description = "Unauthorized"),
+        (status = 403, description = "Forbidden"),
+        (status = 500, description = "Internal Server Error/DatabaseError")
+    )
+)]
+#[post("/validate")]
+async fn validate_project(
+    user_data: ReqData<TokenInfo>,
+    project_request: web::Json<ProjectRequest>,
+    account_service: web::Data<AccountService>,
+) -> HttpResponse {
+    account_service
+        .validate_project(user_data.into_inner().user_id, &project_request)
+        .await
         .map(json_ok_response)
         .unwrap_or_else(error_response)
 }
 
+/// Failed validations are answered with the full report instead of a message.
+fn validation_error_response(e: UserError) -> HttpResponse {
+    match e {
+        UserError::ProjectError(ProjectError::ValidationFailed(report)) => {
+            HttpResponse::UnprocessableEntity().json(report)
+        }
+        e => error_response(e),
+    }
+}
+
 #[utoipa::path(
     get,
     path = "/projects/{project_id}/summarize",
@@ -184,13 +383,16 @@ async fn summarize_project(
 )]
 #[post("/{project_id}/create-session")]
 async fn create_session(
+    req: HttpRequest,
+    user_info: ReqData<TokenInfo>,
     project_id: web::Path<String>,
     session: web::Json<NewSessionRequest>,
     session_service: web::Data<SessionService>,
-    notifier_service: web::Data<SessionNotifier>,
+    notifier_service: web::Data<DeviceNotifier>,
 ) -> HttpResponse {
     session_service
         .create_session(
+            &audit_actor(&req, &user_info),
             &project_id,
             &session.into_inner(),
             &notifier_service.into_inner(),
@@ -228,12 +430,14 @@ async fn get_sessions(
 
 #[get("/{project_id}/sessions/{session_id}")]
 async fn get_session(
+    req: HttpRequest,
+    user_info: ReqData<TokenInfo>,
     path: web::Path<(String, String)>,
     session_service: web::Data<SessionService>,
 ) -> HttpResponse {
     let (project_id, session_id) = path.into_inner();
     session_service
-        .get_session(&project_id, &session_id)
+        .get_session(&audit_actor(&req, &user_info), &project_id, &session_id)
         .await
         .map(json_ok_response)
         .unwrap_or_else(error_response)
@@ -241,17 +445,39 @@ async fn get_session(
 
 #[delete("/{project_id}/sessions/{session_id}")]
 async fn delete_session(
+    req: HttpRequest,
+    user_info: ReqData<TokenInfo>,
     path: web::Path<(String, String)>,
+    query: web::Query<DeletionQuery>,
     session_service: web::Data<SessionService>,
 ) -> HttpResponse {
     let (project_id, session_id) = path.into_inner();
     session_service
-        .delete_session(&project_id, &session_id)
+        .delete_session(
+            &audit_actor(&req, &user_info),
+            &project_id,
+            &session_id,
+            &query,
+        )
         .await
         .map(json_ok_response)
         .unwrap_or_else(error_response)
 }
 
+#[post("/{project_id}/sessions/{session_id}/restore")]
+async fn restore_session(
+    req: HttpRequest,
+    user_info: ReqData<TokenInfo>,
+    path: web::Path<(String, String)>,
+    deletion_service: web::Data<DeletionService>,
+) -> HttpResponse {
+    let (project_id, session_id) = path.into_inner();
+    deletion_service
+        .restore_session(&audit_actor(&req, &user_info), &project_id, &session_id)
+        .map(json_ok_response)
+        .unwrap_or_else(error_response)
+}
+
 #[get("/{project_id}/sessions/{session_id}/participants")]
 async fn get_participants(
     path: web::Path<(String, String)>,
@@ -267,25 +493,34 @@ async fn get_participants(
 
 #[post("/{project_id}/sessions/{session_id}/token")]
 pub async fn generate_session_token(
+    req: HttpRequest,
+    user_info: ReqData<TokenInfo>,
     path: web::Path<(String, String)>,
     token_request: web::Json<TokenRequest>,
     session_service: web::Data<SessionService>,
 ) -> HttpResponse {
     let (project_id, session_id) = path.into_inner();
     session_service
-        .get_session_token(&project_id, &session_id, &token_request.into_inner())
+        .get_session_token(
+            &audit_actor(&req, &user_info),
+            &project_id,
+            &session_id,
+            &token_request.into_inner(),
+        )
         .map(json_ok_response)
         .unwrap_or_else(error_response)
 }
 
 #[post("/{project_id}/sessions/{session_id}/stop")]
 async fn stop_session(
+    req: HttpRequest,
+    user_info: ReqData<TokenInfo>,
     path: web::Path<(String, String)>,
     session_service: web::Data<SessionService>,
 ) -> HttpResponse {
     let (project_id, session_id) = path.into_inner();
     session_service
-        .stop_session(&project_id, &session_id)
+        .stop_session(&audit_actor(&req, &user_info), &project_id, &session_id)
         .await
         .map(json_ok_response)
         .unwrap_or_else(error_response)
@@ -306,6 +541,8 @@ async fn get_session_egresses(
 
 #[post("/{project_id}/sessions/{session_id}/get-media-url")]
 async fn get_egress_media_download_url(
+    req: HttpRequest,
+    user_info: ReqData<TokenInfo>,
     path: web::Path<(String, String)>,
     session_service: web::Data<SessionService>,
     media_path_request: web::Json<EgressMediaPath>,
@@ -314,22 +551,173 @@ async fn get_egress_media_download_url(
     let (project_id, _session_id) = path.into_inner();
 
     session_service
-        .get_egress_download_url(&project_id, &request.path)
+        .get_egress_download_url(&audit_actor(&req, &user_info), &project_id, &request.path)
         .await
         .map(json_ok_response)
         .unwrap_or_else(error_response)
 }
 
+#[get("/{project_id}/sessions/{session_id}/media")]
+async fn list_session_media(
+    path: web::Path<(String, String)>,
+    recording_service: web::Data<RecordingService>,
+) -> HttpResponse {
+    let (project_id, session_id) = path.into_inner();
+    recording_service
+        .list_session_media(&project_id, &session_id)
+        .await
+        .map(json_ok_response)
+        .unwrap_or_else(error_response)
+}
+
+#[get("/{project_id}/sessions/{session_id}/consents")]
+async fn list_consents(
+    path: web::Path<(String, String)>,
+    recording_service: web::Data<RecordingService>,
+) -> HttpResponse {
+    let (project_id, session_id) = path.into_inner();
+    recording_service
+        .list_consents(&project_id, &session_id)
+        .map(json_ok_response)
+        .unwrap_or_else(error_response)
+}
+
+#[post("/{project_id}/sessions/{session_id}/consents")]
+async fn record_consent(
+    req: HttpRequest,
+    user_info: ReqData<TokenInfo>,
+    path: web::Path<(String, String)>,
+    request: web::Json<ConsentRequest>,
+    recording_service: web::Data<RecordingService>,
+) -> HttpResponse {
+    let (project_id, session_id) = path.into_inner();
+    recording_service
+        .record_consent(
+            &audit_actor(&req, &user_info),
+            &project_id,
+            &session_id,
+            &request.into_inner(),
+        )
+        .map(json_ok_response)
+        .unwrap_or_else(error_response)
+}
+
+#[post("/{project_id}/sessions/{session_id}/consents/{identity}/withdraw")]
+async fn withdraw_consent(
+    req: HttpRequest,
+    user_info: ReqData<TokenInfo>,
+    path: web::Path<(String, String, String)>,
+    recording_service: web::Data<RecordingService>,
+) -> HttpResponse {
+    let (project_id, session_id, identity) = path.into_inner();
+    recording_service
+        .withdraw_consent(
+            &audit_actor(&req, &user_info),
+            &project_id,
+            &session_id,
+            &identity,
+        )
+        .await
+        .map(json_ok_response)
+        .unwrap_or_else(error_response)
+}
+
+#[utoipa::path(
+    get,
+    path = "/projects/{project_id}/storage-usage",
+    responses(
+        (status = 200, description = "Size of the project's recordings, in total and per session", body = StorageUsageResponse),
+        (status = 400, description = "Bad Request"),
+        (status = 401, description = "Unauthorized"),
+        (status = 403, description = "Forbidden"),
+        (status = 404, description = "Project not found"),
+        (status = 500, description = "Internal Server Error/DatabaseError"),
+        (status = 502, description = "The bucket could not be listed")
+    ),
+    params(
+        ("project_id", description = "The ID of the project to get the storage usage of")
+    )
+)]
+#[get("/{project_id}/storage-usage")]
+async fn get_storage_usage(
+    project_id: web::Path<String>,
+    recording_service: web::Data<RecordingService>,
+) -> HttpResponse {
+    recording_service
+        .get_storage_usage(&project_id)
+        .await
+        .map(json_ok_response)
+        .unwrap_or_else(error_response)
+}
+
+#[utoipa::path(
+    get,
+    path = "/projects/{project_id}/settings/retention-policy",
+    responses(
+        (status = 200, description = "Retention policy of the project's recordings", body = RetentionPolicyResponse),
+        (status = 400, description = "Bad Request"),
+        (status = 401, description = "Unauthorized"),
+        (status = 403, description = "Forbidden"),
+        (status = 404, description = "Project not found"),
+        (status = 500, description = "Internal Server Error/DatabaseError")
+    ),
+    params(
+        ("project_id", description = "The ID of the project to get the retention policy of")
+    )
+)]
+#[get("{project_id}/settings/retention-policy")]
+async fn get_retention_policy(
+    project_id: web::Path<String>,
+    recording_service: web::Data<RecordingService>,
+) -> HttpResponse {
+    recording_service
+        .get_retention_policy(&project_id)
+        .map(json_ok_response)
+        .unwrap_or_else(error_response)
+}
+
+#[utoipa::path(
+    put,
+    path = "/projects/{project_id}/settings/retention-policy",
+    request_body = RetentionPolicyRequest,
+    responses(
+        (status = 200, description = "The saved retention policy", body = RetentionPolicyResponse),
+        (status = 400, description = "Bad Request"),
+        (status = 401, description = "Unauthorized"),
+        (status = 403, description = "Forbidden"),
+        (status = 404, description = "Project not found"),
+        (status = 500, description = "Internal Server Error/DatabaseError")
+    ),
+    params(
+        ("project_id", description = "The ID of the project to set the retention policy of")
+    )
+)]
+#[put("{project_id}/settings/retention-policy")]
+async fn set_retention_policy(
+    req: HttpRequest,
+    project_id: web::Path<String>,
+    user_info: ReqData<TokenInfo>,
+    request: web::Json<RetentionPolicyRequest>,
+    recording_service: web::Data<RecordingService>,
+) -> HttpResponse {
+    recording_service
+        .set_retention_policy(&audit_actor(&req, &user_info), &project_id, &request)
+        .map(json_ok_response)
+        .unwrap_or_else(error_response)
+}
+
 #[post("/{project_id}/settings/create-api-key")]
 async fn create_api_key(
+    req: HttpRequest,
     project_id: web::Path<String>,
     user_info: ReqData<TokenInfo>,
     account_service: web::Data<AccountService>,
-    request: web::Json<ApiKeyRequest>,
+    request: web::Json<ProjectApiKeyRequest>,
 ) -> HttpResponse {
+    let actor = audit_actor(&req, &user_info);
     let user_id = user_info.into_inner().user_id;
     account_service
-        .create_project_api_key(user_id, &project_id, &request)
+        .create_project_api_key(&actor, user_id, &project_id, &request)
         .map(json_ok_response)
         .unwrap_or_else(error_response)
 }
@@ -337,26 +725,26 @@ async fn create_api_key(
 #[get("{project_id}/settings/api-keys")]
 async fn get_all_api_keys(
     project_id: web::Path<String>,
-    user_info: ReqData<TokenInfo>,
     account_service: web::Data<AccountService>,
 ) -> HttpResponse {
-    let user_id = user_info.into_inner().user_id;
     account_service
-        .list_project_api_keys(user_id, &project_id)
+        .list_project_api_keys(&project_id)
         .map(json_ok_response)
         .unwrap_or_else(error_response)
 }
 
 #[delete("{project_id}/settings/api-keys/{api_key_id}")]
 async fn delete_api_key(
-    path: web::Path<(String, i32)>,
+    req: HttpRequest,
     user_info: ReqData<TokenInfo>,
+    path: web::Path<(String, i32)>,
     account_service: web::Data<AccountService>,
+    rmq_auth_service: web::Data<RMQAuthService>,
 ) -> HttpResponse {
     let (project_id, api_key_id) = path.into_inner();
-    let user_id = user_info.into_inner().user_id;
     account_service
-        .delete_project_api_key(user_id, &project_id, api_key_id)
+        .delete_project_api_key(&audit_actor(&req, &user_info), &project_id, api_key_id)
+        .inspect(|_| rmq_auth_service.invalidate_project(&project_id))
         .map(json_ok_response)
         .unwrap_or_else(error_response)
 }
@@ -364,10 +752,11 @@ async fn delete_api_key(
 #[get("{project_id}/devices")]
 async fn list_devices(
     project_id: web::Path<String>,
+    query: web::Query<DeviceListQuery>,
     device_service: web::Data<DeviceService>,
 ) -> HttpResponse {
     device_service
-        .list_devices(&project_id)
+        .list_devices(&project_id, query.group.as_deref())
         .map(json_ok_response)
         .unwrap_or_else(error_response)
 }
@@ -386,14 +775,16 @@ async fn get_device(
 
 #[post("{project_id}/devices/register")]
 async fn register_device(
+    req: HttpRequest,
     project_id: web::Path<String>,
     user_info: ReqData<TokenInfo>,
     request: web::Json<DeviceRegisterRequest>,
     device_service: web::Data<DeviceService>,
-    notifier_service: web::Data<SessionNotifier>,
+    notifier_service: web::Data<DeviceNotifier>,
 ) -> HttpResponse {
     device_service
         .register_device(
+            &audit_actor(&req, &user_info),
             &project_id,
             user_info.into_inner().user_id,
             &request.into_inner(),
@@ -404,14 +795,367 @@ async fn register_device(
         .unwrap_or_else(error_response)
 }
 
+#[post("{project_id}/devices/register-bulk")]
+async fn register_devices(
+    project_id: web::Path<String>,
+    user_info: ReqData<TokenInfo>,
+    req: HttpRequest,
+    body: web::Bytes,
+    device_service: web::Data<DeviceService>,
+    notifier_service: web::Data<DeviceNotifier>,
+) -> HttpResponse {
+    let is_csv = req
+        .headers()
+        .get(actix_web::http::header::CONTENT_TYPE)
+        .and_then(|ct| ct.to_str().ok())
+        .is_some_and(|ct| ct.starts_with("text/csv"));
+
+    let registration_requests = if is_csv {
+        device_service::parse_devices_csv(&body)
+    } else {
+        serde_json::from_slice::<Vec<DeviceRegisterRequest>>(&body)
+            .map_err(|e| DeviceError::InvalidRequest(e.to_string()))
+    };
+
+    match registration_requests {
+        Ok(requests) => device_service
+            .register_devices(
+                &audit_actor(&req, &user_info),
+                &project_id,
+                user_info.into_inner().user_id,
+                &requests,
+                &notifier_service.into_inner(),
+            )
+            .await
+            .map(json_ok_response)
+            .unwrap_or_else(error_response),
+        Err(e) => error_response(e),
+    }
+}
+
+#[patch("{project_id}/devices/{device_id}")]
+async fn update_device(
+    req: HttpRequest,
+    user_info: ReqData<TokenInfo>,
+    path: web::Path<(String, String)>,
+    request: web::Json<DeviceUpdateRequest>,
+    device_service: web::Data<DeviceService>,
+    notifier_service: web::Data<DeviceNotifier>,
+    rmq_auth_service: web::Data<RMQAuthService>,
+) -> HttpResponse {
+    let (project_id, device_id) = path.into_inner();
+    device_service
+        .update_device(
+            &audit_actor(&req, &user_info),
+            &project_id,
+            &device_id,
+            &request.into_inner(),
+            &notifier_service.into_inner(),
+        )
+        .await
+        .inspect(|_| rmq_auth_service.invalidate_project(&project_id))
+        .map(json_ok_response)
+        .unwrap_or_else(error_response)
+}
+
 #[delete("{project_id}/devices/{device_id}")]
 async fn delete_device(
+    req: HttpRequest,
+    user_info: ReqData<TokenInfo>,
     path: web::Path<(String, String)>,
     device_service: web::Data<DeviceService>,
+    notifier_service: web::Data<DeviceNotifier>,
+    rmq_auth_service: web::Data<RMQAuthService>,
 ) -> HttpResponse {
     let (project_id, device_id) = path.into_inner();
     device_service
-        .delete_device(&project_id, &device_id)
+        .delete_device(
+            &audit_actor(&req, &user_info),
+            &project_id,
+            &device_id,
+            &notifier_service.into_inner(),
+        )
+        .await
+        .inspect(|_| rmq_auth_service.invalidate_project(&project_id))
+        .map(json_ok_response)
+        .unwrap_or_else(error_response)
+}
+
+#[get("{project_id}/device-groups")]
+async fn list_device_groups(
+    project_id: web::Path<String>,
+    device_service: web::Data<DeviceService>,
+) -> HttpResponse {
+    device_service
+        .list_groups(&project_id)
+        .map(json_ok_response)
+        .unwrap_or_else(error_response)
+}
+
+#[post("{project_id}/device-groups")]
+async fn create_device_group(
+    req: HttpRequest,
+    user_info: ReqData<TokenInfo>,
+    project_id: web::Path<String>,
+    request: web::Json<DeviceGroupRequest>,
+    device_service: web::Data<DeviceService>,
+) -> HttpResponse {
+    device_service
+        .create_group(
+            &audit_actor(&req, &user_info),
+            &project_id,
+            &request.into_inner(),
+        )
+        .map(json_ok_response)
+        .unwrap_or_else(error_response)
+}
+
+#[get("{project_id}/device-groups/{group_id}")]
+async fn get_device_group(
+    path: web::Path<(String, String)>,
+    device_service: web::Data<DeviceService>,
+) -> HttpResponse {
+    let (project_id, group_id) = path.into_inner();
+    device_service
+        .get_group(&project_id, &group_id)
+        .map(json_ok_response)
+        .unwrap_or_else(error_response)
+}
+
+#[patch("{project_id}/device-groups/{group_id}")]
+async fn update_device_group(
+    req: HttpRequest,
+    user_info: ReqData<TokenInfo>,
+    path: web::Path<(String, String)>,
+    request: web::Json<DeviceGroupUpdateRequest>,
+    device_service: web::Data<DeviceService>,
+    notifier_service: web::Data<DeviceNotifier>,
+    rmq_auth_service: web::Data<RMQAuthService>,
+) -> HttpResponse {
+    let (project_id, group_id) = path.into_inner();
+    device_service
+        .update_group(
+            &audit_actor(&req, &user_info),
+            &project_id,
+            &group_id,
+            &request.into_inner(),
+            &notifier_service.into_inner(),
+        )
+        .await
+        .inspect(|_| rmq_auth_service.invalidate_project(&project_id))
+        .map(json_ok_response)
+        .unwrap_or_else(error_response)
+}
+
+#[delete("{project_id}/device-groups/{group_id}")]
+async fn delete_device_group(
+    req: HttpRequest,
+    user_info: ReqData<TokenInfo>,
+    path: web::Path<(String, String)>,
+    device_service: web::Data<DeviceService>,
+    rmq_auth_service: web::Data<RMQAuthService>,
+) -> HttpResponse {
+    let (project_id, group_id) = path.into_inner();
+    device_service
+        .delete_group(&audit_actor(&req, &user_info), &project_id, &group_id)
+        .inspect(|_| rmq_auth_service.invalidate_project(&project_id))
+        .map(json_ok_response)
+        .unwrap_or_else(error_response)
+}
+
+#[get("{project_id}/members")]
+async fn list_members(
+    project_id: web::Path<String>,
+    member_service: web::Data<MemberService>,
+) -> HttpResponse {
+    member_service
+        .list_members(&project_id)
+        .map(json_ok_response)
+        .unwrap_or_else(error_response)
+}
+
+#[post("{project_id}/members/invite")]
+async fn invite_member(
+    project_id: web::Path<String>,
+    user_info: ReqData<TokenInfo>,
+    role: ReqData<ProjectRole>,
+    request: web::Json<ProjectInviteRequest>,
+    member_service: web::Data<MemberService>,
+) -> HttpResponse {
+    member_service
+        .invite(
+            &project_id,
+            user_info.into_inner().user_id,
+            role.into_inner(),
+            &request.into_inner(),
+        )
+        .map(json_ok_response)
+        .unwrap_or_else(error_response)
+}
+
+#[get("{project_id}/members/invitations")]
+async fn list_project_invitations(
+    project_id: web::Path<String>,
+    member_service: web::Data<MemberService>,
+) -> HttpResponse {
+    member_service
+        .list_project_invitations(&project_id)
+        .map(json_ok_response)
+        .unwrap_or_else(error_response)
+}
+
+#[delete("{project_id}/members/invitations/{invitation_id}")]
+async fn revoke_invitation(
+    path: web::Path<(String, String)>,
+    member_service: web::Data<MemberService>,
+) -> HttpResponse {
+    let (project_id, invitation_id) = path.into_inner();
+    member_service
+        .revoke_invitation(&project_id, &invitation_id)
+        .map(json_ok_response)
+        .unwrap_or_else(error_response)
+}
+
+#[patch("{project_id}/members/{user_id}")]
+async fn update_member_role(
+    path: web::Path<(String, i32)>,
+    role: ReqData<ProjectRole>,
+    request: web::Json<MemberRoleUpdateRequest>,
+    member_service: web::Data<MemberService>,
+) -> HttpResponse {
+    let (project_id, member_user_id) = path.into_inner();
+    member_service
+        .update_member_role(
+            &project_id,
+            role.into_inner(),
+            member_user_id,
+            &request.into_inner(),
+        )
+        .map(json_ok_response)
+        .unwrap_or_else(error_response)
+}
+
+#[delete("{project_id}/members/{user_id}")]
+async fn remove_member(
+    path: web::Path<(String, i32)>,
+    user_info: ReqData<TokenInfo>,
+    role: ReqData<ProjectRole>,
+    member_service: web::Data<MemberService>,
+    rmq_auth_service: web::Data<RMQAuthService>,
+) -> HttpResponse {
+    let (project_id, member_user_id) = path.into_inner();
+    member_service
+        .remove_member(
+            &project_id,
+            user_info.into_inner().user_id,
+            role.into_inner(),
+            member_user_id,
+        )
+        .inspect(|_| rmq_auth_service.invalidate_project(&project_id))
+        .map(json_ok_response)
+        .unwrap_or_else(error_response)
+}
+
+#[utoipa::path(
+    get,
+    path = "/projects/{project_id}/audit-events",
+    responses(
+        (status = 200, description = "Audit events, newest first", body = Vec<AuditEventResponse>),
+        (status = 400, description = "Bad Request"),
+        (status = 401, description = "Unauthorized"),
+        (status = 403, description = "Forbidden"),
+        (status = 500, description = "Internal Server Error/DatabaseError")
+    ),
+    params(
+        ("project_id", description = "The ID of the project to list the audit events of")
+    )
+)]
+#[get("{project_id}/audit-events")]
+async fn list_audit_events(
+    project_id: web::Path<String>,
+    query: web::Query<AuditEventQuery>,
+    audit_service: web::Data<AuditService>,
+) -> HttpResponse {
+    audit_service
+        .list_events(&project_id, &query)
+        .map(json_ok_response)
+        .unwrap_or_else(error_response)
+}
+
+#[utoipa::path(
+    get,
+    path = "/projects/{project_id}/audit-events/export",
+    responses(
+        (status = 200, description = "Every matching audit event as JSON lines, newest first"),
+        (status = 400, description = "Bad Request"),
+        (status = 401, description = "Unauthorized"),
+        (status = 403, description = "Forbidden"),
+        (status = 500, description = "Internal Server Error/DatabaseError")
+    ),
+    params(
+        ("project_id", description = "The ID of the project to export the audit events of")
+    )
+)]
+#[get("{project_id}/audit-events/export")]
+async fn export_audit_events(
+    project_id: web::Path<String>,
+    query: web::Query<AuditEventQuery>,
+    audit_service: web::Data<AuditService>,
+) -> HttpResponse {
+    let project_id = project_id.into_inner();
+    let mut export = audit_service.export_events(project_id.clone(), query.into_inner());
+    // Errors before the first batch still get a proper error response
+    let first_batch = match export.next().transpose() {
+        Ok(batch) => batch,
+        Err(e) => return error_response(e),
+    };
+    let batches = first_batch.map(Ok).into_iter().chain(export).map(|batch| {
+        batch.map(web::Bytes::from).map_err(|e| {
+            log::error!("Failed to export audit events: {}", e);
+            e
+        })
+    });
+
+    HttpResponse::Ok()
+        .content_type("application/x-ndjson")
+        .insert_header((
+            actix_web::http::header::CONTENT_DISPOSITION,
+            format!("attachment; filename=\"audit-events-{}.jsonl\"", project_id),
+        ))
+        .streaming(futures_util::stream::iter(batches))
+}
+
+#[get("/invitations")]
+async fn list_my_invitations(
+    user_info: ReqData<TokenInfo>,
+    member_service: web::Data<MemberService>,
+) -> HttpResponse {
+    member_service
+        .list_user_invitations(user_info.into_inner().user_id)
+        .map(json_ok_response)
+        .unwrap_or_else(error_response)
+}
+
+#[post("/invitations/{invitation_id}/accept")]
+async fn accept_invitation(
+    invitation_id: web::Path<String>,
+    user_info: ReqData<TokenInfo>,
+    member_service: web::Data<MemberService>,
+) -> HttpResponse {
+    member_service
+        .accept_invitation(user_info.into_inner().user_id, &invitation_id)
+        .map(json_ok_response)
+        .unwrap_or_else(error_response)
+}
+
+#[post("/invitations/{invitation_id}/decline")]
+async fn decline_invitation(
+    invitation_id: web::Path<String>,
+    user_info: ReqData<TokenInfo>,
+    member_service: web::Data<MemberService>,
+) -> HttpResponse {
+    member_service
+        .decline_invitation(user_info.into_inner().user_id, &invitation_id)
         .map(json_ok_response)
         .unwrap_or_else(error_response)
 }
@@ -420,20 +1164,39 @@ pub fn init_routes(
     cfg: &mut web::ServiceConfig,
     session_service: web::Data<SessionService>,
     device_service: web::Data<DeviceService>,
-    notifier_service: web::Data<SessionNotifier>,
+    notifier_service: web::Data<DeviceNotifier>,
+    rmq_auth_service: web::Data<RMQAuthService>,
+    audit_service: web::Data<AuditService>,
+    recording_service: web::Data<RecordingService>,
+    deletion_service: web::Data<DeletionService>,
 ) {
     let projects_scope = web::scope("/projects")
+        // Registered before Ownership so that only members use up project limits
+        .wrap(rate_limit_middleware::RateLimiting::Project)
         .wrap(ownership_middleware::Ownership)
         .app_data(session_service.clone())
         .app_data(notifier_service.clone())
+        .app_data(rmq_auth_service.clone())
+        .app_data(audit_service.clone())
+        .app_data(recording_service.clone())
+        .app_data(deletion_service.clone())
         .service(create_project)
+        .service(validate_project)
         .service(list_projects)
         .service(summarize_projects)
+        .service(list_my_invitations)
+        .service(accept_invitation)
+        .service(decline_invitation)
         .service(get_project)
+        .service(update_project)
         .service(delete_project)
+        .service(restore_project)
+        .service(list_deletions)
+        .service(rotate_project_secrets)
         .service(summarize_project)
         .service(create_session)
         .service(delete_session)
+        .service(restore_session)
         .service(generate_session_token)
         .service(get_participants)
         .service(get_sessions)
@@ -441,6 +1204,13 @@ pub fn init_routes(
         .service(stop_session)
         .service(get_session_egresses)
         .service(get_egress_media_download_url)
+        .service(list_session_media)
+        .service(list_consents)
+        .service(record_consent)
+        .service(withdraw_consent)
+        .service(get_storage_usage)
+        .service(get_retention_policy)
+        .service(set_retention_policy)
         .service(create_api_key)
         .service(get_all_api_keys)
         .service(delete_api_key)
@@ -448,7 +1218,22 @@ pub fn init_routes(
         .service(list_devices)
         .service(get_device)
         .service(register_device)
-        .service(delete_device);
+        .service(register_devices)
+        .service(update_device)
+        .service(delete_device)
+        .service(list_device_groups)
+        .service(create_device_group)
+        .service(get_device_group)
+        .service(update_device_group)
+        .service(delete_device_group)
+        .service(list_members)
+        .service(invite_member)
+        .service(list_project_invitations)
+        .service(revoke_invitation)
+        .service(update_member_role)
+        .service(remove_member)
+        .service(list_audit_events)
+        .service(export_audit_events);
 
     cfg.service(projects_scope);
 }
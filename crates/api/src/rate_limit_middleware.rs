@@ -0,0 +1,143 @@
+use std::future::{ready, Ready};
+
+use actix_web::http::Method;
+use actix_web::{
+    body::EitherBody,
+    dev::{forward_ready, Service, ServiceRequest, ServiceResponse, Transform},
+    web::Data,
+    Error,
+};
+use application::rate_limit::{RateLimiter, RateLimits};
+use futures_util::future::LocalBoxFuture;
+use log::warn;
+
+use crate::helpers::{client_ip, too_many_requests};
+
+/// Routes anyone can call to log in or manage an account, limited per IP.
+const AUTH_ROUTES: [&str; 6] = [
+    "/users/login",
+    "/users/signup",
+    "/users/refresh-token",
+    "/users/password-reset",
+    "/users/email-verification/confirm",
+    "/oauth/",
+];
+
+const RMQ_AUTH_ROUTES: &str = "/rmq/auth/";
+
+/// Throttles requests, answering `429` with a `Retry-After` header once a
+/// bucket is empty. Per account limits are applied by the login handlers,
+/// which know the account.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum RateLimiting {
+    /// The auth and broker auth routes, per client IP. Wraps the app.
+    Client,
+    /// Session creation and token minting, per project. Wraps the project
+    /// routes inside `Ownership`, so only members of the project use up
+    /// its buckets.
+    Project,
+}
+
+/// Limiter and bucket key for a request, if it is throttled at all.
+fn limit_for<'a>(
+    scope: RateLimiting,
+    limits: &'a RateLimits,
+    req: &ServiceRequest,
+) -> Option<(&'a RateLimiter, String)> {
+    let path = req.path();
+
+    if scope == RateLimiting::Client {
+        if AUTH_ROUTES.iter().any(|route| path.starts_with(route)) {
+            return Some((&limits.auth_per_ip, format!("auth:{}", client_ip(req)?)));
+        }
+
+        if path.starts_with(RMQ_AUTH_ROUTES) {
+            return Some((&limits.rmq_auth_per_ip, format!("rmq:{}", client_ip(req)?)));
+        }
+
+        return None;
+    }
+
+    if req.method() != Method::POST {
+        return None;
+    }
+
+    let segments = path
+        .strip_prefix("/projects/")?
+        .split('/')
+        .filter(|segment| !segment.is_empty())
+        .collect::<Vec<_>>();
+    match segments.as_slice() {
+        [project_id, "create-session"] => Some((
+            &limits.sessions_per_project,
+            format!("sessions:{}", project_id),
+        )),
+        [project_id, "sessions", _, "token"] => {
+            Some((&limits.tokens_per_project, format!("tokens:{}", project_id)))
+        }
+        _ => None,
+    }
+}
+
+impl<S, B> Transform<S, ServiceRequest> for RateLimiting
+where
+    S: Service<ServiceRequest, Response = ServiceResponse<B>, Error = Error>,
+    S::Future: 'static,
+    B: 'static,
+{
+    type Response = ServiceResponse<EitherBody<B>>;
+    type Error = Error;
+    type Transform = RateLimitingMiddleware<S>;
+    type InitError = ();
+    type Future = Ready<Result<Self::Transform, Self::InitError>>;
+
+    fn new_transform(&self, service: S) -> Self::Future {
+        ready(Ok(RateLimitingMiddleware {
+            service,
+            scope: *self,
+        }))
+    }
+}
+
+pub struct RateLimitingMiddleware<S> {
+    service: S,
+    scope: RateLimiting,
+}
+
+impl<S, B> Service<ServiceRequest> for RateLimitingMiddleware<S>
+where
+    S: Service<ServiceRequest, Response = ServiceResponse<B>, Error = Error>,
+    S::Future: 'static,
+    B: 'static,
+{
+    type Response = ServiceResponse<EitherBody<B>>;
+    type Error = Error;
+    type Future = LocalBoxFuture<'static, Result<Self::Response, Self::Error>>;
+
+    forward_ready!(service);
+
+    fn call(&self, req: ServiceRequest) -> Self::Future {
+        let decision = match req.app_data::<Data<RateLimits>>() {
+            Some(limits) if Method::OPTIONS != *req.method() => limit_for(self.scope, limits, &req)
+                .map(|(limiter, key)| limiter.check(&key).map_err(|retry| (key, retry)))
+                .unwrap_or(Ok(())),
+            _ => Ok(()),
+        };
+
+        match decision {
+            Ok(()) => {
+                let fut = self.service.call(req);
+                Box::pin(async move {
+                    let res = fut.await?;
+                    Ok(res.map_body(|_, _body| EitherBody::left(_body)))
+                })
+            }
+            Err((key, retry_after)) => {
+                warn!("Rate limit exceeded for {} on {}", key, req.path());
+                let resp = too_many_requests(retry_after);
+                let (request, _pl) = req.into_parts();
+                Box::pin(async { Ok(ServiceResponse::new(request, resp.map_into_right_body())) })
+            }
+        }
+    }
+}
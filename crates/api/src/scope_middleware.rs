@@ -0,0 +1,141 @@
+use std::future::{ready, Ready};
+
+use actix_web::http::Method;
+use actix_web::{
+    body::EitherBody,
+    dev::{forward_ready, Service, ServiceRequest, ServiceResponse, Transform},
+    Error, HttpMessage, HttpResponse,
+};
+use application::users::tokens_manager::{ApiKeyGrant, TokenInfo};
+use futures_util::future::LocalBoxFuture;
+use shared::api_key_scopes::ApiKeyScope;
+use shared::response_models::Response;
+
+use crate::helpers::client_ip;
+
+/// Restricts requests authenticated with a project API key to the project the
+/// key belongs to, the scopes it was granted and its IP allow-list. Requests
+/// made with user tokens pass through untouched.
+pub struct ApiKeyScopes;
+
+/// Scope needed for a project route, keyed by the method and the path
+/// segments that follow the project id.
+fn required_scope(method: &Method, segments: &[&str]) -> ApiKeyScope {
+    match segments {
+        [] | ["summarize" | "deletions" | "storage-usage"] if method == Method::GET => {
+            ApiKeyScope::ProjectRead
+        }
+        ["sessions", ..] if method == Method::GET => ApiKeyScope::SessionsRead,
+        ["create-session"] | ["sessions", _, "stop"] if method == Method::POST => {
+            ApiKeyScope::SessionsWrite
+        }
+        ["sessions", _] if method == Method::DELETE => ApiKeyScope::SessionsWrite,
+        ["sessions", _, "restore"] if method == Method::POST => ApiKeyScope::SessionsWrite,
+        ["sessions", _, "consents", ..] if method == Method::POST => ApiKeyScope::SessionsWrite,
+        ["sessions", _, "token"] if method == Method::POST => ApiKeyScope::TokensMint,
+        ["sessions", _, "get-media-url"] if method == Method::POST => ApiKeyScope::MediaDownload,
+        ["devices" | "device-groups", ..] if method == Method::GET => ApiKeyScope::DevicesRead,
+        ["devices", "register" | "register-bulk"] if method == Method::POST => {
+            ApiKeyScope::DevicesRegister
+        }
+        ["devices" | "device-groups", ..] => ApiKeyScope::DevicesWrite,
+        _ => ApiKeyScope::ProjectAdmin,
+    }
+}
+
+fn check_grant(
+    req: &ServiceRequest,
+    key_project_id: Option<&str>,
+    grant: &ApiKeyGrant,
+) -> Result<(), String> {
+    if !grant.allowed_ips.is_empty() {
+        let allowed = client_ip(req).is_some_and(|address| grant.allows_ip(address));
+        if !allowed {
+            return Err("The API key is not allowed from this address".to_string());
+        }
+    }
+
+    // Project keys only ever reach routes of their own project
+    let mut segments = match req.path().strip_prefix("/projects/") {
+        Some(path) => path.split('/').filter(|segment| !segment.is_empty()),
+        None => return Err("The API key does not grant access to this resource".to_string()),
+    };
+
+    if key_project_id.is_none() || segments.next() != key_project_id {
+        return Err("The API key does not grant access to this resource".to_string());
+    }
+
+    let required = required_scope(req.method(), &segments.collect::<Vec<_>>());
+    if grant.allows_scope(required) {
+        Ok(())
+    } else {
+        Err(format!("The API key lacks the {} scope", required.as_str()))
+    }
+}
+
+impl<S, B> Transform<S, ServiceRequest> for ApiKeyScopes
+where
+    S: Service<ServiceRequest, Response = ServiceResponse<B>, Error = Error>,
+    S::Future: 'static,
+    B: 'static,
+{
+    type Response = ServiceResponse<EitherBody<B>>;
+    type Error = Error;
+    type Transform = ApiKeyScopesMiddleware<S>;
+    type InitError = ();
+    type Future = Ready<Result<Self::Transform, Self::InitError>>;
+
+    fn new_transform(&self, service: S) -> Self::Future {
+        ready(Ok(ApiKeyScopesMiddleware { service }))
+    }
+}
+
+pub struct ApiKeyScopesMiddleware<S> {
+    service: S,
+}
+
+impl<S, B> Service<ServiceRequest> for ApiKeyScopesMiddleware<S>
+where
+    S: Service<ServiceRequest, Response = ServiceResponse<B>, Error = Error>,
+    S::Future: 'static,
+    B: 'static,
+{
+    type Response = ServiceResponse<EitherBody<B>>;
+    type Error = Error;
+    type Future = LocalBoxFuture<'static, Result<Self::Response, Self::Error>>;
+
+    forward_ready!(service);
+
+    fn call(&self, req: ServiceRequest) -> Self::Future {
+        let token_info = req.extensions().get::<TokenInfo>().cloned();
+        let decision = match token_info {
+            Some(TokenInfo {
+                project_id,
+                api_key: Some(grant),
+                ..
+            }) if Method::OPTIONS != *req.method() => {
+                check_grant(&req, project_id.as_deref(), &grant)
+            }
+            _ => Ok(()),
+        };
+
+        match decision {
+            Ok(()) => {
+                let fut = self.service.call(req);
+                Box::pin(async move {
+                    let res = fut.await?;
+                    Ok(res.map_body(|_, _body| EitherBody::left(_body)))
+                })
+            }
+            Err(message) => {
+                let resp: HttpResponse = Response {
+                    status: 403,
+                    message,
+                }
+                .into();
+                let (request, _pl) = req.into_parts();
+                Box::pin(async { Ok(ServiceResponse::new(request, resp.map_into_right_body())) })
+            }
+        }
+    }
+}
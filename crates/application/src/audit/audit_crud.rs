@@ -0,0 +1,156 @@
+use diesel::pg::Pg;
+use diesel::{prelude::*, PgConnection};
+use domain::models::{AuditEvent, AuditResult, NewAuditEvent};
+use domain::schema::syncflow::audit_events;
+use shared::audit_models::AuditEventQuery;
+use shared::response_models::Response;
+use thiserror::Error;
+use uuid::Uuid;
+
+#[derive(Error, Debug)]
+pub enum AuditError {
+    #[error("Database Error: {0}")]
+    DatabaseError(#[from] diesel::result::Error),
+
+    #[error("Database Pool Error: {0}")]
+    PoolError(#[from] diesel::r2d2::PoolError),
+
+    #[error("Invalid Request: {0}")]
+    InvalidRequest(String),
+
+    #[error("Export Error: {0}")]
+    ExportError(#[from] serde_json::Error),
+}
+
+impl From<AuditError> for Response {
+    fn from(error: AuditError) -> Self {
+        match error {
+            AuditError::DatabaseError(e) => Response {
+                status: 500,
+                message: e.to_string(),
+            },
+            AuditError::PoolError(e) => Response {
+                status: 500,
+                message: e.to_string(),
+            },
+            AuditError::InvalidRequest(e) => Response {
+                status: 400,
+                message: e,
+            },
+            AuditError::ExportError(e) => Response {
+                status: 500,
+                message: e.to_string(),
+            },
+        }
+    }
+}
+
+pub fn insert_event(event: &NewAuditEvent, conn: &mut PgConnection) -> Result<(), AuditError> {
+    diesel::insert_into(audit_events::table)
+        .values(event)
+        .execute(conn)?;
+    Ok(())
+}
+
+fn parse_result(value: &str) -> Result<AuditResult, AuditError> {
+    match value {
+        "success" => Ok(AuditResult::Success),
+        "failure" => Ok(AuditResult::Failure),
+        _ => Err(AuditError::InvalidRequest(format!(
+            "Invalid result {}, expected success or failure",
+            value
+        ))),
+    }
+}
+
+fn parse_timestamp(value: usize) -> Result<chrono::NaiveDateTime, AuditError> {
+    chrono::DateTime::from_timestamp(value as i64, 0)
+        .map(|time| time.naive_utc())
+        .ok_or_else(|| AuditError::InvalidRequest(format!("Invalid timestamp {}", value)))
+}
+
+/// The events of a project matching the query, newest first.
+fn filtered_events<'a>(
+    proj_id: &str,
+    query: &'a AuditEventQuery,
+) -> Result<audit_events::BoxedQuery<'a, Pg>, AuditError> {
+    use domain::schema::syncflow::audit_events::dsl::*;
+
+    let proj_uuid = Uuid::parse_str(proj_id)
+        .map_err(|_| AuditError::InvalidRequest("Invalid project id".to_string()))?;
+    let mut events = audit_events
+        .filter(project_id.eq(proj_uuid))
+        .order(id.desc())
+        .into_boxed();
+
+    if let Some(value) = &query.action {
+        events = events.filter(action.eq(value));
+    }
+    if let Some(value) = query.actor_user_id {
+        events = events.filter(actor_user_id.eq(value));
+    }
+    if let Some(value) = query.actor_api_key_id {
+        events = events.filter(actor_api_key_id.eq(value));
+    }
+    if let Some(value) = &query.target_type {
+        events = events.filter(target_type.eq(value));
+    }
+    if let Some(value) = &query.target_id {
+        events = events.filter(target_id.eq(value));
+    }
+    if let Some(value) = &query.result {
+        events = events.filter(result.eq(parse_result(value)?));
+    }
+    if let Some(value) = query.since {
+        events = events.filter(occurred_at.ge(parse_timestamp(value)?));
+    }
+    if let Some(value) = query.until {
+        events = events.filter(occurred_at.le(parse_timestamp(value)?));
+    }
+
+    Ok(events)
+}
+
+pub fn list_events(
+    proj_id: &str,
+    query: &AuditEventQuery,
+    limit: i64,
+    offset: i64,
+    conn: &mut PgConnection,
+) -> Result<Vec<AuditEvent>, AuditError> {
+    Ok(filtered_events(proj_id, query)?
+        .limit(limit)
+        .offset(offset)
+        .load::<AuditEvent>(conn)?)
+}
+
+/// A batch of matching events older than `before_id`, for paging through the
+/// whole log while events are being appended.
+pub fn list_events_before(
+    proj_id: &str,
+    query: &AuditEventQuery,
+    before_id: Option<i64>,
+    limit: i64,
+    conn: &mut PgConnection,
+) -> Result<Vec<AuditEvent>, AuditError> {
+    let mut events = filtered_events(proj_id, query)?;
+    if let Some(before_id) = before_id {
+        events = events.filter(audit_events::id.lt(before_id));
+    }
+    Ok(events.limit(limit).load::<AuditEvent>(conn)?)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_result() {
+        assert_eq!(parse_result("success").unwrap(), AuditResult::Success);
+        assert_eq!(parse_result("failure").unwrap(), AuditResult::Failure);
+        assert!(matches!(
+            parse_result("failed"),
+            Err(AuditError::InvalidRequest(_))
+        ));
+    }
+}
@@ -0,0 +1,151 @@
+use std::fmt::Display;
+use std::sync::Arc;
+
+use domain::models::{AuditResult, NewAuditEvent};
+use infrastructure::DbPool;
+use shared::audit_models::{AuditEventQuery, AuditEventResponse};
+use uuid::Uuid;
+
+use super::audit_crud::{self, AuditError};
+use super::{AuditAction, AuditActor, AuditTarget};
+
+pub const DEFAULT_AUDIT_PAGE_SIZE: i64 = 100;
+pub const MAX_AUDIT_PAGE_SIZE: i64 = 1000;
+const EXPORT_BATCH_SIZE: i64 = 1000;
+
+pub struct AuditService {
+    pool: Arc<DbPool>,
+}
+
+impl AuditService {
+    pub fn new(pool: Arc<DbPool>) -> Self {
+        Self { pool }
+    }
+
+    /// Appends an event for the outcome of an action. A failed write is only
+    /// logged, the log must not make the action itself fail.
+    pub fn record<T, E: Display>(
+        &self,
+        actor: &AuditActor,
+        action: AuditAction,
+        target: AuditTarget,
+        outcome: &Result<T, E>,
+    ) {
+        let (result, error_message) = match outcome {
+            Ok(_) => (AuditResult::Success, None),
+            Err(e) => (AuditResult::Failure, Some(e.to_string())),
+        };
+        let event = NewAuditEvent {
+            actor_user_id: actor.user_id,
+            actor_api_key_id: actor.api_key_id,
+            ip_address: actor.ip_address.clone(),
+            action: action.as_str().to_string(),
+            target_type: target.target_type.as_str().to_string(),
+            target_id: target.id,
+            project_id: target
+                .project_id
+                .and_then(|project_id| Uuid::parse_str(&project_id).ok()),
+            result,
+            error_message,
+        };
+
+        let written = self
+            .pool
+            .get()
+            .map_err(|e| e.to_string())
+            .and_then(|mut conn| {
+                audit_crud::insert_event(&event, &mut conn).map_err(|e| e.to_string())
+            });
+        if let Err(e) = written {
+            log::error!("Failed to write the {} audit event: {}", action.as_str(), e);
+        }
+    }
+
+    pub fn list_events(
+        &self,
+        project_id: &str,
+        query: &AuditEventQuery,
+    ) -> Result<Vec<AuditEventResponse>, AuditError> {
+        let limit = query
+            .limit
+            .unwrap_or(DEFAULT_AUDIT_PAGE_SIZE)
+            .clamp(1, MAX_AUDIT_PAGE_SIZE);
+        let offset = query.offset.unwrap_or(0).max(0);
+
+        let events = audit_crud::list_events(
+            project_id,
+            query,
+            limit,
+            offset,
+            &mut self.pool.get().unwrap(),
+        )?;
+        Ok(events.into_iter().map(Into::into).collect())
+    }
+
+    /// Every matching event as JSON lines, newest first, read in batches as
+    /// the export is consumed.
+    pub fn export_events(&self, project_id: String, query: AuditEventQuery) -> AuditExport {
+        AuditExport {
+            pool: self.pool.clone(),
+            project_id,
+            query,
+            before_id: None,
+            done: false,
+        }
+    }
+}
+
+/// Iterates over the JSON lines of an audit log export, one batch of events
+/// per item. Each batch takes a connection from the pool only while it loads.
+pub struct AuditExport {
+    pool: Arc<DbPool>,
+    project_id: String,
+    query: AuditEventQuery,
+    before_id: Option<i64>,
+    done: bool,
+}
+
+impl AuditExport {
+    fn next_batch(&mut self) -> Result<Option<String>, AuditError> {
+        let events = audit_crud::list_events_before(
+            &self.project_id,
+            &self.query,
+            self.before_id,
+            EXPORT_BATCH_SIZE,
+            &mut self.pool.get()?,
+        )?;
+        let Some(last) = events.last() else {
+            return Ok(None);
+        };
+        self.before_id = Some(last.id);
+
+        let mut lines = String::new();
+        for event in events {
+            lines.push_str(&serde_json::to_string(&AuditEventResponse::from(event))?);
+            lines.push('\n');
+        }
+        Ok(Some(lines))
+    }
+}
+
+impl Iterator for AuditExport {
+    type Item = Result<String, AuditError>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        if self.done {
+            return None;
+        }
+
+        let batch = self.next_batch().transpose();
+        self.done = !matches!(batch, Some(Ok(_)));
+        batch
+    }
+}
+
+impl Clone for AuditService {
+    fn clone(&self) -> Self {
+        Self {
+            pool: self.pool.clone(),
+        }
+    }
+}
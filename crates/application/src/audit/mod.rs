@@ -0,0 +1,186 @@
+pub mod audit_crud;
+pub mod audit_service;
+
+use crate::users::tokens_manager::TokenInfo;
+
+/// Who performed an audited action. Requests made with a project API key are
+/// attributed to the key as well as to the user who created it.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct AuditActor {
+    pub user_id: Option<i32>,
+    pub api_key_id: Option<i32>,
+    pub ip_address: Option<String>,
+}
+
+impl AuditActor {
+    pub fn new(token_info: &TokenInfo, ip_address: Option<String>) -> Self {
+        AuditActor {
+            user_id: Some(token_info.user_id),
+            api_key_id: token_info.api_key.as_ref().map(|grant| grant.key_id),
+            ip_address,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum AuditAction {
+    Login,
+    PasswordChanged,
+    LoginSessionRevoked,
+    AccountDeleted,
+    ApiKeyCreated,
+    ApiKeyDeleted,
+    ProjectCreated,
+    ProjectUpdated,
+    ProjectSecretsRotated,
+    ProjectDeleted,
+    ProjectRestored,
+    ProjectPurged,
+    SessionCreated,
+    SessionStopped,
+    SessionDeleted,
+    SessionRestored,
+    SessionPurged,
+    SessionTokenIssued,
+    RecordingDownloaded,
+    RecordingPurged,
+    RecordingArchived,
+    RetentionPolicyUpdated,
+    ConsentRecorded,
+    ConsentWithdrawn,
+    DeviceRegistered,
+    DeviceUpdated,
+    DeviceDeleted,
+    DeviceGroupCreated,
+    DeviceGroupUpdated,
+    DeviceGroupDeleted,
+}
+
+impl AuditAction {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            AuditAction::Login => "login",
+            AuditAction::PasswordChanged => "password_changed",
+            AuditAction::LoginSessionRevoked => "login_session_revoked",
+            AuditAction::AccountDeleted => "account_deleted",
+            AuditAction::ApiKeyCreated => "api_key_created",
+            AuditAction::ApiKeyDeleted => "api_key_deleted",
+            AuditAction::ProjectCreated => "project_created",
+            AuditAction::ProjectUpdated => "project_updated",
+            AuditAction::ProjectSecretsRotated => "project_secrets_rotated",
+            AuditAction::ProjectDeleted => "project_deleted",
+            AuditAction::ProjectRestored => "project_restored",
+            AuditAction::ProjectPurged => "project_purged",
+            AuditAction::SessionCreated => "session_created",
+            AuditAction::SessionStopped => "session_stopped",
+            AuditAction::SessionDeleted => "session_deleted",
+            AuditAction::SessionRestored => "session_restored",
+            AuditAction::SessionPurged => "session_purged",
+            AuditAction::SessionTokenIssued => "session_token_issued",
+            AuditAction::RecordingDownloaded => "recording_downloaded",
+            AuditAction::RecordingPurged => "recording_purged",
+            AuditAction::RecordingArchived => "recording_archived",
+            AuditAction::RetentionPolicyUpdated => "retention_policy_updated",
+            AuditAction::ConsentRecorded => "consent_recorded",
+            AuditAction::ConsentWithdrawn => "consent_withdrawn",
+            AuditAction::DeviceRegistered => "device_registered",
+            AuditAction::DeviceUpdated => "device_updated",
+            AuditAction::DeviceDeleted => "device_deleted",
+            AuditAction::DeviceGroupCreated => "device_group_created",
+            AuditAction::DeviceGroupUpdated => "device_group_updated",
+            AuditAction::DeviceGroupDeleted => "device_group_deleted",
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum AuditTargetType {
+    User,
+    LoginSession,
+    ApiKey,
+    Project,
+    ProjectApiKey,
+    Session,
+    Recording,
+    Consent,
+    Device,
+    DeviceGroup,
+}
+
+impl AuditTargetType {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            AuditTargetType::User => "user",
+            AuditTargetType::LoginSession => "login_session",
+            AuditTargetType::ApiKey => "api_key",
+            AuditTargetType::Project => "project",
+            AuditTargetType::ProjectApiKey => "project_api_key",
+            AuditTargetType::Session => "session",
+            AuditTargetType::Recording => "recording",
+            AuditTargetType::Consent => "consent",
+            AuditTargetType::Device => "device",
+            AuditTargetType::DeviceGroup => "device_group",
+        }
+    }
+}
+
+/// What an audited action was performed on. The id is unknown for creations
+/// that failed.
+#[derive(Debug, Clone)]
+pub struct AuditTarget {
+    pub target_type: AuditTargetType,
+    pub id: Option<String>,
+    pub project_id: Option<String>,
+}
+
+impl AuditTarget {
+    pub fn new(target_type: AuditTargetType, id: Option<String>) -> Self {
+        AuditTarget {
+            target_type,
+            id,
+            project_id: None,
+        }
+    }
+
+    pub fn in_project(project_id: &str, target_type: AuditTargetType, id: Option<String>) -> Self {
+        AuditTarget {
+            target_type,
+            id,
+            project_id: Some(project_id.to_string()),
+        }
+    }
+
+    pub fn project(project_id: &str) -> Self {
+        Self::in_project(
+            project_id,
+            AuditTargetType::Project,
+            Some(project_id.to_string()),
+        )
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::users::tokens_manager::ApiKeyGrant;
+
+    #[test]
+    fn test_actor_of_project_key_names_the_key() {
+        let token_info = TokenInfo {
+            user_id: 7,
+            user_name: "owner".to_string(),
+            login_session: None,
+            project_id: Some("project".to_string()),
+            api_key: Some(ApiKeyGrant {
+                key_id: 42,
+                allowed_ips: vec![],
+                scopes: vec![],
+            }),
+        };
+
+        let actor = AuditActor::new(&token_info, Some("10.0.0.1".to_string()));
+        assert_eq!(actor.user_id, Some(7));
+        assert_eq!(actor.api_key_id, Some(42));
+        assert_eq!(actor.ip_address.as_deref(), Some("10.0.0.1"));
+    }
+}
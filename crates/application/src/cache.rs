@@ -0,0 +1,131 @@
+use std::collections::HashMap;
+use std::hash::Hash;
+use std::sync::Mutex;
+use std::time::{Duration, Instant};
+
+/// A bounded, thread-safe cache whose entries expire after a fixed time to live.
+///
+/// When the cache is full, expired entries are dropped first and then the
+/// oldest entry is evicted to make room.
+pub struct TtlCache<K, V> {
+    entries: Mutex<HashMap<K, (Instant, V)>>,
+    ttl: Duration,
+    capacity: usize,
+}
+
+impl<K, V> TtlCache<K, V>
+where
+    K: Eq + Hash + Clone,
+    V: Clone,
+{
+    pub fn new(ttl: Duration, capacity: usize) -> Self {
+        Self {
+            entries: Mutex::new(HashMap::new()),
+            ttl,
+            capacity,
+        }
+    }
+
+    pub fn get(&self, key: &K) -> Option<V> {
+        let mut entries = self.entries.lock().unwrap();
+        match entries.get(key) {
+            Some((inserted_at, value)) if inserted_at.elapsed() < self.ttl => Some(value.clone()),
+            Some(_) => {
+                entries.remove(key);
+                None
+            }
+            None => None,
+        }
+    }
+
+    pub fn insert(&self, key: K, value: V) {
+        if self.capacity == 0 {
+            return;
+        }
+
+        let mut entries = self.entries.lock().unwrap();
+        if entries.len() >= self.capacity && !entries.contains_key(&key) {
+            entries.retain(|_, (inserted_at, _)| inserted_at.elapsed() < self.ttl);
+            if entries.len() >= self.capacity {
+                let oldest = entries
+                    .iter()
+                    .min_by_key(|(_, (inserted_at, _))| *inserted_at)
+                    .map(|(k, _)| k.clone());
+                if let Some(oldest) = oldest {
+                    entries.remove(&oldest);
+                }
+            }
+        }
+        entries.insert(key, (Instant::now(), value));
+    }
+
+    pub fn remove(&self, key: &K) -> Option<V> {
+        let mut entries = self.entries.lock().unwrap();
+        entries.remove(key).map(|(_, value)| value)
+    }
+
+    /// Removes every entry for which `predicate` returns true.
+    pub fn invalidate_where<F>(&self, predicate: F)
+    where
+        F: Fn(&K, &V) -> bool,
+    {
+        let mut entries = self.entries.lock().unwrap();
+        entries.retain(|key, (_, value)| !predicate(key, value));
+    }
+
+    pub fn clear(&self) {
+        self.entries.lock().unwrap().clear();
+    }
+
+    pub fn len(&self) -> usize {
+        self.entries.lock().unwrap().len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_get_and_expire() {
+        let cache = TtlCache::new(Duration::from_millis(50), 10);
+        cache.insert("key", 1);
+        assert_eq!(cache.get(&"key"), Some(1));
+
+        std::thread::sleep(Duration::from_millis(60));
+        assert_eq!(cache.get(&"key"), None);
+        assert!(cache.is_empty());
+    }
+
+    #[test]
+    fn test_capacity_evicts_oldest() {
+        let cache = TtlCache::new(Duration::from_secs(60), 2);
+        cache.insert("first", 1);
+        std::thread::sleep(Duration::from_millis(2));
+        cache.insert("second", 2);
+        std::thread::sleep(Duration::from_millis(2));
+        cache.insert("third", 3);
+
+        assert_eq!(cache.len(), 2);
+        assert_eq!(cache.get(&"first"), None);
+        assert_eq!(cache.get(&"second"), Some(2));
+        assert_eq!(cache.get(&"third"), Some(3));
+    }
+
+    #[test]
+    fn test_invalidate_where() {
+        let cache = TtlCache::new(Duration::from_secs(60), 10);
+        cache.insert("a", "project-1");
+        cache.insert("b", "project-2");
+        cache.insert("c", "project-1");
+
+        cache.invalidate_where(|_, project| *project == "project-1");
+
+        assert_eq!(cache.len(), 1);
+        assert_eq!(cache.get(&"b"), Some("project-2"));
+    }
+}
@@ -1,5 +1,12 @@
+pub mod audit;
+pub mod cache;
 pub mod livekit;
+pub mod mailer;
+pub mod notifier;
+pub mod organizations;
 pub mod project;
+pub mod rate_limit;
+pub mod recordings;
 pub mod rmq;
-pub mod s3;
+pub mod storage;
 pub mod users;
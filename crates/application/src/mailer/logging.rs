@@ -0,0 +1,20 @@
+use async_trait::async_trait;
+
+use super::{Email, Mailer, MailerError};
+
+/// Writes emails to the log instead of sending them. Links in account emails
+/// are single use secrets, so this is meant for development only.
+pub struct LogMailer;
+
+#[async_trait]
+impl Mailer for LogMailer {
+    async fn send(&self, email: Email) -> Result<(), MailerError> {
+        log::info!(
+            "Email to {}, subject: {}\n{}",
+            email.to,
+            email.subject,
+            email.body
+        );
+        Ok(())
+    }
+}
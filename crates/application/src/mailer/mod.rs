@@ -0,0 +1,38 @@
+use std::sync::Arc;
+
+use async_trait::async_trait;
+use shared::deployment_config::{DeploymentConfig, MailerConfig};
+use thiserror::Error;
+
+pub mod logging;
+pub mod smtp;
+
+#[derive(Debug, Error)]
+pub enum MailerError {
+    #[error("Invalid address: {0}")]
+    AddressError(String),
+
+    #[error("SMTP Error: {0}")]
+    SmtpError(String),
+}
+
+/// A plain text email.
+#[derive(Debug, Clone)]
+pub struct Email {
+    pub to: String,
+    pub subject: String,
+    pub body: String,
+}
+
+/// A backend that delivers account emails, such as password reset links.
+#[async_trait]
+pub trait Mailer: Send + Sync {
+    async fn send(&self, email: Email) -> Result<(), MailerError>;
+}
+
+pub fn from_config(config: &DeploymentConfig) -> Result<Arc<dyn Mailer>, MailerError> {
+    match &config.mailer_config {
+        None | Some(MailerConfig::Log) => Ok(Arc::new(logging::LogMailer)),
+        Some(MailerConfig::Smtp(smtp_config)) => Ok(Arc::new(smtp::SmtpMailer::new(smtp_config)?)),
+    }
+}
@@ -0,0 +1,71 @@
+use async_trait::async_trait;
+use lettre::message::{header::ContentType, Mailbox};
+use lettre::transport::smtp::authentication::Credentials;
+use lettre::{AsyncSmtpTransport, AsyncTransport, Message, Tokio1Executor};
+use shared::deployment_config::{SmtpConfig, SmtpSecurity};
+
+use super::{Email, Mailer, MailerError};
+
+/// Sends emails through an SMTP relay.
+pub struct SmtpMailer {
+    transport: AsyncSmtpTransport<Tokio1Executor>,
+    from: Mailbox,
+}
+
+impl SmtpMailer {
+    pub fn new(smtp_config: &SmtpConfig) -> Result<Self, MailerError> {
+        let host = smtp_config.host.as_str();
+        let mut builder = match smtp_config.security.unwrap_or(SmtpSecurity::StartTls) {
+            SmtpSecurity::StartTls => AsyncSmtpTransport::<Tokio1Executor>::starttls_relay(host)
+                .map_err(|e| MailerError::SmtpError(e.to_string()))?,
+            SmtpSecurity::Tls => AsyncSmtpTransport::<Tokio1Executor>::relay(host)
+                .map_err(|e| MailerError::SmtpError(e.to_string()))?,
+            SmtpSecurity::None => AsyncSmtpTransport::<Tokio1Executor>::builder_dangerous(host),
+        };
+
+        if let Some(port) = smtp_config.port {
+            builder = builder.port(port);
+        }
+        if let Some(username) = &smtp_config.username {
+            builder = builder.credentials(Credentials::new(
+                username.clone(),
+                smtp_config.password.clone().unwrap_or_default(),
+            ));
+        }
+
+        let from = smtp_config
+            .from
+            .parse::<Mailbox>()
+            .map_err(|e| MailerError::AddressError(e.to_string()))?;
+
+        Ok(Self {
+            transport: builder.build(),
+            from,
+        })
+    }
+}
+
+#[async_trait]
+impl Mailer for SmtpMailer {
+    async fn send(&self, email: Email) -> Result<(), MailerError> {
+        let to = email
+            .to
+            .parse::<Mailbox>()
+            .map_err(|e| MailerError::AddressError(e.to_string()))?;
+
+        let message = Message::builder()
+            .from(self.from.clone())
+            .to(to)
+            .subject(email.subject)
+            .header(ContentType::TEXT_PLAIN)
+            .body(email.body)
+            .map_err(|e| MailerError::SmtpError(e.to_string()))?;
+
+        self.transport
+            .send(message)
+            .await
+            .map_err(|e| MailerError::SmtpError(e.to_string()))?;
+
+        Ok(())
+    }
+}
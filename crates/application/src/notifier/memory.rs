@@ -0,0 +1,110 @@
+use std::collections::HashSet;
+use std::sync::Mutex;
+
+use async_trait::async_trait;
+
+use super::{Notifier, NotifierError};
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct PublishedMessage {
+    pub routing_key: String,
+    pub message: Vec<u8>,
+}
+
+/// Keeps bindings and published messages in memory, for tests and single
+/// process setups without a broker.
+#[derive(Default)]
+pub struct InMemoryNotifier {
+    bindings: Mutex<HashSet<String>>,
+    published: Mutex<Vec<PublishedMessage>>,
+}
+
+impl InMemoryNotifier {
+    pub fn bound_routing_keys(&self) -> HashSet<String> {
+        self.bindings.lock().unwrap().clone()
+    }
+
+    /// Every message published so far, including those to unbound keys.
+    pub fn published(&self) -> Vec<PublishedMessage> {
+        self.published.lock().unwrap().clone()
+    }
+}
+
+#[async_trait]
+impl Notifier for InMemoryNotifier {
+    async fn initialize(&self) -> Result<String, NotifierError> {
+        Ok("in-memory".to_string())
+    }
+
+    async fn ensure_connected(&self) -> Result<u64, NotifierError> {
+        Ok(1)
+    }
+
+    async fn bind_routing_key(&self, routing_key: &str) -> Result<(), NotifierError> {
+        self.bindings
+            .lock()
+            .unwrap()
+            .insert(routing_key.to_string());
+        Ok(())
+    }
+
+    async fn unbind_routing_key(&self, routing_key: &str) -> Result<(), NotifierError> {
+        self.bindings.lock().unwrap().remove(routing_key);
+        Ok(())
+    }
+
+    async fn sync_routing_keys(&self, routing_keys: &HashSet<String>) -> Result<(), NotifierError> {
+        *self.bindings.lock().unwrap() = routing_keys.clone();
+        Ok(())
+    }
+
+    async fn publish(&self, routing_key: &str, message: Vec<u8>) -> Result<(), NotifierError> {
+        self.published.lock().unwrap().push(PublishedMessage {
+            routing_key: routing_key.to_string(),
+            message,
+        });
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[tokio::test]
+    async fn test_bindings_and_publish() {
+        let notifier = InMemoryNotifier::default();
+        notifier.bind_routing_key("project.group-1").await.unwrap();
+        notifier.bind_routing_key("project.group-2").await.unwrap();
+        notifier
+            .unbind_routing_key("project.group-1")
+            .await
+            .unwrap();
+
+        assert_eq!(
+            notifier.bound_routing_keys(),
+            HashSet::from(["project.group-2".to_string()])
+        );
+
+        notifier
+            .sync_routing_keys(&HashSet::from(["project.group-3".to_string()]))
+            .await
+            .unwrap();
+        assert_eq!(
+            notifier.bound_routing_keys(),
+            HashSet::from(["project.group-3".to_string()])
+        );
+
+        notifier
+            .publish("project.group-3", b"hello".to_vec())
+            .await
+            .unwrap();
+        assert_eq!(
+            notifier.published(),
+            vec![PublishedMessage {
+                routing_key: "project.group-3".to_string(),
+                message: b"hello".to_vec(),
+            }]
+        );
+    }
+}
@@ -0,0 +1,96 @@
+use std::collections::HashSet;
+use std::ops::Deref;
+use std::sync::Arc;
+
+use async_trait::async_trait;
+use shared::deployment_config::{DeploymentConfig, NotifierConfig};
+use thiserror::Error;
+use tokio::sync::Notify;
+
+use crate::rmq::session_notifier::{SessionNotifier, SessionNotifierError};
+
+pub mod memory;
+pub mod webhook;
+
+#[derive(Debug, Error)]
+pub enum NotifierError {
+    #[error("AMQP Error: {0}")]
+    AmqpError(#[from] SessionNotifierError),
+
+    #[error("Webhook Error: {0}")]
+    WebhookError(String),
+}
+
+/// A backend that delivers session notifications to device groups.
+///
+/// Notifications are addressed by `{project_id}.{device_group}` routing keys.
+/// Backends that route by subscription (AMQP) keep a binding per registered
+/// group; backends that push every notification (webhooks) treat binding as a
+/// no-op.
+#[async_trait]
+pub trait Notifier: Send + Sync {
+    /// Prepares the backend and returns a description of its destination.
+    async fn initialize(&self) -> Result<String, NotifierError>;
+
+    /// Returns the connection generation, which changes whenever the backend
+    /// reconnects and bindings may need to be restored.
+    async fn ensure_connected(&self) -> Result<u64, NotifierError>;
+
+    async fn bind_routing_key(&self, routing_key: &str) -> Result<(), NotifierError>;
+
+    async fn unbind_routing_key(&self, routing_key: &str) -> Result<(), NotifierError>;
+
+    /// Makes the bound routing keys match `routing_keys` exactly.
+    async fn sync_routing_keys(&self, routing_keys: &HashSet<String>) -> Result<(), NotifierError>;
+
+    async fn publish(&self, routing_key: &str, message: Vec<u8>) -> Result<(), NotifierError>;
+}
+
+/// Shared handle to the configured notifier backend, along with the signal
+/// that wakes the outbox worker.
+#[derive(Clone)]
+pub struct DeviceNotifier {
+    backend: Arc<dyn Notifier>,
+    outbox_signal: Arc<Notify>,
+}
+
+impl DeviceNotifier {
+    pub fn new(backend: Arc<dyn Notifier>) -> Self {
+        Self {
+            backend,
+            outbox_signal: Arc::new(Notify::new()),
+        }
+    }
+
+    pub fn from_config(config: &DeploymentConfig) -> Self {
+        let backend: Arc<dyn Notifier> = match &config.notifier_config {
+            None | Some(NotifierConfig::Amqp) => {
+                Arc::new(SessionNotifier::new(config.rabbitmq_config.clone()))
+            }
+            Some(NotifierConfig::Webhook(webhook_config)) => {
+                Arc::new(webhook::WebhookNotifier::new(webhook_config.clone()))
+            }
+            Some(NotifierConfig::InMemory) => Arc::new(memory::InMemoryNotifier::default()),
+        };
+
+        Self::new(backend)
+    }
+
+    /// Wakes the outbox worker so newly queued notifications go out without
+    /// waiting for the next poll.
+    pub fn notify_outbox_worker(&self) {
+        self.outbox_signal.notify_one();
+    }
+
+    pub fn outbox_signal(&self) -> Arc<Notify> {
+        self.outbox_signal.clone()
+    }
+}
+
+impl Deref for DeviceNotifier {
+    type Target = dyn Notifier;
+
+    fn deref(&self) -> &Self::Target {
+        self.backend.as_ref()
+    }
+}
@@ -0,0 +1,136 @@
+use std::collections::HashSet;
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+
+use async_trait::async_trait;
+use hmac::{Hmac, Mac};
+use serde::Serialize;
+use sha2::Sha256;
+use shared::deployment_config::WebhookConfig;
+
+use super::{Notifier, NotifierError};
+
+const DEFAULT_TIMEOUT_SECONDS: u64 = 10;
+pub const SIGNATURE_HEADER: &str = "X-SyncFlow-Signature";
+pub const TIMESTAMP_HEADER: &str = "X-SyncFlow-Timestamp";
+
+#[derive(Serialize)]
+#[serde(rename_all = "camelCase")]
+struct WebhookPayload {
+    routing_key: String,
+    message: serde_json::Value,
+}
+
+/// Signs `{timestamp}.{body}` with HMAC-SHA256, returning the hex digest.
+/// Receivers recompute it with the shared secret to authenticate a delivery.
+pub fn sign_payload(secret: &str, timestamp: u64, body: &[u8]) -> String {
+    let mut mac =
+        Hmac::<Sha256>::new_from_slice(secret.as_bytes()).expect("HMAC accepts keys of any length");
+    mac.update(timestamp.to_string().as_bytes());
+    mac.update(b".");
+    mac.update(body);
+
+    mac.finalize()
+        .into_bytes()
+        .iter()
+        .map(|b| format!("{:02x}", b))
+        .collect()
+}
+
+/// Posts every notification to a single HTTP endpoint. The routing key is part
+/// of the payload, so there is nothing to bind.
+pub struct WebhookNotifier {
+    client: reqwest::Client,
+    webhook_config: WebhookConfig,
+}
+
+impl WebhookNotifier {
+    pub fn new(webhook_config: WebhookConfig) -> Self {
+        let timeout = Duration::from_secs(
+            webhook_config
+                .timeout_seconds
+                .unwrap_or(DEFAULT_TIMEOUT_SECONDS),
+        );
+        let client = reqwest::Client::builder()
+            .timeout(timeout)
+            .build()
+            .unwrap_or_default();
+
+        Self {
+            client,
+            webhook_config,
+        }
+    }
+}
+
+#[async_trait]
+impl Notifier for WebhookNotifier {
+    async fn initialize(&self) -> Result<String, NotifierError> {
+        Ok(self.webhook_config.url.clone())
+    }
+
+    async fn ensure_connected(&self) -> Result<u64, NotifierError> {
+        Ok(1)
+    }
+
+    async fn bind_routing_key(&self, _routing_key: &str) -> Result<(), NotifierError> {
+        Ok(())
+    }
+
+    async fn unbind_routing_key(&self, _routing_key: &str) -> Result<(), NotifierError> {
+        Ok(())
+    }
+
+    async fn sync_routing_keys(
+        &self,
+        _routing_keys: &HashSet<String>,
+    ) -> Result<(), NotifierError> {
+        Ok(())
+    }
+
+    async fn publish(&self, routing_key: &str, message: Vec<u8>) -> Result<(), NotifierError> {
+        let message = serde_json::from_slice(&message).unwrap_or_else(|_| {
+            serde_json::Value::String(String::from_utf8_lossy(&message).into_owned())
+        });
+        let body = serde_json::to_vec(&WebhookPayload {
+            routing_key: routing_key.to_string(),
+            message,
+        })
+        .map_err(|e| NotifierError::WebhookError(e.to_string()))?;
+
+        let timestamp = SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .unwrap()
+            .as_secs();
+        let signature = sign_payload(&self.webhook_config.secret, timestamp, &body);
+
+        self.client
+            .post(&self.webhook_config.url)
+            .header("Content-Type", "application/json")
+            .header(TIMESTAMP_HEADER, timestamp.to_string())
+            .header(SIGNATURE_HEADER, format!("sha256={}", signature))
+            .body(body)
+            .send()
+            .await
+            .and_then(|response| response.error_for_status())
+            .map_err(|e| NotifierError::WebhookError(e.to_string()))?;
+
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_sign_payload() {
+        assert_eq!(
+            sign_payload("secret", 1700000000, br#"{"a":1}"#),
+            "49f24e537407743fa4a0242bb63b94b9a47ee99cbbe071ccd8a22550ae411686"
+        );
+        assert_ne!(
+            sign_payload("other-secret", 1700000000, br#"{"a":1}"#),
+            sign_payload("secret", 1700000000, br#"{"a":1}"#)
+        );
+    }
+}
@@ -0,0 +1,4 @@
+pub mod organization_crud;
+pub mod organization_service;
+
+pub use domain::models::OrganizationRole;
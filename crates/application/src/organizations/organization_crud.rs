@@ -0,0 +1,673 @@
+use crate::project::project_crud::Encryptable;
+use crate::users::secret::{decrypt_string, encrypt_string, SecretError};
+use diesel::result::DatabaseErrorKind;
+use diesel::{prelude::*, PgConnection};
+use domain::models::{
+    LivekitCredential, LivekitCredentialChangeset, NewLivekitCredential, NewOrganization,
+    NewOrganizationInvitation, NewOrganizationMember, NewStorageCredential, Organization,
+    OrganizationChangeset, OrganizationInvitation, OrganizationMember, OrganizationRole,
+    StorageCredential, StorageCredentialChangeset,
+};
+use thiserror::Error;
+use uuid::Uuid;
+
+#[derive(Error, Debug)]
+pub enum OrganizationError {
+    #[error("Database Error: {0}")]
+    DatabaseError(#[from] diesel::result::Error),
+
+    #[error("Configuration Error: {0}")]
+    ConfigurationError(#[from] uuid::Error),
+
+    #[error("Encryption Error: {0}")]
+    EncryptionError(#[from] SecretError),
+
+    #[error("Not found: {0}")]
+    NotFound(String),
+
+    #[error("Forbidden: {0}")]
+    Forbidden(String),
+
+    #[error("Conflict: {0}")]
+    Conflict(String),
+
+    #[error("Invalid Request: {0}")]
+    InvalidRequest(String),
+}
+
+impl From<OrganizationError> for shared::response_models::Response {
+    fn from(error: OrganizationError) -> Self {
+        match error {
+            OrganizationError::DatabaseError(e) => match e {
+                diesel::result::Error::NotFound => shared::response_models::Response {
+                    status: 404,
+                    message: e.to_string(),
+                },
+                diesel::result::Error::DatabaseError(DatabaseErrorKind::UniqueViolation, _) => {
+                    shared::response_models::Response {
+                        status: 409,
+                        message: "The name is already taken".to_string(),
+                    }
+                }
+                diesel::result::Error::DatabaseError(DatabaseErrorKind::ForeignKeyViolation, _) => {
+                    shared::response_models::Response {
+                        status: 409,
+                        message: "The resource is still used by projects".to_string(),
+                    }
+                }
+                _ => shared::response_models::Response {
+                    status: 500,
+                    message: e.to_string(),
+                },
+            },
+            OrganizationError::ConfigurationError(e) => shared::response_models::Response {
+                status: 400,
+                message: e.to_string(),
+            },
+            OrganizationError::EncryptionError(e) => shared::response_models::Response {
+                status: 500,
+                message: e.to_string(),
+            },
+            OrganizationError::NotFound(e) => shared::response_models::Response {
+                status: 404,
+                message: e,
+            },
+            OrganizationError::Forbidden(e) => shared::response_models::Response {
+                status: 403,
+                message: e,
+            },
+            OrganizationError::Conflict(e) => shared::response_models::Response {
+                status: 409,
+                message: e,
+            },
+            OrganizationError::InvalidRequest(e) => shared::response_models::Response {
+                status: 400,
+                message: e,
+            },
+        }
+    }
+}
+
+fn encrypt_optional(value: &Option<String>, key: &str) -> Result<Option<String>, SecretError> {
+    value.as_deref().map(|v| encrypt_string(v, key)).transpose()
+}
+
+fn decrypt_optional(value: &Option<String>, key: &str) -> Result<Option<String>, SecretError> {
+    value.as_deref().map(|v| decrypt_string(v, key)).transpose()
+}
+
+impl Encryptable for NewLivekitCredential {
+    fn encrypt(&mut self, key: &str) -> Result<(), SecretError> {
+        self.api_key = encrypt_string(&self.api_key, key)?;
+        self.api_secret = encrypt_string(&self.api_secret, key)?;
+        Ok(())
+    }
+
+    fn decrypt(&mut self, key: &str) -> Result<(), SecretError> {
+        self.api_key = decrypt_string(&self.api_key, key)?;
+        self.api_secret = decrypt_string(&self.api_secret, key)?;
+        Ok(())
+    }
+}
+
+impl Encryptable for LivekitCredentialChangeset {
+    fn encrypt(&mut self, key: &str) -> Result<(), SecretError> {
+        self.api_key = encrypt_optional(&self.api_key, key)?;
+        self.api_secret = encrypt_optional(&self.api_secret, key)?;
+        Ok(())
+    }
+
+    fn decrypt(&mut self, key: &str) -> Result<(), SecretError> {
+        self.api_key = decrypt_optional(&self.api_key, key)?;
+        self.api_secret = decrypt_optional(&self.api_secret, key)?;
+        Ok(())
+    }
+}
+
+impl Encryptable for LivekitCredential {
+    fn encrypt(&mut self, key: &str) -> Result<(), SecretError> {
+        self.api_key = encrypt_string(&self.api_key, key)?;
+        self.api_secret = encrypt_string(&self.api_secret, key)?;
+        Ok(())
+    }
+
+    fn decrypt(&mut self, key: &str) -> Result<(), SecretError> {
+        self.api_key = decrypt_string(&self.api_key, key)?;
+        self.api_secret = decrypt_string(&self.api_secret, key)?;
+        Ok(())
+    }
+}
+
+impl Encryptable for NewStorageCredential {
+    fn encrypt(&mut self, key: &str) -> Result<(), SecretError> {
+        self.access_key = encrypt_string(&self.access_key, key)?;
+        self.secret_key = encrypt_string(&self.secret_key, key)?;
+        Ok(())
+    }
+
+    fn decrypt(&mut self, key: &str) -> Result<(), SecretError> {
+        self.access_key = decrypt_string(&self.access_key, key)?;
+        self.secret_key = decrypt_string(&self.secret_key, key)?;
+        Ok(())
+    }
+}
+
+impl Encryptable for StorageCredentialChangeset {
+    fn encrypt(&mut self, key: &str) -> Result<(), SecretError> {
+        self.access_key = encrypt_optional(&self.access_key, key)?;
+        self.secret_key = encrypt_optional(&self.secret_key, key)?;
+        Ok(())
+    }
+
+    fn decrypt(&mut self, key: &str) -> Result<(), SecretError> {
+        self.access_key = decrypt_optional(&self.access_key, key)?;
+        self.secret_key = decrypt_optional(&self.secret_key, key)?;
+        Ok(())
+    }
+}
+
+impl Encryptable for StorageCredential {
+    fn encrypt(&mut self, key: &str) -> Result<(), SecretError> {
+        self.access_key = encrypt_string(&self.access_key, key)?;
+        self.secret_key = encrypt_string(&self.secret_key, key)?;
+        Ok(())
+    }
+
+    fn decrypt(&mut self, key: &str) -> Result<(), SecretError> {
+        self.access_key = decrypt_string(&self.access_key, key)?;
+        self.secret_key = decrypt_string(&self.secret_key, key)?;
+        Ok(())
+    }
+}
+
+/// Returns the role of the user in the organization. Non-members get the
+/// same not found error as missing organizations.
+pub fn get_role(
+    org_id: &str,
+    uid: i32,
+    conn: &mut PgConnection,
+) -> Result<OrganizationRole, OrganizationError> {
+    use domain::schema::syncflow::organization_members::dsl::*;
+
+    let org_uuid = Uuid::parse_str(org_id)?;
+
+    organization_members
+        .filter(organization_id.eq(org_uuid).and(user_id.eq(uid)))
+        .select(role)
+        .first::<OrganizationRole>(conn)
+        .optional()?
+        .ok_or_else(|| OrganizationError::NotFound("Organization not found".to_string()))
+}
+
+/// Creates an organization with `uid` as its owner.
+pub fn create_organization(
+    new_organization: &NewOrganization,
+    uid: i32,
+    conn: &mut PgConnection,
+) -> Result<Organization, OrganizationError> {
+    use domain::schema::syncflow::{organization_members, organizations};
+
+    let organization = conn.transaction(|conn| {
+        let organization = diesel::insert_into(organizations::table)
+            .values(new_organization)
+            .get_result::<Organization>(conn)?;
+
+        diesel::insert_into(organization_members::table)
+            .values(&NewOrganizationMember {
+                role: OrganizationRole::Owner,
+                organization_id: organization.id,
+                user_id: uid,
+            })
+            .execute(conn)?;
+
+        Ok::<_, diesel::result::Error>(organization)
+    })?;
+
+    Ok(organization)
+}
+
+/// Lists the organizations of a user along with the user's role in each.
+pub fn list_organizations(
+    uid: i32,
+    conn: &mut PgConnection,
+) -> Result<Vec<(Organization, OrganizationRole)>, OrganizationError> {
+    use domain::schema::syncflow::{organization_members, organizations};
+
+    let all_organizations = organizations::table
+        .inner_join(organization_members::table)
+        .filter(organization_members::user_id.eq(uid))
+        .order(organizations::name.asc())
+        .select((organizations::all_columns, organization_members::role))
+        .load::<(Organization, OrganizationRole)>(conn)?;
+
+    Ok(all_organizations)
+}
+
+pub fn get_organization(
+    org_id: &str,
+    conn: &mut PgConnection,
+) -> Result<Organization, OrganizationError> {
+    use domain::schema::syncflow::organizations::dsl::*;
+
+    let org_uuid = Uuid::parse_str(org_id)?;
+
+    organizations
+        .filter(id.eq(org_uuid))
+        .first::<Organization>(conn)
+        .optional()?
+        .ok_or_else(|| OrganizationError::NotFound("Organization not found".to_string()))
+}
+
+pub fn update_organization(
+    org_id: &str,
+    changeset: &OrganizationChangeset,
+    conn: &mut PgConnection,
+) -> Result<Organization, OrganizationError> {
+    use domain::schema::syncflow::organizations::dsl::*;
+
+    let org_uuid = Uuid::parse_str(org_id)?;
+
+    let organization = diesel::update(organizations.filter(id.eq(org_uuid)))
+        .set(changeset)
+        .get_result::<Organization>(conn)?;
+
+    Ok(organization)
+}
+
+/// Deletes an organization. Fails while it still owns projects.
+pub fn delete_organization(
+    org_id: &str,
+    conn: &mut PgConnection,
+) -> Result<Organization, OrganizationError> {
+    use domain::schema::syncflow::organizations::dsl::*;
+
+    let org_uuid = Uuid::parse_str(org_id)?;
+
+    let organization =
+        diesel::delete(organizations.filter(id.eq(org_uuid))).get_result::<Organization>(conn)?;
+
+    Ok(organization)
+}
+
+pub fn add_member(
+    org_uuid: Uuid,
+    uid: i32,
+    member_role: OrganizationRole,
+    conn: &mut PgConnection,
+) -> Result<OrganizationMember, OrganizationError> {
+    use domain::schema::syncflow::organization_members::dsl::*;
+
+    let new_member = NewOrganizationMember {
+        role: member_role,
+        organization_id: org_uuid,
+        user_id: uid,
+    };
+
+    let member = diesel::insert_into(organization_members)
+        .values(new_member)
+        .get_result::<OrganizationMember>(conn)?;
+
+    Ok(member)
+}
+
+/// Lists the members of an organization along with their usernames and emails.
+pub fn list_members(
+    org_id: &str,
+    conn: &mut PgConnection,
+) -> Result<Vec<(OrganizationMember, String, String)>, OrganizationError> {
+    use domain::schema::syncflow::{organization_members, users};
+
+    let org_uuid = Uuid::parse_str(org_id)?;
+
+    let members = organization_members::table
+        .inner_join(users::table)
+        .filter(organization_members::organization_id.eq(org_uuid))
+        .order(organization_members::created_at.asc())
+        .select((
+            organization_members::all_columns,
+            users::username,
+            users::email,
+        ))
+        .load::<(OrganizationMember, String, String)>(conn)?;
+
+    Ok(members)
+}
+
+pub fn get_member(
+    org_id: &str,
+    uid: i32,
+    conn: &mut PgConnection,
+) -> Result<OrganizationMember, OrganizationError> {
+    use domain::schema::syncflow::organization_members::dsl::*;
+
+    let org_uuid = Uuid::parse_str(org_id)?;
+
+    organization_members
+        .filter(organization_id.eq(org_uuid).and(user_id.eq(uid)))
+        .first::<OrganizationMember>(conn)
+        .optional()?
+        .ok_or_else(|| OrganizationError::NotFound("Member not found".to_string()))
+}
+
+pub fn count_owners(org_id: &str, conn: &mut PgConnection) -> Result<i64, OrganizationError> {
+    use domain::schema::syncflow::organization_members::dsl::*;
+
+    let org_uuid = Uuid::parse_str(org_id)?;
+
+    let owners = organization_members
+        .filter(
+            organization_id
+                .eq(org_uuid)
+                .and(role.eq(OrganizationRole::Owner)),
+        )
+        .count()
+        .get_result::<i64>(conn)?;
+
+    Ok(owners)
+}
+
+pub fn update_role(
+    org_id: &str,
+    uid: i32,
+    new_role: OrganizationRole,
+    conn: &mut PgConnection,
+) -> Result<OrganizationMember, OrganizationError> {
+    use domain::schema::syncflow::organization_members::dsl::*;
+
+    let org_uuid = Uuid::parse_str(org_id)?;
+
+    let member = diesel::update(
+        organization_members.filter(organization_id.eq(org_uuid).and(user_id.eq(uid))),
+    )
+    .set(role.eq(new_role))
+    .get_result::<OrganizationMember>(conn)?;
+
+    Ok(member)
+}
+
+pub fn remove_member(
+    org_id: &str,
+    uid: i32,
+    conn: &mut PgConnection,
+) -> Result<OrganizationMember, OrganizationError> {
+    use domain::schema::syncflow::organization_members::dsl::*;
+
+    let org_uuid = Uuid::parse_str(org_id)?;
+
+    let member = diesel::delete(
+        organization_members.filter(organization_id.eq(org_uuid).and(user_id.eq(uid))),
+    )
+    .get_result::<OrganizationMember>(conn)?;
+
+    Ok(member)
+}
+
+pub fn create_invitation(
+    new_invitation: &NewOrganizationInvitation,
+    conn: &mut PgConnection,
+) -> Result<OrganizationInvitation, OrganizationError> {
+    use domain::schema::syncflow::organization_invitations::dsl::*;
+
+    let invitation = diesel::insert_into(organization_invitations)
+        .values(new_invitation)
+        .get_result::<OrganizationInvitation>(conn)?;
+
+    Ok(invitation)
+}
+
+pub fn list_organization_invitations(
+    org_id: &str,
+    conn: &mut PgConnection,
+) -> Result<Vec<OrganizationInvitation>, OrganizationError> {
+    use domain::schema::syncflow::organization_invitations::dsl::*;
+
+    let org_uuid = Uuid::parse_str(org_id)?;
+    let now = chrono::Utc::now().naive_utc();
+
+    let invitations = organization_invitations
+        .filter(organization_id.eq(org_uuid).and(expires_at.gt(now)))
+        .order(created_at.desc())
+        .load::<OrganizationInvitation>(conn)?;
+
+    Ok(invitations)
+}
+
+pub fn list_invitations_for_email(
+    invitee_email: &str,
+    conn: &mut PgConnection,
+) -> Result<Vec<OrganizationInvitation>, OrganizationError> {
+    use domain::schema::syncflow::organization_invitations::dsl::*;
+
+    let now = chrono::Utc::now().naive_utc();
+
+    let invitations = organization_invitations
+        .filter(
+            email
+                .eq(invitee_email.to_lowercase())
+                .and(expires_at.gt(now)),
+        )
+        .order(created_at.desc())
+        .load::<OrganizationInvitation>(conn)?;
+
+    Ok(invitations)
+}
+
+pub fn get_invitation(
+    invitation_id: &str,
+    conn: &mut PgConnection,
+) -> Result<OrganizationInvitation, OrganizationError> {
+    use domain::schema::syncflow::organization_invitations::dsl::*;
+
+    let invitation_uuid = Uuid::parse_str(invitation_id)?;
+    let now = chrono::Utc::now().naive_utc();
+
+    organization_invitations
+        .filter(id.eq(invitation_uuid).and(expires_at.gt(now)))
+        .first::<OrganizationInvitation>(conn)
+        .optional()?
+        .ok_or_else(|| OrganizationError::NotFound("Invitation not found".to_string()))
+}
+
+pub fn delete_invitation(
+    invitation_uuid: Uuid,
+    conn: &mut PgConnection,
+) -> Result<OrganizationInvitation, OrganizationError> {
+    use domain::schema::syncflow::organization_invitations::dsl::*;
+
+    let invitation = diesel::delete(organization_invitations.filter(id.eq(invitation_uuid)))
+        .get_result::<OrganizationInvitation>(conn)?;
+
+    Ok(invitation)
+}
+
+pub fn delete_organization_invitation(
+    org_id: &str,
+    invitation_id: &str,
+    conn: &mut PgConnection,
+) -> Result<OrganizationInvitation, OrganizationError> {
+    use domain::schema::syncflow::organization_invitations::dsl::*;
+
+    let org_uuid = Uuid::parse_str(org_id)?;
+    let invitation_uuid = Uuid::parse_str(invitation_id)?;
+
+    let invitation = diesel::delete(
+        organization_invitations.filter(organization_id.eq(org_uuid).and(id.eq(invitation_uuid))),
+    )
+    .get_result::<OrganizationInvitation>(conn)?;
+
+    Ok(invitation)
+}
+
+pub fn create_livekit_credential(
+    new_credential: &NewLivekitCredential,
+    conn: &mut PgConnection,
+) -> Result<LivekitCredential, OrganizationError> {
+    use domain::schema::syncflow::organization_livekit_credentials::dsl::*;
+
+    let credential = diesel::insert_into(organization_livekit_credentials)
+        .values(new_credential)
+        .get_result::<LivekitCredential>(conn)?;
+
+    Ok(credential)
+}
+
+pub fn list_livekit_credentials(
+    org_id: &str,
+    conn: &mut PgConnection,
+) -> Result<Vec<LivekitCredential>, OrganizationError> {
+    use domain::schema::syncflow::organization_livekit_credentials::dsl::*;
+
+    let org_uuid = Uuid::parse_str(org_id)?;
+
+    let credentials = organization_livekit_credentials
+        .filter(organization_id.eq(org_uuid))
+        .order(name.asc())
+        .load::<LivekitCredential>(conn)?;
+
+    Ok(credentials)
+}
+
+pub fn get_livekit_credential(
+    org_id: &str,
+    credential_id: &str,
+    conn: &mut PgConnection,
+) -> Result<LivekitCredential, OrganizationError> {
+    use domain::schema::syncflow::organization_livekit_credentials::dsl::*;
+
+    let org_uuid = Uuid::parse_str(org_id)?;
+    let credential_uuid = Uuid::parse_str(credential_id)?;
+
+    organization_livekit_credentials
+        .filter(organization_id.eq(org_uuid).and(id.eq(credential_uuid)))
+        .first::<LivekitCredential>(conn)
+        .optional()?
+        .ok_or_else(|| OrganizationError::NotFound("LiveKit credentials not found".to_string()))
+}
+
+pub fn update_livekit_credential(
+    org_id: &str,
+    credential_id: &str,
+    changeset: &LivekitCredentialChangeset,
+    conn: &mut PgConnection,
+) -> Result<LivekitCredential, OrganizationError> {
+    use domain::schema::syncflow::organization_livekit_credentials::dsl::*;
+
+    let org_uuid = Uuid::parse_str(org_id)?;
+    let credential_uuid = Uuid::parse_str(credential_id)?;
+
+    let credential = diesel::update(
+        organization_livekit_credentials
+            .filter(organization_id.eq(org_uuid).and(id.eq(credential_uuid))),
+    )
+    .set(changeset)
+    .get_result::<LivekitCredential>(conn)?;
+
+    Ok(credential)
+}
+
+/// Deletes LiveKit credentials. Fails while projects still use them.
+pub fn delete_livekit_credential(
+    org_id: &str,
+    credential_id: &str,
+    conn: &mut PgConnection,
+) -> Result<LivekitCredential, OrganizationError> {
+    use domain::schema::syncflow::organization_livekit_credentials::dsl::*;
+
+    let org_uuid = Uuid::parse_str(org_id)?;
+    let credential_uuid = Uuid::parse_str(credential_id)?;
+
+    let credential = diesel::delete(
+        organization_livekit_credentials
+            .filter(organization_id.eq(org_uuid).and(id.eq(credential_uuid))),
+    )
+    .get_result::<LivekitCredential>(conn)?;
+
+    Ok(credential)
+}
+
+pub fn create_storage_credential(
+    new_credential: &NewStorageCredential,
+    conn: &mut PgConnection,
+) -> Result<StorageCredential, OrganizationError> {
+    use domain::schema::syncflow::organization_storage_credentials::dsl::*;
+
+    let credential = diesel::insert_into(organization_storage_credentials)
+        .values(new_credential)
+        .get_result::<StorageCredential>(conn)?;
+
+    Ok(credential)
+}
+
+pub fn list_storage_credentials(
+    org_id: &str,
+    conn: &mut PgConnection,
+) -> Result<Vec<StorageCredential>, OrganizationError> {
+    use domain::schema::syncflow::organization_storage_credentials::dsl::*;
+
+    let org_uuid = Uuid::parse_str(org_id)?;
+
+    let credentials = organization_storage_credentials
+        .filter(organization_id.eq(org_uuid))
+        .order(name.asc())
+        .load::<StorageCredential>(conn)?;
+
+    Ok(credentials)
+}
+
+pub fn get_storage_credential(
+    org_id: &str,
+    credential_id: &str,
+    conn: &mut PgConnection,
+) -> Result<StorageCredential, OrganizationError> {
+    use domain::schema::syncflow::organization_storage_credentials::dsl::*;
+
+    let org_uuid = Uuid::parse_str(org_id)?;
+    let credential_uuid = Uuid::parse_str(credential_id)?;
+
+    organization_storage_credentials
+        .filter(organization_id.eq(org_uuid).and(id.eq(credential_uuid)))
+        .first::<StorageCredential>(conn)
+        .optional()?
+        .ok_or_else(|| OrganizationError::NotFound("Storage credentials not found".to_string()))
+}
+
+pub fn update_storage_credential(
+    org_id: &str,
+    credential_id: &str,
+    changeset: &StorageCredentialChangeset,
+    conn: &mut PgConnection,
+) -> Result<StorageCredential, OrganizationError> {
+    use domain::schema::syncflow::organization_storage_credentials::dsl::*;
+
+    let org_uuid = Uuid::parse_str(org_id)?;
+    let credential_uuid = Uuid::parse_str(credential_id)?;
+
+    let credential = diesel::update(
+        organization_storage_credentials
+            .filter(organization_id.eq(org_uuid).and(id.eq(credential_uuid))),
+    )
+    .set(changeset)
+    .get_result::<StorageCredential>(conn)?;
+
+    Ok(credential)
+}
+
+/// Deletes storage credentials. Fails while projects still use them.
+pub fn delete_storage_credential(
+    org_id: &str,
+    credential_id: &str,
+    conn: &mut PgConnection,
+) -> Result<StorageCredential, OrganizationError> {
+    use domain::schema::syncflow::organization_storage_credentials::dsl::*;
+
+    let org_uuid = Uuid::parse_str(org_id)?;
+    let credential_uuid = Uuid::parse_str(credential_id)?;
+
+    let credential = diesel::delete(
+        organization_storage_credentials
+            .filter(organization_id.eq(org_uuid).and(id.eq(credential_uuid))),
+    )
+    .get_result::<StorageCredential>(conn)?;
+
+    Ok(credential)
+}
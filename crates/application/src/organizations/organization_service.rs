@@ -0,0 +1,674 @@
+use std::str::FromStr;
+use std::sync::Arc;
+
+use diesel::Connection;
+use domain::models::{
+    LivekitCredentialChangeset, NewLivekitCredential, NewOrganization, NewOrganizationInvitation,
+    NewStorageCredential, Organization, OrganizationChangeset, OrganizationRole,
+    StorageCredentialChangeset, StorageType, User,
+};
+use infrastructure::DbPool;
+use shared::member_models::MemberRoleUpdateRequest;
+use shared::organization_models::{
+    LivekitCredentialRequest, LivekitCredentialResponse, LivekitCredentialUpdateRequest,
+    OrganizationInvitationResponse, OrganizationMemberRequest, OrganizationMemberResponse,
+    OrganizationRequest, OrganizationResponse, OrganizationUpdateRequest, StorageCredentialRequest,
+    StorageCredentialResponse, StorageCredentialUpdateRequest,
+};
+use uuid::Uuid;
+
+use super::organization_crud::{self, OrganizationError};
+use crate::project::members::member_crud::MemberError;
+use crate::project::members::member_service::{check_invitee, INVITATION_VALIDITY_DAYS};
+use crate::project::project_crud::Encryptable;
+use crate::users::user;
+
+const MAX_NAME_LENGTH: usize = 50;
+
+fn parse_role(role: &str) -> Result<OrganizationRole, OrganizationError> {
+    OrganizationRole::from_str(&role.to_lowercase()).map_err(OrganizationError::InvalidRequest)
+}
+
+fn validate_name(name: &str) -> Result<(), OrganizationError> {
+    if name.trim().is_empty() || name.chars().count() > MAX_NAME_LENGTH {
+        return Err(OrganizationError::InvalidRequest(format!(
+            "Names must be between 1 and {} characters",
+            MAX_NAME_LENGTH
+        )));
+    }
+    Ok(())
+}
+
+fn parse_storage_type(storage_type: &str) -> Result<StorageType, OrganizationError> {
+    match storage_type.to_lowercase().as_str() {
+        "s3" => Ok(StorageType::S3),
+        _ => Err(OrganizationError::InvalidRequest(format!(
+            "Unsupported storage type: {}",
+            storage_type
+        ))),
+    }
+}
+
+fn require_role(
+    actual: OrganizationRole,
+    required: OrganizationRole,
+) -> Result<(), OrganizationError> {
+    if actual.includes(required) {
+        Ok(())
+    } else {
+        Err(OrganizationError::Forbidden(format!(
+            "This action requires the organization {} role",
+            required.as_str()
+        )))
+    }
+}
+
+/// Checks that `actor` may hand out or take away `role`: only owners manage
+/// owners, and admins manage everyone else.
+fn check_can_manage(
+    actor: OrganizationRole,
+    role: OrganizationRole,
+) -> Result<(), OrganizationError> {
+    require_role(actor, OrganizationRole::Admin)?;
+
+    if role == OrganizationRole::Owner && actor != OrganizationRole::Owner {
+        return Err(OrganizationError::Forbidden(
+            "Only owners can manage the owner role".to_string(),
+        ));
+    }
+
+    Ok(())
+}
+
+/// Only the verified owner of the invited email may respond to an invitation.
+fn check_organization_invitee(
+    invitation_email: &str,
+    invitee: &User,
+) -> Result<(), OrganizationError> {
+    check_invitee(
+        invitation_email,
+        &invitee.email,
+        invitee.email_verified_at.is_some(),
+    )
+    .map_err(|e| match e {
+        MemberError::Forbidden(message) => OrganizationError::Forbidden(message),
+        _ => OrganizationError::NotFound("Invitation not found".to_string()),
+    })
+}
+
+fn organization_response(
+    organization: Organization,
+    role: OrganizationRole,
+) -> OrganizationResponse {
+    OrganizationResponse {
+        id: organization.id.to_string(),
+        name: organization.name,
+        description: organization.description,
+        role: role.as_str().to_string(),
+        created_at: organization.created_at.and_utc().timestamp() as usize,
+    }
+}
+
+/// Manages organizations, their members and the LiveKit and storage
+/// credentials shared by their projects.
+pub struct OrganizationService {
+    pool: Arc<DbPool>,
+    encryption_key: String,
+}
+
+impl OrganizationService {
+    pub fn new(encryption_key: &str, pool: Arc<DbPool>) -> Self {
+        Self {
+            pool,
+            encryption_key: encryption_key.to_string(),
+        }
+    }
+
+    pub fn create_organization(
+        &self,
+        user_id: i32,
+        request: &OrganizationRequest,
+    ) -> Result<OrganizationResponse, OrganizationError> {
+        validate_name(&request.name)?;
+
+        let new_organization = NewOrganization {
+            name: request.name.trim().to_string(),
+            description: request.description.clone(),
+        };
+
+        let organization = organization_crud::create_organization(
+            &new_organization,
+            user_id,
+            &mut self.pool.get().unwrap(),
+        )?;
+
+        Ok(organization_response(organization, OrganizationRole::Owner))
+    }
+
+    pub fn list_organizations(
+        &self,
+        user_id: i32,
+    ) -> Result<Vec<OrganizationResponse>, OrganizationError> {
+        let organizations =
+            organization_crud::list_organizations(user_id, &mut self.pool.get().unwrap())?;
+
+        Ok(organizations
+            .into_iter()
+            .map(|(organization, role)| organization_response(organization, role))
+            .collect())
+    }
+
+    pub fn get_organization(
+        &self,
+        org_id: &str,
+        user_id: i32,
+    ) -> Result<OrganizationResponse, OrganizationError> {
+        let conn = &mut self.pool.get().unwrap();
+        let role = organization_crud::get_role(org_id, user_id, conn)?;
+        let organization = organization_crud::get_organization(org_id, conn)?;
+
+        Ok(organization_response(organization, role))
+    }
+
+    pub fn update_organization(
+        &self,
+        org_id: &str,
+        user_id: i32,
+        request: &OrganizationUpdateRequest,
+    ) -> Result<OrganizationResponse, OrganizationError> {
+        if let Some(name) = &request.name {
+            validate_name(name)?;
+        }
+
+        let conn = &mut self.pool.get().unwrap();
+        let role = organization_crud::get_role(org_id, user_id, conn)?;
+        require_role(role, OrganizationRole::Admin)?;
+
+        let changeset = OrganizationChangeset {
+            name: request.name.as_ref().map(|name| name.trim().to_string()),
+            description: request.description.clone(),
+            updated_at: chrono::Utc::now().naive_utc(),
+        };
+
+        let organization = organization_crud::update_organization(org_id, &changeset, conn)?;
+
+        Ok(organization_response(organization, role))
+    }
+
+    /// Deletes an organization along with its credentials. Organizations
+    /// that still own projects cannot be deleted.
+    pub fn delete_organization(
+        &self,
+        org_id: &str,
+        user_id: i32,
+    ) -> Result<OrganizationResponse, OrganizationError> {
+        let conn = &mut self.pool.get().unwrap();
+        let role = organization_crud::get_role(org_id, user_id, conn)?;
+        require_role(role, OrganizationRole::Owner)?;
+
+        let organization = organization_crud::delete_organization(org_id, conn)?;
+
+        Ok(organization_response(organization, role))
+    }
+
+    pub fn list_members(
+        &self,
+        org_id: &str,
+        user_id: i32,
+    ) -> Result<Vec<OrganizationMemberResponse>, OrganizationError> {
+        let conn = &mut self.pool.get().unwrap();
+        organization_crud::get_role(org_id, user_id, conn)?;
+
+        let members = organization_crud::list_members(org_id, conn)?;
+
+        Ok(members
+            .into_iter()
+            .map(|(member, username, email)| OrganizationMemberResponse {
+                user_id: member.user_id,
+                username,
+                email,
+                role: member.role.as_str().to_string(),
+                joined_at: member.created_at.and_utc().timestamp() as usize,
+            })
+            .collect())
+    }
+
+    /// Invites a user to the organization. They only become a member once
+    /// they accept the invitation sent to their email address.
+    pub fn invite_member(
+        &self,
+        org_id: &str,
+        user_id: i32,
+        request: &OrganizationMemberRequest,
+    ) -> Result<OrganizationInvitationResponse, OrganizationError> {
+        let role = parse_role(&request.role)?;
+        let conn = &mut self.pool.get().unwrap();
+
+        let actor_role = organization_crud::get_role(org_id, user_id, conn)?;
+        check_can_manage(actor_role, role)?;
+
+        let identifier = request.username_or_email.trim();
+        let invitee = if identifier.contains('@') {
+            user::get_user_by_email(identifier, conn).ok()
+        } else {
+            Some(user::get_user_by_username(identifier, conn).map_err(|_| {
+                OrganizationError::NotFound(format!("User {} not found", identifier))
+            })?)
+        };
+
+        if let Some(invitee) = &invitee {
+            if organization_crud::get_member(org_id, invitee.id, conn).is_ok() {
+                return Err(OrganizationError::Conflict(format!(
+                    "{} is already a member of the organization",
+                    identifier
+                )));
+            }
+        }
+
+        let email = invitee.map_or(identifier.to_string(), |invitee| invitee.email);
+        let new_invitation = NewOrganizationInvitation {
+            email: email.to_lowercase(),
+            role,
+            expires_at: chrono::Utc::now().naive_utc()
+                + chrono::Duration::days(INVITATION_VALIDITY_DAYS),
+            organization_id: Uuid::parse_str(org_id)?,
+            invited_by: user_id,
+        };
+
+        let invitation = organization_crud::create_invitation(&new_invitation, conn)?;
+
+        Ok(invitation.into())
+    }
+
+    pub fn list_invitations(
+        &self,
+        org_id: &str,
+        user_id: i32,
+    ) -> Result<Vec<OrganizationInvitationResponse>, OrganizationError> {
+        let conn = &mut self.pool.get().unwrap();
+        let role = organization_crud::get_role(org_id, user_id, conn)?;
+        require_role(role, OrganizationRole::Admin)?;
+
+        let invitations = organization_crud::list_organization_invitations(org_id, conn)?;
+
+        Ok(invitations.into_iter().map(Into::into).collect())
+    }
+
+    pub fn revoke_invitation(
+        &self,
+        org_id: &str,
+        user_id: i32,
+        invitation_id: &str,
+    ) -> Result<OrganizationInvitationResponse, OrganizationError> {
+        let conn = &mut self.pool.get().unwrap();
+        let role = organization_crud::get_role(org_id, user_id, conn)?;
+        require_role(role, OrganizationRole::Admin)?;
+
+        let invitation =
+            organization_crud::delete_organization_invitation(org_id, invitation_id, conn)?;
+
+        Ok(invitation.into())
+    }
+
+    pub fn list_user_invitations(
+        &self,
+        user_id: i32,
+    ) -> Result<Vec<OrganizationInvitationResponse>, OrganizationError> {
+        let conn = &mut self.pool.get().unwrap();
+        let invitee = self.get_user(user_id, conn)?;
+
+        let invitations = organization_crud::list_invitations_for_email(&invitee.email, conn)?;
+
+        Ok(invitations.into_iter().map(Into::into).collect())
+    }
+
+    pub fn accept_invitation(
+        &self,
+        user_id: i32,
+        invitation_id: &str,
+    ) -> Result<OrganizationMemberResponse, OrganizationError> {
+        let conn = &mut self.pool.get().unwrap();
+        let invitee = self.get_user(user_id, conn)?;
+        let invitation = organization_crud::get_invitation(invitation_id, conn)?;
+
+        check_organization_invitee(&invitation.email, &invitee)?;
+
+        let member = conn.transaction(|conn| {
+            organization_crud::delete_invitation(invitation.id, conn)?;
+            let org_id = invitation.organization_id.to_string();
+            if organization_crud::get_member(&org_id, user_id, conn).is_ok() {
+                return Err(OrganizationError::Conflict(
+                    "Already a member of the organization".to_string(),
+                ));
+            }
+            organization_crud::add_member(
+                invitation.organization_id,
+                user_id,
+                invitation.role,
+                conn,
+            )
+        })?;
+
+        Ok(OrganizationMemberResponse {
+            user_id: member.user_id,
+            username: invitee.username,
+            email: invitee.email,
+            role: member.role.as_str().to_string(),
+            joined_at: member.created_at.and_utc().timestamp() as usize,
+        })
+    }
+
+    pub fn decline_invitation(
+        &self,
+        user_id: i32,
+        invitation_id: &str,
+    ) -> Result<OrganizationInvitationResponse, OrganizationError> {
+        let conn = &mut self.pool.get().unwrap();
+        let invitee = self.get_user(user_id, conn)?;
+        let invitation = organization_crud::get_invitation(invitation_id, conn)?;
+
+        check_organization_invitee(&invitation.email, &invitee)?;
+
+        let invitation = organization_crud::delete_invitation(invitation.id, conn)?;
+
+        Ok(invitation.into())
+    }
+
+    pub fn update_member_role(
+        &self,
+        org_id: &str,
+        user_id: i32,
+        member_user_id: i32,
+        request: &MemberRoleUpdateRequest,
+    ) -> Result<OrganizationMemberResponse, OrganizationError> {
+        let new_role = parse_role(&request.role)?;
+        let conn = &mut self.pool.get().unwrap();
+
+        let actor_role = organization_crud::get_role(org_id, user_id, conn)?;
+        let member = organization_crud::get_member(org_id, member_user_id, conn)?;
+        check_can_manage(actor_role, member.role)?;
+        check_can_manage(actor_role, new_role)?;
+
+        if member.role == OrganizationRole::Owner
+            && new_role != OrganizationRole::Owner
+            && organization_crud::count_owners(org_id, conn)? <= 1
+        {
+            return Err(OrganizationError::Conflict(
+                "An organization must keep at least one owner".to_string(),
+            ));
+        }
+
+        organization_crud::update_role(org_id, member_user_id, new_role, conn)?;
+
+        self.member_response(org_id, member_user_id, conn)
+    }
+
+    /// Removes a member. Members may always leave an organization themselves;
+    /// removing someone else requires the right to manage their role.
+    pub fn remove_member(
+        &self,
+        org_id: &str,
+        user_id: i32,
+        member_user_id: i32,
+    ) -> Result<OrganizationMemberResponse, OrganizationError> {
+        let conn = &mut self.pool.get().unwrap();
+
+        let actor_role = organization_crud::get_role(org_id, user_id, conn)?;
+        let member = organization_crud::get_member(org_id, member_user_id, conn)?;
+        if user_id != member_user_id {
+            check_can_manage(actor_role, member.role)?;
+        }
+
+        if member.role == OrganizationRole::Owner
+            && organization_crud::count_owners(org_id, conn)? <= 1
+        {
+            return Err(OrganizationError::Conflict(
+                "An organization must keep at least one owner".to_string(),
+            ));
+        }
+
+        let response = self.member_response(org_id, member_user_id, conn)?;
+        organization_crud::remove_member(org_id, member_user_id, conn)?;
+
+        Ok(response)
+    }
+
+    pub fn list_livekit_credentials(
+        &self,
+        org_id: &str,
+        user_id: i32,
+    ) -> Result<Vec<LivekitCredentialResponse>, OrganizationError> {
+        let conn = &mut self.pool.get().unwrap();
+        organization_crud::get_role(org_id, user_id, conn)?;
+
+        let credentials = organization_crud::list_livekit_credentials(org_id, conn)?;
+        Ok(credentials.into_iter().map(Into::into).collect())
+    }
+
+    pub fn create_livekit_credential(
+        &self,
+        org_id: &str,
+        user_id: i32,
+        request: &LivekitCredentialRequest,
+    ) -> Result<LivekitCredentialResponse, OrganizationError> {
+        validate_name(&request.name)?;
+
+        let conn = &mut self.pool.get().unwrap();
+        let role = organization_crud::get_role(org_id, user_id, conn)?;
+        require_role(role, OrganizationRole::Admin)?;
+
+        let mut new_credential = NewLivekitCredential {
+            name: request.name.trim().to_string(),
+            server_url: request.server_url.clone(),
+            api_key: request.api_key.clone(),
+            api_secret: request.api_secret.clone(),
+            organization_id: Uuid::parse_str(org_id)?,
+        };
+        new_credential.encrypt(&self.encryption_key)?;
+
+        let credential = organization_crud::create_livekit_credential(&new_credential, conn)?;
+        Ok(credential.into())
+    }
+
+    /// Updates LiveKit credentials, which takes effect for every project using them.
+    pub fn update_livekit_credential(
+        &self,
+        org_id: &str,
+        user_id: i32,
+        credential_id: &str,
+        request: &LivekitCredentialUpdateRequest,
+    ) -> Result<LivekitCredentialResponse, OrganizationError> {
+        if let Some(name) = &request.name {
+            validate_name(name)?;
+        }
+
+        let conn = &mut self.pool.get().unwrap();
+        let role = organization_crud::get_role(org_id, user_id, conn)?;
+        require_role(role, OrganizationRole::Admin)?;
+        organization_crud::get_livekit_credential(org_id, credential_id, conn)?;
+
+        let mut changeset = LivekitCredentialChangeset {
+            name: request.name.as_ref().map(|name| name.trim().to_string()),
+            server_url: request.server_url.clone(),
+            api_key: request.api_key.clone(),
+            api_secret: request.api_secret.clone(),
+            updated_at: chrono::Utc::now().naive_utc(),
+        };
+        changeset.encrypt(&self.encryption_key)?;
+
+        let credential =
+            organization_crud::update_livekit_credential(org_id, credential_id, &changeset, conn)?;
+        Ok(credential.into())
+    }
+
+    pub fn delete_livekit_credential(
+        &self,
+        org_id: &str,
+        user_id: i32,
+        credential_id: &str,
+    ) -> Result<LivekitCredentialResponse, OrganizationError> {
+        let conn = &mut self.pool.get().unwrap();
+        let role = organization_crud::get_role(org_id, user_id, conn)?;
+        require_role(role, OrganizationRole::Admin)?;
+        organization_crud::get_livekit_credential(org_id, credential_id, conn)?;
+
+        let credential = organization_crud::delete_livekit_credential(org_id, credential_id, conn)?;
+        Ok(credential.into())
+    }
+
+    pub fn list_storage_credentials(
+        &self,
+        org_id: &str,
+        user_id: i32,
+    ) -> Result<Vec<StorageCredentialResponse>, OrganizationError> {
+        let conn = &mut self.pool.get().unwrap();
+        organization_crud::get_role(org_id, user_id, conn)?;
+
+        let credentials = organization_crud::list_storage_credentials(org_id, conn)?;
+        Ok(credentials.into_iter().map(Into::into).collect())
+    }
+
+    pub fn create_storage_credential(
+        &self,
+        org_id: &str,
+        user_id: i32,
+        request: &StorageCredentialRequest,
+    ) -> Result<StorageCredentialResponse, OrganizationError> {
+        validate_name(&request.name)?;
+        let storage_type = parse_storage_type(&request.storage_type)?;
+
+        let conn = &mut self.pool.get().unwrap();
+        let role = organization_crud::get_role(org_id, user_id, conn)?;
+        require_role(role, OrganizationRole::Admin)?;
+
+        let mut new_credential = NewStorageCredential {
+            name: request.name.trim().to_string(),
+            storage_type,
+            bucket_name: request.bucket_name.clone(),
+            endpoint: request.endpoint.clone(),
+            access_key: request.access_key.clone(),
+            secret_key: request.secret_key.clone(),
+            region: request.region.clone(),
+            organization_id: Uuid::parse_str(org_id)?,
+        };
+        new_credential.encrypt(&self.encryption_key)?;
+
+        let credential = organization_crud::create_storage_credential(&new_credential, conn)?;
+        Ok(credential.into())
+    }
+
+    /// Updates storage credentials, which takes effect for every project using them.
+    pub fn update_storage_credential(
+        &self,
+        org_id: &str,
+        user_id: i32,
+        credential_id: &str,
+        request: &StorageCredentialUpdateRequest,
+    ) -> Result<StorageCredentialResponse, OrganizationError> {
+        if let Some(name) = &request.name {
+            validate_name(name)?;
+        }
+
+        let conn = &mut self.pool.get().unwrap();
+        let role = organization_crud::get_role(org_id, user_id, conn)?;
+        require_role(role, OrganizationRole::Admin)?;
+        organization_crud::get_storage_credential(org_id, credential_id, conn)?;
+
+        let mut changeset = StorageCredentialChangeset {
+            name: request.name.as_ref().map(|name| name.trim().to_string()),
+            bucket_name: request.bucket_name.clone(),
+            endpoint: request.endpoint.clone(),
+            access_key: request.access_key.clone(),
+            secret_key: request.secret_key.clone(),
+            region: request.region.clone(),
+            updated_at: chrono::Utc::now().naive_utc(),
+        };
+        changeset.encrypt(&self.encryption_key)?;
+
+        let credential =
+            organization_crud::update_storage_credential(org_id, credential_id, &changeset, conn)?;
+        Ok(credential.into())
+    }
+
+    pub fn delete_storage_credential(
+        &self,
+        org_id: &str,
+        user_id: i32,
+        credential_id: &str,
+    ) -> Result<StorageCredentialResponse, OrganizationError> {
+        let conn = &mut self.pool.get().unwrap();
+        let role = organization_crud::get_role(org_id, user_id, conn)?;
+        require_role(role, OrganizationRole::Admin)?;
+        organization_crud::get_storage_credential(org_id, credential_id, conn)?;
+
+        let credential = organization_crud::delete_storage_credential(org_id, credential_id, conn)?;
+        Ok(credential.into())
+    }
+
+    fn member_response(
+        &self,
+        org_id: &str,
+        member_user_id: i32,
+        conn: &mut diesel::PgConnection,
+    ) -> Result<OrganizationMemberResponse, OrganizationError> {
+        let member = organization_crud::get_member(org_id, member_user_id, conn)?;
+        let member_user = self.get_user(member_user_id, conn)?;
+
+        Ok(OrganizationMemberResponse {
+            user_id: member.user_id,
+            username: member_user.username,
+            email: member_user.email,
+            role: member.role.as_str().to_string(),
+            joined_at: member.created_at.and_utc().timestamp() as usize,
+        })
+    }
+
+    fn get_user(
+        &self,
+        user_id: i32,
+        conn: &mut diesel::PgConnection,
+    ) -> Result<User, OrganizationError> {
+        user::get_user(user_id, conn).map_err(|e| OrganizationError::NotFound(e.to_string()))
+    }
+}
+
+impl Clone for OrganizationService {
+    fn clone(&self) -> Self {
+        Self {
+            pool: self.pool.clone(),
+            encryption_key: self.encryption_key.clone(),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_check_can_manage() {
+        assert!(check_can_manage(OrganizationRole::Owner, OrganizationRole::Owner).is_ok());
+        assert!(check_can_manage(OrganizationRole::Admin, OrganizationRole::Admin).is_ok());
+        assert!(check_can_manage(OrganizationRole::Admin, OrganizationRole::Member).is_ok());
+        assert!(check_can_manage(OrganizationRole::Admin, OrganizationRole::Owner).is_err());
+        assert!(check_can_manage(OrganizationRole::Member, OrganizationRole::Member).is_err());
+    }
+
+    #[test]
+    fn test_organization_role_grants_project_role() {
+        use domain::models::ProjectRole;
+
+        assert_eq!(OrganizationRole::Owner.project_role(), ProjectRole::Owner);
+        assert_eq!(OrganizationRole::Admin.project_role(), ProjectRole::Admin);
+        assert_eq!(OrganizationRole::Member.project_role(), ProjectRole::Viewer);
+    }
+
+    #[test]
+    fn test_validate_name() {
+        assert!(validate_name("Research Lab").is_ok());
+        assert!(validate_name("  ").is_err());
+        assert!(validate_name(&"a".repeat(MAX_NAME_LENGTH + 1)).is_err());
+        assert!(parse_storage_type("S3").is_ok());
+        assert!(parse_storage_type("gcs").is_err());
+    }
+}
@@ -0,0 +1,331 @@
+use std::str::FromStr;
+
+use diesel::{prelude::*, PgConnection};
+use domain::models::{MediaDeletionMode, NewScheduledDeletion, ScheduledDeletion};
+use domain::schema::syncflow::{project_sessions, projects, scheduled_deletions, session_egresses};
+use shared::deletion_models::DeletionQuery;
+use shared::deployment_config::DeploymentConfig;
+use shared::response_models::Response;
+use thiserror::Error;
+use uuid::Uuid;
+
+use crate::project::project_crud::ProjectError;
+use crate::project::session_crud::SessionError;
+use crate::storage::StorageError;
+
+const DEFAULT_GRACE_PERIOD_SECONDS: u64 = 7 * 24 * 60 * 60;
+
+const DEFAULT_ARCHIVE_PREFIX: &str = "archive";
+
+#[derive(Error, Debug)]
+pub enum DeletionError {
+    #[error("Database Error: {0}")]
+    DatabaseError(#[from] diesel::result::Error),
+
+    #[error("Project Error: {0}")]
+    ProjectError(#[from] ProjectError),
+
+    #[error("Session Error: {0}")]
+    SessionError(#[from] SessionError),
+
+    #[error("Storage Error: {0}")]
+    StorageError(#[from] StorageError),
+
+    #[error("Not Deleted: {0}")]
+    NotDeleted(String),
+}
+
+impl From<DeletionError> for Response {
+    fn from(error: DeletionError) -> Self {
+        match error {
+            DeletionError::DatabaseError(diesel::result::Error::NotFound) => Response {
+                status: 404,
+                message: "Not found".to_string(),
+            },
+            DeletionError::DatabaseError(e) => Response {
+                status: 500,
+                message: e.to_string(),
+            },
+            DeletionError::ProjectError(e) => e.into(),
+            DeletionError::SessionError(e) => e.into(),
+            DeletionError::StorageError(e) => Response {
+                status: 502,
+                message: e.to_string(),
+            },
+            DeletionError::NotDeleted(e) => Response {
+                status: 404,
+                message: e,
+            },
+        }
+    }
+}
+
+/// How long a deletion can be undone.
+pub fn grace_period(config: &DeploymentConfig) -> chrono::Duration {
+    chrono::Duration::seconds(
+        config
+            .deletion_grace_period_seconds
+            .unwrap_or(DEFAULT_GRACE_PERIOD_SECONDS) as i64,
+    )
+}
+
+/// Checks the media mode of a deletion, `keep` when unset. Only `archive`
+/// takes a prefix, which must be a relative path without `.` or `..` segments.
+pub fn parse_deletion_query(
+    query: &DeletionQuery,
+) -> Result<(MediaDeletionMode, Option<String>), ProjectError> {
+    let mode = match query.media.as_deref() {
+        Some(media) => {
+            MediaDeletionMode::from_str(media).map_err(ProjectError::ConfigurationError)?
+        }
+        None => MediaDeletionMode::Keep,
+    };
+
+    if mode != MediaDeletionMode::Archive {
+        return match query.archive_prefix {
+            Some(_) => Err(ProjectError::ConfigurationError(format!(
+                "The {} media mode does not take an archivePrefix",
+                mode.as_str()
+            ))),
+            None => Ok((mode, None)),
+        };
+    }
+
+    let prefix = query
+        .archive_prefix
+        .as_deref()
+        .unwrap_or(DEFAULT_ARCHIVE_PREFIX)
+        .trim_matches('/');
+    if prefix.is_empty()
+        || prefix
+            .split('/')
+            .any(|segment| segment.is_empty() || segment == "." || segment == "..")
+    {
+        return Err(ProjectError::ConfigurationError(format!(
+            "Invalid archivePrefix: {:?}",
+            prefix
+        )));
+    }
+
+    Ok((mode, Some(prefix.to_string())))
+}
+
+/// A deletion as requested, before it is scheduled for a project or session.
+#[derive(Debug, Clone)]
+pub struct DeletionRequest {
+    pub media_mode: MediaDeletionMode,
+    pub archive_prefix: Option<String>,
+    pub requested_by: Option<i32>,
+    pub purge_after: chrono::NaiveDateTime,
+}
+
+impl DeletionRequest {
+    pub fn from_query(
+        query: &DeletionQuery,
+        requested_by: Option<i32>,
+        grace_period: chrono::Duration,
+    ) -> Result<Self, ProjectError> {
+        let (media_mode, archive_prefix) = parse_deletion_query(query)?;
+
+        Ok(DeletionRequest {
+            media_mode,
+            archive_prefix,
+            requested_by,
+            purge_after: chrono::Utc::now().naive_utc() + grace_period,
+        })
+    }
+
+    /// The deletion of the project, or of one of its sessions.
+    pub fn scheduled(&self, proj_uuid: Uuid, sess_uuid: Option<Uuid>) -> NewScheduledDeletion {
+        NewScheduledDeletion {
+            project_id: proj_uuid,
+            session_id: sess_uuid,
+            media_mode: self.media_mode,
+            archive_prefix: self.archive_prefix.clone(),
+            requested_by: self.requested_by,
+            purge_after: self.purge_after,
+        }
+    }
+}
+
+pub fn schedule_deletion(
+    deletion: &NewScheduledDeletion,
+    conn: &mut PgConnection,
+) -> Result<ScheduledDeletion, diesel::result::Error> {
+    diesel::insert_into(scheduled_deletions::table)
+        .values(deletion)
+        .get_result::<ScheduledDeletion>(conn)
+}
+
+/// Pending deletions of the project and of its sessions, the soonest purged first.
+pub fn list_deletions(
+    proj_uuid: Uuid,
+    conn: &mut PgConnection,
+) -> Result<Vec<ScheduledDeletion>, DeletionError> {
+    let deletions = scheduled_deletions::table
+        .filter(scheduled_deletions::project_id.eq(proj_uuid))
+        .order(scheduled_deletions::purge_after.asc())
+        .load::<ScheduledDeletion>(conn)?;
+
+    Ok(deletions)
+}
+
+/// Undoes the pending deletion of the project, or of one of its sessions.
+pub fn cancel_deletion(
+    proj_uuid: Uuid,
+    sess_uuid: Option<Uuid>,
+    conn: &mut PgConnection,
+) -> Result<ScheduledDeletion, DeletionError> {
+    let pending = scheduled_deletions::table
+        .filter(scheduled_deletions::project_id.eq(proj_uuid))
+        .into_boxed();
+    let pending = match sess_uuid {
+        Some(sess_uuid) => pending.filter(scheduled_deletions::session_id.eq(sess_uuid)),
+        None => pending.filter(scheduled_deletions::session_id.is_null()),
+    };
+
+    let deletion = pending
+        .first::<ScheduledDeletion>(conn)
+        .optional()?
+        .ok_or_else(|| {
+            DeletionError::NotDeleted(match sess_uuid {
+                Some(_) => "The session is not pending deletion".to_string(),
+                None => "The project is not pending deletion".to_string(),
+            })
+        })?;
+
+    diesel::delete(scheduled_deletions::table.find(deletion.id)).execute(conn)?;
+
+    Ok(deletion)
+}
+
+/// Deletions whose grace period ended before `now`, the longest overdue first.
+pub fn due_deletions(
+    now: chrono::NaiveDateTime,
+    limit: i64,
+    conn: &mut PgConnection,
+) -> Result<Vec<ScheduledDeletion>, DeletionError> {
+    let deletions = scheduled_deletions::table
+        .filter(scheduled_deletions::purge_after.le(now))
+        .order(scheduled_deletions::purge_after.asc())
+        .limit(limit)
+        .load::<ScheduledDeletion>(conn)?;
+
+    Ok(deletions)
+}
+
+/// The LiveKit room of the session, and whether other sessions of the
+/// project reuse its name.
+pub fn session_room(
+    proj_uuid: Uuid,
+    sess_uuid: Uuid,
+    conn: &mut PgConnection,
+) -> Result<(String, bool), DeletionError> {
+    let room_name = project_sessions::table
+        .find(sess_uuid)
+        .select(project_sessions::livekit_room_name)
+        .first::<String>(conn)?;
+
+    let num_sharing = project_sessions::table
+        .filter(project_sessions::project_id.eq(proj_uuid))
+        .filter(project_sessions::livekit_room_name.eq(&room_name))
+        .filter(project_sessions::id.ne(sess_uuid))
+        .count()
+        .get_result::<i64>(conn)?;
+
+    Ok((room_name, num_sharing > 0))
+}
+
+/// Recordings tracked for the deleted project or session that are still in
+/// the bucket.
+pub fn egress_destinations(
+    deletion: &ScheduledDeletion,
+    conn: &mut PgConnection,
+) -> Result<Vec<String>, DeletionError> {
+    let egresses = session_egresses::table
+        .inner_join(project_sessions::table)
+        .filter(project_sessions::project_id.eq(deletion.project_id))
+        .filter(session_egresses::destination.is_not_null())
+        .filter(session_egresses::purged_at.is_null())
+        .into_boxed();
+    let egresses = match deletion.session_id {
+        Some(sess_uuid) => egresses.filter(project_sessions::id.eq(sess_uuid)),
+        None => egresses,
+    };
+
+    let destinations = egresses
+        .select(session_egresses::destination.assume_not_null())
+        .load::<String>(conn)?;
+
+    Ok(destinations)
+}
+
+pub fn record_failure(
+    deletion_id: Uuid,
+    error: &str,
+    conn: &mut PgConnection,
+) -> Result<(), DeletionError> {
+    diesel::update(scheduled_deletions::table.find(deletion_id))
+        .set((
+            scheduled_deletions::attempts.eq(scheduled_deletions::attempts + 1),
+            scheduled_deletions::last_error.eq(error),
+        ))
+        .execute(conn)?;
+
+    Ok(())
+}
+
+/// Removes the deleted project or session. Its sessions, egresses and the
+/// deletion itself go with it.
+pub fn complete_deletion(
+    deletion: &ScheduledDeletion,
+    conn: &mut PgConnection,
+) -> Result<(), DeletionError> {
+    match deletion.session_id {
+        Some(sess_uuid) => {
+            diesel::delete(project_sessions::table.find(sess_uuid)).execute(conn)?;
+        }
+        None => {
+            diesel::delete(projects::table.find(deletion.project_id)).execute(conn)?;
+        }
+    }
+
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn query(media: Option<&str>, archive_prefix: Option<&str>) -> DeletionQuery {
+        DeletionQuery {
+            media: media.map(str::to_string),
+            archive_prefix: archive_prefix.map(str::to_string),
+        }
+    }
+
+    #[test]
+    fn test_parse_deletion_query() {
+        assert_eq!(
+            parse_deletion_query(&query(None, None)).unwrap(),
+            (MediaDeletionMode::Keep, None)
+        );
+        assert_eq!(
+            parse_deletion_query(&query(Some("delete"), None)).unwrap(),
+            (MediaDeletionMode::Delete, None)
+        );
+        assert_eq!(
+            parse_deletion_query(&query(Some("archive"), None)).unwrap(),
+            (MediaDeletionMode::Archive, Some("archive".to_string()))
+        );
+        assert_eq!(
+            parse_deletion_query(&query(Some("archive"), Some("/studies/2025/"))).unwrap(),
+            (MediaDeletionMode::Archive, Some("studies/2025".to_string()))
+        );
+        assert!(parse_deletion_query(&query(Some("shred"), None)).is_err());
+        assert!(parse_deletion_query(&query(Some("delete"), Some("archive"))).is_err());
+        assert!(parse_deletion_query(&query(Some("archive"), Some("/"))).is_err());
+        assert!(parse_deletion_query(&query(Some("archive"), Some("a/../b"))).is_err());
+        assert!(parse_deletion_query(&query(Some("archive"), Some("a//b"))).is_err());
+    }
+}
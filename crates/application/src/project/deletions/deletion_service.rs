@@ -0,0 +1,339 @@
+use std::collections::BTreeSet;
+use std::sync::Arc;
+
+use domain::models::{MediaDeletionMode, Project, ProjectSession, ScheduledDeletion};
+use infrastructure::DbPool;
+use shared::deletion_models::ScheduledDeletionResponse;
+use shared::project_models::ProjectSessionResponse;
+use shared::user_models::ProjectInfo;
+use uuid::Uuid;
+
+use super::deletion_crud::{self, DeletionError};
+use crate::audit::{
+    audit_service::AuditService, AuditAction, AuditActor, AuditTarget, AuditTargetType,
+};
+use crate::project::project_crud::{self, Encryptable, ProjectError};
+use crate::project::session_crud;
+use crate::recordings::media;
+use crate::storage::{Storage, StorageError};
+
+/// Upper bound on the deletions purged in one run.
+const PURGE_BATCH_SIZE: i64 = 50;
+
+/// The recordings to remove: every file under `prefix`, if any, and the
+/// recorded egress destinations.
+async fn recording_keys(
+    storage: &dyn Storage,
+    prefix: Option<&str>,
+    destinations: Vec<String>,
+) -> Result<BTreeSet<String>, StorageError> {
+    let mut keys = match prefix {
+        Some(prefix) => storage
+            .list(prefix)
+            .await?
+            .into_iter()
+            .map(|object| object.key)
+            .collect::<BTreeSet<String>>(),
+        None => BTreeSet::new(),
+    };
+    keys.extend(destinations);
+
+    Ok(keys)
+}
+
+/// Restores deleted projects and sessions during their grace period, and
+/// purges them once it ended. Every recording moved or removed is audited.
+pub struct DeletionService {
+    encryption_key: String,
+    pool: Arc<DbPool>,
+    audit: AuditService,
+}
+
+impl DeletionService {
+    pub fn new(encryption_key: &str, pool: Arc<DbPool>) -> Self {
+        Self {
+            encryption_key: encryption_key.to_string(),
+            audit: AuditService::new(pool.clone()),
+            pool,
+        }
+    }
+
+    /// Pending deletions of the project and of its sessions. Listed for
+    /// deleted projects too, so that they can be found and restored.
+    pub fn list_deletions(
+        &self,
+        project_id: &str,
+    ) -> Result<Vec<ScheduledDeletionResponse>, DeletionError> {
+        let conn = &mut self.pool.get().unwrap();
+        let project = project_crud::get_project_including_deleted(project_id, conn)?;
+        let deletions = deletion_crud::list_deletions(project.id, conn)?;
+
+        Ok(deletions.into_iter().map(Into::into).collect())
+    }
+
+    pub fn restore_project(
+        &self,
+        actor: &AuditActor,
+        project_id: &str,
+    ) -> Result<ProjectInfo, DeletionError> {
+        let restored = self.cancel_project_deletion(project_id);
+        self.audit.record(
+            actor,
+            AuditAction::ProjectRestored,
+            AuditTarget::project(project_id),
+            &restored,
+        );
+
+        Ok(restored?.into())
+    }
+
+    fn cancel_project_deletion(&self, project_id: &str) -> Result<Project, DeletionError> {
+        let conn = &mut self.pool.get().unwrap();
+        let project = project_crud::get_project_including_deleted(project_id, conn)?;
+        deletion_crud::cancel_deletion(project.id, None, conn)?;
+
+        Ok(project)
+    }
+
+    /// Restores a deleted session. Sessions of a deleted project can only be
+    /// restored once the project is.
+    pub fn restore_session(
+        &self,
+        actor: &AuditActor,
+        project_id: &str,
+        session_id: &str,
+    ) -> Result<ProjectSessionResponse, DeletionError> {
+        let restored = self.cancel_session_deletion(project_id, session_id);
+        self.audit.record(
+            actor,
+            AuditAction::SessionRestored,
+            AuditTarget::in_project(
+                project_id,
+                AuditTargetType::Session,
+                Some(session_id.to_string()),
+            ),
+            &restored,
+        );
+
+        Ok(restored?.into())
+    }
+
+    fn cancel_session_deletion(
+        &self,
+        project_id: &str,
+        session_id: &str,
+    ) -> Result<ProjectSession, DeletionError> {
+        let conn = &mut self.pool.get().unwrap();
+        let project = project_crud::get_project_by_id(project_id, conn)?;
+        let sess_uuid = Uuid::parse_str(session_id)
+            .map_err(|_| ProjectError::ConfigurationError("Invalid session id".to_string()))?;
+        deletion_crud::cancel_deletion(project.id, Some(sess_uuid), conn)?;
+
+        Ok(session_crud::get_session(project_id, session_id, conn)?)
+    }
+
+    /// Purges the deletions whose grace period ended. Deletions that fail are
+    /// retried on the next run. Returns the number purged.
+    pub async fn purge_due_deletions(&self) -> Result<usize, DeletionError> {
+        let actor = AuditActor::default();
+        let now = chrono::Utc::now().naive_utc();
+        let mut num_purged = 0;
+
+        for deletion in
+            deletion_crud::due_deletions(now, PURGE_BATCH_SIZE, &mut self.pool.get().unwrap())?
+        {
+            let project_id = deletion.project_id.to_string();
+            let (action, target) = match deletion.session_id {
+                Some(sess_uuid) => (
+                    AuditAction::SessionPurged,
+                    AuditTarget::in_project(
+                        &project_id,
+                        AuditTargetType::Session,
+                        Some(sess_uuid.to_string()),
+                    ),
+                ),
+                None => (
+                    AuditAction::ProjectPurged,
+                    AuditTarget::project(&project_id),
+                ),
+            };
+
+            let purged = self.purge(&actor, &deletion).await;
+            self.audit.record(&actor, action, target, &purged);
+
+            match purged {
+                Ok(()) => num_purged += 1,
+                Err(e) => {
+                    log::warn!("Failed to purge deletion {}: {}", deletion.id, e);
+                    deletion_crud::record_failure(
+                        deletion.id,
+                        &e.to_string(),
+                        &mut self.pool.get().unwrap(),
+                    )?;
+                }
+            }
+        }
+
+        Ok(num_purged)
+    }
+
+    /// Applies the media mode of the deletion to every recording of the
+    /// project or session, tracked or only found under its prefix, then
+    /// removes the project or session.
+    async fn purge(
+        &self,
+        actor: &AuditActor,
+        deletion: &ScheduledDeletion,
+    ) -> Result<(), DeletionError> {
+        let project = self.decrypted_project(deletion.project_id)?;
+
+        if deletion.media_mode != MediaDeletionMode::Keep {
+            let storage: Box<dyn Storage> = (&project).into();
+            let prefix = self.media_prefix(&project, deletion)?;
+            let destinations =
+                deletion_crud::egress_destinations(deletion, &mut self.pool.get().unwrap())?;
+            let keys = recording_keys(storage.as_ref(), prefix.as_deref(), destinations).await?;
+
+            for key in keys {
+                self.remove_recording(actor, storage.as_ref(), &project, deletion, &key)
+                    .await?;
+            }
+        }
+
+        deletion_crud::complete_deletion(deletion, &mut self.pool.get().unwrap())
+    }
+
+    async fn remove_recording(
+        &self,
+        actor: &AuditActor,
+        storage: &dyn Storage,
+        project: &Project,
+        deletion: &ScheduledDeletion,
+        key: &str,
+    ) -> Result<(), DeletionError> {
+        let (action, removed) = match deletion.archive_prefix.as_deref() {
+            Some(archive_prefix) if deletion.media_mode == MediaDeletionMode::Archive => {
+                let archived = format!("{}/{}", archive_prefix, key);
+                let moved = match storage.copy(key, &archived).await {
+                    Ok(()) => storage.delete(key).await,
+                    Err(e) => Err(e),
+                };
+                (AuditAction::RecordingArchived, moved)
+            }
+            _ => (AuditAction::RecordingPurged, storage.delete(key).await),
+        };
+        self.audit.record(
+            actor,
+            action,
+            AuditTarget::in_project(
+                &project.id.to_string(),
+                AuditTargetType::Recording,
+                Some(key.to_string()),
+            ),
+            &removed,
+        );
+
+        Ok(removed?)
+    }
+
+    /// Where LiveKit writes the recordings of the project or session. Room
+    /// names are not unique, the files under a room other sessions of the
+    /// project reuse may be theirs, so such a session has no prefix.
+    fn media_prefix(
+        &self,
+        project: &Project,
+        deletion: &ScheduledDeletion,
+    ) -> Result<Option<String>, DeletionError> {
+        let recording_root = project.get_recording_root();
+
+        match deletion.session_id {
+            Some(sess_uuid) => {
+                let (room_name, shared) = deletion_crud::session_room(
+                    project.id,
+                    sess_uuid,
+                    &mut self.pool.get().unwrap(),
+                )?;
+                Ok((!shared).then(|| media::room_prefix(&recording_root, &room_name)))
+            }
+            None => Ok(Some(media::root_prefix(&recording_root))),
+        }
+    }
+
+    fn decrypted_project(&self, proj_uuid: Uuid) -> Result<Project, DeletionError> {
+        let mut project = project_crud::get_project_including_deleted(
+            &proj_uuid.to_string(),
+            &mut self.pool.get().unwrap(),
+        )?;
+        project
+            .decrypt(&self.encryption_key)
+            .map_err(ProjectError::from)?;
+        Ok(project)
+    }
+}
+
+impl Clone for DeletionService {
+    fn clone(&self) -> Self {
+        Self {
+            encryption_key: self.encryption_key.clone(),
+            pool: self.pool.clone(),
+            audit: self.audit.clone(),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::storage::memory::InMemoryStorage;
+
+    #[tokio::test]
+    async fn test_recording_keys_of_sessions_sharing_a_room() {
+        let storage = InMemoryStorage::new();
+        for key in [
+            "root/room/tracks/alice/1/audio.ogg",
+            "root/room/tracks/bob/2/audio.ogg",
+            "root/room/composite.mp4",
+            "room/tracks/TR_A1.ogg",
+        ] {
+            storage.put(key, vec![1]).await.unwrap();
+        }
+        let prefix = media::room_prefix("root", "room");
+        let destinations = vec![
+            "root/room/tracks/alice/1/audio.ogg".to_string(),
+            "room/tracks/TR_A1.ogg".to_string(),
+        ];
+
+        // A session alone in its room owns everything under the prefix
+        let keys = recording_keys(&storage, Some(&prefix), destinations.clone())
+            .await
+            .unwrap();
+        assert_eq!(
+            keys.iter().map(String::as_str).collect::<Vec<_>>(),
+            vec![
+                "room/tracks/TR_A1.ogg",
+                "root/room/composite.mp4",
+                "root/room/tracks/alice/1/audio.ogg",
+                "root/room/tracks/bob/2/audio.ogg",
+            ]
+        );
+
+        // Another session reusing the room keeps its files
+        let keys = recording_keys(&storage, None, destinations).await.unwrap();
+        for key in &keys {
+            storage.delete(key).await.unwrap();
+        }
+        assert_eq!(
+            storage
+                .list("")
+                .await
+                .unwrap()
+                .into_iter()
+                .map(|object| object.key)
+                .collect::<Vec<_>>(),
+            vec![
+                "root/room/composite.mp4",
+                "root/room/tracks/bob/2/audio.ogg"
+            ]
+        );
+    }
+}
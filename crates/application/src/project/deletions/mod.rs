@@ -0,0 +1,3 @@
+pub mod deletion_crud;
+pub mod deletion_service;
+pub mod purger;
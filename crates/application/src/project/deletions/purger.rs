@@ -0,0 +1,41 @@
+use std::sync::Arc;
+use std::time::Duration;
+
+use infrastructure::DbPool;
+use shared::deployment_config::DeploymentConfig;
+
+use super::deletion_service::DeletionService;
+
+const DEFAULT_PURGE_INTERVAL_SECONDS: u64 = 600;
+
+/// Periodically purges the projects and sessions whose deletion grace period
+/// ended, applying the media mode each was deleted with.
+pub struct DeletionPurger {
+    deletion_service: DeletionService,
+    interval: Duration,
+}
+
+impl DeletionPurger {
+    pub fn new(config: &DeploymentConfig, pool: Arc<DbPool>) -> Self {
+        Self {
+            deletion_service: DeletionService::new(&config.encryption_key, pool),
+            interval: Duration::from_secs(
+                config
+                    .deletion_purge_interval_seconds
+                    .unwrap_or(DEFAULT_PURGE_INTERVAL_SECONDS),
+            ),
+        }
+    }
+
+    pub async fn run(self) {
+        loop {
+            match self.deletion_service.purge_due_deletions().await {
+                Ok(0) => {}
+                Ok(purged) => log::info!("Purged {} deleted projects and sessions", purged),
+                Err(e) => log::error!("Failed to purge deletions: {}", e),
+            }
+
+            tokio::time::sleep(self.interval).await;
+        }
+    }
+}
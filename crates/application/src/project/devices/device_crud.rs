@@ -1,10 +1,19 @@
-use crate::rmq::session_notifier::SessionNotifierError;
+use crate::notifier::NotifierError;
+use diesel::result::DatabaseErrorKind;
 use diesel::{prelude::*, PgConnection};
-use domain::models::{NewProjectDevice, ProjectDevice};
-use shared::device_models::DeviceRegisterRequest;
+use domain::models::{
+    DeviceGroup, DeviceGroupChangeset, NewDeviceGroup, NewProjectDevice, ProjectDevice,
+    ProjectDeviceChangeset,
+};
+use shared::device_models::{
+    DeviceGroupRequest, DeviceGroupUpdateRequest, DeviceRegisterRequest, DeviceUpdateRequest,
+};
 use thiserror::Error;
 use uuid::Uuid;
 
+/// The unique constraint on the name of a group within its project.
+const DEVICE_GROUP_NAME_CONSTRAINT: &str = "device_groups_project_id_name_key";
+
 #[derive(Error, Debug)]
 pub enum DeviceError {
     #[error("Database Error: {0}")]
@@ -16,8 +25,14 @@ pub enum DeviceError {
     #[error("Device not found")]
     NotFound(String),
 
-    #[error("Session Notifier Error: {0}")]
-    SessionNotifierError(#[from] SessionNotifierError),
+    #[error("Invalid Request: {0}")]
+    InvalidRequest(String),
+
+    #[error("Notifier Error: {0}")]
+    NotifierError(#[from] NotifierError),
+
+    #[error("Connection Pool Error: {0}")]
+    PoolError(#[from] diesel::r2d2::PoolError),
 }
 
 impl From<DeviceError> for shared::response_models::Response {
@@ -28,6 +43,27 @@ impl From<DeviceError> for shared::response_models::Response {
                     status: 404,
                     message: e.to_string(),
                 },
+                diesel::result::Error::DatabaseError(
+                    DatabaseErrorKind::UniqueViolation,
+                    ref info,
+                ) if info.constraint_name() == Some(DEVICE_GROUP_NAME_CONSTRAINT) => {
+                    shared::response_models::Response {
+                        status: 409,
+                        message: "A device group with this name already exists".to_string(),
+                    }
+                }
+                diesel::result::Error::DatabaseError(DatabaseErrorKind::UniqueViolation, _) => {
+                    shared::response_models::Response {
+                        status: 409,
+                        message: e.to_string(),
+                    }
+                }
+                diesel::result::Error::DatabaseError(DatabaseErrorKind::ForeignKeyViolation, _) => {
+                    shared::response_models::Response {
+                        status: 409,
+                        message: "The device group still has registered devices".to_string(),
+                    }
+                }
                 _ => shared::response_models::Response {
                     status: 500,
                     message: e.to_string(),
@@ -41,7 +77,15 @@ impl From<DeviceError> for shared::response_models::Response {
                 status: 404,
                 message: e,
             },
-            DeviceError::SessionNotifierError(e) => shared::response_models::Response {
+            DeviceError::InvalidRequest(e) => shared::response_models::Response {
+                status: 400,
+                message: e,
+            },
+            DeviceError::NotifierError(e) => shared::response_models::Response {
+                status: 500,
+                message: e.to_string(),
+            },
+            DeviceError::PoolError(e) => shared::response_models::Response {
                 status: 500,
                 message: e.to_string(),
             },
@@ -59,6 +103,9 @@ pub fn register_device(
 
     let proj_uuid = Uuid::parse_str(proj_id)?;
 
+    validate_device_name(&registration_request.name)?;
+    ensure_group(proj_uuid, &registration_request.group, conn)?;
+
     let new_project_device = NewProjectDevice {
         project_id: proj_uuid,
         registered_by: uid,
@@ -73,17 +120,38 @@ pub fn register_device(
     Ok(project_device)
 }
 
+pub fn register_devices(
+    proj_id: &str,
+    uid: i32,
+    registration_requests: &[DeviceRegisterRequest],
+    conn: &mut PgConnection,
+) -> Result<Vec<ProjectDevice>, DeviceError> {
+    conn.transaction(|conn| {
+        registration_requests
+            .iter()
+            .map(|request| register_device(proj_id, uid, request, conn))
+            .collect()
+    })
+}
+
 pub fn list_devices(
     proj_id: &str,
+    group: Option<&str>,
     conn: &mut PgConnection,
 ) -> Result<Vec<ProjectDevice>, DeviceError> {
     use domain::schema::syncflow::project_devices::dsl::*;
 
     let proj_uuid = Uuid::parse_str(proj_id)?;
 
-    let devices = project_devices
+    let mut query = project_devices
         .filter(project_id.eq(proj_uuid))
-        .load::<ProjectDevice>(conn)?;
+        .into_boxed();
+
+    if let Some(grp) = group {
+        query = query.filter(device_group.eq(grp.to_string()));
+    }
+
+    let devices = query.load::<ProjectDevice>(conn)?;
 
     Ok(devices)
 }
@@ -106,6 +174,52 @@ pub fn get_device(
     Ok(device)
 }
 
+/// Applies a partial update to a device, returning the device as it was before
+/// and after the update.
+pub fn update_device(
+    proj_id: &str,
+    device_id: &str,
+    update_request: &DeviceUpdateRequest,
+    conn: &mut PgConnection,
+) -> Result<(ProjectDevice, ProjectDevice), DeviceError> {
+    use domain::schema::syncflow::project_devices::dsl::*;
+
+    if update_request.is_empty() {
+        return Err(DeviceError::InvalidRequest(
+            "At least one of name, group or comments must be provided".to_string(),
+        ));
+    }
+
+    let proj_uuid = Uuid::parse_str(proj_id)?;
+    let device_uuid = Uuid::parse_str(device_id)?;
+
+    if let Some(name) = &update_request.name {
+        validate_device_name(name)?;
+    }
+
+    conn.transaction(|conn| {
+        let previous = project_devices
+            .filter(project_id.eq(proj_uuid).and(id.eq(device_uuid)))
+            .first::<ProjectDevice>(conn)?;
+
+        if let Some(grp) = &update_request.group {
+            ensure_group(proj_uuid, grp, conn)?;
+        }
+
+        let changeset = ProjectDeviceChangeset {
+            device_name: update_request.name.clone(),
+            device_group: update_request.group.clone(),
+            comments: update_request.comments.clone(),
+        };
+
+        let updated = diesel::update(project_devices.filter(id.eq(device_uuid)))
+            .set(changeset)
+            .get_result::<ProjectDevice>(conn)?;
+
+        Ok((previous, updated))
+    })
+}
+
 pub fn delete_device(
     proj_id: &str,
     device_id: &str,
@@ -124,22 +238,239 @@ pub fn delete_device(
     Ok(device)
 }
 
+/// The routing keys of the device groups of a project. Projects scheduled for
+/// deletion have none.
 pub fn get_possible_routing_keys(
     proj_id: &str,
     conn: &mut PgConnection,
 ) -> Result<Vec<String>, DeviceError> {
     use domain::schema::syncflow::project_devices::dsl::*;
+    use domain::schema::syncflow::scheduled_deletions;
 
     let proj_uuid = Uuid::parse_str(proj_id)?;
 
-    let devices = project_devices
+    let device_groups = project_devices
         .filter(project_id.eq(proj_uuid))
-        .load::<ProjectDevice>(conn)?;
+        .filter(
+            project_id.ne_all(
+                scheduled_deletions::table
+                    .filter(scheduled_deletions::session_id.is_null())
+                    .select(scheduled_deletions::project_id),
+            ),
+        )
+        .select(device_group)
+        .distinct()
+        .load::<String>(conn)?;
 
-    let routing_keys = devices
+    let routing_keys = device_groups
         .iter()
-        .map(|d| format!("{}.{}", proj_id, d.device_group))
+        .map(|grp| format!("{}.{}", proj_id, grp))
         .collect();
 
     Ok(routing_keys)
 }
+
+pub fn count_devices_in_group(
+    proj_id: &str,
+    group: &str,
+    conn: &mut PgConnection,
+) -> Result<i64, DeviceError> {
+    use domain::schema::syncflow::project_devices::dsl::*;
+
+    let proj_uuid = Uuid::parse_str(proj_id)?;
+
+    let count = project_devices
+        .filter(project_id.eq(proj_uuid).and(device_group.eq(group)))
+        .count()
+        .get_result::<i64>(conn)?;
+
+    Ok(count)
+}
+
+pub fn get_all_routing_keys(conn: &mut PgConnection) -> Result<Vec<String>, DeviceError> {
+    use domain::schema::syncflow::project_devices::dsl::*;
+
+    let project_groups = project_devices
+        .select((project_id, device_group))
+        .distinct()
+        .load::<(Uuid, String)>(conn)?;
+
+    let routing_keys = project_groups
+        .iter()
+        .map(|(proj_uuid, grp)| format!("{}.{}", proj_uuid, grp))
+        .collect();
+
+    Ok(routing_keys)
+}
+
+pub fn validate_device_name(name: &str) -> Result<(), DeviceError> {
+    if name.trim().is_empty() || name.chars().count() > 50 {
+        return Err(DeviceError::InvalidRequest(
+            "Device name must be between 1 and 50 characters".to_string(),
+        ));
+    }
+
+    Ok(())
+}
+
+/// Group names become the last word of the `{project_id}.{group}` routing key,
+/// so they may not contain topic separators or wildcards.
+pub fn validate_group_name(name: &str) -> Result<(), DeviceError> {
+    if name.trim().is_empty() || name.chars().count() > 50 {
+        return Err(DeviceError::InvalidRequest(
+            "Device group name must be between 1 and 50 characters".to_string(),
+        ));
+    }
+
+    if name.contains(['.', '*', '#']) {
+        return Err(DeviceError::InvalidRequest(format!(
+            "Device group name {} may not contain '.', '*' or '#'",
+            name
+        )));
+    }
+
+    Ok(())
+}
+
+/// Returns the named group of the project, creating it with default settings
+/// if it does not exist yet.
+fn ensure_group(
+    proj_uuid: Uuid,
+    group_name: &str,
+    conn: &mut PgConnection,
+) -> Result<DeviceGroup, DeviceError> {
+    use domain::schema::syncflow::device_groups::dsl::*;
+
+    validate_group_name(group_name)?;
+
+    let new_group = NewDeviceGroup {
+        name: group_name.to_string(),
+        description: None,
+        notify_on_session_start: false,
+        project_id: proj_uuid,
+    };
+
+    diesel::insert_into(device_groups)
+        .values(new_group)
+        .on_conflict((project_id, name))
+        .do_nothing()
+        .execute(conn)?;
+
+    let group = device_groups
+        .filter(project_id.eq(proj_uuid).and(name.eq(group_name)))
+        .first::<DeviceGroup>(conn)?;
+
+    Ok(group)
+}
+
+pub fn create_group(
+    proj_id: &str,
+    group_request: &DeviceGroupRequest,
+    conn: &mut PgConnection,
+) -> Result<DeviceGroup, DeviceError> {
+    use domain::schema::syncflow::device_groups::dsl::*;
+
+    let proj_uuid = Uuid::parse_str(proj_id)?;
+
+    validate_group_name(&group_request.name)?;
+
+    let new_group = NewDeviceGroup {
+        name: group_request.name.clone(),
+        description: group_request.description.clone(),
+        notify_on_session_start: group_request.notify_on_session_start,
+        project_id: proj_uuid,
+    };
+
+    let group = diesel::insert_into(device_groups)
+        .values(new_group)
+        .get_result::<DeviceGroup>(conn)?;
+
+    Ok(group)
+}
+
+pub fn list_groups(
+    proj_id: &str,
+    conn: &mut PgConnection,
+) -> Result<Vec<DeviceGroup>, DeviceError> {
+    use domain::schema::syncflow::device_groups::dsl::*;
+
+    let proj_uuid = Uuid::parse_str(proj_id)?;
+
+    let groups = device_groups
+        .filter(project_id.eq(proj_uuid))
+        .order(name.asc())
+        .load::<DeviceGroup>(conn)?;
+
+    Ok(groups)
+}
+
+pub fn get_group(
+    proj_id: &str,
+    group_id: &str,
+    conn: &mut PgConnection,
+) -> Result<DeviceGroup, DeviceError> {
+    use domain::schema::syncflow::device_groups::dsl::*;
+
+    let proj_uuid = Uuid::parse_str(proj_id)?;
+    let group_uuid = Uuid::parse_str(group_id)?;
+
+    let group = device_groups
+        .filter(project_id.eq(proj_uuid).and(id.eq(group_uuid)))
+        .first::<DeviceGroup>(conn)?;
+
+    Ok(group)
+}
+
+/// Applies a partial update to a device group, returning the group as it was
+/// before and after the update. Renaming a group cascades to its devices.
+pub fn update_group(
+    proj_id: &str,
+    group_id: &str,
+    update_request: &DeviceGroupUpdateRequest,
+    conn: &mut PgConnection,
+) -> Result<(DeviceGroup, DeviceGroup), DeviceError> {
+    use domain::schema::syncflow::device_groups::dsl::*;
+
+    let proj_uuid = Uuid::parse_str(proj_id)?;
+    let group_uuid = Uuid::parse_str(group_id)?;
+
+    if let Some(new_name) = &update_request.name {
+        validate_group_name(new_name)?;
+    }
+
+    conn.transaction(|conn| {
+        let previous = device_groups
+            .filter(project_id.eq(proj_uuid).and(id.eq(group_uuid)))
+            .first::<DeviceGroup>(conn)?;
+
+        let changeset = DeviceGroupChangeset {
+            name: update_request.name.clone(),
+            description: update_request.description.clone(),
+            notify_on_session_start: update_request.notify_on_session_start,
+            updated_at: Some(chrono::Utc::now().naive_utc()),
+        };
+
+        let updated = diesel::update(device_groups.filter(id.eq(group_uuid)))
+            .set(changeset)
+            .get_result::<DeviceGroup>(conn)?;
+
+        Ok((previous, updated))
+    })
+}
+
+pub fn delete_group(
+    proj_id: &str,
+    group_id: &str,
+    conn: &mut PgConnection,
+) -> Result<DeviceGroup, DeviceError> {
+    use domain::schema::syncflow::device_groups::dsl::*;
+
+    let proj_uuid = Uuid::parse_str(proj_id)?;
+    let group_uuid = Uuid::parse_str(group_id)?;
+
+    let group =
+        diesel::delete(device_groups.filter(project_id.eq(proj_uuid).and(id.eq(group_uuid))))
+            .get_result::<DeviceGroup>(conn)?;
+
+    Ok(group)
+}
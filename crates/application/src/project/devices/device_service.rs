@@ -1,39 +1,101 @@
+use std::collections::HashSet;
 use std::sync::Arc;
 
 use domain::models::ProjectDevice;
 use infrastructure::DbPool;
-use shared::deployment_config::DeploymentConfig;
-use shared::device_models::{DeviceRegisterRequest, DeviceResponse};
+use shared::deployment_config::{DeploymentConfig, NotifierConfig};
+use shared::device_models::{
+    BulkDeviceRegisterResponse, DeviceGroupRequest, DeviceGroupResponse, DeviceGroupUpdateRequest,
+    DeviceRegisterRequest, DeviceResponse, DeviceUpdateRequest,
+};
 
 use super::device_crud::{self, DeviceError};
-use crate::rmq::session_notifier::SessionNotifier;
+use crate::audit::{
+    audit_service::AuditService, AuditAction, AuditActor, AuditTarget, AuditTargetType,
+};
+use crate::notifier::{DeviceNotifier, Notifier};
+
+/// Unbinds the routing key of a group left without devices. The group is
+/// counted again once unbound, so that a device registered into it in the
+/// meantime gets its binding back. The device change is already saved, a
+/// failed unbind only leaves a stale binding that the next resync drops.
+async fn release_routing_key<F>(notifier: &dyn Notifier, routing_key: &str, count_devices: F)
+where
+    F: FnOnce() -> Result<i64, DeviceError>,
+{
+    if let Err(e) = notifier.unbind_routing_key(routing_key).await {
+        log::warn!("Failed to unbind routing key {}: {}", routing_key, e);
+        return;
+    }
+
+    if !matches!(count_devices(), Ok(0)) {
+        if let Err(e) = notifier.bind_routing_key(routing_key).await {
+            log::error!("Failed to rebind routing key {}: {}", routing_key, e);
+        }
+    }
+}
+
+/// Upper bound on the number of devices accepted by a single bulk registration.
+pub const MAX_BULK_REGISTRATIONS: usize = 500;
+
+/// Parses a bulk registration CSV with a `name,group,comments` header row.
+/// Empty `comments` fields are read as absent.
+pub fn parse_devices_csv(data: &[u8]) -> Result<Vec<DeviceRegisterRequest>, DeviceError> {
+    let mut reader = csv::ReaderBuilder::new()
+        .trim(csv::Trim::All)
+        .from_reader(data);
+
+    reader
+        .deserialize::<DeviceRegisterRequest>()
+        .enumerate()
+        .map(|(idx, record)| {
+            record.map_err(|e| {
+                DeviceError::InvalidRequest(format!("Invalid CSV record {}: {}", idx + 1, e))
+            })
+        })
+        .collect()
+}
 
 pub struct DeviceService {
     config: DeploymentConfig,
     pool: Arc<DbPool>,
+    audit: AuditService,
 }
 
 impl DeviceService {
     pub fn new(config: &DeploymentConfig, pool: Arc<DbPool>) -> Self {
         Self {
             config: config.clone(),
+            audit: AuditService::new(pool.clone()),
             pool,
         }
     }
 
     pub async fn register_device(
         &self,
+        actor: &AuditActor,
         project_id: &str,
         user_id: i32,
         registration_request: &DeviceRegisterRequest,
-        notifier: &SessionNotifier,
+        notifier: &DeviceNotifier,
     ) -> Result<DeviceResponse, DeviceError> {
-        let device = device_crud::register_device(
+        let registered = device_crud::register_device(
             project_id,
             user_id,
             registration_request,
             &mut self.pool.get().unwrap(),
-        )?;
+        );
+        self.audit.record(
+            actor,
+            AuditAction::DeviceRegistered,
+            AuditTarget::in_project(
+                project_id,
+                AuditTargetType::Device,
+                registered.as_ref().ok().map(|device| device.id.to_string()),
+            ),
+            &registered,
+        );
+        let device = registered?;
 
         let routing_key = self.routing_key_for(&device);
         notifier.bind_routing_key(&routing_key).await?;
@@ -41,8 +103,72 @@ impl DeviceService {
         Ok(self.device_to_response(&device))
     }
 
-    pub fn list_devices(&self, project_id: &str) -> Result<Vec<DeviceResponse>, DeviceError> {
-        let devices = device_crud::list_devices(project_id, &mut self.pool.get().unwrap())?;
+    pub async fn register_devices(
+        &self,
+        actor: &AuditActor,
+        project_id: &str,
+        user_id: i32,
+        registration_requests: &[DeviceRegisterRequest],
+        notifier: &DeviceNotifier,
+    ) -> Result<BulkDeviceRegisterResponse, DeviceError> {
+        if registration_requests.is_empty() {
+            return Err(DeviceError::InvalidRequest(
+                "No devices to register".to_string(),
+            ));
+        }
+
+        if registration_requests.len() > MAX_BULK_REGISTRATIONS {
+            return Err(DeviceError::InvalidRequest(format!(
+                "At most {} devices can be registered at once",
+                MAX_BULK_REGISTRATIONS
+            )));
+        }
+
+        let registered = device_crud::register_devices(
+            project_id,
+            user_id,
+            registration_requests,
+            &mut self.pool.get().unwrap(),
+        );
+        match &registered {
+            Ok(devices) => {
+                for device in devices {
+                    self.audit.record(
+                        actor,
+                        AuditAction::DeviceRegistered,
+                        self.device_target(project_id, &device.id.to_string()),
+                        &registered,
+                    );
+                }
+            }
+            Err(_) => self.audit.record(
+                actor,
+                AuditAction::DeviceRegistered,
+                AuditTarget::in_project(project_id, AuditTargetType::Device, None),
+                &registered,
+            ),
+        }
+        let devices = registered?;
+
+        let routing_keys = devices
+            .iter()
+            .map(|d| self.routing_key_for(d))
+            .collect::<HashSet<String>>();
+        for routing_key in routing_keys {
+            notifier.bind_routing_key(&routing_key).await?;
+        }
+
+        Ok(BulkDeviceRegisterResponse {
+            registered: devices.iter().map(|d| self.device_to_response(d)).collect(),
+        })
+    }
+
+    pub fn list_devices(
+        &self,
+        project_id: &str,
+        group: Option<&str>,
+    ) -> Result<Vec<DeviceResponse>, DeviceError> {
+        let devices = device_crud::list_devices(project_id, group, &mut self.pool.get().unwrap())?;
         Ok(devices.iter().map(|d| self.device_to_response(d)).collect())
     }
 
@@ -55,24 +181,241 @@ impl DeviceService {
         Ok(self.device_to_response(&device))
     }
 
-    pub fn delete_device(
+    pub async fn update_device(
         &self,
+        actor: &AuditActor,
         project_id: &str,
         device_id: &str,
+        update_request: &DeviceUpdateRequest,
+        notifier: &DeviceNotifier,
     ) -> Result<DeviceResponse, DeviceError> {
-        let device =
-            device_crud::delete_device(project_id, device_id, &mut self.pool.get().unwrap())?;
+        let (previous, updated, remaining_in_group) = {
+            let conn = &mut self.pool.get().unwrap();
+            let changed = device_crud::update_device(project_id, device_id, update_request, conn);
+            self.audit.record(
+                actor,
+                AuditAction::DeviceUpdated,
+                self.device_target(project_id, device_id),
+                &changed,
+            );
+            let (previous, updated) = changed?;
+            let remaining_in_group =
+                device_crud::count_devices_in_group(project_id, &previous.device_group, conn)?;
+            (previous, updated, remaining_in_group)
+        };
+
+        if previous.device_group != updated.device_group {
+            notifier
+                .bind_routing_key(&self.routing_key_for(&updated))
+                .await?;
+            if remaining_in_group == 0 {
+                self.release_group_binding(
+                    project_id,
+                    &previous.device_group,
+                    &self.routing_key_for(&previous),
+                    notifier,
+                )
+                .await;
+            }
+        }
+
+        Ok(self.device_to_response(&updated))
+    }
+
+    pub async fn delete_device(
+        &self,
+        actor: &AuditActor,
+        project_id: &str,
+        device_id: &str,
+        notifier: &DeviceNotifier,
+    ) -> Result<DeviceResponse, DeviceError> {
+        let (device, remaining_in_group) = {
+            let conn = &mut self.pool.get().unwrap();
+            let deleted = device_crud::delete_device(project_id, device_id, conn);
+            self.audit.record(
+                actor,
+                AuditAction::DeviceDeleted,
+                self.device_target(project_id, device_id),
+                &deleted,
+            );
+            let device = deleted?;
+            let remaining_in_group =
+                device_crud::count_devices_in_group(project_id, &device.device_group, conn)?;
+            (device, remaining_in_group)
+        };
+
+        if remaining_in_group == 0 {
+            self.release_group_binding(
+                project_id,
+                &device.device_group,
+                &self.routing_key_for(&device),
+                notifier,
+            )
+            .await;
+        }
+
         Ok(self.device_to_response(&device))
     }
 
+    /// Unbinds the routing key of a group left without devices, see
+    /// `release_routing_key`.
+    async fn release_group_binding(
+        &self,
+        project_id: &str,
+        group: &str,
+        routing_key: &str,
+        notifier: &DeviceNotifier,
+    ) {
+        release_routing_key(notifier, routing_key, || {
+            device_crud::count_devices_in_group(project_id, group, &mut self.pool.get()?)
+        })
+        .await
+    }
+
+    /// Rebinds the server queue to exactly the routing keys of the registered
+    /// device groups, dropping bindings left behind by deleted groups.
+    pub async fn sync_routing_keys(
+        &self,
+        notifier: &DeviceNotifier,
+    ) -> Result<HashSet<String>, DeviceError> {
+        let routing_keys = device_crud::get_all_routing_keys(&mut self.pool.get()?)?
+            .into_iter()
+            .collect::<HashSet<String>>();
+
+        notifier.sync_routing_keys(&routing_keys).await?;
+
+        Ok(routing_keys)
+    }
+
+    pub fn create_group(
+        &self,
+        actor: &AuditActor,
+        project_id: &str,
+        group_request: &DeviceGroupRequest,
+    ) -> Result<DeviceGroupResponse, DeviceError> {
+        let created =
+            device_crud::create_group(project_id, group_request, &mut self.pool.get().unwrap());
+        self.audit.record(
+            actor,
+            AuditAction::DeviceGroupCreated,
+            AuditTarget::in_project(
+                project_id,
+                AuditTargetType::DeviceGroup,
+                created.as_ref().ok().map(|group| group.id.to_string()),
+            ),
+            &created,
+        );
+        Ok(created?.into_group_response(0))
+    }
+
+    pub fn list_groups(&self, project_id: &str) -> Result<Vec<DeviceGroupResponse>, DeviceError> {
+        let conn = &mut self.pool.get().unwrap();
+        let groups = device_crud::list_groups(project_id, conn)?;
+
+        groups
+            .into_iter()
+            .map(|group| {
+                let num_devices =
+                    device_crud::count_devices_in_group(project_id, &group.name, conn)?;
+                Ok(group.into_group_response(num_devices))
+            })
+            .collect()
+    }
+
+    pub fn get_group(
+        &self,
+        project_id: &str,
+        group_id: &str,
+    ) -> Result<DeviceGroupResponse, DeviceError> {
+        let conn = &mut self.pool.get().unwrap();
+        let group = device_crud::get_group(project_id, group_id, conn)?;
+        let num_devices = device_crud::count_devices_in_group(project_id, &group.name, conn)?;
+        Ok(group.into_group_response(num_devices))
+    }
+
+    /// Updates a device group. Renaming a group moves its devices along with it
+    /// and swaps the queue binding over to the new routing key.
+    pub async fn update_group(
+        &self,
+        actor: &AuditActor,
+        project_id: &str,
+        group_id: &str,
+        update_request: &DeviceGroupUpdateRequest,
+        notifier: &DeviceNotifier,
+    ) -> Result<DeviceGroupResponse, DeviceError> {
+        let (previous, updated, num_devices) = {
+            let conn = &mut self.pool.get().unwrap();
+            let changed = device_crud::update_group(project_id, group_id, update_request, conn);
+            self.audit.record(
+                actor,
+                AuditAction::DeviceGroupUpdated,
+                self.group_target(project_id, group_id),
+                &changed,
+            );
+            let (previous, updated) = changed?;
+            let num_devices = device_crud::count_devices_in_group(project_id, &updated.name, conn)?;
+            (previous, updated, num_devices)
+        };
+
+        if previous.name != updated.name && num_devices > 0 {
+            notifier
+                .bind_routing_key(&format!("{}.{}", project_id, updated.name))
+                .await?;
+            notifier
+                .unbind_routing_key(&format!("{}.{}", project_id, previous.name))
+                .await?;
+        }
+
+        Ok(updated.into_group_response(num_devices))
+    }
+
+    pub fn delete_group(
+        &self,
+        actor: &AuditActor,
+        project_id: &str,
+        group_id: &str,
+    ) -> Result<DeviceGroupResponse, DeviceError> {
+        let deleted =
+            device_crud::delete_group(project_id, group_id, &mut self.pool.get().unwrap());
+        self.audit.record(
+            actor,
+            AuditAction::DeviceGroupDeleted,
+            self.group_target(project_id, group_id),
+            &deleted,
+        );
+        Ok(deleted?.into_group_response(0))
+    }
+
+    fn device_target(&self, project_id: &str, device_id: &str) -> AuditTarget {
+        AuditTarget::in_project(
+            project_id,
+            AuditTargetType::Device,
+            Some(device_id.to_string()),
+        )
+    }
+
+    fn group_target(&self, project_id: &str, group_id: &str) -> AuditTarget {
+        AuditTarget::in_project(
+            project_id,
+            AuditTargetType::DeviceGroup,
+            Some(group_id.to_string()),
+        )
+    }
+
     fn routing_key_for(&self, device: &ProjectDevice) -> String {
         format!("{}.{}", device.project_id, device.device_group)
     }
 
     fn device_to_response(&self, device: &ProjectDevice) -> DeviceResponse {
-        let routing_key = self.routing_key_for(device);
-        let exchange_name = &self.config.rabbitmq_config.exchange_name;
-        device.into_device_response(&routing_key, exchange_name)
+        // Only AMQP devices subscribe to an exchange themselves
+        match self.config.notifier_config {
+            None | Some(NotifierConfig::Amqp) => {
+                let routing_key = self.routing_key_for(device);
+                let exchange_name = &self.config.rabbitmq_config.exchange_name;
+                device.into_device_response(&routing_key, exchange_name)
+            }
+            _ => device.clone().into(),
+        }
     }
 }
 
@@ -81,6 +424,73 @@ impl Clone for DeviceService {
         Self {
             config: self.config.clone(),
             pool: self.pool.clone(),
+            audit: self.audit.clone(),
         }
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::notifier::memory::InMemoryNotifier;
+
+    #[test]
+    fn test_parse_devices_csv() {
+        let csv = b"name,group,comments\nCamera 1, lab-a ,Front door\nCamera 2,lab-b,\n";
+        let devices = parse_devices_csv(csv).unwrap();
+
+        assert_eq!(devices.len(), 2);
+        assert_eq!(devices[0].name, "Camera 1");
+        assert_eq!(devices[0].group, "lab-a");
+        assert_eq!(devices[0].comments.as_deref(), Some("Front door"));
+        assert_eq!(devices[1].group, "lab-b");
+        assert_eq!(devices[1].comments, None);
+    }
+
+    #[test]
+    fn test_parse_devices_csv_rejects_missing_columns() {
+        let csv = b"name,comments\nCamera 1,Front door\n";
+        assert!(matches!(
+            parse_devices_csv(csv),
+            Err(DeviceError::InvalidRequest(_))
+        ));
+    }
+
+    #[tokio::test]
+    async fn test_release_routing_key() {
+        fn bound(keys: &[&str]) -> HashSet<String> {
+            keys.iter().map(|key| key.to_string()).collect()
+        }
+        let notifier = InMemoryNotifier::default();
+
+        // The group stayed empty
+        notifier.bind_routing_key("project.empty").await.unwrap();
+        release_routing_key(&notifier, "project.empty", || Ok(0)).await;
+        assert_eq!(notifier.bound_routing_keys(), bound(&[]));
+
+        // A device registered into the group while it was unbound
+        notifier.bind_routing_key("project.joined").await.unwrap();
+        release_routing_key(&notifier, "project.joined", || Ok(1)).await;
+        assert_eq!(notifier.bound_routing_keys(), bound(&["project.joined"]));
+
+        // Keeping a binding too many is safer than losing notifications
+        notifier.bind_routing_key("project.unknown").await.unwrap();
+        release_routing_key(&notifier, "project.unknown", || {
+            Err(DeviceError::InvalidRequest("Count failed".to_string()))
+        })
+        .await;
+        assert_eq!(
+            notifier.bound_routing_keys(),
+            bound(&["project.joined", "project.unknown"])
+        );
+    }
+
+    #[test]
+    fn test_validate_group_name() {
+        assert!(device_crud::validate_group_name("lab-a").is_ok());
+        assert!(device_crud::validate_group_name("").is_err());
+        assert!(device_crud::validate_group_name("lab.a").is_err());
+        assert!(device_crud::validate_group_name("lab-*").is_err());
+        assert!(device_crud::validate_group_name(&"a".repeat(51)).is_err());
+    }
+}
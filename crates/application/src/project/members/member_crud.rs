@@ -0,0 +1,317 @@
+use diesel::{prelude::*, PgConnection};
+use domain::models::{
+    NewProjectInvitation, NewProjectMember, OrganizationRole, ProjectInvitation, ProjectMember,
+    ProjectRole,
+};
+use thiserror::Error;
+use uuid::Uuid;
+
+#[derive(Error, Debug)]
+pub enum MemberError {
+    #[error("Database Error: {0}")]
+    DatabaseError(#[from] diesel::result::Error),
+
+    #[error("Configuration Error: {0}")]
+    ConfigurationError(#[from] uuid::Error),
+
+    #[error("Not found: {0}")]
+    NotFound(String),
+
+    #[error("Forbidden: {0}")]
+    Forbidden(String),
+
+    #[error("Conflict: {0}")]
+    Conflict(String),
+
+    #[error("Invalid Request: {0}")]
+    InvalidRequest(String),
+}
+
+impl From<MemberError> for shared::response_models::Response {
+    fn from(error: MemberError) -> Self {
+        match error {
+            MemberError::DatabaseError(e) => match e {
+                diesel::result::Error::NotFound => shared::response_models::Response {
+                    status: 404,
+                    message: e.to_string(),
+                },
+                _ => shared::response_models::Response {
+                    status: 500,
+                    message: e.to_string(),
+                },
+            },
+            MemberError::ConfigurationError(e) => shared::response_models::Response {
+                status: 400,
+                message: e.to_string(),
+            },
+            MemberError::NotFound(e) => shared::response_models::Response {
+                status: 404,
+                message: e,
+            },
+            MemberError::Forbidden(e) => shared::response_models::Response {
+                status: 403,
+                message: e,
+            },
+            MemberError::Conflict(e) => shared::response_models::Response {
+                status: 409,
+                message: e,
+            },
+            MemberError::InvalidRequest(e) => shared::response_models::Response {
+                status: 400,
+                message: e,
+            },
+        }
+    }
+}
+
+/// Returns the role of the user in the project, the higher of the project
+/// membership and the role granted by the organization owning the project.
+/// Non-members get the same not found error as missing projects, so project
+/// ids are not disclosed.
+pub fn get_role(
+    proj_id: &str,
+    uid: i32,
+    conn: &mut PgConnection,
+) -> Result<ProjectRole, MemberError> {
+    use domain::schema::syncflow::{organization_members, project_members, projects};
+
+    let proj_uuid = Uuid::parse_str(proj_id)?;
+
+    let member_role = project_members::table
+        .filter(
+            project_members::project_id
+                .eq(proj_uuid)
+                .and(project_members::user_id.eq(uid)),
+        )
+        .select(project_members::role)
+        .first::<ProjectRole>(conn)
+        .optional()?;
+
+    let organization_role = projects::table
+        .inner_join(
+            organization_members::table
+                .on(projects::organization_id.eq(organization_members::organization_id.nullable())),
+        )
+        .filter(
+            projects::id
+                .eq(proj_uuid)
+                .and(organization_members::user_id.eq(uid)),
+        )
+        .select(organization_members::role)
+        .first::<OrganizationRole>(conn)
+        .optional()?
+        .map(|role| role.project_role());
+
+    match (member_role, organization_role) {
+        (Some(member_role), Some(organization_role)) => {
+            if member_role.includes(organization_role) {
+                Ok(member_role)
+            } else {
+                Ok(organization_role)
+            }
+        }
+        (Some(role), None) | (None, Some(role)) => Ok(role),
+        (None, None) => Err(MemberError::NotFound("Project not found".to_string())),
+    }
+}
+
+pub fn add_member(
+    proj_uuid: Uuid,
+    uid: i32,
+    member_role: ProjectRole,
+    conn: &mut PgConnection,
+) -> Result<ProjectMember, MemberError> {
+    use domain::schema::syncflow::project_members::dsl::*;
+
+    let new_member = NewProjectMember {
+        role: member_role,
+        project_id: proj_uuid,
+        user_id: uid,
+    };
+
+    let member = diesel::insert_into(project_members)
+        .values(new_member)
+        .get_result::<ProjectMember>(conn)?;
+
+    Ok(member)
+}
+
+/// Lists the members of a project along with their usernames and emails.
+pub fn list_members(
+    proj_id: &str,
+    conn: &mut PgConnection,
+) -> Result<Vec<(ProjectMember, String, String)>, MemberError> {
+    use domain::schema::syncflow::{project_members, users};
+
+    let proj_uuid = Uuid::parse_str(proj_id)?;
+
+    let members = project_members::table
+        .inner_join(users::table)
+        .filter(project_members::project_id.eq(proj_uuid))
+        .order(project_members::created_at.asc())
+        .select((project_members::all_columns, users::username, users::email))
+        .load::<(ProjectMember, String, String)>(conn)?;
+
+    Ok(members)
+}
+
+pub fn get_member(
+    proj_id: &str,
+    member_uid: i32,
+    conn: &mut PgConnection,
+) -> Result<ProjectMember, MemberError> {
+    use domain::schema::syncflow::project_members::dsl::*;
+
+    let proj_uuid = Uuid::parse_str(proj_id)?;
+
+    project_members
+        .filter(project_id.eq(proj_uuid).and(user_id.eq(member_uid)))
+        .first::<ProjectMember>(conn)
+        .optional()?
+        .ok_or_else(|| MemberError::NotFound("Member not found".to_string()))
+}
+
+pub fn count_owners(proj_id: &str, conn: &mut PgConnection) -> Result<i64, MemberError> {
+    use domain::schema::syncflow::project_members::dsl::*;
+
+    let proj_uuid = Uuid::parse_str(proj_id)?;
+
+    let count = project_members
+        .filter(project_id.eq(proj_uuid).and(role.eq(ProjectRole::Owner)))
+        .count()
+        .get_result::<i64>(conn)?;
+
+    Ok(count)
+}
+
+pub fn update_role(
+    proj_id: &str,
+    member_uid: i32,
+    new_role: ProjectRole,
+    conn: &mut PgConnection,
+) -> Result<ProjectMember, MemberError> {
+    use domain::schema::syncflow::project_members::dsl::*;
+
+    let proj_uuid = Uuid::parse_str(proj_id)?;
+
+    let member = diesel::update(
+        project_members.filter(project_id.eq(proj_uuid).and(user_id.eq(member_uid))),
+    )
+    .set(role.eq(new_role))
+    .get_result::<ProjectMember>(conn)?;
+
+    Ok(member)
+}
+
+pub fn remove_member(
+    proj_id: &str,
+    member_uid: i32,
+    conn: &mut PgConnection,
+) -> Result<ProjectMember, MemberError> {
+    use domain::schema::syncflow::project_members::dsl::*;
+
+    let proj_uuid = Uuid::parse_str(proj_id)?;
+
+    let member = diesel::delete(
+        project_members.filter(project_id.eq(proj_uuid).and(user_id.eq(member_uid))),
+    )
+    .get_result::<ProjectMember>(conn)?;
+
+    Ok(member)
+}
+
+pub fn create_invitation(
+    new_invitation: &NewProjectInvitation,
+    conn: &mut PgConnection,
+) -> Result<ProjectInvitation, MemberError> {
+    use domain::schema::syncflow::project_invitations::dsl::*;
+
+    let invitation = diesel::insert_into(project_invitations)
+        .values(new_invitation)
+        .get_result::<ProjectInvitation>(conn)?;
+
+    Ok(invitation)
+}
+
+pub fn list_project_invitations(
+    proj_id: &str,
+    conn: &mut PgConnection,
+) -> Result<Vec<ProjectInvitation>, MemberError> {
+    use domain::schema::syncflow::project_invitations::dsl::*;
+
+    let proj_uuid = Uuid::parse_str(proj_id)?;
+    let now = chrono::Utc::now().naive_utc();
+
+    let invitations = project_invitations
+        .filter(project_id.eq(proj_uuid).and(expires_at.gt(now)))
+        .order(created_at.desc())
+        .load::<ProjectInvitation>(conn)?;
+
+    Ok(invitations)
+}
+
+pub fn list_invitations_for_email(
+    invitee_email: &str,
+    conn: &mut PgConnection,
+) -> Result<Vec<ProjectInvitation>, MemberError> {
+    use domain::schema::syncflow::project_invitations::dsl::*;
+
+    let now = chrono::Utc::now().naive_utc();
+
+    let invitations = project_invitations
+        .filter(
+            email
+                .eq(invitee_email.to_lowercase())
+                .and(expires_at.gt(now)),
+        )
+        .order(created_at.desc())
+        .load::<ProjectInvitation>(conn)?;
+
+    Ok(invitations)
+}
+
+pub fn get_invitation(
+    invitation_id: &str,
+    conn: &mut PgConnection,
+) -> Result<ProjectInvitation, MemberError> {
+    use domain::schema::syncflow::project_invitations::dsl::*;
+
+    let invitation_uuid = Uuid::parse_str(invitation_id)?;
+    let now = chrono::Utc::now().naive_utc();
+
+    project_invitations
+        .filter(id.eq(invitation_uuid).and(expires_at.gt(now)))
+        .first::<ProjectInvitation>(conn)
+        .optional()?
+        .ok_or_else(|| MemberError::NotFound("Invitation not found".to_string()))
+}
+
+pub fn delete_invitation(
+    invitation_uuid: Uuid,
+    conn: &mut PgConnection,
+) -> Result<ProjectInvitation, MemberError> {
+    use domain::schema::syncflow::project_invitations::dsl::*;
+
+    let invitation = diesel::delete(project_invitations.filter(id.eq(invitation_uuid)))
+        .get_result::<ProjectInvitation>(conn)?;
+
+    Ok(invitation)
+}
+
+pub fn delete_project_invitation(
+    proj_id: &str,
+    invitation_id: &str,
+    conn: &mut PgConnection,
+) -> Result<ProjectInvitation, MemberError> {
+    use domain::schema::syncflow::project_invitations::dsl::*;
+
+    let proj_uuid = Uuid::parse_str(proj_id)?;
+    let invitation_uuid = Uuid::parse_str(invitation_id)?;
+
+    let invitation = diesel::delete(
+        project_invitations.filter(project_id.eq(proj_uuid).and(id.eq(invitation_uuid))),
+    )
+    .get_result::<ProjectInvitation>(conn)?;
+
+    Ok(invitation)
+}
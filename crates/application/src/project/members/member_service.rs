@@ -0,0 +1,352 @@
+use std::str::FromStr;
+use std::sync::Arc;
+
+use diesel::Connection;
+use domain::models::{NewProjectInvitation, ProjectRole, User};
+use infrastructure::DbPool;
+use shared::member_models::{
+    MemberRoleUpdateRequest, ProjectInvitationResponse, ProjectInviteRequest, ProjectMemberResponse,
+};
+use uuid::Uuid;
+
+use super::member_crud::{self, MemberError};
+use crate::users::user;
+
+/// How long an invitation can be accepted after it was sent.
+pub(crate) const INVITATION_VALIDITY_DAYS: i64 = 7;
+
+fn parse_role(role: &str) -> Result<ProjectRole, MemberError> {
+    ProjectRole::from_str(&role.to_lowercase()).map_err(MemberError::InvalidRequest)
+}
+
+/// Checks that `actor` may hand out or take away `role`: only owners manage
+/// owners, and nobody can grant more than they have.
+fn check_can_manage(actor: ProjectRole, role: ProjectRole) -> Result<(), MemberError> {
+    if !actor.includes(ProjectRole::Admin) {
+        return Err(MemberError::Forbidden(
+            "Managing members requires the admin role".to_string(),
+        ));
+    }
+
+    if !actor.includes(role) || (role == ProjectRole::Owner && actor != ProjectRole::Owner) {
+        return Err(MemberError::Forbidden(format!(
+            "A project {} cannot manage the {} role",
+            actor.as_str(),
+            role.as_str()
+        )));
+    }
+
+    Ok(())
+}
+
+/// Invitations are addressed to an email, only a user who verified owning it
+/// may accept or decline them.
+pub(crate) fn check_invitee(
+    invitation_email: &str,
+    invitee_email: &str,
+    email_verified: bool,
+) -> Result<(), MemberError> {
+    if invitation_email != invitee_email.to_lowercase() {
+        return Err(MemberError::NotFound("Invitation not found".to_string()));
+    }
+    if !email_verified {
+        return Err(MemberError::Forbidden(
+            "Verify your email address to respond to invitations".to_string(),
+        ));
+    }
+
+    Ok(())
+}
+
+pub struct MemberService {
+    pool: Arc<DbPool>,
+}
+
+impl MemberService {
+    pub fn new(pool: Arc<DbPool>) -> Self {
+        Self { pool }
+    }
+
+    pub fn get_role(&self, project_id: &str, user_id: i32) -> Result<ProjectRole, MemberError> {
+        member_crud::get_role(project_id, user_id, &mut self.pool.get().unwrap())
+    }
+
+    pub fn list_members(
+        &self,
+        project_id: &str,
+    ) -> Result<Vec<ProjectMemberResponse>, MemberError> {
+        let members = member_crud::list_members(project_id, &mut self.pool.get().unwrap())?;
+
+        Ok(members
+            .into_iter()
+            .map(|(member, username, email)| ProjectMemberResponse {
+                user_id: member.user_id,
+                username,
+                email,
+                role: member.role.as_str().to_string(),
+                joined_at: member.created_at.and_utc().timestamp() as usize,
+            })
+            .collect())
+    }
+
+    pub fn invite(
+        &self,
+        project_id: &str,
+        inviter_id: i32,
+        inviter_role: ProjectRole,
+        invite_request: &ProjectInviteRequest,
+    ) -> Result<ProjectInvitationResponse, MemberError> {
+        let role = parse_role(&invite_request.role)?;
+        check_can_manage(inviter_role, role)?;
+
+        let conn = &mut self.pool.get().unwrap();
+        let identifier = invite_request.username_or_email.trim();
+
+        let email = if identifier.contains('@') {
+            identifier.to_lowercase()
+        } else {
+            let invitee = user::get_user_by_username(identifier, conn)
+                .map_err(|_| MemberError::NotFound(format!("User {} not found", identifier)))?;
+            if member_crud::get_member(project_id, invitee.id, conn).is_ok() {
+                return Err(MemberError::Conflict(format!(
+                    "{} is already a member of the project",
+                    identifier
+                )));
+            }
+            invitee.email.to_lowercase()
+        };
+
+        let new_invitation = NewProjectInvitation {
+            email,
+            role,
+            expires_at: chrono::Utc::now().naive_utc()
+                + chrono::Duration::days(INVITATION_VALIDITY_DAYS),
+            project_id: Uuid::parse_str(project_id)?,
+            invited_by: inviter_id,
+        };
+
+        let invitation = member_crud::create_invitation(&new_invitation, conn)?;
+
+        Ok(invitation.into())
+    }
+
+    pub fn list_project_invitations(
+        &self,
+        project_id: &str,
+    ) -> Result<Vec<ProjectInvitationResponse>, MemberError> {
+        let invitations =
+            member_crud::list_project_invitations(project_id, &mut self.pool.get().unwrap())?;
+        Ok(invitations.into_iter().map(Into::into).collect())
+    }
+
+    pub fn revoke_invitation(
+        &self,
+        project_id: &str,
+        invitation_id: &str,
+    ) -> Result<ProjectInvitationResponse, MemberError> {
+        let invitation = member_crud::delete_project_invitation(
+            project_id,
+            invitation_id,
+            &mut self.pool.get().unwrap(),
+        )?;
+        Ok(invitation.into())
+    }
+
+    /// Pending invitations addressed to the user's email.
+    pub fn list_user_invitations(
+        &self,
+        user_id: i32,
+    ) -> Result<Vec<ProjectInvitationResponse>, MemberError> {
+        let conn = &mut self.pool.get().unwrap();
+        let invitee = self.get_user(user_id, conn)?;
+        let invitations = member_crud::list_invitations_for_email(&invitee.email, conn)?;
+        Ok(invitations.into_iter().map(Into::into).collect())
+    }
+
+    pub fn accept_invitation(
+        &self,
+        user_id: i32,
+        invitation_id: &str,
+    ) -> Result<ProjectMemberResponse, MemberError> {
+        let conn = &mut self.pool.get().unwrap();
+        let invitee = self.get_user(user_id, conn)?;
+        let invitation = member_crud::get_invitation(invitation_id, conn)?;
+
+        check_invitee(
+            &invitation.email,
+            &invitee.email,
+            invitee.email_verified_at.is_some(),
+        )?;
+
+        let member = conn.transaction(|conn| {
+            member_crud::delete_invitation(invitation.id, conn)?;
+            let project_id = invitation.project_id.to_string();
+            if member_crud::get_member(&project_id, user_id, conn).is_ok() {
+                return Err(MemberError::Conflict(
+                    "Already a member of the project".to_string(),
+                ));
+            }
+            member_crud::add_member(invitation.project_id, user_id, invitation.role, conn)
+        })?;
+
+        Ok(ProjectMemberResponse {
+            user_id: member.user_id,
+            username: invitee.username,
+            email: invitee.email,
+            role: member.role.as_str().to_string(),
+            joined_at: member.created_at.and_utc().timestamp() as usize,
+        })
+    }
+
+    pub fn decline_invitation(
+        &self,
+        user_id: i32,
+        invitation_id: &str,
+    ) -> Result<ProjectInvitationResponse, MemberError> {
+        let conn = &mut self.pool.get().unwrap();
+        let invitee = self.get_user(user_id, conn)?;
+        let invitation = member_crud::get_invitation(invitation_id, conn)?;
+
+        check_invitee(
+            &invitation.email,
+            &invitee.email,
+            invitee.email_verified_at.is_some(),
+        )?;
+
+        let invitation = member_crud::delete_invitation(invitation.id, conn)?;
+        Ok(invitation.into())
+    }
+
+    pub fn update_member_role(
+        &self,
+        project_id: &str,
+        actor_role: ProjectRole,
+        member_user_id: i32,
+        update_request: &MemberRoleUpdateRequest,
+    ) -> Result<ProjectMemberResponse, MemberError> {
+        let new_role = parse_role(&update_request.role)?;
+        let conn = &mut self.pool.get().unwrap();
+
+        let member = member_crud::get_member(project_id, member_user_id, conn)?;
+        check_can_manage(actor_role, member.role)?;
+        check_can_manage(actor_role, new_role)?;
+
+        if member.role == ProjectRole::Owner
+            && new_role != ProjectRole::Owner
+            && member_crud::count_owners(project_id, conn)? <= 1
+        {
+            return Err(MemberError::Conflict(
+                "A project must keep at least one owner".to_string(),
+            ));
+        }
+
+        member_crud::update_role(project_id, member_user_id, new_role, conn)?;
+
+        self.member_response(project_id, member_user_id, conn)
+    }
+
+    /// Removes a member. Members may always leave a project themselves;
+    /// removing someone else requires the right to manage their role.
+    pub fn remove_member(
+        &self,
+        project_id: &str,
+        actor_id: i32,
+        actor_role: ProjectRole,
+        member_user_id: i32,
+    ) -> Result<ProjectMemberResponse, MemberError> {
+        let conn = &mut self.pool.get().unwrap();
+
+        let member = member_crud::get_member(project_id, member_user_id, conn)?;
+        if actor_id != member_user_id {
+            check_can_manage(actor_role, member.role)?;
+        }
+
+        if member.role == ProjectRole::Owner && member_crud::count_owners(project_id, conn)? <= 1 {
+            return Err(MemberError::Conflict(
+                "A project must keep at least one owner".to_string(),
+            ));
+        }
+
+        let response = self.member_response(project_id, member_user_id, conn)?;
+        member_crud::remove_member(project_id, member_user_id, conn)?;
+
+        Ok(response)
+    }
+
+    fn member_response(
+        &self,
+        project_id: &str,
+        member_user_id: i32,
+        conn: &mut diesel::PgConnection,
+    ) -> Result<ProjectMemberResponse, MemberError> {
+        let member = member_crud::get_member(project_id, member_user_id, conn)?;
+        let member_user = self.get_user(member_user_id, conn)?;
+
+        Ok(ProjectMemberResponse {
+            user_id: member.user_id,
+            username: member_user.username,
+            email: member_user.email,
+            role: member.role.as_str().to_string(),
+            joined_at: member.created_at.and_utc().timestamp() as usize,
+        })
+    }
+
+    fn get_user(&self, user_id: i32, conn: &mut diesel::PgConnection) -> Result<User, MemberError> {
+        user::get_user(user_id, conn).map_err(|e| MemberError::NotFound(e.to_string()))
+    }
+}
+
+impl Clone for MemberService {
+    fn clone(&self) -> Self {
+        Self {
+            pool: self.pool.clone(),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_role_includes() {
+        assert!(ProjectRole::Owner.includes(ProjectRole::Viewer));
+        assert!(ProjectRole::Admin.includes(ProjectRole::Operator));
+        assert!(ProjectRole::Operator.includes(ProjectRole::Operator));
+        assert!(!ProjectRole::Viewer.includes(ProjectRole::Operator));
+        assert!(!ProjectRole::Admin.includes(ProjectRole::Owner));
+    }
+
+    #[test]
+    fn test_check_can_manage() {
+        assert!(check_can_manage(ProjectRole::Owner, ProjectRole::Owner).is_ok());
+        assert!(check_can_manage(ProjectRole::Owner, ProjectRole::Viewer).is_ok());
+        assert!(check_can_manage(ProjectRole::Admin, ProjectRole::Admin).is_ok());
+        assert!(check_can_manage(ProjectRole::Admin, ProjectRole::Operator).is_ok());
+        assert!(check_can_manage(ProjectRole::Admin, ProjectRole::Owner).is_err());
+        assert!(check_can_manage(ProjectRole::Operator, ProjectRole::Viewer).is_err());
+        assert!(check_can_manage(ProjectRole::Viewer, ProjectRole::Viewer).is_err());
+    }
+
+    #[test]
+    fn test_check_invitee() {
+        assert!(check_invitee("ada@example.com", "Ada@Example.com", true).is_ok());
+        assert!(matches!(
+            check_invitee("ada@example.com", "ada@example.com", false),
+            Err(MemberError::Forbidden(_))
+        ));
+        assert!(matches!(
+            check_invitee("ada@example.com", "eve@example.com", true),
+            Err(MemberError::NotFound(_))
+        ));
+    }
+
+    #[test]
+    fn test_parse_role() {
+        assert_eq!(parse_role("Admin").unwrap(), ProjectRole::Admin);
+        assert!(matches!(
+            parse_role("superuser"),
+            Err(MemberError::InvalidRequest(_))
+        ));
+    }
+}
@@ -0,0 +1,4 @@
+pub mod member_crud;
+pub mod member_service;
+
+pub use domain::models::ProjectRole;
@@ -1,5 +1,8 @@
+pub mod deletions;
 pub mod devices;
+pub mod members;
 pub mod project_crud;
 pub mod session_crud;
 pub mod session_listener;
 pub mod session_service;
+pub mod validation;
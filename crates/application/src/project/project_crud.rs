@@ -1,25 +1,35 @@
 use super::super::livekit::room::RoomService;
+use super::deletions::deletion_crud::{self, DeletionRequest};
+use super::{session_crud, validation};
 use crate::{
     livekit::egress::EgressService,
     users::secret::{decrypt_string, encrypt_string, key_secret_pair, SecretError},
 };
-use diesel::{prelude::*, PgConnection};
+use diesel::{pg::Pg, prelude::*, PgConnection};
 use domain::models::{
-    NewProject, NewProjectAPIKey, Project, ProjectAPIKey, ProjectDevice, ProjectSession,
-    ProjectSessionStatus, StorageType,
+    LivekitCredential, NewProject, NewProjectAPIKey, NewProjectMember, OrganizationRole, Project,
+    ProjectAPIKey, ProjectDevice, ProjectRole, ProjectSession, ProjectSessionStatus,
+    StorageCredential, StorageType,
 };
+use domain::schema::syncflow::projects;
 use livekit_api::services::ServiceError;
 use shared::{
+    api_key_scopes::{ApiKeyScope, IpRange},
     deployment_config::{S3Config, StorageConfig},
-    project_models::{ProjectSummary, ProjectsSummary},
-    user_models::ProjectRequest,
+    project_models::{ProjectSummary, ProjectValidationReport, ProjectsSummary},
+    user_models::{
+        ProjectApiKeyRequest, ProjectRequest, ProjectSecretsRequest, ProjectUpdateRequest,
+    },
 };
+use std::str::FromStr;
 
-use super::super::s3::storage_service::StorageService;
+use crate::storage::{s3::S3Storage, Storage};
 
 use thiserror::Error;
 use uuid::Uuid;
 
+const API_KEY_LAST_USED_RESOLUTION_SECONDS: i64 = 60;
+
 #[derive(Debug, Error)]
 pub enum ProjectError {
     #[error("Project Not Found Error: {0}")]
@@ -36,6 +46,12 @@ pub enum ProjectError {
 
     #[error("Livekit Error: {0}")]
     LivekitError(#[from] ServiceError),
+
+    #[error("Forbidden: {0}")]
+    Forbidden(String),
+
+    #[error("Project validation failed: {}", .0.failures())]
+    ValidationFailed(ProjectValidationReport),
 }
 
 pub(crate) trait Encryptable<T = Self> {
@@ -123,7 +139,7 @@ impl Encryptable for NewProjectAPIKey {
 
 impl Encryptable for ProjectAPIKey {
     fn encrypt(&mut self, key: &str) -> Result<(), SecretError> {
-        let api_secret = encrypt_string(&self.api_key, key)?;
+        let api_secret = encrypt_string(&self.api_secret, key)?;
 
         self.api_secret = api_secret;
 
@@ -162,62 +178,308 @@ impl From<ProjectError> for shared::response_models::Response {
                 status: 500,
                 message: e.to_string(),
             },
+            ProjectError::Forbidden(e) => shared::response_models::Response {
+                status: 403,
+                message: e,
+            },
+            ProjectError::ValidationFailed(report) => shared::response_models::Response {
+                status: 422,
+                message: format!("Project validation failed: {}", report.failures()),
+            },
         }
     }
 }
 
-pub fn create_project(
+/// Builds the project row for a request. When the request references
+/// organization credentials, the project's own credential columns are left
+/// empty and filled from the organization on every read. Updates of an
+/// `existing` project only need the organization admin role when they change
+/// its organization or organization credentials.
+fn new_project_from_request(
+    owner_id: i32,
+    actor_id: i32,
+    existing: Option<&Project>,
+    request: &ProjectRequest,
+    conn: &mut PgConnection,
+) -> Result<NewProject, ProjectError> {
+    use domain::schema::syncflow::{
+        organization_livekit_credentials, organization_members, organization_storage_credentials,
+    };
+
+    let parse_id = |value: &str| {
+        Uuid::parse_str(value)
+            .map_err(|_| ProjectError::ConfigurationError(format!("Invalid id {}", value)))
+    };
+
+    let org_uuid = request
+        .organization_id
+        .as_deref()
+        .map(parse_id)
+        .transpose()?;
+
+    let optional_id = |value: Option<&str>| value.map(parse_id).transpose();
+    let organization_changed = match existing {
+        Some(existing) => {
+            existing.organization_id != org_uuid
+                || existing.livekit_credential_id
+                    != optional_id(request.livekit_credential_id.as_deref())?
+                || existing.storage_credential_id
+                    != optional_id(request.storage_credential_id.as_deref())?
+        }
+        None => org_uuid.is_some(),
+    };
+    let admin_org = org_uuid.or(existing.and_then(|existing| existing.organization_id));
+
+    if let Some(org_uuid) = admin_org.filter(|_| organization_changed) {
+        let role = organization_members::table
+            .filter(
+                organization_members::organization_id
+                    .eq(org_uuid)
+                    .and(organization_members::user_id.eq(actor_id)),
+            )
+            .select(organization_members::role)
+            .first::<OrganizationRole>(conn)
+            .optional()?;
+
+        if !role.is_some_and(|role| role.includes(OrganizationRole::Admin)) {
+            return Err(ProjectError::Forbidden(
+                "Managing organization projects requires the organization admin role".to_string(),
+            ));
+        }
+    }
+
+    let credential_org = |kind: &str| {
+        org_uuid.ok_or_else(|| {
+            ProjectError::ConfigurationError(format!(
+                "Organization {} credentials require an organization id",
+                kind
+            ))
+        })
+    };
+
+    let livekit_credential = match request.livekit_credential_id.as_deref() {
+        Some(credential_id) => Some(
+            organization_livekit_credentials::table
+                .filter(
+                    organization_livekit_credentials::id
+                        .eq(parse_id(credential_id)?)
+                        .and(
+                            organization_livekit_credentials::organization_id
+                                .eq(credential_org("LiveKit")?),
+                        ),
+                )
+                .first::<LivekitCredential>(conn)
+                .optional()?
+                .ok_or_else(|| {
+                    ProjectError::ConfigurationError(
+                        "LiveKit credentials not found in the organization".to_string(),
+                    )
+                })?,
+        ),
+        None => {
+            if request.livekit_server_url.is_empty()
+                || request.livekit_server_api_key.is_empty()
+                || request.livekit_server_api_secret.is_empty()
+            {
+                return Err(ProjectError::ConfigurationError(
+                    "LiveKit server url, api key and api secret are required".to_string(),
+                ));
+            }
+            None
+        }
+    };
+
+    let storage_credential = match request.storage_credential_id.as_deref() {
+        Some(credential_id) => Some(
+            organization_storage_credentials::table
+                .filter(
+                    organization_storage_credentials::id
+                        .eq(parse_id(credential_id)?)
+                        .and(
+                            organization_storage_credentials::organization_id
+                                .eq(credential_org("storage")?),
+                        ),
+                )
+                .first::<StorageCredential>(conn)
+                .optional()?
+                .ok_or_else(|| {
+                    ProjectError::ConfigurationError(
+                        "Storage credentials not found in the organization".to_string(),
+                    )
+                })?,
+        ),
+        None => {
+            if request.storage_type != "s3" {
+                return Err(ProjectError::ConfigurationError(
+                    "Storage type not found".to_string(),
+                ));
+            }
+            None
+        }
+    };
+
+    let mut new_project = NewProject {
+        user_id: owner_id,
+        name: request.name.clone(),
+        description: request.description.clone(),
+        livekit_server_url: request.livekit_server_url.clone(),
+        livekit_server_api_key: request.livekit_server_api_key.clone(),
+        livekit_server_api_secret: request.livekit_server_api_secret.clone(),
+        storage_type: StorageType::S3,
+        bucket_name: request.bucket_name.clone(),
+        endpoint: request.endpoint.clone(),
+        access_key: request.access_key.clone(),
+        secret_key: request.secret_key.clone(),
+        region: request.region.clone(),
+        organization_id: org_uuid,
+        livekit_credential_id: None,
+        storage_credential_id: None,
+    };
+
+    if let Some(credential) = livekit_credential {
+        new_project.livekit_server_url = String::new();
+        new_project.livekit_server_api_key = String::new();
+        new_project.livekit_server_api_secret = String::new();
+        new_project.livekit_credential_id = Some(credential.id);
+    }
+
+    if let Some(credential) = storage_credential {
+        new_project.storage_type = credential.storage_type;
+        new_project.bucket_name = String::new();
+        new_project.endpoint = String::new();
+        new_project.access_key = String::new();
+        new_project.secret_key = String::new();
+        new_project.region = None;
+        new_project.storage_credential_id = Some(credential.id);
+    }
+
+    Ok(new_project)
+}
+
+/// Replaces the credential columns of a project that uses organization
+/// credentials. The values stay encrypted, `decrypt` works on either.
+fn apply_organization_credentials(
+    project: &mut Project,
+    conn: &mut PgConnection,
+) -> Result<(), ProjectError> {
+    use domain::schema::syncflow::{
+        organization_livekit_credentials, organization_storage_credentials,
+    };
+
+    if let Some(credential_id) = project.livekit_credential_id {
+        let credential = organization_livekit_credentials::table
+            .find(credential_id)
+            .first::<LivekitCredential>(conn)?;
+
+        project.livekit_server_url = credential.server_url;
+        project.livekit_server_api_key = credential.api_key;
+        project.livekit_server_api_secret = credential.api_secret;
+    }
+
+    if let Some(credential_id) = project.storage_credential_id {
+        let credential = organization_storage_credentials::table
+            .find(credential_id)
+            .first::<StorageCredential>(conn)?;
+
+        project.storage_type = credential.storage_type;
+        project.bucket_name = credential.bucket_name;
+        project.endpoint = credential.endpoint;
+        project.access_key = credential.access_key;
+        project.secret_key = credential.secret_key;
+        project.region = credential.region;
+    }
+
+    Ok(())
+}
+
+/// Projects that are not pending deletion. Deleted projects stay hidden until
+/// they are restored or purged.
+fn live_projects<'a>() -> projects::BoxedQuery<'a, Pg> {
+    use domain::schema::syncflow::scheduled_deletions;
+
+    projects::table
+        .filter(
+            projects::id.ne_all(
+                scheduled_deletions::table
+                    .filter(scheduled_deletions::session_id.is_null())
+                    .select(scheduled_deletions::project_id),
+            ),
+        )
+        .into_boxed()
+}
+
+/// Projects the user can access, either as a project member or as a member
+/// of the organization owning the project.
+fn accessible_projects<'a>(uid: i32) -> projects::BoxedQuery<'a, Pg> {
+    use domain::schema::syncflow::{organization_members, project_members};
+
+    live_projects().filter(
+        projects::id
+            .eq_any(
+                project_members::table
+                    .filter(project_members::user_id.eq(uid))
+                    .select(project_members::project_id),
+            )
+            .or(projects::organization_id.eq_any(
+                organization_members::table
+                    .filter(organization_members::user_id.eq(uid))
+                    .select(organization_members::organization_id.nullable()),
+            )),
+    )
+}
+
+/// Creates a project once its LiveKit and storage settings pass validation.
+pub async fn create_project(
     uid: i32,
     new_project_request: &ProjectRequest,
     encryption_secret: &str,
     conn: &mut PgConnection,
 ) -> Result<Project, ProjectError> {
-    use domain::schema::syncflow::projects::dsl::*;
+    use domain::schema::syncflow::project_members;
 
-    if new_project_request.storage_type != "s3" {
-        return Err(ProjectError::ConfigurationError(
-            "Storage type not found".to_string(),
-        ));
+    let mut new_project = new_project_from_request(uid, uid, None, new_project_request, conn)?;
+    new_project.encrypt(encryption_secret)?;
+
+    let mut candidate = preview_project(Uuid::nil(), &new_project, conn)?;
+    candidate.decrypt(encryption_secret)?;
+
+    let report = validation::validate_project(&candidate).await;
+    if !report.valid {
+        return Err(ProjectError::ValidationFailed(report));
     }
 
-    let storage = StorageType::S3;
+    let mut project = conn.transaction(|conn| {
+        let project = diesel::insert_into(projects::table)
+            .values(&new_project)
+            .get_result::<Project>(conn)?;
 
-    let mut new_project = NewProject {
-        user_id: uid,
-        name: new_project_request.name.clone(),
-        description: new_project_request.description.clone(),
-        access_key: new_project_request.access_key.clone(),
-        bucket_name: new_project_request.bucket_name.clone(),
-        endpoint: new_project_request.endpoint.clone(),
-        livekit_server_api_key: new_project_request.livekit_server_api_key.clone(),
-        livekit_server_api_secret: new_project_request.livekit_server_api_secret.clone(),
-        livekit_server_url: new_project_request.livekit_server_url.clone(),
-        region: new_project_request.region.clone(),
-        secret_key: new_project_request.secret_key.clone(),
-        storage_type: storage,
-    };
+        diesel::insert_into(project_members::table)
+            .values(&NewProjectMember {
+                project_id: project.id,
+                user_id: uid,
+                role: ProjectRole::Owner,
+            })
+            .execute(conn)?;
 
-    new_project.encrypt(encryption_secret)?;
+        Ok::<_, diesel::result::Error>(project)
+    })?;
 
-    let project = diesel::insert_into(projects)
-        .values(&new_project)
-        .get_result::<Project>(conn)?;
+    apply_organization_credentials(&mut project, conn)?;
 
     Ok(project)
 }
 
+/// Fetches a project the user can access.
 pub fn get_project(
     uid: i32,
     proj_id: &str,
     conn: &mut PgConnection,
 ) -> Result<Project, ProjectError> {
-    use domain::schema::syncflow::projects::dsl::*;
-
     let proj_uuid = Uuid::parse_str(proj_id)
         .map_err(|_| ProjectError::ConfigurationError("Invalid project id".to_string()))?;
 
-    let project = projects
-        .filter(user_id.eq(uid).and(id.eq(proj_uuid)))
+    let mut project = accessible_projects(uid)
+        .filter(projects::id.eq(proj_uuid))
         .first::<Project>(conn)
         .map_err(|err| match err {
             diesel::result::Error::NotFound => {
@@ -226,28 +488,33 @@ pub fn get_project(
             _ => ProjectError::DatabaseError(err),
         })?;
 
+    apply_organization_credentials(&mut project, conn)?;
+
     Ok(project)
 }
 
+/// Lists every project the user can access.
 pub fn list_projects(uid: i32, conn: &mut PgConnection) -> Result<Vec<Project>, ProjectError> {
-    use domain::schema::syncflow::projects::dsl::*;
+    let mut all_projects = accessible_projects(uid).load::<Project>(conn)?;
+
+    for project in all_projects.iter_mut() {
+        apply_organization_credentials(project, conn)?;
+    }
 
-    let all_projects = projects.filter(user_id.eq(uid)).load::<Project>(conn)?;
     Ok(all_projects)
 }
 
+/// Schedules the deletion of a project the user can access. The project is
+/// hidden right away and purged once the deletion's grace period ends.
 pub fn delete_project(
     uid: i32,
     proj_id: &str,
+    deletion: &DeletionRequest,
     conn: &mut PgConnection,
 ) -> Result<Project, ProjectError> {
-    use domain::schema::syncflow::projects::dsl::*;
+    let project = get_project(uid, proj_id, conn)?;
 
-    let proj_uuid = Uuid::parse_str(proj_id)
-        .map_err(|_| ProjectError::ConfigurationError("Invalid project id".to_string()))?;
-
-    let project = diesel::delete(projects.filter(user_id.eq(uid).and(id.eq(proj_uuid))))
-        .get_result::<Project>(conn)?;
+    deletion_crud::schedule_deletion(&deletion.scheduled(project.id, None), conn)?;
 
     Ok(project)
 }
@@ -259,55 +526,318 @@ pub fn update_project(
     encryption_secret: &str,
     conn: &mut PgConnection,
 ) -> Result<Project, ProjectError> {
-    use domain::schema::syncflow::projects::dsl::*;
+    let existing_project = get_project(uid, &proj_id.to_string(), conn)?;
+
+    let mut updated_project = new_project_from_request(
+        existing_project.user_id,
+        uid,
+        Some(&existing_project),
+        new_project_request,
+        conn,
+    )?;
+    updated_project.encrypt(encryption_secret)?;
 
-    projects
-        .filter(user_id.eq(uid).and(id.eq(proj_id)))
-        .first::<Project>(conn)
-        .map_err(|err| match err {
-            diesel::result::Error::NotFound => {
-                ProjectError::ProjectNotFoundError("Project not found".to_string())
-            }
-            _ => ProjectError::DatabaseError(err),
-        })?;
+    let mut project = diesel::update(projects::table.filter(projects::id.eq(proj_id)))
+        .set(&updated_project)
+        .get_result::<Project>(conn)?;
 
-    let storage = StorageType::S3;
+    apply_organization_credentials(&mut project, conn)?;
 
-    let mut updated_project = NewProject {
-        user_id: uid,
-        name: new_project_request.name.clone(),
-        description: new_project_request.description.clone(),
-        access_key: new_project_request.access_key.clone(),
-        bucket_name: new_project_request.bucket_name.clone(),
-        endpoint: new_project_request.endpoint.clone(),
-        livekit_server_api_key: new_project_request.livekit_server_api_key.clone(),
-        livekit_server_api_secret: new_project_request.livekit_server_api_secret.clone(),
-        livekit_server_url: new_project_request.livekit_server_url.clone(),
-        region: new_project_request.region.clone(),
-        secret_key: new_project_request.secret_key.clone(),
-        storage_type: storage,
+    Ok(project)
+}
+
+/// Applies a partial update to a project. Changed LiveKit and storage
+/// settings are checked against the servers before anything is saved.
+pub async fn patch_project(
+    uid: i32,
+    proj_id: &str,
+    request: &ProjectUpdateRequest,
+    encryption_secret: &str,
+    conn: &mut PgConnection,
+) -> Result<Project, ProjectError> {
+    let mut existing = get_project(uid, proj_id, conn)?;
+    existing.decrypt(encryption_secret)?;
+
+    if existing.livekit_credential_id.is_some()
+        && request.livekit_credential_id.is_none()
+        && request.livekit_server_url.is_some()
+    {
+        return Err(ProjectError::ConfigurationError(
+            "The project uses organization LiveKit credentials".to_string(),
+        ));
+    }
+
+    if existing.storage_credential_id.is_some()
+        && request.storage_credential_id.is_none()
+        && (request.bucket_name.is_some() || request.endpoint.is_some() || request.region.is_some())
+    {
+        return Err(ProjectError::ConfigurationError(
+            "The project uses organization storage credentials".to_string(),
+        ));
+    }
+
+    // Recordings are stored under a root derived from the name, renaming
+    // would strand them out of reach of the media listing and purges
+    if request
+        .name
+        .as_ref()
+        .is_some_and(|name| *name != existing.name)
+    {
+        return Err(ProjectError::ConfigurationError(
+            "Projects cannot be renamed".to_string(),
+        ));
+    }
+
+    let mut changes = project_request(&existing);
+    if let Some(description) = &request.description {
+        changes.description = Some(description.clone());
+    }
+    if let Some(server_url) = &request.livekit_server_url {
+        changes.livekit_server_url = server_url.clone();
+    }
+    if let Some(bucket_name) = &request.bucket_name {
+        changes.bucket_name = bucket_name.clone();
+    }
+    if let Some(endpoint) = &request.endpoint {
+        changes.endpoint = endpoint.clone();
+    }
+    if let Some(region) = &request.region {
+        changes.region = Some(region.clone());
+    }
+    if let Some(credential_id) = &request.livekit_credential_id {
+        changes.livekit_credential_id = Some(credential_id.clone());
+    }
+    if let Some(credential_id) = &request.storage_credential_id {
+        changes.storage_credential_id = Some(credential_id.clone());
+    }
+
+    save_project_changes(uid, &existing, &changes, encryption_secret, conn).await
+}
+
+/// Replaces the project's own LiveKit and/or storage secrets once the new
+/// ones have been checked against the servers.
+pub async fn rotate_project_secrets(
+    uid: i32,
+    proj_id: &str,
+    request: &ProjectSecretsRequest,
+    encryption_secret: &str,
+    conn: &mut PgConnection,
+) -> Result<Project, ProjectError> {
+    let mut existing = get_project(uid, proj_id, conn)?;
+    existing.decrypt(encryption_secret)?;
+
+    let mut changes = project_request(&existing);
+
+    let livekit_secrets = match (
+        &request.livekit_server_api_key,
+        &request.livekit_server_api_secret,
+    ) {
+        (Some(api_key), Some(api_secret)) => Some((api_key, api_secret)),
+        (None, None) => None,
+        _ => {
+            return Err(ProjectError::ConfigurationError(
+                "The LiveKit api key and api secret must be rotated together".to_string(),
+            ))
+        }
     };
 
+    let storage_secrets = match (&request.access_key, &request.secret_key) {
+        (Some(access_key), Some(secret_key)) => Some((access_key, secret_key)),
+        (None, None) => None,
+        _ => {
+            return Err(ProjectError::ConfigurationError(
+                "The storage access key and secret key must be rotated together".to_string(),
+            ))
+        }
+    };
+
+    if livekit_secrets.is_none() && storage_secrets.is_none() {
+        return Err(ProjectError::ConfigurationError(
+            "No secrets to rotate".to_string(),
+        ));
+    }
+
+    if let Some((api_key, api_secret)) = livekit_secrets {
+        if existing.livekit_credential_id.is_some() {
+            return Err(ProjectError::ConfigurationError(
+                "The project uses organization LiveKit credentials, rotate them on the organization"
+                    .to_string(),
+            ));
+        }
+        changes.livekit_server_api_key = api_key.clone();
+        changes.livekit_server_api_secret = api_secret.clone();
+    }
+
+    if let Some((access_key, secret_key)) = storage_secrets {
+        if existing.storage_credential_id.is_some() {
+            return Err(ProjectError::ConfigurationError(
+                "The project uses organization storage credentials, rotate them on the organization"
+                    .to_string(),
+            ));
+        }
+        changes.access_key = access_key.clone();
+        changes.secret_key = secret_key.clone();
+    }
+
+    save_project_changes(uid, &existing, &changes, encryption_secret, conn).await
+}
+
+/// The request that recreates a decrypted project as it is.
+fn project_request(project: &Project) -> ProjectRequest {
+    ProjectRequest {
+        name: project.name.clone(),
+        description: project.description.clone(),
+        livekit_server_url: project.livekit_server_url.clone(),
+        livekit_server_api_key: project.livekit_server_api_key.clone(),
+        livekit_server_api_secret: project.livekit_server_api_secret.clone(),
+        storage_type: match project.storage_type {
+            StorageType::S3 => "s3".to_string(),
+        },
+        bucket_name: project.bucket_name.clone(),
+        endpoint: project.endpoint.clone(),
+        access_key: project.access_key.clone(),
+        secret_key: project.secret_key.clone(),
+        region: project.region.clone(),
+        organization_id: project.organization_id.map(|id| id.to_string()),
+        livekit_credential_id: project.livekit_credential_id.map(|id| id.to_string()),
+        storage_credential_id: project.storage_credential_id.map(|id| id.to_string()),
+    }
+}
+
+/// Saves the changes to a decrypted project, checking the LiveKit and storage
+/// settings that differ from the current ones first.
+async fn save_project_changes(
+    uid: i32,
+    existing: &Project,
+    changes: &ProjectRequest,
+    encryption_secret: &str,
+    conn: &mut PgConnection,
+) -> Result<Project, ProjectError> {
+    let mut updated_project =
+        new_project_from_request(existing.user_id, uid, Some(existing), changes, conn)?;
     updated_project.encrypt(encryption_secret)?;
 
-    let project = diesel::update(projects.filter(user_id.eq(uid).and(id.eq(proj_id))))
-        .set(&updated_project)
+    let mut candidate = preview_project(existing.id, &updated_project, conn)?;
+    candidate.decrypt(encryption_secret)?;
+
+    let livekit_settings = |project: &Project| {
+        (
+            project.livekit_server_url.clone(),
+            project.livekit_server_api_key.clone(),
+            project.livekit_server_api_secret.clone(),
+        )
+    };
+    let storage_settings = |project: &Project| {
+        (
+            project.bucket_name.clone(),
+            project.endpoint.clone(),
+            project.access_key.clone(),
+            project.secret_key.clone(),
+            project.region.clone(),
+        )
+    };
+
+    let mut checks = Vec::new();
+    if livekit_settings(&candidate) != livekit_settings(existing) {
+        checks.extend(validation::validate_livekit(&candidate).await);
+    }
+    if storage_settings(&candidate) != storage_settings(existing) {
+        checks.extend(validation::validate_storage(&candidate).await);
+    }
+
+    let report = ProjectValidationReport::new(checks);
+    if !report.valid {
+        return Err(ProjectError::ValidationFailed(report));
+    }
+
+    let mut project = diesel::update(projects::table.filter(projects::id.eq(existing.id)))
+        .set((
+            &updated_project,
+            projects::updated_at.eq(chrono::Utc::now().naive_utc()),
+        ))
         .get_result::<Project>(conn)?;
 
+    apply_organization_credentials(&mut project, conn)?;
+
     Ok(project)
 }
 
+/// The project a new row would read as, with organization credentials
+/// applied. The values stay encrypted like those of `new_project`.
+fn preview_project(
+    proj_id: Uuid,
+    new_project: &NewProject,
+    conn: &mut PgConnection,
+) -> Result<Project, ProjectError> {
+    let mut project = Project {
+        id: proj_id,
+        user_id: new_project.user_id,
+        name: new_project.name.clone(),
+        description: new_project.description.clone(),
+        livekit_server_url: new_project.livekit_server_url.clone(),
+        livekit_server_api_key: new_project.livekit_server_api_key.clone(),
+        livekit_server_api_secret: new_project.livekit_server_api_secret.clone(),
+        storage_type: new_project.storage_type.clone(),
+        bucket_name: new_project.bucket_name.clone(),
+        endpoint: new_project.endpoint.clone(),
+        access_key: new_project.access_key.clone(),
+        secret_key: new_project.secret_key.clone(),
+        region: new_project.region.clone(),
+        created_at: None,
+        updated_at: None,
+        organization_id: new_project.organization_id,
+        livekit_credential_id: new_project.livekit_credential_id,
+        storage_credential_id: new_project.storage_credential_id,
+    };
+
+    apply_organization_credentials(&mut project, conn)?;
+
+    Ok(project)
+}
+
+/// Checks the LiveKit and storage settings of a project request without
+/// creating anything.
+pub async fn validate_project_request(
+    uid: i32,
+    request: &ProjectRequest,
+    encryption_secret: &str,
+    conn: &mut PgConnection,
+) -> Result<ProjectValidationReport, ProjectError> {
+    let mut new_project = new_project_from_request(uid, uid, None, request, conn)?;
+    new_project.encrypt(encryption_secret)?;
+
+    let mut candidate = preview_project(Uuid::nil(), &new_project, conn)?;
+    candidate.decrypt(encryption_secret)?;
+
+    Ok(validation::validate_project(&candidate).await)
+}
+
 pub(crate) fn get_project_by_id(
     proj_id: &str,
     conn: &mut PgConnection,
 ) -> Result<Project, ProjectError> {
-    use domain::schema::syncflow::projects::dsl::*;
+    find_project_by_id(live_projects(), proj_id, conn)
+}
 
+/// Fetches a project whether or not it is pending deletion, for restoring
+/// and purging it.
+pub(crate) fn get_project_including_deleted(
+    proj_id: &str,
+    conn: &mut PgConnection,
+) -> Result<Project, ProjectError> {
+    find_project_by_id(projects::table.into_boxed(), proj_id, conn)
+}
+
+fn find_project_by_id(
+    candidates: projects::BoxedQuery<'_, Pg>,
+    proj_id: &str,
+    conn: &mut PgConnection,
+) -> Result<Project, ProjectError> {
     let proj_uuid = Uuid::parse_str(proj_id)
         .map_err(|_| ProjectError::ConfigurationError("Invalid project id".to_string()))?;
 
-    let project = projects
-        .filter(id.eq(proj_uuid))
+    let mut project = candidates
+        .filter(projects::id.eq(proj_uuid))
         .first::<Project>(conn)
         .map_err(|err| match err {
             diesel::result::Error::NotFound => {
@@ -316,6 +846,8 @@ pub(crate) fn get_project_by_id(
             _ => ProjectError::DatabaseError(err),
         })?;
 
+    apply_organization_credentials(&mut project, conn)?;
+
     Ok(project)
 }
 
@@ -330,6 +862,7 @@ pub(crate) fn summarize_projects(
 
     let num_sessions = project_sessions
         .filter(project_id.eq_any(user_projects.iter().map(|p| p.id).collect::<Vec<Uuid>>()))
+        .filter(session_crud::not_deleted())
         .count()
         .get_result::<i64>(conn)?;
 
@@ -339,6 +872,7 @@ pub(crate) fn summarize_projects(
                 .eq_any(user_projects.iter().map(|p| p.id).collect::<Vec<Uuid>>())
                 .and(status.eq(ProjectSessionStatus::Started)),
         )
+        .filter(session_crud::not_deleted())
         .count()
         .get_result::<i64>(conn)?;
 
@@ -366,6 +900,7 @@ pub async fn summarize_project(
 
     let sessions = project_sessions
         .filter(project_id.eq(proj_uuid))
+        .filter(session_crud::not_deleted())
         .load::<ProjectSession>(conn)?;
 
     let num_sessions = sessions.len();
@@ -439,7 +974,7 @@ impl From<&Project> for EgressService {
     }
 }
 
-impl From<&Project> for StorageService {
+impl From<&Project> for Box<dyn Storage> {
     fn from(value: &Project) -> Self {
         let config = S3Config {
             bucket: value.bucket_name.clone(),
@@ -449,14 +984,14 @@ impl From<&Project> for StorageService {
             endpoint: value.endpoint.clone(),
         };
 
-        StorageService::new(&config)
+        Box::new(S3Storage::new(&config))
     }
 }
 
 pub fn create_api_key(
     uid: i32,
     proj_id: &str,
-    key_comments: Option<String>,
+    request: &ProjectApiKeyRequest,
     encryption_secret: &str,
     conn: &mut PgConnection,
 ) -> Result<ProjectAPIKey, ProjectError> {
@@ -467,12 +1002,51 @@ pub fn create_api_key(
     let proj_uuid = Uuid::parse_str(proj_id)
         .map_err(|_| ProjectError::ConfigurationError("Invalid project id".to_string()))?;
 
+    let key_scopes = match &request.scopes {
+        Some(requested) if !requested.is_empty() => requested
+            .iter()
+            .map(|scope| ApiKeyScope::from_str(scope))
+            .collect::<Result<Vec<_>, _>>()
+            .map_err(ProjectError::ConfigurationError)?,
+        Some(_) => {
+            return Err(ProjectError::ConfigurationError(
+                "An API key needs at least one scope".to_string(),
+            ))
+        }
+        None => vec![ApiKeyScope::ProjectAdmin],
+    };
+
+    let key_allowed_ips = request
+        .allowed_ips
+        .iter()
+        .flatten()
+        .map(|range| IpRange::from_str(range).map(|range| range.to_string()))
+        .collect::<Result<Vec<_>, _>>()
+        .map_err(ProjectError::ConfigurationError)?;
+
+    let key_expires_at = request
+        .expires_at
+        .map(|timestamp| {
+            chrono::DateTime::from_timestamp(timestamp as i64, 0)
+                .map(|expiry| expiry.naive_utc())
+                .filter(|expiry| *expiry > chrono::Utc::now().naive_utc())
+                .ok_or_else(|| {
+                    ProjectError::ConfigurationError(
+                        "The expiry must be a timestamp in the future".to_string(),
+                    )
+                })
+        })
+        .transpose()?;
+
     let mut new_key = NewProjectAPIKey {
         project_id: proj_uuid,
         user_id: uid,
         api_key: key_pair.key.clone(),
         api_secret: key_pair.secret.clone(),
-        comments: key_comments.clone(),
+        comments: Some(request.comment.clone()),
+        scopes: ApiKeyScope::join(&key_scopes),
+        expires_at: key_expires_at,
+        allowed_ips: (!key_allowed_ips.is_empty()).then(|| key_allowed_ips.join(" ")),
     };
 
     new_key.encrypt(encryption_secret)?;
@@ -484,7 +1058,6 @@ pub fn create_api_key(
 }
 
 pub fn list_all_api_keys(
-    uid: i32,
     proj_id: &str,
     conn: &mut PgConnection,
 ) -> Result<Vec<ProjectAPIKey>, ProjectError> {
@@ -493,14 +1066,13 @@ pub fn list_all_api_keys(
     let proj_uuid = Uuid::parse_str(proj_id)
         .map_err(|_| ProjectError::ConfigurationError("Invalid project id".to_string()))?;
     let keys = project_api_keys
-        .filter(user_id.eq(uid).and(project_id.eq(proj_uuid)))
+        .filter(project_id.eq(proj_uuid))
         .load::<ProjectAPIKey>(conn)?;
 
     Ok(keys)
 }
 
 pub fn delete_api_key(
-    uid: i32,
     proj_id: &str,
     key_id: i32,
     conn: &mut PgConnection,
@@ -510,30 +1082,34 @@ pub fn delete_api_key(
     let proj_uuid = Uuid::parse_str(proj_id)
         .map_err(|_| ProjectError::ConfigurationError("Invalid project id".to_string()))?;
 
-    let key = diesel::delete(
-        project_api_keys.filter(
-            user_id
-                .eq(uid)
-                .and(project_id.eq(proj_uuid))
-                .and(id.eq(key_id)),
-        ),
-    )
-    .get_result::<ProjectAPIKey>(conn)?;
+    let key = diesel::delete(project_api_keys.filter(project_id.eq(proj_uuid).and(id.eq(key_id))))
+        .get_result::<ProjectAPIKey>(conn)?;
 
     Ok(key)
 }
 
+/// The key of a project, unless the project is scheduled for deletion. Keys
+/// of deleted projects stop working right away, for the API and the broker.
 pub fn fetch_api_key_by_key(
     key: &str,
     proj_id: &str,
     conn: &mut PgConnection,
 ) -> Result<ProjectAPIKey, ProjectError> {
     use domain::schema::syncflow::project_api_keys::dsl::*;
+    use domain::schema::syncflow::scheduled_deletions;
+
     let proj_uuid = Uuid::parse_str(proj_id)
         .map_err(|_| ProjectError::ConfigurationError("Invalid project id".to_string()))?;
 
     let key = project_api_keys
         .filter(api_key.eq(key).and(project_id.eq(proj_uuid)))
+        .filter(
+            project_id.ne_all(
+                scheduled_deletions::table
+                    .filter(scheduled_deletions::session_id.is_null())
+                    .select(scheduled_deletions::project_id),
+            ),
+        )
         .first::<ProjectAPIKey>(conn)
         .map_err(|err| match err {
             diesel::result::Error::NotFound => {
@@ -545,6 +1121,26 @@ pub fn fetch_api_key_by_key(
     Ok(key)
 }
 
+/// Records the use of a key, at most once a minute so that verifying tokens
+/// does not write on every request.
+pub fn touch_api_key(key_id: i32, conn: &mut PgConnection) -> Result<(), ProjectError> {
+    use domain::schema::syncflow::project_api_keys::dsl::*;
+
+    let now = chrono::Utc::now().naive_utc();
+    let threshold = now - chrono::Duration::seconds(API_KEY_LAST_USED_RESOLUTION_SECONDS);
+
+    diesel::update(
+        project_api_keys.filter(
+            id.eq(key_id)
+                .and(last_used_at.is_null().or(last_used_at.lt(threshold))),
+        ),
+    )
+    .set(last_used_at.eq(now))
+    .execute(conn)?;
+
+    Ok(())
+}
+
 pub fn project_contains_device_group(
     proj_id: &str,
     device_group_name: &str,
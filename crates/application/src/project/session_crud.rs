@@ -1,4 +1,5 @@
 use diesel::prelude::*;
+use std::collections::HashSet;
 use std::fmt::Display;
 use std::str::FromStr;
 
@@ -8,21 +9,26 @@ use crate::project::project_crud::Encryptable;
 use crate::users::secret::SecretError;
 use crate::{livekit, project};
 
-use diesel::PgConnection;
+use diesel::{pg::Pg, sql_types::Bool, PgConnection};
 use domain::models::{
-    NewParticipantTrack, NewProjectSession, NewSessionEgress, NewSessionParticipant,
-    ParticipantTrack, ProjectSession, ProjectSessionStatus, SessionEgress, SessionParticipant,
+    NewOutboxMessage, NewParticipantTrack, NewProjectSession, NewSessionEgress,
+    NewSessionParticipant, ParticipantTrack, ProjectSession, ProjectSessionStatus, SessionEgress,
+    SessionParticipant,
 };
 use livekit_api::services::ServiceError;
 use livekit_client::RoomError;
 use livekit_protocol::ParticipantInfo;
+use shared::device_models::NewSessionMessage;
 use shared::livekit_models::{RoomOptions, TokenRequest, TokenResponse};
 use shared::project_models::NewSessionRequest;
 use thiserror::Error;
 use uuid::Uuid;
 
+use super::deletions::deletion_crud::{self, DeletionRequest};
 use super::project_crud::ProjectError;
-use crate::rmq::session_notifier::SessionNotifierError;
+use crate::notifier::NotifierError;
+use crate::rmq::outbox_crud;
+use crate::storage::StorageError;
 
 #[derive(Debug, Error)]
 pub enum SessionError {
@@ -53,14 +59,14 @@ pub enum SessionError {
     #[error("Inactive Session Error: {0}")]
     InactiveSessionError(String),
 
-    #[error("Session Notifier Error: {0}")]
-    SessionNotifierError(#[from] SessionNotifierError),
+    #[error("Notifier Error: {0}")]
+    NotifierError(#[from] NotifierError),
 
     #[error("Invalid Device Group Error: {0}")]
     InvalidDeviceGroupError(String),
 
     #[error("Storage Service Error: {0}")]
-    StorageServiceError(#[from] rusoto_credential::CredentialsError),
+    StorageServiceError(#[from] StorageError),
 
     #[error("Room Listener Error: {0}")]
     RoomListenerError(#[from] room_listener::RoomListenerError),
@@ -146,7 +152,7 @@ impl From<SessionError> for shared::response_models::Response {
                 status: 400,
                 message: e,
             },
-            SessionError::SessionNotifierError(e) => shared::response_models::Response {
+            SessionError::NotifierError(e) => shared::response_models::Response {
                 status: 500,
                 message: e.to_string(),
             },
@@ -170,9 +176,13 @@ impl From<SessionError> for shared::response_models::Response {
     }
 }
 
+/// Creates the LiveKit room and stores the session. A session start
+/// notification for each of `notified_groups` is queued in the outbox within
+/// the same transaction as the session row.
 pub async fn create_session(
     proj_id: &str,
     session: &NewSessionRequest,
+    notified_groups: &[String],
     encryption_key: &str,
     conn: &mut PgConnection,
 ) -> Result<ProjectSession, SessionError> {
@@ -213,9 +223,28 @@ pub async fn create_session(
         name: room_name,
     };
 
-    let session = diesel::insert_into(project_sessions)
-        .values(&new_session)
-        .get_result::<ProjectSession>(conn)?;
+    let session = conn.transaction(|conn| {
+        let session = diesel::insert_into(project_sessions)
+            .values(&new_session)
+            .get_result::<ProjectSession>(conn)?;
+
+        let payload = serde_json::to_vec(&NewSessionMessage {
+            session_id: session.id.to_string(),
+            session_name: session.livekit_room_name.clone(),
+        })
+        .unwrap();
+        let notifications = notified_groups
+            .iter()
+            .map(|grp| NewOutboxMessage {
+                routing_key: format!("{}.{}", project_uuid, grp),
+                payload: payload.clone(),
+                session_id: Some(session.id),
+            })
+            .collect::<Vec<_>>();
+        outbox_crud::enqueue(&notifications, conn)?;
+
+        Ok::<_, diesel::result::Error>(session)
+    })?;
 
     let room_metadata = RoomMetadata {
         session_id: session.id,
@@ -230,6 +259,24 @@ pub async fn create_session(
     Ok(session)
 }
 
+type SessionFilter = Box<
+    dyn BoxableExpression<domain::schema::syncflow::project_sessions::table, Pg, SqlType = Bool>,
+>;
+
+/// Excludes sessions pending deletion, which stay hidden until they are
+/// restored or purged.
+pub(crate) fn not_deleted() -> SessionFilter {
+    use domain::schema::syncflow::{project_sessions, scheduled_deletions};
+
+    Box::new(
+        project_sessions::id.ne_all(
+            scheduled_deletions::table
+                .filter(scheduled_deletions::session_id.is_not_null())
+                .select(scheduled_deletions::session_id.assume_not_null()),
+        ),
+    )
+}
+
 pub fn get_session(
     proj_id: &str,
     session_id: &str,
@@ -245,6 +292,7 @@ pub fn get_session(
 
     let session = project_sessions
         .filter(id.eq(session_id).and(project_id.eq(proj_uuid)))
+        .filter(not_deleted())
         .first::<ProjectSession>(conn)?;
 
     Ok(session)
@@ -298,17 +346,17 @@ pub fn get_session_token(
     }
 }
 
+/// Stops the session's room if it is still running and schedules the
+/// session's deletion. The session is hidden right away and purged once the
+/// deletion's grace period ends.
 pub async fn delete_session(
     proj_id: &str,
     session_id: &str,
+    deletion: &DeletionRequest,
     encryption_key: &str,
     conn: &mut PgConnection,
 ) -> Result<ProjectSession, SessionError> {
-    use domain::schema::syncflow::project_sessions::dsl::*;
-
-    let session = get_session(proj_id, session_id, conn)?;
-    let session = diesel::delete(project_sessions.filter(id.eq(session.id)))
-        .get_result::<ProjectSession>(conn)?;
+    let mut session = get_session(proj_id, session_id, conn)?;
 
     if session.status != ProjectSessionStatus::Stopped {
         let mut project = project::project_crud::get_project_by_id(proj_id, conn)?;
@@ -317,10 +365,14 @@ pub async fn delete_session(
         let room_service: RoomService = (&project).into();
         room_service.delete_room(&session.livekit_room_name).await?;
 
-        let session_status = ProjectSessionStatus::Stopped;
-        let _ = update_session_status(session_id, session_status, conn);
+        session = update_session_status(session_id, ProjectSessionStatus::Stopped, conn)?;
     }
 
+    deletion_crud::schedule_deletion(
+        &deletion.scheduled(session.project_id, Some(session.id)),
+        conn,
+    )?;
+
     Ok(session)
 }
 
@@ -335,11 +387,31 @@ pub fn get_sessions(
 
     let sessions = project_sessions
         .filter(project_id.eq(proj_uuid))
+        .filter(not_deleted())
         .load::<ProjectSession>(conn)?;
 
     Ok(sessions)
 }
 
+/// Names of the rooms more than one session of the project was held in,
+/// deleted sessions included. Files under such a room cannot be told apart by
+/// their path alone.
+pub fn shared_room_names(
+    proj_uuid: Uuid,
+    conn: &mut PgConnection,
+) -> Result<HashSet<String>, SessionError> {
+    use domain::schema::syncflow::project_sessions::dsl::*;
+
+    let room_names = project_sessions
+        .filter(project_id.eq(proj_uuid))
+        .group_by(livekit_room_name)
+        .having(diesel::dsl::count(id).gt(1_i64))
+        .select(livekit_room_name)
+        .load::<String>(conn)?;
+
+    Ok(room_names.into_iter().collect())
+}
+
 pub async fn get_participants(
     proj_id: &str,
     session_id: &str,
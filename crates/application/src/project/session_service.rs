@@ -8,13 +8,15 @@ use infrastructure::DbPool;
 use livekit_protocol::ParticipantInfo;
 
 use crate::{
+    audit::{audit_service::AuditService, AuditAction, AuditActor, AuditTarget, AuditTargetType},
     livekit::{egress::EgressService, room::RoomService},
+    notifier::DeviceNotifier,
     project::session_crud::{self, SessionError},
-    rmq::session_notifier::SessionNotifier,
-    s3::storage_service::StorageService,
+    storage::Storage,
 };
 use shared::{
-    device_models::NewSessionMessage,
+    deletion_models::DeletionQuery,
+    deployment_config::DeploymentConfig,
     livekit_models::{TokenRequest, TokenResponse},
     project_models::{
         EgressMediaDownloadResponse, EgressResponse, LivekitSessionInfo, MultimediaDetails,
@@ -23,6 +25,7 @@ use shared::{
 };
 
 use super::{
+    deletions::deletion_crud::{self, DeletionRequest},
     devices::device_crud,
     project_crud::{self, Encryptable},
     session_listener::session_listener,
@@ -30,7 +33,9 @@ use super::{
 
 pub struct SessionService {
     encryption_key: String,
+    deletion_grace_period: chrono::Duration,
     pool: Arc<DbPool>,
+    audit: AuditService,
 }
 
 fn get_duration(start_time: i64, end_time: i64) -> i64 {
@@ -38,30 +43,62 @@ fn get_duration(start_time: i64, end_time: i64) -> i64 {
 }
 
 impl SessionService {
-    pub fn new(encryption_key: &str, pool: Arc<DbPool>) -> Self {
+    pub fn new(config: &DeploymentConfig, pool: Arc<DbPool>) -> Self {
         SessionService {
-            encryption_key: encryption_key.to_string(),
+            encryption_key: config.encryption_key.clone(),
+            deletion_grace_period: deletion_crud::grace_period(config),
+            audit: AuditService::new(pool.clone()),
             pool,
         }
     }
 
     pub async fn create_session(
         &self,
+        actor: &AuditActor,
         project_id: &str,
         session: &NewSessionRequest,
-        notifier: &SessionNotifier,
+        notifier: &DeviceNotifier,
     ) -> Result<ProjectSessionResponse, SessionError> {
-        let registered_devices =
-            device_crud::list_devices(project_id, &mut self.pool.get().unwrap())
-                .unwrap_or_default()
-                .into_iter()
-                .map(|d| d.device_group)
-                .collect::<Vec<String>>();
-        let notified_devices = session.device_groups.clone().unwrap_or_default();
+        let created = self.start_session(project_id, session, notifier).await;
+        self.audit.record(
+            actor,
+            AuditAction::SessionCreated,
+            AuditTarget::in_project(
+                project_id,
+                AuditTargetType::Session,
+                created.as_ref().ok().map(|session| session.id.clone()),
+            ),
+            &created,
+        );
+        created
+    }
+
+    async fn start_session(
+        &self,
+        project_id: &str,
+        session: &NewSessionRequest,
+        notifier: &DeviceNotifier,
+    ) -> Result<ProjectSessionResponse, SessionError> {
+        let device_groups =
+            device_crud::list_groups(project_id, &mut self.pool.get().unwrap()).unwrap_or_default();
+        let registered_groups = device_groups
+            .iter()
+            .map(|g| g.name.clone())
+            .collect::<Vec<String>>();
+
+        // Without an explicit list, notify the groups that opted in to session start notifications
+        let notified_devices = match &session.device_groups {
+            Some(groups) => groups.clone(),
+            None => device_groups
+                .iter()
+                .filter(|g| g.notify_on_session_start)
+                .map(|g| g.name.clone())
+                .collect(),
+        };
         if !notified_devices.is_empty() {
             let unregistered_devices = notified_devices
                 .iter()
-                .filter(|grp| !registered_devices.contains(grp))
+                .filter(|grp| !registered_groups.contains(grp))
                 .cloned()
                 .collect::<Vec<String>>();
 
@@ -76,6 +113,7 @@ impl SessionService {
         let new_session = session_crud::create_session(
             project_id,
             session,
+            &notified_devices,
             &self.encryption_key,
             &mut self.pool.get().unwrap(),
         )
@@ -100,16 +138,8 @@ impl SessionService {
             .await;
         });
 
-        for grp in notified_devices {
-            let routing_key = format!("{}.{}", project_id, grp);
-            let new_session_message = NewSessionMessage {
-                session_id: session_id.to_string(),
-                session_name: new_session.livekit_room_name.clone(),
-            };
-
-            let bytes = serde_json::to_vec(&new_session_message).unwrap();
-
-            notifier.publish(&routing_key, bytes).await?;
+        if !notified_devices.is_empty() {
+            notifier.notify_outbox_worker();
         }
 
         Ok(new_session.into())
@@ -202,8 +232,11 @@ impl SessionService {
         })
     }
 
+    /// The session with its recordings. Stopped sessions come with download
+    /// links for their recordings, each of which is audited.
     pub async fn get_session(
         &self,
+        actor: &AuditActor,
         project_id: &str,
         session_id: &str,
     ) -> Result<ProjectSessionResponse, SessionError> {
@@ -215,7 +248,7 @@ impl SessionService {
                 let mut project = project_crud::get_project_by_id(project_id, conn)?;
                 project.decrypt(&self.encryption_key)?;
 
-                let storage_service: StorageService = (&project).into();
+                let storage: Box<dyn Storage> = (&project).into();
 
                 let (participants, recordings) =
                     session_crud::load_session_participant_tracks_recordings(
@@ -244,13 +277,25 @@ impl SessionService {
                         {
                             if egress.status == SessionEgressStatus::EgressComplete
                                 && egress.destination.is_some()
+                                && egress.purged_at.is_none()
                             {
-                                let url = storage_service
-                                    .generate_presigned_url(
+                                let url = storage
+                                    .presign_get(
                                         egress.destination.as_ref().unwrap(),
-                                        Some(500),
+                                        Duration::from_secs(500),
                                     )
-                                    .await?;
+                                    .await;
+                                self.audit.record(
+                                    actor,
+                                    AuditAction::RecordingDownloaded,
+                                    AuditTarget::in_project(
+                                        project_id,
+                                        AuditTargetType::Recording,
+                                        egress.destination.clone(),
+                                    ),
+                                    &url,
+                                );
+                                let url = url?;
 
                                 track.multimedia_details = Some(MultimediaDetails {
                                     file_name: egress
@@ -289,21 +334,30 @@ impl SessionService {
 
     pub fn get_session_token(
         &self,
+        actor: &AuditActor,
         project_id: &str,
         session_id: &str,
         token_request: &TokenRequest,
     ) -> Result<TokenResponse, SessionError> {
-        session_crud::get_session_token(
+        let token = session_crud::get_session_token(
             project_id,
             session_id,
             token_request,
             &self.encryption_key,
             &mut self.pool.get().unwrap(),
-        )
+        );
+        self.audit.record(
+            actor,
+            AuditAction::SessionTokenIssued,
+            self.session_target(project_id, session_id),
+            &token,
+        );
+        token
     }
 
     pub async fn stop_session(
         &self,
+        actor: &AuditActor,
         project_id: &str,
         session_id: &str,
     ) -> Result<ProjectSessionResponse, SessionError> {
@@ -314,26 +368,60 @@ impl SessionService {
             &mut self.pool.get().unwrap(),
         )
         .await;
+        self.audit.record(
+            actor,
+            AuditAction::SessionStopped,
+            self.session_target(project_id, session_id),
+            &session,
+        );
 
         Ok(session?.into())
     }
 
+    /// Deletes the session softly, it can be restored until the grace
+    /// period ends and its recordings are handled as `deletion` asks.
     pub async fn delete_session(
         &self,
+        actor: &AuditActor,
         project_id: &str,
         session_id: &str,
+        deletion: &DeletionQuery,
     ) -> Result<ProjectSessionResponse, SessionError> {
-        let session = session_crud::delete_session(
-            project_id,
-            session_id,
-            &self.encryption_key,
-            &mut self.pool.get().unwrap(),
-        )
-        .await;
+        let session = match DeletionRequest::from_query(
+            deletion,
+            actor.user_id,
+            self.deletion_grace_period,
+        ) {
+            Ok(deletion) => {
+                session_crud::delete_session(
+                    project_id,
+                    session_id,
+                    &deletion,
+                    &self.encryption_key,
+                    &mut self.pool.get().unwrap(),
+                )
+                .await
+            }
+            Err(e) => Err(e.into()),
+        };
+        self.audit.record(
+            actor,
+            AuditAction::SessionDeleted,
+            self.session_target(project_id, session_id),
+            &session,
+        );
 
         Ok(session?.into())
     }
 
+    fn session_target(&self, project_id: &str, session_id: &str) -> AuditTarget {
+        AuditTarget::in_project(
+            project_id,
+            AuditTargetType::Session,
+            Some(session_id.to_string()),
+        )
+    }
+
     pub async fn list_egresses(
         &self,
         _project_id: &str,
@@ -346,6 +434,26 @@ impl SessionService {
     }
 
     pub async fn get_egress_download_url(
+        &self,
+        actor: &AuditActor,
+        project_id: &str,
+        path: &str,
+    ) -> Result<EgressMediaDownloadResponse, SessionError> {
+        let download = self.presign_download(project_id, path).await;
+        self.audit.record(
+            actor,
+            AuditAction::RecordingDownloaded,
+            AuditTarget::in_project(
+                project_id,
+                AuditTargetType::Recording,
+                Some(path.to_string()),
+            ),
+            &download,
+        );
+        download
+    }
+
+    async fn presign_download(
         &self,
         project_id: &str,
         path: &str,
@@ -354,11 +462,9 @@ impl SessionService {
             project_crud::get_project_by_id(project_id, &mut self.pool.get().unwrap())?;
         project.decrypt(&self.encryption_key)?;
 
-        let storage_service: StorageService = (&project).into();
+        let storage: Box<dyn Storage> = (&project).into();
 
-        let url = storage_service
-            .generate_presigned_url(path, Some(300))
-            .await?;
+        let url = storage.presign_get(path, Duration::from_secs(300)).await?;
 
         Ok(EgressMediaDownloadResponse {
             bucket_name: project.bucket_name.clone(),
@@ -377,7 +483,9 @@ impl Clone for SessionService {
     fn clone(&self) -> Self {
         SessionService {
             encryption_key: self.encryption_key.clone(),
+            deletion_grace_period: self.deletion_grace_period,
             pool: self.pool.clone(),
+            audit: self.audit.clone(),
         }
     }
 }
@@ -0,0 +1,112 @@
+use std::time::Duration;
+
+use domain::models::Project;
+use shared::project_models::{ProjectValidationReport, ValidationCheck};
+use uuid::Uuid;
+
+use crate::livekit::room::RoomService;
+use crate::storage::{Storage, StorageError};
+
+const LIVEKIT_REACHABLE: &str = "livekit_reachable";
+const LIVEKIT_CREDENTIALS: &str = "livekit_credentials";
+const STORAGE_BUCKET: &str = "storage_bucket";
+const STORAGE_WRITE: &str = "storage_write";
+
+const REACHABILITY_TIMEOUT_SECONDS: u64 = 5;
+
+/// Prefix of the object written and removed again by the write check.
+const WRITE_CHECK_PREFIX: &str = ".syncflow-write-check";
+
+/// Runs every check against the settings of a decrypted project.
+pub async fn validate_project(project: &Project) -> ProjectValidationReport {
+    let mut checks = validate_livekit(project).await;
+    checks.extend(validate_storage(project).await);
+    ProjectValidationReport::new(checks)
+}
+
+/// The http(s) address of a LiveKit server given by its ws(s) url. Urls with
+/// any other scheme are used as they are.
+fn livekit_http_url(server_url: &str) -> String {
+    match server_url.strip_prefix("ws") {
+        Some(rest) if rest.starts_with("://") || rest.starts_with("s://") => {
+            format!("http{}", rest)
+        }
+        _ => server_url.to_string(),
+    }
+}
+
+/// Checks that the LiveKit server answers and accepts the api key and secret.
+pub async fn validate_livekit(project: &Project) -> Vec<ValidationCheck> {
+    let http_url = livekit_http_url(&project.livekit_server_url);
+    let reachable = reqwest::Client::new()
+        .get(&http_url)
+        .timeout(Duration::from_secs(REACHABILITY_TIMEOUT_SECONDS))
+        .send()
+        .await;
+
+    if let Err(e) = reachable {
+        return vec![
+            ValidationCheck::failed(LIVEKIT_REACHABLE, e),
+            ValidationCheck::skipped(LIVEKIT_CREDENTIALS, "The LiveKit server is not reachable"),
+        ];
+    }
+
+    let room_service: RoomService = project.into();
+    let credentials = match room_service.list_rooms(None).await {
+        Ok(_) => ValidationCheck::passed(LIVEKIT_CREDENTIALS),
+        Err(e) => ValidationCheck::failed(LIVEKIT_CREDENTIALS, e),
+    };
+
+    vec![ValidationCheck::passed(LIVEKIT_REACHABLE), credentials]
+}
+
+/// Uploads and removes an empty object at `key`.
+async fn probe_write(storage: &dyn Storage, key: &str) -> Result<(), StorageError> {
+    storage.put(key, Vec::new()).await?;
+    storage.delete(key).await
+}
+
+/// Checks that the bucket exists and that the credentials can write to it.
+pub async fn validate_storage(project: &Project) -> Vec<ValidationCheck> {
+    let storage: Box<dyn Storage> = project.into();
+
+    if let Err(e) = storage.probe().await {
+        return vec![
+            ValidationCheck::failed(STORAGE_BUCKET, e),
+            ValidationCheck::skipped(STORAGE_WRITE, "The bucket is not accessible"),
+        ];
+    }
+
+    let key = format!("{}-{}", WRITE_CHECK_PREFIX, Uuid::new_v4());
+    let write = match probe_write(storage.as_ref(), &key).await {
+        Ok(()) => ValidationCheck::passed(STORAGE_WRITE),
+        Err(e) => ValidationCheck::failed(STORAGE_WRITE, e),
+    };
+
+    vec![ValidationCheck::passed(STORAGE_BUCKET), write]
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_livekit_http_url() {
+        assert_eq!(
+            livekit_http_url("ws://localhost:7880"),
+            "http://localhost:7880"
+        );
+        assert_eq!(
+            livekit_http_url("wss://livekit.example.com"),
+            "https://livekit.example.com"
+        );
+        assert_eq!(
+            livekit_http_url("https://livekit.example.com/ws"),
+            "https://livekit.example.com/ws"
+        );
+        assert_eq!(
+            livekit_http_url("wsproxy.example.com"),
+            "wsproxy.example.com"
+        );
+    }
+}
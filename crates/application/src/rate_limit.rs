@@ -0,0 +1,278 @@
+use std::collections::{BTreeMap, HashMap};
+use std::sync::Mutex;
+use std::time::{Duration, Instant};
+
+use shared::deployment_config::{DeploymentConfig, RateLimitConfig};
+
+const DEFAULT_AUTH_PER_IP: u32 = 30;
+const DEFAULT_AUTH_PER_ACCOUNT: u32 = 10;
+const DEFAULT_RMQ_AUTH_PER_IP: u32 = 1200;
+const DEFAULT_SESSIONS_PER_PROJECT: u32 = 60;
+const DEFAULT_TOKENS_PER_PROJECT: u32 = 600;
+const DEFAULT_LOCKOUT_THRESHOLD: u32 = 10;
+const DEFAULT_LOCKOUT_SECONDS: u64 = 15 * 60;
+
+/// Keys a limiter tracks at most. Once they are all in use, new keys are
+/// turned away until the least recently used bucket refilled completely.
+const MAX_TRACKED_KEYS: usize = 100_000;
+
+/// How long an unused bucket takes to refill completely.
+const REFILL_PERIOD: Duration = Duration::from_secs(60);
+
+struct Bucket {
+    tokens: f64,
+    updated_at: Instant,
+    sequence: u64,
+}
+
+/// The buckets in use, with their keys ordered from least to most recently
+/// used. A bucket unused for `REFILL_PERIOD` is full and is dropped, since a
+/// fresh bucket behaves the same.
+#[derive(Default)]
+struct Buckets {
+    by_key: HashMap<String, Bucket>,
+    by_use: BTreeMap<(Instant, u64), String>,
+    next_sequence: u64,
+}
+
+impl Buckets {
+    fn drop_refilled(&mut self, now: Instant) {
+        while let Some((&(used_at, _), _)) = self.by_use.first_key_value() {
+            if now.saturating_duration_since(used_at) < REFILL_PERIOD {
+                break;
+            }
+            if let Some((_, key)) = self.by_use.pop_first() {
+                self.by_key.remove(&key);
+            }
+        }
+    }
+
+    /// How long until the least recently used bucket can be dropped.
+    fn until_refilled(&self, now: Instant) -> Duration {
+        self.by_use
+            .first_key_value()
+            .map(|(&(used_at, _), _)| {
+                REFILL_PERIOD.saturating_sub(now.saturating_duration_since(used_at))
+            })
+            .unwrap_or_default()
+    }
+
+    /// The bucket of `key`, marked as used at `now`.
+    fn touch(&mut self, key: &str, capacity: f64, now: Instant) -> &mut Bucket {
+        let sequence = self.next_sequence;
+        self.next_sequence += 1;
+
+        let bucket = self.by_key.entry(key.to_string()).or_insert(Bucket {
+            tokens: capacity,
+            updated_at: now,
+            sequence,
+        });
+        self.by_use.remove(&(bucket.updated_at, bucket.sequence));
+        self.by_use.insert((now, sequence), key.to_string());
+        bucket.sequence = sequence;
+        bucket
+    }
+}
+
+/// Token buckets keyed by e.g. a client IP or a project id. Every key may make
+/// `per_minute` requests in a burst, refilled evenly over a minute. A limit of
+/// zero allows everything.
+pub struct RateLimiter {
+    per_minute: u32,
+    max_keys: usize,
+    buckets: Mutex<Buckets>,
+}
+
+impl RateLimiter {
+    pub fn new(per_minute: u32) -> Self {
+        Self {
+            per_minute,
+            max_keys: MAX_TRACKED_KEYS,
+            buckets: Mutex::new(Buckets::default()),
+        }
+    }
+
+    pub fn unlimited() -> Self {
+        Self::new(0)
+    }
+
+    /// Takes a request from the bucket of `key`. When it is empty, returns how
+    /// long until the next request is allowed.
+    pub fn check(&self, key: &str) -> Result<(), Duration> {
+        self.check_at(key, Instant::now())
+    }
+
+    fn check_at(&self, key: &str, now: Instant) -> Result<(), Duration> {
+        if self.per_minute == 0 {
+            return Ok(());
+        }
+
+        let capacity = self.per_minute as f64;
+        let refill_per_second = capacity / REFILL_PERIOD.as_secs_f64();
+
+        let mut buckets = self.buckets.lock().unwrap();
+        buckets.drop_refilled(now);
+        if buckets.by_key.len() >= self.max_keys && !buckets.by_key.contains_key(key) {
+            return Err(buckets.until_refilled(now));
+        }
+
+        let bucket = buckets.touch(key, capacity, now);
+        let elapsed = now.saturating_duration_since(bucket.updated_at);
+        bucket.tokens = (bucket.tokens + elapsed.as_secs_f64() * refill_per_second).min(capacity);
+        bucket.updated_at = now;
+
+        if bucket.tokens >= 1.0 {
+            bucket.tokens -= 1.0;
+            Ok(())
+        } else {
+            Err(Duration::from_secs_f64(
+                (1.0 - bucket.tokens) / refill_per_second,
+            ))
+        }
+    }
+}
+
+/// The request limits of the API, see `RateLimitConfig`.
+pub struct RateLimits {
+    pub trust_proxy_headers: bool,
+    pub auth_per_ip: RateLimiter,
+    pub auth_per_account: RateLimiter,
+    pub rmq_auth_per_ip: RateLimiter,
+    pub sessions_per_project: RateLimiter,
+    pub tokens_per_project: RateLimiter,
+}
+
+impl RateLimits {
+    pub fn from_config(config: &DeploymentConfig) -> Self {
+        let limits = config.rate_limit_config.clone().unwrap_or_default();
+        if !limits.enabled.unwrap_or(true) {
+            return Self::unlimited();
+        }
+
+        Self {
+            trust_proxy_headers: limits.trust_proxy_headers.unwrap_or(false),
+            auth_per_ip: RateLimiter::new(limits.auth_per_ip.unwrap_or(DEFAULT_AUTH_PER_IP)),
+            auth_per_account: RateLimiter::new(
+                limits.auth_per_account.unwrap_or(DEFAULT_AUTH_PER_ACCOUNT),
+            ),
+            rmq_auth_per_ip: RateLimiter::new(
+                limits.rmq_auth_per_ip.unwrap_or(DEFAULT_RMQ_AUTH_PER_IP),
+            ),
+            sessions_per_project: RateLimiter::new(
+                limits
+                    .sessions_per_project
+                    .unwrap_or(DEFAULT_SESSIONS_PER_PROJECT),
+            ),
+            tokens_per_project: RateLimiter::new(
+                limits
+                    .tokens_per_project
+                    .unwrap_or(DEFAULT_TOKENS_PER_PROJECT),
+            ),
+        }
+    }
+
+    pub fn unlimited() -> Self {
+        Self {
+            trust_proxy_headers: false,
+            auth_per_ip: RateLimiter::unlimited(),
+            auth_per_account: RateLimiter::unlimited(),
+            rmq_auth_per_ip: RateLimiter::unlimited(),
+            sessions_per_project: RateLimiter::unlimited(),
+            tokens_per_project: RateLimiter::unlimited(),
+        }
+    }
+}
+
+/// Locks an account for `duration` after `threshold` consecutive password
+/// failures. A threshold of zero never locks.
+#[derive(Debug, Clone, Copy)]
+pub struct AccountLockout {
+    pub threshold: u32,
+    pub duration: chrono::Duration,
+}
+
+impl AccountLockout {
+    pub fn from_config(config: &DeploymentConfig) -> Self {
+        let limits: RateLimitConfig = config.rate_limit_config.clone().unwrap_or_default();
+        let threshold = if limits.enabled.unwrap_or(true) {
+            limits
+                .lockout_threshold
+                .unwrap_or(DEFAULT_LOCKOUT_THRESHOLD)
+        } else {
+            0
+        };
+
+        Self {
+            threshold,
+            duration: chrono::Duration::seconds(
+                limits.lockout_seconds.unwrap_or(DEFAULT_LOCKOUT_SECONDS) as i64,
+            ),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_bucket_empties_and_refills() {
+        let limiter = RateLimiter::new(60);
+        let start = Instant::now();
+
+        for _ in 0..60 {
+            assert!(limiter.check_at("ip", start).is_ok());
+        }
+        let retry_after = limiter.check_at("ip", start).unwrap_err();
+        assert!(retry_after <= Duration::from_secs(1));
+
+        // Other keys have their own bucket
+        assert!(limiter.check_at("other-ip", start).is_ok());
+
+        // One request per second comes back
+        let later = start + Duration::from_secs(1);
+        assert!(limiter.check_at("ip", later).is_ok());
+        assert!(limiter.check_at("ip", later).is_err());
+    }
+
+    #[test]
+    fn test_zero_limit_allows_everything() {
+        let limiter = RateLimiter::unlimited();
+        let now = Instant::now();
+        for _ in 0..1000 {
+            assert!(limiter.check_at("ip", now).is_ok());
+        }
+    }
+
+    #[test]
+    fn test_tracked_keys_are_bounded() {
+        let limiter = RateLimiter {
+            max_keys: 2,
+            ..RateLimiter::new(60)
+        };
+        let start = Instant::now();
+
+        assert!(limiter.check_at("a", start).is_ok());
+        assert!(limiter
+            .check_at("b", start + Duration::from_secs(10))
+            .is_ok());
+
+        // New keys wait for the least recently used bucket to refill
+        let retry_after = limiter
+            .check_at("c", start + Duration::from_secs(20))
+            .unwrap_err();
+        assert_eq!(retry_after, Duration::from_secs(40));
+        assert!(limiter
+            .check_at("a", start + Duration::from_secs(20))
+            .is_ok());
+
+        // "b" refilled completely first, so it makes room for "c"
+        assert!(limiter
+            .check_at("c", start + Duration::from_secs(70))
+            .is_ok());
+        let buckets = limiter.buckets.lock().unwrap();
+        assert_eq!(buckets.by_key.len(), 2);
+        assert_eq!(buckets.by_use.len(), 2);
+        assert!(!buckets.by_key.contains_key("b"));
+    }
+}
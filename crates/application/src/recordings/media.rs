@@ -0,0 +1,459 @@
+use std::collections::{BTreeMap, HashMap, HashSet};
+
+use domain::models::{ParticipantTrack, ProjectSession, SessionEgress, SessionParticipant};
+use shared::recording_models::{SessionMediaResponse, SessionStorageUsage, StorageUsageResponse};
+
+use uuid::Uuid;
+
+use crate::storage::{ObjectInfo, Storage, StorageError};
+
+/// Where auto egress writes the recordings of every room of the project.
+pub fn root_prefix(recording_root: &str) -> String {
+    format!("{}/", recording_root)
+}
+
+/// Where auto egress writes the recordings of a room.
+pub fn room_prefix(recording_root: &str, room_name: &str) -> String {
+    format!("{}/{}/", recording_root, room_name)
+}
+
+/// The objects under all of the prefixes, once each and ordered by key, and
+/// the recorded egress destinations outside of them. Track egresses started
+/// for a single track write next to other projects sharing the bucket, so
+/// only the files their egress rows name belong to the project. Destinations
+/// are listed by folder, one request per folder rather than per file.
+pub async fn list_objects(
+    storage: &dyn Storage,
+    prefixes: &[String],
+    destinations: &[String],
+) -> Result<Vec<ObjectInfo>, StorageError> {
+    let mut objects = BTreeMap::new();
+    for prefix in prefixes {
+        for object in storage.list(prefix).await? {
+            objects.insert(object.key.clone(), object);
+        }
+    }
+
+    let mut folders: BTreeMap<&str, HashSet<&str>> = BTreeMap::new();
+    for destination in destinations {
+        if objects.contains_key(destination)
+            || prefixes
+                .iter()
+                .any(|prefix| destination.starts_with(prefix))
+        {
+            continue;
+        }
+        folders
+            .entry(destination_folder(destination))
+            .or_default()
+            .insert(destination.as_str());
+    }
+
+    for (folder, wanted) in folders {
+        for object in storage.list(folder).await? {
+            if wanted.contains(object.key.as_str()) {
+                objects.insert(object.key.clone(), object);
+            }
+        }
+    }
+
+    Ok(objects.into_values().collect())
+}
+
+/// The folder of a key including its trailing `/`, the key itself for keys
+/// at the top of the bucket.
+fn destination_folder(key: &str) -> &str {
+    key.rfind('/').map_or(key, |index| &key[..=index])
+}
+
+/// The publisher in a `.../tracks/{publisher}/{time}/{file}` key.
+fn publisher_from_key(key: &str) -> Option<&str> {
+    let segments = key.split('/').collect::<Vec<_>>();
+    match segments.as_slice() {
+        [.., "tracks", publisher, _, _] => Some(*publisher),
+        _ => None,
+    }
+}
+
+/// Whether a `{track_type}-{track_source}-{track_id}-{time}` file name
+/// records the track.
+fn file_records_track(file_name: &str, track_sid: &str) -> bool {
+    file_name
+        .split(['-', '.'])
+        .any(|segment| segment == track_sid)
+}
+
+/// Matches the files of a session to its tracks. Files an egress recorded
+/// take the egress's track, the others the track named in the file name.
+pub fn match_session_media(
+    objects: Vec<ObjectInfo>,
+    participants: &[(SessionParticipant, Vec<ParticipantTrack>)],
+    egresses: &[SessionEgress],
+) -> Vec<SessionMediaResponse> {
+    let publishers = participants
+        .iter()
+        .flat_map(|(participant, tracks)| {
+            tracks
+                .iter()
+                .map(move |track| (track.sid.as_str(), participant.identity.as_str()))
+        })
+        .collect::<HashMap<_, _>>();
+
+    objects
+        .into_iter()
+        .map(|object| {
+            let file_name = object
+                .key
+                .rsplit('/')
+                .next()
+                .unwrap_or_default()
+                .to_string();
+            let egress = egresses
+                .iter()
+                .find(|egress| egress.destination.as_deref() == Some(object.key.as_str()));
+            let track_id = match egress {
+                Some(egress) => Some(egress.track_id.clone()),
+                None => publishers
+                    .keys()
+                    .find(|sid| file_records_track(&file_name, sid))
+                    .map(|sid| sid.to_string()),
+            };
+            let publisher = track_id
+                .as_deref()
+                .and_then(|sid| publishers.get(sid).copied())
+                .or_else(|| publisher_from_key(&object.key))
+                .map(str::to_string);
+
+            SessionMediaResponse {
+                file_name,
+                size: object.size,
+                last_modified: object
+                    .last_modified
+                    .map(|time| time.and_utc().timestamp() as usize),
+                publisher,
+                track_id,
+                egress_id: egress.map(|egress| egress.egress_id.clone()),
+                key: object.key,
+            }
+        })
+        .collect()
+}
+
+/// Adds up the files of the project per session. A file an egress recorded
+/// belongs to the session of the egress, any other file to the session whose
+/// room it was written under, unless several sessions reuse that room name.
+pub fn summarize_storage_usage(
+    project_id: &str,
+    recording_root: &str,
+    sessions: &[ProjectSession],
+    egress_sessions: &HashMap<String, Uuid>,
+    shared_rooms: &HashSet<String>,
+    objects: &[ObjectInfo],
+) -> StorageUsageResponse {
+    let mut usage = StorageUsageResponse {
+        project_id: project_id.to_string(),
+        num_objects: 0,
+        total_size: 0,
+        sessions: Vec::new(),
+        unattributed_objects: 0,
+        unattributed_size: 0,
+    };
+    let root = root_prefix(recording_root);
+    let mut per_session: HashMap<Uuid, (u64, u64)> = HashMap::new();
+
+    for object in objects {
+        usage.num_objects += 1;
+        usage.total_size += object.size;
+
+        let room_name = object
+            .key
+            .strip_prefix(&root)
+            .unwrap_or(object.key.as_str())
+            .split('/')
+            .next()
+            .unwrap_or_default();
+        let session = match egress_sessions.get(&object.key) {
+            Some(sess_uuid) => sessions.iter().find(|session| session.id == *sess_uuid),
+            None if shared_rooms.contains(room_name) => None,
+            None => sessions
+                .iter()
+                .find(|session| session.livekit_room_name == room_name),
+        };
+
+        match session {
+            Some(session) => {
+                let totals = per_session.entry(session.id).or_default();
+                totals.0 += 1;
+                totals.1 += object.size;
+            }
+            None => {
+                usage.unattributed_objects += 1;
+                usage.unattributed_size += object.size;
+            }
+        }
+    }
+
+    usage.sessions = sessions
+        .iter()
+        .filter_map(|session| {
+            per_session
+                .get(&session.id)
+                .map(|(num_objects, total_size)| SessionStorageUsage {
+                    session_id: session.id.to_string(),
+                    session_name: session.name.clone(),
+                    livekit_room_name: session.livekit_room_name.clone(),
+                    num_objects: *num_objects,
+                    total_size: *total_size,
+                })
+        })
+        .collect();
+    usage
+        .sessions
+        .sort_by(|a, b| b.total_size.cmp(&a.total_size));
+
+    usage
+}
+
+#[cfg(test)]
+mod tests {
+    use domain::models::{ProjectSessionStatus, SessionEgressStatus, TrackKind, TrackSource};
+    use uuid::Uuid;
+
+    use super::*;
+    use crate::storage::memory::InMemoryStorage;
+
+    fn object(key: &str, size: u64) -> ObjectInfo {
+        ObjectInfo {
+            key: key.to_string(),
+            size,
+            last_modified: None,
+        }
+    }
+
+    fn participant(
+        identity: &str,
+        track_sids: &[&str],
+    ) -> (SessionParticipant, Vec<ParticipantTrack>) {
+        let participant = SessionParticipant {
+            id: Uuid::new_v4(),
+            identity: identity.to_string(),
+            name: identity.to_string(),
+            joined_at: 0,
+            left_at: None,
+            session_id: Uuid::new_v4(),
+        };
+        let tracks = track_sids
+            .iter()
+            .map(|sid| ParticipantTrack {
+                id: Uuid::new_v4(),
+                sid: sid.to_string(),
+                name: None,
+                kind: TrackKind::Video,
+                source: TrackSource::Camera,
+                participant_id: participant.id,
+            })
+            .collect();
+        (participant, tracks)
+    }
+
+    fn session(room_name: &str) -> ProjectSession {
+        ProjectSession {
+            id: Uuid::new_v4(),
+            name: room_name.to_string(),
+            comments: None,
+            empty_timeout: 0,
+            max_participants: 0,
+            livekit_room_name: room_name.to_string(),
+            created_at: None,
+            updated_at: None,
+            status: ProjectSessionStatus::Stopped,
+            project_id: Uuid::new_v4(),
+            stopped_at: None,
+        }
+    }
+
+    #[test]
+    fn test_publisher_from_key() {
+        assert_eq!(
+            publisher_from_key("root/room/tracks/alice/2024-01-01T1200/video-camera-TR_1-x.mp4"),
+            Some("alice")
+        );
+        assert_eq!(publisher_from_key("root/room/notes.txt"), None);
+    }
+
+    #[test]
+    fn test_match_session_media() {
+        let participants = vec![
+            participant("alice", &["TR_A1"]),
+            participant("bob", &["TR_B1"]),
+        ];
+        let egress = SessionEgress {
+            id: Uuid::new_v4(),
+            track_id: "TR_A1".to_string(),
+            egress_id: "EG_1".to_string(),
+            started_at: 0,
+            egress_type: None,
+            status: SessionEgressStatus::EgressComplete,
+            destination: Some("root/room/tracks/alice/t/audio-microphone-TR_A1-t.ogg".to_string()),
+            room_name: "room".to_string(),
+            session_id: Uuid::new_v4(),
+            participant_id: None,
+            db_track_id: None,
+            purged_at: None,
+            purge_failures: 0,
+            purge_attempted_at: None,
+        };
+
+        let media = match_session_media(
+            vec![
+                object("root/room/tracks/alice/t/audio-microphone-TR_A1-t.ogg", 10),
+                object("room/tracks/bob/t/video-camera-TR_B1-t.mp4", 20),
+                object("root/room/tracks/carol/t/video-camera-TR_C1-t.mp4", 30),
+            ],
+            &participants,
+            &[egress],
+        );
+
+        let matched = media
+            .iter()
+            .map(|m| {
+                (
+                    m.publisher.as_deref(),
+                    m.track_id.as_deref(),
+                    m.egress_id.as_deref(),
+                )
+            })
+            .collect::<Vec<_>>();
+        assert_eq!(
+            matched,
+            vec![
+                (Some("alice"), Some("TR_A1"), Some("EG_1")),
+                (Some("bob"), Some("TR_B1"), None),
+                (Some("carol"), None, None),
+            ]
+        );
+        assert_eq!(media[1].file_name, "video-camera-TR_B1-t.mp4");
+    }
+
+    #[test]
+    fn test_summarize_storage_usage() {
+        let sessions = vec![session("small"), session("large")];
+        let usage = summarize_storage_usage(
+            "project",
+            "root",
+            &sessions,
+            &HashMap::new(),
+            &HashSet::new(),
+            &[
+                object("root/small/tracks/a/t/1.mp4", 5),
+                object("root/large/tracks/a/t/1.mp4", 50),
+                object("large/tracks/a/t/2.mp4", 25),
+                object("root/deleted/tracks/a/t/1.mp4", 7),
+            ],
+        );
+
+        assert_eq!(usage.num_objects, 4);
+        assert_eq!(usage.total_size, 87);
+        assert_eq!(
+            usage
+                .sessions
+                .iter()
+                .map(|s| (s.livekit_room_name.as_str(), s.num_objects, s.total_size))
+                .collect::<Vec<_>>(),
+            vec![("large", 2, 75), ("small", 1, 5)]
+        );
+        assert_eq!(
+            (usage.unattributed_objects, usage.unattributed_size),
+            (1, 7)
+        );
+    }
+
+    #[test]
+    fn test_summarize_storage_usage_of_shared_rooms() {
+        let (first, second) = (session("room"), session("room"));
+        let egress_sessions = HashMap::from([
+            ("root/room/tracks/a/t/1.mp4".to_string(), first.id),
+            ("room/tracks/b/t/2.mp4".to_string(), second.id),
+        ]);
+        let usage = summarize_storage_usage(
+            "project",
+            "root",
+            &[first.clone(), second.clone()],
+            &egress_sessions,
+            &HashSet::from(["room".to_string()]),
+            &[
+                object("root/room/tracks/a/t/1.mp4", 5),
+                object("room/tracks/b/t/2.mp4", 50),
+                object("root/room/composite.mp4", 7),
+            ],
+        );
+
+        assert_eq!(
+            usage
+                .sessions
+                .iter()
+                .map(|s| (s.session_id.clone(), s.num_objects, s.total_size))
+                .collect::<Vec<_>>(),
+            vec![(second.id.to_string(), 1, 50), (first.id.to_string(), 1, 5)]
+        );
+        // Neither session can claim a file no egress recorded
+        assert_eq!(
+            (usage.unattributed_objects, usage.unattributed_size),
+            (1, 7)
+        );
+    }
+
+    #[test]
+    fn test_destination_folder() {
+        assert_eq!(destination_folder("room/tracks/b.mp4"), "room/tracks/");
+        assert_eq!(destination_folder("b.mp4"), "b.mp4");
+    }
+
+    #[tokio::test]
+    async fn test_list_objects_includes_only_recorded_destinations() {
+        let storage = InMemoryStorage::new();
+        storage
+            .put("root/room/tracks/a.mp4", vec![1])
+            .await
+            .unwrap();
+        storage.put("room/tracks/b.mp4", vec![1]).await.unwrap();
+        storage.put("top.mp4", vec![1]).await.unwrap();
+        storage.put("top.mp4.part", vec![1]).await.unwrap();
+        storage
+            .put("room/tracks/b.mp4.part", vec![1])
+            .await
+            .unwrap();
+        storage
+            .put("room/tracks/other-project.mp4", vec![1])
+            .await
+            .unwrap();
+        storage
+            .put("root/other/tracks/c.mp4", vec![1])
+            .await
+            .unwrap();
+
+        let prefixes = vec![room_prefix("root", "room"), root_prefix("root")];
+        let destinations = vec![
+            "room/tracks/b.mp4".to_string(),
+            "root/room/tracks/a.mp4".to_string(),
+            "room/tracks/purged.mp4".to_string(),
+            "top.mp4".to_string(),
+        ];
+        let keys = list_objects(&storage, &prefixes, &destinations)
+            .await
+            .unwrap()
+            .into_iter()
+            .map(|object| object.key)
+            .collect::<Vec<_>>();
+
+        assert_eq!(
+            keys,
+            vec![
+                "room/tracks/b.mp4",
+                "root/other/tracks/c.mp4",
+                "root/room/tracks/a.mp4",
+                "top.mp4"
+            ]
+        );
+    }
+}
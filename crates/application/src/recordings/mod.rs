@@ -0,0 +1,4 @@
+pub mod media;
+pub mod recording_crud;
+pub mod recording_service;
+pub mod retention;
@@ -0,0 +1,343 @@
+use diesel::{prelude::*, PgConnection};
+use domain::models::{
+    NewParticipantConsent, ParticipantConsent, ProjectSessionStatus, RetentionMode,
+    RetentionPolicy, SessionEgress,
+};
+use domain::schema::syncflow::{
+    participant_consents, participant_tracks, project_sessions, recording_retention_policies,
+    session_egresses, session_participants,
+};
+use shared::response_models::Response;
+use thiserror::Error;
+use uuid::Uuid;
+
+use crate::project::project_crud::ProjectError;
+use crate::project::session_crud::SessionError;
+use crate::storage::StorageError;
+
+#[derive(Error, Debug)]
+pub enum RecordingError {
+    #[error("Database Error: {0}")]
+    DatabaseError(#[from] diesel::result::Error),
+
+    #[error("Project Error: {0}")]
+    ProjectError(#[from] ProjectError),
+
+    #[error("Storage Error: {0}")]
+    StorageError(#[from] StorageError),
+
+    #[error("Session Error: {0}")]
+    SessionError(#[from] SessionError),
+
+    #[error("Invalid Request: {0}")]
+    InvalidRequest(String),
+}
+
+impl From<RecordingError> for Response {
+    fn from(error: RecordingError) -> Self {
+        match error {
+            RecordingError::DatabaseError(diesel::result::Error::NotFound) => Response {
+                status: 404,
+                message: "Not found".to_string(),
+            },
+            RecordingError::DatabaseError(e) => Response {
+                status: 500,
+                message: e.to_string(),
+            },
+            RecordingError::ProjectError(e) => e.into(),
+            RecordingError::StorageError(e) => Response {
+                status: 502,
+                message: e.to_string(),
+            },
+            RecordingError::SessionError(e) => e.into(),
+            RecordingError::InvalidRequest(e) => Response {
+                status: 400,
+                message: e,
+            },
+        }
+    }
+}
+
+/// Recordings whose purge failed this often are left for an operator to look
+/// into rather than retried forever.
+pub const MAX_PURGE_FAILURES: i32 = 10;
+
+fn parse_uuid(value: &str, what: &str) -> Result<Uuid, RecordingError> {
+    Uuid::parse_str(value).map_err(|_| RecordingError::InvalidRequest(format!("Invalid {}", what)))
+}
+
+pub fn get_retention_policy(
+    proj_id: &str,
+    conn: &mut PgConnection,
+) -> Result<Option<RetentionPolicy>, RecordingError> {
+    let proj_uuid = parse_uuid(proj_id, "project id")?;
+
+    let policy = recording_retention_policies::table
+        .find(proj_uuid)
+        .first::<RetentionPolicy>(conn)
+        .optional()?;
+
+    Ok(policy)
+}
+
+pub fn save_retention_policy(
+    policy: &RetentionPolicy,
+    conn: &mut PgConnection,
+) -> Result<RetentionPolicy, RecordingError> {
+    let policy = diesel::insert_into(recording_retention_policies::table)
+        .values(policy)
+        .on_conflict(recording_retention_policies::project_id)
+        .do_update()
+        .set(policy)
+        .get_result::<RetentionPolicy>(conn)?;
+
+    Ok(policy)
+}
+
+/// Policies under which recordings expire.
+pub fn list_expiring_policies(
+    conn: &mut PgConnection,
+) -> Result<Vec<RetentionPolicy>, RecordingError> {
+    let policies = recording_retention_policies::table
+        .filter(recording_retention_policies::mode.ne(RetentionMode::KeepMedia))
+        .load::<RetentionPolicy>(conn)?;
+
+    Ok(policies)
+}
+
+/// Recordings still in the bucket of sessions of the project that stopped
+/// before `cutoff`. Sessions stopped before stop times were recorded count
+/// from their last update. Recordings never tried come first, those whose
+/// purge keeps failing last.
+pub fn expired_egresses(
+    proj_uuid: Uuid,
+    cutoff: chrono::NaiveDateTime,
+    limit: i64,
+    conn: &mut PgConnection,
+) -> Result<Vec<SessionEgress>, RecordingError> {
+    let egresses = session_egresses::table
+        .inner_join(project_sessions::table)
+        .filter(project_sessions::project_id.eq(proj_uuid))
+        .filter(project_sessions::status.eq(ProjectSessionStatus::Stopped))
+        .filter(
+            project_sessions::stopped_at
+                .lt(cutoff)
+                .or(project_sessions::stopped_at
+                    .is_null()
+                    .and(project_sessions::updated_at.lt(cutoff))),
+        )
+        .filter(session_egresses::destination.is_not_null())
+        .filter(session_egresses::purged_at.is_null())
+        .filter(session_egresses::purge_failures.lt(MAX_PURGE_FAILURES))
+        .select(session_egresses::all_columns)
+        .order((
+            session_egresses::purge_failures.asc(),
+            session_egresses::purge_attempted_at.asc().nulls_first(),
+            session_egresses::id.asc(),
+        ))
+        .limit(limit)
+        .load::<SessionEgress>(conn)?;
+
+    Ok(egresses)
+}
+
+/// Where the egresses of the sessions of the project wrote their recordings,
+/// for those still in the bucket, by session.
+pub fn egress_destinations(
+    proj_uuid: Uuid,
+    conn: &mut PgConnection,
+) -> Result<Vec<(Uuid, String)>, RecordingError> {
+    let destinations = session_egresses::table
+        .inner_join(project_sessions::table)
+        .filter(project_sessions::project_id.eq(proj_uuid))
+        .filter(session_egresses::destination.is_not_null())
+        .filter(session_egresses::purged_at.is_null())
+        .select((
+            session_egresses::session_id,
+            session_egresses::destination.assume_not_null(),
+        ))
+        .load::<(Uuid, String)>(conn)?;
+
+    Ok(destinations)
+}
+
+/// Recordings still in the bucket of the participant with `identity` in the
+/// session, matched through the participant or the track they published.
+pub fn participant_egresses(
+    sess_uuid: Uuid,
+    identity: &str,
+    conn: &mut PgConnection,
+) -> Result<Vec<SessionEgress>, RecordingError> {
+    let participant_ids = session_participants::table
+        .filter(session_participants::session_id.eq(sess_uuid))
+        .filter(session_participants::participant_identity.eq(identity))
+        .select(session_participants::id)
+        .load::<Uuid>(conn)?;
+
+    let track_sids = participant_tracks::table
+        .filter(participant_tracks::participant_id.eq_any(&participant_ids))
+        .select(participant_tracks::sid)
+        .load::<String>(conn)?;
+
+    let egresses = session_egresses::table
+        .filter(session_egresses::session_id.eq(sess_uuid))
+        .filter(
+            session_egresses::participant_id
+                .eq_any(participant_ids.clone())
+                .or(session_egresses::track_id.eq_any(&track_sids)),
+        )
+        .filter(session_egresses::destination.is_not_null())
+        .filter(session_egresses::purged_at.is_null())
+        .load::<SessionEgress>(conn)?;
+
+    Ok(egresses)
+}
+
+pub fn mark_purged(egress_id: Uuid, conn: &mut PgConnection) -> Result<(), RecordingError> {
+    let now = chrono::Utc::now().naive_utc();
+
+    diesel::update(session_egresses::table.find(egress_id))
+        .set((
+            session_egresses::purged_at.eq(now),
+            session_egresses::purge_attempted_at.eq(now),
+        ))
+        .execute(conn)?;
+
+    Ok(())
+}
+
+/// Counts a failed attempt at purging the recordings, which moves them to the
+/// back of the next batches.
+pub fn record_purge_failure(
+    egress_ids: &[Uuid],
+    conn: &mut PgConnection,
+) -> Result<(), RecordingError> {
+    diesel::update(session_egresses::table.filter(session_egresses::id.eq_any(egress_ids)))
+        .set((
+            session_egresses::purge_failures.eq(session_egresses::purge_failures + 1),
+            session_egresses::purge_attempted_at.eq(chrono::Utc::now().naive_utc()),
+        ))
+        .execute(conn)?;
+
+    Ok(())
+}
+
+/// Records the consent of a participant, granting it again if it had been
+/// withdrawn. Recordings purged on withdrawal stay purged.
+pub fn record_consent(
+    sess_uuid: Uuid,
+    identity: &str,
+    recorded_by: Option<i32>,
+    conn: &mut PgConnection,
+) -> Result<ParticipantConsent, RecordingError> {
+    let consent = diesel::insert_into(participant_consents::table)
+        .values(NewParticipantConsent {
+            participant_identity: identity.to_string(),
+            recorded_by,
+            session_id: sess_uuid,
+        })
+        .on_conflict((
+            participant_consents::session_id,
+            participant_consents::participant_identity,
+        ))
+        .do_update()
+        .set((
+            participant_consents::granted_at.eq(chrono::Utc::now().naive_utc()),
+            participant_consents::withdrawn_at.eq(None::<chrono::NaiveDateTime>),
+            participant_consents::recorded_by.eq(recorded_by),
+        ))
+        .get_result::<ParticipantConsent>(conn)?;
+
+    Ok(consent)
+}
+
+pub fn list_consents(
+    sess_uuid: Uuid,
+    conn: &mut PgConnection,
+) -> Result<Vec<ParticipantConsent>, RecordingError> {
+    let consents = participant_consents::table
+        .filter(participant_consents::session_id.eq(sess_uuid))
+        .order(participant_consents::granted_at.asc())
+        .load::<ParticipantConsent>(conn)?;
+
+    Ok(consents)
+}
+
+/// Marks the consent of a participant withdrawn. A participant without a
+/// consent record gets one that is withdrawn right away, so that their
+/// recordings are purged all the same.
+pub fn withdraw_consent(
+    sess_uuid: Uuid,
+    identity: &str,
+    withdrawn_by: Option<i32>,
+    conn: &mut PgConnection,
+) -> Result<ParticipantConsent, RecordingError> {
+    let now = chrono::Utc::now().naive_utc();
+
+    let consent = diesel::insert_into(participant_consents::table)
+        .values((
+            participant_consents::participant_identity.eq(identity),
+            participant_consents::recorded_by.eq(withdrawn_by),
+            participant_consents::session_id.eq(sess_uuid),
+            participant_consents::withdrawn_at.eq(now),
+        ))
+        .on_conflict((
+            participant_consents::session_id,
+            participant_consents::participant_identity,
+        ))
+        .do_update()
+        .set(participant_consents::withdrawn_at.eq(now))
+        .get_result::<ParticipantConsent>(conn)?;
+
+    Ok(consent)
+}
+
+/// Recordings still in the bucket of participants who withdrew their consent,
+/// with the project they belong to. Recordings are matched through the
+/// participant or the tracks they published, in the order of
+/// [`expired_egresses`].
+pub fn withdrawn_egresses(
+    limit: i64,
+    conn: &mut PgConnection,
+) -> Result<Vec<(Uuid, SessionEgress)>, RecordingError> {
+    let egresses = session_egresses::table
+        .inner_join(project_sessions::table)
+        .inner_join(
+            session_participants::table
+                .on(session_participants::session_id.eq(session_egresses::session_id)),
+        )
+        .inner_join(
+            participant_consents::table.on(participant_consents::session_id
+                .eq(session_participants::session_id)
+                .and(
+                    participant_consents::participant_identity
+                        .eq(session_participants::participant_identity),
+                )),
+        )
+        .left_join(
+            participant_tracks::table
+                .on(participant_tracks::participant_id.eq(session_participants::id)),
+        )
+        .filter(participant_consents::withdrawn_at.is_not_null())
+        .filter(
+            session_egresses::participant_id
+                .eq(session_participants::id.nullable())
+                .or(session_egresses::track_id
+                    .nullable()
+                    .eq(participant_tracks::sid.nullable())),
+        )
+        .filter(session_egresses::destination.is_not_null())
+        .filter(session_egresses::purged_at.is_null())
+        .filter(session_egresses::purge_failures.lt(MAX_PURGE_FAILURES))
+        .select((project_sessions::project_id, session_egresses::all_columns))
+        .distinct()
+        .order((
+            session_egresses::purge_failures.asc(),
+            session_egresses::purge_attempted_at.asc().nulls_first(),
+            session_egresses::id.asc(),
+        ))
+        .limit(limit)
+        .load::<(Uuid, SessionEgress)>(conn)?;
+
+    Ok(egresses)
+}
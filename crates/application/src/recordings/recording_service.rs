@@ -0,0 +1,475 @@
+use std::collections::HashMap;
+use std::str::FromStr;
+use std::sync::Arc;
+
+use domain::models::{Project, RetentionMode, RetentionPolicy, SessionEgress};
+use infrastructure::DbPool;
+use shared::recording_models::{
+    ConsentRequest, ConsentWithdrawalResponse, ParticipantConsentResponse, RetentionPolicyRequest,
+    RetentionPolicyResponse, SessionMediaResponse, StorageUsageResponse,
+};
+use uuid::Uuid;
+
+use super::media;
+use super::recording_crud::{self, RecordingError};
+use crate::audit::{
+    audit_service::AuditService, AuditAction, AuditActor, AuditTarget, AuditTargetType,
+};
+use crate::project::project_crud::{self, Encryptable, ProjectError};
+use crate::project::session_crud;
+use crate::storage::Storage;
+
+/// Upper bound on the recordings purged per project in one retention run.
+const PURGE_BATCH_SIZE: i64 = 500;
+
+/// Checks a retention policy request, `delete_media` needs a positive number
+/// of days and the other modes take none.
+pub fn parse_retention_policy(
+    request: &RetentionPolicyRequest,
+) -> Result<(RetentionMode, Option<i32>), RecordingError> {
+    let mode = RetentionMode::from_str(&request.mode).map_err(RecordingError::InvalidRequest)?;
+
+    match (mode, request.media_retention_days) {
+        (RetentionMode::DeleteMedia, Some(days)) if days > 0 => Ok((mode, Some(days))),
+        (RetentionMode::DeleteMedia, _) => Err(RecordingError::InvalidRequest(
+            "delete_media requires a positive mediaRetentionDays".to_string(),
+        )),
+        (_, Some(_)) => Err(RecordingError::InvalidRequest(format!(
+            "{} does not take mediaRetentionDays",
+            mode.as_str()
+        ))),
+        (_, None) => Ok((mode, None)),
+    }
+}
+
+/// Retention policies and participant consent for the recordings of a
+/// project. Every removal of a recording from the bucket is audited.
+pub struct RecordingService {
+    encryption_key: String,
+    pool: Arc<DbPool>,
+    audit: AuditService,
+}
+
+impl RecordingService {
+    pub fn new(encryption_key: &str, pool: Arc<DbPool>) -> Self {
+        Self {
+            encryption_key: encryption_key.to_string(),
+            audit: AuditService::new(pool.clone()),
+            pool,
+        }
+    }
+
+    /// The retention policy of the project, `keep_media` for projects without one.
+    pub fn get_retention_policy(
+        &self,
+        project_id: &str,
+    ) -> Result<RetentionPolicyResponse, RecordingError> {
+        let conn = &mut self.pool.get().unwrap();
+        let project = project_crud::get_project_by_id(project_id, conn)?;
+
+        Ok(recording_crud::get_retention_policy(project_id, conn)?
+            .map(Into::into)
+            .unwrap_or_else(|| RetentionPolicyResponse {
+                project_id: project.id.to_string(),
+                mode: RetentionMode::KeepMedia.as_str().to_string(),
+                media_retention_days: None,
+                updated_at: None,
+                updated_by: None,
+            }))
+    }
+
+    pub fn set_retention_policy(
+        &self,
+        actor: &AuditActor,
+        project_id: &str,
+        request: &RetentionPolicyRequest,
+    ) -> Result<RetentionPolicyResponse, RecordingError> {
+        let saved = self.save_retention_policy(actor, project_id, request);
+        self.audit.record(
+            actor,
+            AuditAction::RetentionPolicyUpdated,
+            AuditTarget::project(project_id),
+            &saved,
+        );
+        saved
+    }
+
+    fn save_retention_policy(
+        &self,
+        actor: &AuditActor,
+        project_id: &str,
+        request: &RetentionPolicyRequest,
+    ) -> Result<RetentionPolicyResponse, RecordingError> {
+        let (mode, media_retention_days) = parse_retention_policy(request)?;
+        let conn = &mut self.pool.get().unwrap();
+        let project = project_crud::get_project_by_id(project_id, conn)?;
+
+        let policy = recording_crud::save_retention_policy(
+            &RetentionPolicy {
+                project_id: project.id,
+                mode,
+                media_retention_days,
+                updated_at: chrono::Utc::now().naive_utc(),
+                updated_by: actor.user_id,
+            },
+            conn,
+        )?;
+
+        Ok(policy.into())
+    }
+
+    pub fn record_consent(
+        &self,
+        actor: &AuditActor,
+        project_id: &str,
+        session_id: &str,
+        request: &ConsentRequest,
+    ) -> Result<ParticipantConsentResponse, RecordingError> {
+        let recorded = self
+            .session_uuid(project_id, session_id)
+            .and_then(|sess_uuid| {
+                if request.participant_identity.trim().is_empty() {
+                    return Err(RecordingError::InvalidRequest(
+                        "The participant identity cannot be empty".to_string(),
+                    ));
+                }
+                recording_crud::record_consent(
+                    sess_uuid,
+                    &request.participant_identity,
+                    actor.user_id,
+                    &mut self.pool.get().unwrap(),
+                )
+            });
+        self.audit.record(
+            actor,
+            AuditAction::ConsentRecorded,
+            self.consent_target(project_id, session_id, &request.participant_identity),
+            &recorded,
+        );
+
+        Ok(recorded?.into())
+    }
+
+    pub fn list_consents(
+        &self,
+        project_id: &str,
+        session_id: &str,
+    ) -> Result<Vec<ParticipantConsentResponse>, RecordingError> {
+        let sess_uuid = self.session_uuid(project_id, session_id)?;
+        let consents = recording_crud::list_consents(sess_uuid, &mut self.pool.get().unwrap())?;
+
+        Ok(consents.into_iter().map(Into::into).collect())
+    }
+
+    /// Withdraws the consent of a participant and purges the recordings of
+    /// their tracks. Recordings that cannot be removed now are left to the
+    /// retention job.
+    pub async fn withdraw_consent(
+        &self,
+        actor: &AuditActor,
+        project_id: &str,
+        session_id: &str,
+        identity: &str,
+    ) -> Result<ConsentWithdrawalResponse, RecordingError> {
+        let withdrawn = self
+            .session_uuid(project_id, session_id)
+            .and_then(|sess_uuid| {
+                recording_crud::withdraw_consent(
+                    sess_uuid,
+                    identity,
+                    actor.user_id,
+                    &mut self.pool.get().unwrap(),
+                )
+            });
+        self.audit.record(
+            actor,
+            AuditAction::ConsentWithdrawn,
+            self.consent_target(project_id, session_id, identity),
+            &withdrawn,
+        );
+        let consent = withdrawn?;
+
+        let egresses = recording_crud::participant_egresses(
+            consent.session_id,
+            identity,
+            &mut self.pool.get().unwrap(),
+        )?;
+        let project = self.decrypted_project(project_id)?;
+        let (purged, pending) = self.purge(actor, &project, egresses).await;
+
+        Ok(ConsentWithdrawalResponse {
+            consent: consent.into(),
+            purged,
+            pending,
+        })
+    }
+
+    /// Every file under the prefixes of the session, including those written
+    /// without an egress being recorded, matched to its tracks where possible.
+    /// When other sessions reuse the room name only the recorded files are
+    /// listed, the others may be theirs.
+    pub async fn list_session_media(
+        &self,
+        project_id: &str,
+        session_id: &str,
+    ) -> Result<Vec<SessionMediaResponse>, RecordingError> {
+        let (project, session, participants, egresses, shared_rooms) = {
+            let conn = &mut self.pool.get().unwrap();
+            let mut project = project_crud::get_project_by_id(project_id, conn)?;
+            project
+                .decrypt(&self.encryption_key)
+                .map_err(ProjectError::from)?;
+            let session = session_crud::get_session(project_id, session_id, conn)?;
+            let (participants, egresses) =
+                session_crud::load_session_participant_tracks_recordings(&session, conn)?;
+            let shared_rooms = session_crud::shared_room_names(project.id, conn)?;
+            (project, session, participants, egresses, shared_rooms)
+        };
+
+        let destinations = egresses
+            .iter()
+            .filter_map(|egress| egress.destination.clone())
+            .collect::<Vec<_>>();
+        let prefixes = if shared_rooms.contains(&session.livekit_room_name) {
+            Vec::new()
+        } else {
+            vec![media::room_prefix(
+                &project.get_recording_root(),
+                &session.livekit_room_name,
+            )]
+        };
+        let storage: Box<dyn Storage> = (&project).into();
+        let objects = media::list_objects(storage.as_ref(), &prefixes, &destinations).await?;
+
+        Ok(media::match_session_media(
+            objects,
+            &participants,
+            &egresses,
+        ))
+    }
+
+    /// The size of the project's recordings, in total and per session.
+    pub async fn get_storage_usage(
+        &self,
+        project_id: &str,
+    ) -> Result<StorageUsageResponse, RecordingError> {
+        let (project, sessions, shared_rooms, egress_sessions) = {
+            let conn = &mut self.pool.get().unwrap();
+            let mut project = project_crud::get_project_by_id(project_id, conn)?;
+            project
+                .decrypt(&self.encryption_key)
+                .map_err(ProjectError::from)?;
+            let sessions = session_crud::get_sessions(project_id, conn)?;
+            let shared_rooms = session_crud::shared_room_names(project.id, conn)?;
+            let egress_sessions = recording_crud::egress_destinations(project.id, conn)?
+                .into_iter()
+                .map(|(sess_uuid, destination)| (destination, sess_uuid))
+                .collect::<HashMap<_, _>>();
+            (project, sessions, shared_rooms, egress_sessions)
+        };
+
+        let recording_root = project.get_recording_root();
+        let storage: Box<dyn Storage> = (&project).into();
+        let destinations = egress_sessions.keys().cloned().collect::<Vec<_>>();
+        let objects = media::list_objects(
+            storage.as_ref(),
+            &[media::root_prefix(&recording_root)],
+            &destinations,
+        )
+        .await?;
+
+        Ok(media::summarize_storage_usage(
+            &project.id.to_string(),
+            &recording_root,
+            &sessions,
+            &egress_sessions,
+            &shared_rooms,
+            &objects,
+        ))
+    }
+
+    /// Purges the recordings past the retention policy of their project and
+    /// those of participants who withdrew consent. Returns the number removed.
+    pub async fn purge_due_recordings(&self) -> Result<usize, RecordingError> {
+        let actor = AuditActor::default();
+        let now = chrono::Utc::now().naive_utc();
+        let mut num_purged = 0;
+
+        let policies = recording_crud::list_expiring_policies(&mut self.pool.get().unwrap())?;
+        for policy in policies {
+            let Some(cutoff) = policy.media_cutoff(now) else {
+                continue;
+            };
+            let egresses = recording_crud::expired_egresses(
+                policy.project_id,
+                cutoff,
+                PURGE_BATCH_SIZE,
+                &mut self.pool.get().unwrap(),
+            )?;
+            if egresses.is_empty() {
+                continue;
+            }
+
+            match self.decrypted_project(&policy.project_id.to_string()) {
+                Ok(project) => num_purged += self.purge(&actor, &project, egresses).await.0.len(),
+                Err(e) => {
+                    log::error!(
+                        "Failed to load project {} to purge expired recordings: {}",
+                        policy.project_id,
+                        e
+                    );
+                    self.record_purge_failure(&egresses);
+                }
+            }
+        }
+
+        let mut withdrawn: HashMap<Uuid, Vec<SessionEgress>> = HashMap::new();
+        for (proj_uuid, egress) in
+            recording_crud::withdrawn_egresses(PURGE_BATCH_SIZE, &mut self.pool.get().unwrap())?
+        {
+            withdrawn.entry(proj_uuid).or_default().push(egress);
+        }
+        for (proj_uuid, egresses) in withdrawn {
+            match self.decrypted_project(&proj_uuid.to_string()) {
+                Ok(project) => num_purged += self.purge(&actor, &project, egresses).await.0.len(),
+                Err(e) => {
+                    log::error!(
+                        "Failed to load project {} to purge withdrawn recordings: {}",
+                        proj_uuid,
+                        e
+                    );
+                    self.record_purge_failure(&egresses);
+                }
+            }
+        }
+
+        Ok(num_purged)
+    }
+
+    /// Removes the files of the egresses from the project's bucket, keeping
+    /// their metadata. Returns the purged and the still pending paths.
+    async fn purge(
+        &self,
+        actor: &AuditActor,
+        project: &Project,
+        egresses: Vec<SessionEgress>,
+    ) -> (Vec<String>, Vec<String>) {
+        let storage: Box<dyn Storage> = project.into();
+        let project_id = project.id.to_string();
+        let mut purged = Vec::new();
+        let mut pending = Vec::new();
+
+        for egress in egresses {
+            let Some(destination) = egress.destination.clone() else {
+                continue;
+            };
+
+            let removed = match storage.delete(&destination).await {
+                Ok(()) => recording_crud::mark_purged(egress.id, &mut self.pool.get().unwrap()),
+                Err(e) => Err(e.into()),
+            };
+            self.audit.record(
+                actor,
+                AuditAction::RecordingPurged,
+                AuditTarget::in_project(
+                    &project_id,
+                    AuditTargetType::Recording,
+                    Some(destination.clone()),
+                ),
+                &removed,
+            );
+
+            match removed {
+                Ok(()) => purged.push(destination),
+                Err(e) => {
+                    log::warn!("Failed to purge recording {}: {}", destination, e);
+                    self.record_purge_failure(std::slice::from_ref(&egress));
+                    pending.push(destination);
+                }
+            }
+        }
+
+        (purged, pending)
+    }
+
+    fn record_purge_failure(&self, egresses: &[SessionEgress]) {
+        let egress_ids = egresses.iter().map(|egress| egress.id).collect::<Vec<_>>();
+        if let Err(e) =
+            recording_crud::record_purge_failure(&egress_ids, &mut self.pool.get().unwrap())
+        {
+            log::error!("Failed to record failed recording purges: {}", e);
+        }
+
+        for egress in egresses {
+            if egress.purge_failures + 1 >= recording_crud::MAX_PURGE_FAILURES {
+                log::error!(
+                    "Giving up purging recording {} of egress {} after {} failed attempts",
+                    egress.destination.as_deref().unwrap_or_default(),
+                    egress.egress_id,
+                    recording_crud::MAX_PURGE_FAILURES
+                );
+            }
+        }
+    }
+
+    fn session_uuid(&self, project_id: &str, session_id: &str) -> Result<Uuid, RecordingError> {
+        let session =
+            session_crud::get_session(project_id, session_id, &mut self.pool.get().unwrap())?;
+        Ok(session.id)
+    }
+
+    fn decrypted_project(&self, project_id: &str) -> Result<Project, RecordingError> {
+        let mut project =
+            project_crud::get_project_including_deleted(project_id, &mut self.pool.get().unwrap())?;
+        project
+            .decrypt(&self.encryption_key)
+            .map_err(ProjectError::from)?;
+        Ok(project)
+    }
+
+    fn consent_target(&self, project_id: &str, session_id: &str, identity: &str) -> AuditTarget {
+        AuditTarget::in_project(
+            project_id,
+            AuditTargetType::Consent,
+            Some(format!("{}/{}", session_id, identity)),
+        )
+    }
+}
+
+impl Clone for RecordingService {
+    fn clone(&self) -> Self {
+        Self {
+            encryption_key: self.encryption_key.clone(),
+            pool: self.pool.clone(),
+            audit: self.audit.clone(),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn request(mode: &str, media_retention_days: Option<i32>) -> RetentionPolicyRequest {
+        RetentionPolicyRequest {
+            mode: mode.to_string(),
+            media_retention_days,
+        }
+    }
+
+    #[test]
+    fn test_parse_retention_policy() {
+        assert_eq!(
+            parse_retention_policy(&request("delete_media", Some(30))).unwrap(),
+            (RetentionMode::DeleteMedia, Some(30))
+        );
+        assert_eq!(
+            parse_retention_policy(&request("metadata_only", None)).unwrap(),
+            (RetentionMode::MetadataOnly, None)
+        );
+        assert!(parse_retention_policy(&request("delete_media", None)).is_err());
+        assert!(parse_retention_policy(&request("delete_media", Some(0))).is_err());
+        assert!(parse_retention_policy(&request("keep_media", Some(30))).is_err());
+        assert!(parse_retention_policy(&request("forever", None)).is_err());
+    }
+}
@@ -0,0 +1,42 @@
+use std::sync::Arc;
+use std::time::Duration;
+
+use infrastructure::DbPool;
+use shared::deployment_config::DeploymentConfig;
+
+use super::recording_service::RecordingService;
+
+const DEFAULT_RETENTION_INTERVAL_SECONDS: u64 = 3600;
+
+/// Periodically removes recordings past their project's retention policy and
+/// those of participants who withdrew consent. Session and egress metadata is
+/// kept, the egresses are marked purged.
+pub struct RetentionWorker {
+    recording_service: RecordingService,
+    interval: Duration,
+}
+
+impl RetentionWorker {
+    pub fn new(config: &DeploymentConfig, pool: Arc<DbPool>) -> Self {
+        Self {
+            recording_service: RecordingService::new(&config.encryption_key, pool),
+            interval: Duration::from_secs(
+                config
+                    .retention_interval_seconds
+                    .unwrap_or(DEFAULT_RETENTION_INTERVAL_SECONDS),
+            ),
+        }
+    }
+
+    pub async fn run(self) {
+        loop {
+            match self.recording_service.purge_due_recordings().await {
+                Ok(0) => {}
+                Ok(purged) => log::info!("Purged {} recordings", purged),
+                Err(e) => log::error!("Failed to purge recordings: {}", e),
+            }
+
+            tokio::time::sleep(self.interval).await;
+        }
+    }
+}
@@ -1,16 +1,27 @@
 use crate::{
+    cache::TtlCache,
     project::{
         devices::device_crud::get_possible_routing_keys,
         project_crud::project_contains_device_group,
     },
-    users::account_service::AccountService,
+    users::{account_service::AccountService, tokens_manager::TokenInfo},
 };
 use infrastructure::DbPool;
+use sha2::{Digest, Sha256};
+use shared::api_key_scopes::ApiKeyScope;
 use shared::deployment_config::DeploymentConfig;
 use std::sync::Arc;
+use std::time::Duration;
+use thiserror::Error;
 
 use serde::{Deserialize, Serialize};
 
+/// Prefix RabbitMQ uses for server-named (exclusive, auto-delete) queues.
+const SERVER_NAMED_QUEUE_PREFIX: &str = "amq.gen-";
+
+const DEFAULT_AUTH_CACHE_TTL_SECONDS: u64 = 30;
+const DEFAULT_AUTH_CACHE_CAPACITY: usize = 10_000;
+
 #[derive(Serialize, Deserialize, Debug, Clone)]
 pub struct RMQAuthQuery {
     pub username: String,
@@ -43,10 +54,198 @@ pub struct RMQAuthTopicQuery {
     pub routing_key: String,
 }
 
+#[derive(Debug, Error, Clone, PartialEq, Eq)]
+pub enum RMQAuthDenial {
+    #[error("Invalid token: {0}")]
+    InvalidToken(String),
+
+    #[error("Token is not scoped to a project")]
+    NotAProjectToken,
+
+    #[error("API key lacks the {0} scope")]
+    ScopeNotGranted(String),
+
+    #[error("API key is not allowed from {0}")]
+    AddressNotAllowed(String),
+
+    #[error("Device group {0} is not registered in the project")]
+    UnknownDeviceGroup(String),
+
+    #[error("Access to vhost {0} is not permitted")]
+    VhostNotPermitted(String),
+
+    #[error("{permission} on {resource} {name} is not permitted")]
+    ResourceNotPermitted {
+        resource: String,
+        name: String,
+        permission: String,
+    },
+
+    #[error("Routing key {0} does not belong to a device group of the project")]
+    RoutingKeyNotPermitted(String),
+
+    #[error("Lookup failed: {0}")]
+    LookupError(String),
+}
+
+/// Devices only ever need one vhost: the one configured for SyncFlow.
+fn check_vhost(vhost: &str, allowed_vhost: &str) -> Result<(), RMQAuthDenial> {
+    if vhost == allowed_vhost {
+        Ok(())
+    } else {
+        Err(RMQAuthDenial::VhostNotPermitted(vhost.to_string()))
+    }
+}
+
+/// Broker credentials must be project tokens, user tokens are never accepted.
+/// Receiving session notifications requires the `sessions:read` scope.
+fn project_scope(token_info: &TokenInfo) -> Result<String, RMQAuthDenial> {
+    let project_id = token_info
+        .project_id
+        .clone()
+        .ok_or(RMQAuthDenial::NotAProjectToken)?;
+
+    match &token_info.api_key {
+        Some(grant) if !grant.allows_scope(ApiKeyScope::SessionsRead) => Err(
+            RMQAuthDenial::ScopeNotGranted(ApiKeyScope::SessionsRead.as_str().to_string()),
+        ),
+        _ => Ok(project_id),
+    }
+}
+
+/// Enforces the API key's IP allow-list on the address the broker reports
+/// for the connection.
+fn check_client_ip(token_info: &TokenInfo, ip: &str) -> Result<(), RMQAuthDenial> {
+    let Some(grant) = &token_info.api_key else {
+        return Ok(());
+    };
+
+    if grant.allowed_ips.is_empty() {
+        return Ok(());
+    }
+
+    match ip.parse() {
+        Ok(address) if grant.allows_ip(address) => Ok(()),
+        _ => Err(RMQAuthDenial::AddressNotAllowed(ip.to_string())),
+    }
+}
+
+/// The permission matrix for devices. A device may declare, bind and consume
+/// from server-named queues and may read from the session exchange, nothing else.
+fn check_resource_permission(
+    resource: &str,
+    name: &str,
+    permission: &str,
+    exchange_name: &str,
+) -> Result<(), RMQAuthDenial> {
+    let allowed = match (resource, permission) {
+        ("queue", "configure" | "write" | "read") => name.starts_with(SERVER_NAMED_QUEUE_PREFIX),
+        ("exchange", "read") => name == exchange_name,
+        _ => false,
+    };
+
+    if allowed {
+        Ok(())
+    } else {
+        Err(RMQAuthDenial::ResourceNotPermitted {
+            resource: resource.to_string(),
+            name: name.to_string(),
+            permission: permission.to_string(),
+        })
+    }
+}
+
+/// Topic checks only allow binding (read) the session exchange with the
+/// routing keys derived from the project's device groups.
+fn check_topic_permission(
+    topic_query: &RMQAuthTopicQuery,
+    exchange_name: &str,
+    routing_keys: &[String],
+) -> Result<(), RMQAuthDenial> {
+    check_resource_permission(
+        "exchange",
+        &topic_query.name,
+        &topic_query.permission,
+        exchange_name,
+    )
+    .and_then(|_| {
+        if topic_query.resource != "topic" {
+            return Err(RMQAuthDenial::ResourceNotPermitted {
+                resource: topic_query.resource.clone(),
+                name: topic_query.name.clone(),
+                permission: topic_query.permission.clone(),
+            });
+        }
+        if routing_keys.contains(&topic_query.routing_key) {
+            Ok(())
+        } else {
+            Err(RMQAuthDenial::RoutingKeyNotPermitted(
+                topic_query.routing_key.clone(),
+            ))
+        }
+    })
+}
+
+fn log_decision(check: &str, target: &str, decision: &Result<(), RMQAuthDenial>) -> bool {
+    match decision {
+        Ok(_) => true,
+        Err(reason) => {
+            log::warn!("RMQ {} check denied for {}: {}", check, target, reason);
+            false
+        }
+    }
+}
+
+/// Queries carry the device's token, so decisions are cached by digest and
+/// the cache holds no usable credentials.
+fn decision_cache_key(query: &str) -> String {
+    Sha256::digest(query.as_bytes())
+        .iter()
+        .map(|b| format!("{:02x}", b))
+        .collect()
+}
+
+/// Granted decisions, keyed by check and query and tagged with the project
+/// they were granted for so that they can be dropped when the project's
+/// devices or API keys change. Denials are never cached.
+struct AuthDecisionCache {
+    granted: TtlCache<String, String>,
+}
+
+impl AuthDecisionCache {
+    fn new(ttl: Duration, capacity: usize) -> Self {
+        Self {
+            granted: TtlCache::new(ttl, capacity),
+        }
+    }
+
+    fn invalidate_project(&self, project_id: &str) {
+        self.granted
+            .invalidate_where(|_, cached_project_id| cached_project_id == project_id);
+    }
+
+    /// Runs `check` unless the query was granted recently. `check` returns the
+    /// project the query is granted for.
+    fn decide<F>(&self, query: &str, check: F) -> Result<(), RMQAuthDenial>
+    where
+        F: FnOnce() -> Result<String, RMQAuthDenial>,
+    {
+        let cache_key = decision_cache_key(query);
+        if self.granted.get(&cache_key).is_some() {
+            return Ok(());
+        }
+
+        let project_id = check()?;
+        self.granted.insert(cache_key, project_id);
+        Ok(())
+    }
+}
+
 pub struct RMQAuthService {
     account_service: AccountService,
     deployment_config: DeploymentConfig,
     pool: Arc<DbPool>,
+    decision_cache: Arc<AuthDecisionCache>,
 }
 
 impl RMQAuthService {
@@ -55,107 +254,144 @@ impl RMQAuthService {
         deployment_config: DeploymentConfig,
         pool: Arc<DbPool>,
     ) -> Self {
+        let rabbitmq_config = &deployment_config.rabbitmq_config;
+        let ttl = Duration::from_secs(
+            rabbitmq_config
+                .auth_cache_ttl_seconds
+                .unwrap_or(DEFAULT_AUTH_CACHE_TTL_SECONDS),
+        );
+        let capacity = rabbitmq_config
+            .auth_cache_capacity
+            .unwrap_or(DEFAULT_AUTH_CACHE_CAPACITY);
+
         Self {
             account_service,
             deployment_config,
             pool,
+            decision_cache: Arc::new(AuthDecisionCache::new(ttl, capacity)),
         }
     }
 
+    /// Drops every cached decision granted for the project.
+    pub fn invalidate_project(&self, project_id: &str) {
+        self.decision_cache.invalidate_project(project_id);
+    }
+
+    fn cached_decision<F>(&self, query: String, check: F) -> Result<(), RMQAuthDenial>
+    where
+        F: FnOnce() -> Result<String, RMQAuthDenial>,
+    {
+        self.decision_cache.decide(&query, check)
+    }
+
+    /// Verifies a token of the broker. Keys of projects scheduled for
+    /// deletion are rejected by the lookup, so a decision dropped by
+    /// `invalidate_project` is not granted again.
+    fn verified_token(&self, token: &str) -> Result<TokenInfo, RMQAuthDenial> {
+        self.account_service
+            .verify_token(token)
+            .map_err(|e| RMQAuthDenial::InvalidToken(e.to_string()))
+    }
+
+    fn verified_project_id(&self, token: &str) -> Result<String, RMQAuthDenial> {
+        project_scope(&self.verified_token(token)?)
+    }
+
     pub fn authorize(&self, auth_query: &RMQAuthQuery) -> bool {
-        let token_info = self.account_service.verify_token(&auth_query.username);
-        token_info
-            .map(|token| {
-                let project_id = token.project_id;
-
-                if project_id.is_none() {
-                    false
-                } else {
-                    let project_id = project_id.unwrap();
-
-                    project_contains_device_group(
-                        &project_id,
-                        &auth_query.password,
-                        &mut self.pool.get().unwrap(),
-                    )
-                    .map(|_| true)
-                    .unwrap_or(false)
-                }
-            })
-            .unwrap_or(false)
+        let query = format!("user|{}|{}", auth_query.username, auth_query.password);
+        let decision = self.cached_decision(query, || {
+            let project_id = self.verified_project_id(&auth_query.username)?;
+            let devices = project_contains_device_group(
+                &project_id,
+                &auth_query.password,
+                &mut self.pool.get().unwrap(),
+            )
+            .map_err(|e| RMQAuthDenial::LookupError(e.to_string()))?;
+
+            if devices.is_empty() {
+                Err(RMQAuthDenial::UnknownDeviceGroup(
+                    auth_query.password.clone(),
+                ))
+            } else {
+                Ok(project_id)
+            }
+        });
+
+        log_decision("user", &auth_query.password, &decision)
     }
 
     pub fn authorize_vhost(&self, vhost_query: &RMQAuthVhostQuery) -> bool {
-        let token_info = self.account_service.verify_token(&vhost_query.username);
-        token_info
-            .map(|_| vhost_query.vhost == self.deployment_config.rabbitmq_config.vhost_name)
-            .unwrap_or(false)
+        let query = format!(
+            "vhost|{}|{}|{}",
+            vhost_query.username, vhost_query.vhost, vhost_query.ip
+        );
+        let decision = self.cached_decision(query, || {
+            check_vhost(
+                &vhost_query.vhost,
+                &self.deployment_config.rabbitmq_config.vhost_name,
+            )?;
+            let token_info = self.verified_token(&vhost_query.username)?;
+            check_client_ip(&token_info, &vhost_query.ip)?;
+            project_scope(&token_info)
+        });
+
+        log_decision("vhost", &vhost_query.vhost, &decision)
     }
 
     pub fn authorize_resource_path(&self, resource_path_query: &RMQAuthResourcePathQuery) -> bool {
-        if resource_path_query.vhost != self.deployment_config.rabbitmq_config.vhost_name {
-            return false;
-        }
+        let rabbitmq_config = &self.deployment_config.rabbitmq_config;
+        let query = format!(
+            "resource|{}|{}|{}|{}|{}",
+            resource_path_query.username,
+            resource_path_query.vhost,
+            resource_path_query.resource,
+            resource_path_query.name,
+            resource_path_query.permission
+        );
+        let decision = self.cached_decision(query, || {
+            check_vhost(&resource_path_query.vhost, &rabbitmq_config.vhost_name)?;
+            check_resource_permission(
+                &resource_path_query.resource,
+                &resource_path_query.name,
+                &resource_path_query.permission,
+                &rabbitmq_config.exchange_name,
+            )?;
+            self.verified_project_id(&resource_path_query.username)
+        });
 
-        let token_info = self
-            .account_service
-            .verify_token(&resource_path_query.username);
-        token_info
-            .map(|token| {
-                let project_id = token.project_id;
-                if project_id.is_none() {
-                    return false;
-                }
-                if resource_path_query.resource == "exchange"
-                    && resource_path_query.name
-                        == self.deployment_config.rabbitmq_config.exchange_name
-                    && resource_path_query.permission == "read"
-                {
-                    return true;
-                }
-
-                if resource_path_query.resource == "queue"
-                    && resource_path_query.name.starts_with("amq")
-                    && resource_path_query.permission == "write"
-                {
-                    return true;
-                }
-
-                true
-            })
-            .unwrap_or(false)
+        let target = format!(
+            "{} {} ({})",
+            resource_path_query.resource, resource_path_query.name, resource_path_query.permission
+        );
+        log_decision("resource", &target, &decision)
     }
 
     pub fn authorize_topic(&self, topic_query: &RMQAuthTopicQuery) -> bool {
-        if topic_query.vhost != self.deployment_config.rabbitmq_config.vhost_name {
-            return false;
-        }
-
-        let token_info = self.account_service.verify_token(&topic_query.username);
-
-        token_info
-            .map(|token| {
-                let project_id = token.project_id;
-                if project_id.is_none() {
-                    return false;
-                }
-                if topic_query.resource == "topic"
-                    && topic_query.name == self.deployment_config.rabbitmq_config.exchange_name
-                    && topic_query.permission == "read"
-                {
-                    let project_id = project_id.unwrap();
-                    let all_routing_keys_result =
-                        get_possible_routing_keys(&project_id, &mut self.pool.get().unwrap());
+        let rabbitmq_config = &self.deployment_config.rabbitmq_config;
+        let query = format!(
+            "topic|{}|{}|{}|{}|{}|{}",
+            topic_query.username,
+            topic_query.vhost,
+            topic_query.resource,
+            topic_query.name,
+            topic_query.permission,
+            topic_query.routing_key
+        );
+        let decision = self.cached_decision(query, || {
+            check_vhost(&topic_query.vhost, &rabbitmq_config.vhost_name)?;
+            let project_id = self.verified_project_id(&topic_query.username)?;
+            let routing_keys =
+                get_possible_routing_keys(&project_id, &mut self.pool.get().unwrap())
+                    .map_err(|e| RMQAuthDenial::LookupError(e.to_string()))?;
+            check_topic_permission(topic_query, &rabbitmq_config.exchange_name, &routing_keys)?;
+            Ok(project_id)
+        });
 
-                    let access = all_routing_keys_result
-                        .map(|routing_keys| routing_keys.contains(&topic_query.routing_key))
-                        .unwrap_or(false);
-
-                    return access;
-                }
-                true
-            })
-            .unwrap_or(false)
+        let target = format!(
+            "{} {} ({}, {})",
+            topic_query.resource, topic_query.name, topic_query.permission, topic_query.routing_key
+        );
+        log_decision("topic", &target, &decision)
     }
 }
 
@@ -165,6 +401,210 @@ impl Clone for RMQAuthService {
             account_service: self.account_service.clone(),
             deployment_config: self.deployment_config.clone(),
             pool: self.pool.clone(),
+            decision_cache: self.decision_cache.clone(),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::users::tokens_manager::ApiKeyGrant;
+
+    const EXCHANGE: &str = "syncflow-sessions";
+    const VHOST: &str = "syncflow";
+
+    #[test]
+    fn test_user_check_requires_project_token() {
+        let cases = [
+            (None, Err(RMQAuthDenial::NotAProjectToken)),
+            (Some("project-1"), Ok("project-1".to_string())),
+        ];
+
+        for (project_id, expected) in cases {
+            let token_info = TokenInfo {
+                user_id: 1,
+                user_name: "device".to_string(),
+                login_session: None,
+                project_id: project_id.map(|p| p.to_string()),
+                api_key: None,
+            };
+            assert_eq!(project_scope(&token_info), expected, "{:?}", project_id);
+        }
+    }
+
+    fn api_key_token(scopes: Vec<ApiKeyScope>, allowed_ips: &[&str]) -> TokenInfo {
+        TokenInfo {
+            user_id: 1,
+            user_name: "device".to_string(),
+            login_session: None,
+            project_id: Some("project-1".to_string()),
+            api_key: Some(ApiKeyGrant {
+                key_id: 1,
+                scopes,
+                allowed_ips: allowed_ips.iter().map(|ip| ip.to_string()).collect(),
+            }),
+        }
+    }
+
+    #[test]
+    fn test_user_check_requires_sessions_read_scope() {
+        let cases = [
+            (vec![ApiKeyScope::SessionsRead], true),
+            (vec![ApiKeyScope::ProjectAdmin], true),
+            (vec![ApiKeyScope::DevicesRegister], false),
+            (
+                vec![ApiKeyScope::MediaDownload, ApiKeyScope::TokensMint],
+                false,
+            ),
+        ];
+
+        for (scopes, allowed) in cases {
+            let token_info = api_key_token(scopes.clone(), &[]);
+            assert_eq!(project_scope(&token_info).is_ok(), allowed, "{:?}", scopes);
+        }
+    }
+
+    #[test]
+    fn test_client_ip_check() {
+        let cases = [
+            (vec![], "198.51.100.7", true),
+            (vec!["10.0.0.0/8"], "10.1.2.3", true),
+            (vec!["10.0.0.0/8"], "192.168.1.1", false),
+            (vec!["10.0.0.0/8", "192.168.1.0/24"], "192.168.1.1", true),
+            (vec!["10.0.0.0/8"], "not-an-address", false),
+        ];
+
+        for (allowed_ips, ip, allowed) in cases {
+            let token_info = api_key_token(vec![ApiKeyScope::SessionsRead], &allowed_ips);
+            assert_eq!(
+                check_client_ip(&token_info, ip).is_ok(),
+                allowed,
+                "{:?} {}",
+                allowed_ips,
+                ip
+            );
+        }
+    }
+
+    #[test]
+    fn test_vhost_check() {
+        let cases = [
+            (VHOST, true),
+            ("/", false),
+            ("syncflow-other", false),
+            ("", false),
+        ];
+
+        for (vhost, allowed) in cases {
+            assert_eq!(check_vhost(vhost, VHOST).is_ok(), allowed, "{}", vhost);
+        }
+    }
+
+    #[test]
+    fn test_resource_check() {
+        let cases = [
+            ("queue", "amq.gen-JzTY20BRgKO", "configure", true),
+            ("queue", "amq.gen-JzTY20BRgKO", "write", true),
+            ("queue", "amq.gen-JzTY20BRgKO", "read", true),
+            ("queue", "amq.direct", "configure", false),
+            ("queue", "syncflow-server-queue", "read", false),
+            ("queue", "syncflow-server-queue", "write", false),
+            ("queue", "syncflow-server-queue", "configure", false),
+            ("exchange", EXCHANGE, "read", true),
+            ("exchange", EXCHANGE, "write", false),
+            ("exchange", EXCHANGE, "configure", false),
+            ("exchange", "amq.topic", "read", false),
+            ("exchange", "amq.gen-JzTY20BRgKO", "read", false),
+            ("topic", EXCHANGE, "read", false),
+            ("queue", "amq.gen-JzTY20BRgKO", "unknown", false),
+        ];
+
+        for (resource, name, permission, allowed) in cases {
+            assert_eq!(
+                check_resource_permission(resource, name, permission, EXCHANGE).is_ok(),
+                allowed,
+                "{} {} {}",
+                resource,
+                name,
+                permission
+            );
+        }
+    }
+
+    #[test]
+    fn test_granted_decision_is_cached_until_the_project_is_invalidated() {
+        let cache = AuthDecisionCache::new(Duration::from_secs(60), 10);
+        let checks = std::cell::Cell::new(0);
+        let grant = || {
+            checks.set(checks.get() + 1);
+            Ok("project-1".to_string())
+        };
+
+        assert!(cache.decide("user|token|group-a", grant).is_ok());
+        assert!(cache.decide("user|token|group-a", grant).is_ok());
+        assert_eq!(checks.get(), 1, "the grant is served from the cache");
+
+        // Deleting an API key or device group invalidates its project only
+        cache.invalidate_project("project-2");
+        assert!(cache.decide("user|token|group-a", grant).is_ok());
+        assert_eq!(checks.get(), 1);
+
+        cache.invalidate_project("project-1");
+        assert!(cache.decide("user|token|group-a", grant).is_ok());
+        assert_eq!(checks.get(), 2, "the grant is checked again");
+    }
+
+    #[test]
+    fn test_denials_are_never_cached() {
+        let cache = AuthDecisionCache::new(Duration::from_secs(60), 10);
+        let checks = std::cell::Cell::new(0);
+        let deny = || {
+            checks.set(checks.get() + 1);
+            Err(RMQAuthDenial::NotAProjectToken)
+        };
+
+        assert!(cache.decide("user|token|group-a", deny).is_err());
+        assert!(cache.decide("user|token|group-a", deny).is_err());
+        assert_eq!(checks.get(), 2);
+    }
+
+    #[test]
+    fn test_decision_cache_key_holds_no_token() {
+        let key = decision_cache_key("user|secret-token|group-a");
+        assert_eq!(key.len(), 64);
+        assert!(!key.contains("secret-token"));
+        assert_eq!(key, decision_cache_key("user|secret-token|group-a"));
+    }
+
+    #[test]
+    fn test_topic_check() {
+        let routing_keys = vec!["project-1.group-a".to_string()];
+        let cases = [
+            ("topic", EXCHANGE, "read", "project-1.group-a", true),
+            ("topic", EXCHANGE, "read", "project-1.group-b", false),
+            ("topic", EXCHANGE, "read", "project-2.group-a", false),
+            ("topic", EXCHANGE, "read", "#", false),
+            ("topic", EXCHANGE, "write", "project-1.group-a", false),
+            ("topic", "amq.topic", "read", "project-1.group-a", false),
+            ("exchange", EXCHANGE, "read", "project-1.group-a", false),
+        ];
+
+        for (resource, name, permission, routing_key, allowed) in cases {
+            let query = RMQAuthTopicQuery {
+                username: "token".to_string(),
+                vhost: VHOST.to_string(),
+                resource: resource.to_string(),
+                name: name.to_string(),
+                permission: permission.to_string(),
+                routing_key: routing_key.to_string(),
+            };
+            assert_eq!(
+                check_topic_permission(&query, EXCHANGE, &routing_keys).is_ok(),
+                allowed,
+                "{:?}",
+                query
+            );
         }
     }
 }
@@ -1,2 +1,4 @@
 pub mod auth;
+pub mod outbox;
+pub mod outbox_crud;
 pub mod session_notifier;
@@ -0,0 +1,155 @@
+use std::sync::Arc;
+use std::time::Duration;
+
+use infrastructure::DbPool;
+use shared::deployment_config::DeploymentConfig;
+use thiserror::Error;
+
+use super::outbox_crud;
+use crate::notifier::DeviceNotifier;
+use crate::project::devices::device_service::DeviceService;
+
+const DEFAULT_POLL_INTERVAL_SECONDS: u64 = 5;
+const BATCH_SIZE: i64 = 100;
+const MAX_RETRY_DELAY_SECONDS: i64 = 300;
+const DELIVERED_RETENTION_HOURS: i64 = 24;
+/// How long a claimed batch is hidden from other workers, longer than a
+/// batch takes to deliver.
+const CLAIM_LEASE_SECONDS: i64 = 120;
+
+#[derive(Debug, Error)]
+enum OutboxError {
+    #[error("Database Error: {0}")]
+    DatabaseError(#[from] diesel::result::Error),
+
+    #[error("Connection Pool Error: {0}")]
+    PoolError(#[from] diesel::r2d2::PoolError),
+}
+
+/// Delay before retrying a message that has failed `attempts` times.
+fn retry_delay(attempts: i32) -> chrono::Duration {
+    let exponent = attempts.clamp(1, 16) as u32 - 1;
+    chrono::Duration::seconds((1i64 << exponent).min(MAX_RETRY_DELAY_SECONDS))
+}
+
+/// Delivers session notifications queued in the `notification_outbox` table.
+///
+/// Messages are delivered at least once: a message is only marked delivered
+/// after the broker confirms it. Whenever the notifier establishes a new
+/// connection, the queue bindings are resynced from the registered devices.
+pub struct OutboxWorker {
+    pool: Arc<DbPool>,
+    notifier: DeviceNotifier,
+    device_service: DeviceService,
+    poll_interval: Duration,
+}
+
+impl OutboxWorker {
+    pub fn new(config: &DeploymentConfig, pool: Arc<DbPool>, notifier: DeviceNotifier) -> Self {
+        let poll_interval = Duration::from_secs(
+            config
+                .rabbitmq_config
+                .outbox_poll_interval_seconds
+                .unwrap_or(DEFAULT_POLL_INTERVAL_SECONDS),
+        );
+
+        Self {
+            device_service: DeviceService::new(config, pool.clone()),
+            pool,
+            notifier,
+            poll_interval,
+        }
+    }
+
+    pub async fn run(self) {
+        let signal = self.notifier.outbox_signal();
+        let mut synced_generation = 0;
+
+        loop {
+            if let Ok(generation) = self.notifier.ensure_connected().await {
+                if generation != synced_generation {
+                    match self.device_service.sync_routing_keys(&self.notifier).await {
+                        Ok(routing_keys) => {
+                            log::info!("Bound {} device routing keys", routing_keys.len());
+                            synced_generation = generation;
+                        }
+                        Err(e) => log::error!("Failed to sync device routing keys: {}", e),
+                    }
+                }
+
+                match self.deliver_due().await {
+                    Ok(0) => {}
+                    Ok(delivered) => log::debug!("Delivered {} outbox notifications", delivered),
+                    Err(e) => log::error!("Failed to read the notification outbox: {}", e),
+                }
+            }
+
+            tokio::select! {
+                _ = signal.notified() => {}
+                _ = tokio::time::sleep(self.poll_interval) => {}
+            }
+        }
+    }
+
+    /// Publishes every due message, stopping at the first failure so an
+    /// unreachable broker does not burn through the retry budget of the batch.
+    async fn deliver_due(&self) -> Result<usize, OutboxError> {
+        let lease_until =
+            chrono::Utc::now().naive_utc() + chrono::Duration::seconds(CLAIM_LEASE_SECONDS);
+        let messages =
+            outbox_crud::claim_due_messages(BATCH_SIZE, lease_until, &mut self.pool.get()?)?;
+        let mut delivered = 0;
+        let mut pending = messages.into_iter();
+
+        while let Some(message) = pending.next() {
+            match self
+                .notifier
+                .publish(&message.routing_key, message.payload)
+                .await
+            {
+                Ok(()) => {
+                    outbox_crud::mark_delivered(message.id, &mut self.pool.get()?)?;
+                    delivered += 1;
+                }
+                Err(e) => {
+                    let attempts = message.attempts + 1;
+                    let retry_at = chrono::Utc::now().naive_utc() + retry_delay(attempts);
+                    log::warn!(
+                        "Failed to deliver outbox notification {} (attempt {}): {}",
+                        message.id,
+                        attempts,
+                        e
+                    );
+                    let conn = &mut self.pool.get()?;
+                    outbox_crud::mark_failed(message.id, &e.to_string(), retry_at, conn)?;
+                    outbox_crud::release_messages(
+                        &pending.map(|message| message.id).collect::<Vec<_>>(),
+                        conn,
+                    )?;
+                    break;
+                }
+            }
+        }
+
+        let cutoff =
+            chrono::Utc::now().naive_utc() - chrono::Duration::hours(DELIVERED_RETENTION_HOURS);
+        outbox_crud::delete_delivered_before(cutoff, &mut self.pool.get()?)?;
+
+        Ok(delivered)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_retry_delay() {
+        assert_eq!(retry_delay(1), chrono::Duration::seconds(1));
+        assert_eq!(retry_delay(3), chrono::Duration::seconds(4));
+        assert_eq!(
+            retry_delay(20),
+            chrono::Duration::seconds(MAX_RETRY_DELAY_SECONDS)
+        );
+    }
+}
@@ -0,0 +1,108 @@
+use chrono::NaiveDateTime;
+use diesel::{prelude::*, PgConnection};
+use domain::models::{NewOutboxMessage, OutboxMessage};
+use uuid::Uuid;
+
+pub fn enqueue(
+    messages: &[NewOutboxMessage],
+    conn: &mut PgConnection,
+) -> Result<usize, diesel::result::Error> {
+    use domain::schema::syncflow::notification_outbox::dsl::*;
+
+    diesel::insert_into(notification_outbox)
+        .values(messages)
+        .execute(conn)
+}
+
+/// Claims undelivered messages whose next attempt is due, oldest first.
+/// Claimed messages are leased until `lease_until` by moving their next
+/// attempt, so that the workers of other replicas skip them, and pick them up
+/// again should this worker die before delivering them.
+pub fn claim_due_messages(
+    limit: i64,
+    lease_until: NaiveDateTime,
+    conn: &mut PgConnection,
+) -> Result<Vec<OutboxMessage>, diesel::result::Error> {
+    use domain::schema::syncflow::notification_outbox::dsl::*;
+
+    conn.transaction(|conn| {
+        let now = chrono::Utc::now().naive_utc();
+
+        let messages = notification_outbox
+            .filter(delivered_at.is_null().and(next_attempt_at.le(now)))
+            .order(created_at.asc())
+            .limit(limit)
+            .for_update()
+            .skip_locked()
+            .load::<OutboxMessage>(conn)?;
+
+        let message_ids = messages
+            .iter()
+            .map(|message| message.id)
+            .collect::<Vec<_>>();
+        diesel::update(notification_outbox.filter(id.eq_any(&message_ids)))
+            .set(next_attempt_at.eq(lease_until))
+            .execute(conn)?;
+
+        Ok(messages)
+    })
+}
+
+/// Ends the lease of claimed messages that were not attempted.
+pub fn release_messages(
+    message_ids: &[Uuid],
+    conn: &mut PgConnection,
+) -> Result<(), diesel::result::Error> {
+    use domain::schema::syncflow::notification_outbox::dsl::*;
+
+    diesel::update(notification_outbox.filter(id.eq_any(message_ids)))
+        .set(next_attempt_at.eq(chrono::Utc::now().naive_utc()))
+        .execute(conn)?;
+
+    Ok(())
+}
+
+pub fn mark_delivered(
+    message_id: Uuid,
+    conn: &mut PgConnection,
+) -> Result<(), diesel::result::Error> {
+    use domain::schema::syncflow::notification_outbox::dsl::*;
+
+    diesel::update(notification_outbox.filter(id.eq(message_id)))
+        .set((
+            delivered_at.eq(chrono::Utc::now().naive_utc()),
+            attempts.eq(attempts + 1),
+            last_error.eq(None::<String>),
+        ))
+        .execute(conn)?;
+
+    Ok(())
+}
+
+pub fn mark_failed(
+    message_id: Uuid,
+    error: &str,
+    retry_at: NaiveDateTime,
+    conn: &mut PgConnection,
+) -> Result<(), diesel::result::Error> {
+    use domain::schema::syncflow::notification_outbox::dsl::*;
+
+    diesel::update(notification_outbox.filter(id.eq(message_id)))
+        .set((
+            attempts.eq(attempts + 1),
+            last_error.eq(Some(error.to_string())),
+            next_attempt_at.eq(retry_at),
+        ))
+        .execute(conn)?;
+
+    Ok(())
+}
+
+pub fn delete_delivered_before(
+    cutoff: NaiveDateTime,
+    conn: &mut PgConnection,
+) -> Result<usize, diesel::result::Error> {
+    use domain::schema::syncflow::notification_outbox::dsl::*;
+
+    diesel::delete(notification_outbox.filter(delivered_at.lt(cutoff))).execute(conn)
+}
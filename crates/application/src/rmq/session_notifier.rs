@@ -1,15 +1,28 @@
-use amqprs::BasicProperties;
+use std::collections::{BTreeMap, BTreeSet, HashSet};
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant};
+
+use amqprs::callbacks::ChannelCallback;
+use amqprs::{Ack, BasicProperties, Cancel, CloseChannel, Nack, Return};
+use async_trait::async_trait;
+use serde::Deserialize;
 use shared::deployment_config::RabbitMQConfig;
 
 use amqprs::channel::{
-    BasicPublishArguments, Channel, ExchangeDeclareArguments, QueueBindArguments,
-    QueueDeclareArguments,
+    BasicPublishArguments, Channel, ConfirmSelectArguments, ExchangeDeclareArguments,
+    QueueBindArguments, QueueDeclareArguments, QueueUnbindArguments,
 };
 use amqprs::connection::{Connection, OpenConnectionArguments};
 use thiserror::Error;
+use tokio::sync::oneshot;
+
+use crate::notifier::{Notifier, NotifierError};
 
 use amqprs::tls::TlsAdaptor;
 
+const DEFAULT_CONFIRM_TIMEOUT_SECONDS: u64 = 10;
+const DEFAULT_RECONNECT_MAX_BACKOFF_SECONDS: u64 = 30;
+
 #[derive(Debug, Error)]
 pub enum SessionNotifierError {
     #[error("Failed to connect to RabbitMQ: {0}")]
@@ -17,16 +30,238 @@ pub enum SessionNotifierError {
 
     #[error("Failed to declare queue: {0}")]
     QueueDeclareError(String),
+
+    #[error("RabbitMQ management API error: {0}")]
+    ManagementApiError(String),
+
+    #[error("RabbitMQ is unavailable, next connection attempt in {0:?}")]
+    Unavailable(Duration),
+
+    #[error("Publish was not confirmed by RabbitMQ: {0}")]
+    PublishNotConfirmed(String),
 }
 
-pub struct SessionNotifier {
-    rabbitmq_config: RabbitMQConfig,
+#[derive(Deserialize, Debug)]
+struct QueueBinding {
+    source: String,
+    routing_key: String,
+}
+
+/// Publishes awaiting a broker confirm, keyed by delivery tag.
+struct PendingConfirms {
+    next_delivery_tag: u64,
+    waiting: BTreeMap<u64, oneshot::Sender<bool>>,
+}
+
+impl PendingConfirms {
+    fn new() -> Self {
+        // Delivery tags of a channel in confirm mode start at 1
+        Self {
+            next_delivery_tag: 1,
+            waiting: BTreeMap::new(),
+        }
+    }
+
+    fn register(&mut self) -> (u64, oneshot::Receiver<bool>) {
+        let delivery_tag = self.next_delivery_tag;
+        self.next_delivery_tag += 1;
+        let (sender, receiver) = oneshot::channel();
+        self.waiting.insert(delivery_tag, sender);
+        (delivery_tag, receiver)
+    }
+
+    fn resolve(&mut self, delivery_tag: u64, multiple: bool, acked: bool) {
+        let resolved = if multiple {
+            let remaining = self.waiting.split_off(&(delivery_tag + 1));
+            std::mem::replace(&mut self.waiting, remaining)
+        } else {
+            self.waiting
+                .remove(&delivery_tag)
+                .map(|sender| BTreeMap::from([(delivery_tag, sender)]))
+                .unwrap_or_default()
+        };
+
+        for sender in resolved.into_values() {
+            let _ = sender.send(acked);
+        }
+    }
+}
+
+/// Forwards publisher confirms of a channel to the waiting publishers.
+struct ConfirmCallback {
+    confirms: Arc<Mutex<PendingConfirms>>,
+}
+
+#[async_trait]
+impl ChannelCallback for ConfirmCallback {
+    async fn close(
+        &mut self,
+        _channel: &Channel,
+        close: CloseChannel,
+    ) -> Result<(), amqprs::error::Error> {
+        log::warn!("RabbitMQ closed the notifier channel: {}", close);
+        Ok(())
+    }
+
+    async fn cancel(
+        &mut self,
+        _channel: &Channel,
+        _cancel: Cancel,
+    ) -> Result<(), amqprs::error::Error> {
+        Ok(())
+    }
+
+    async fn flow(
+        &mut self,
+        _channel: &Channel,
+        active: bool,
+    ) -> Result<bool, amqprs::error::Error> {
+        Ok(active)
+    }
+
+    async fn publish_ack(&mut self, _channel: &Channel, ack: Ack) {
+        self.confirms
+            .lock()
+            .unwrap()
+            .resolve(ack.delivery_tag(), ack.mutiple(), true);
+    }
+
+    async fn publish_nack(&mut self, _channel: &Channel, nack: Nack) {
+        self.confirms
+            .lock()
+            .unwrap()
+            .resolve(nack.delivery_tag(), nack.multiple(), false);
+    }
+
+    async fn publish_return(
+        &mut self,
+        _channel: &Channel,
+        _ret: Return,
+        _basic_properties: BasicProperties,
+        _content: Vec<u8>,
+    ) {
+    }
+}
+
+struct AmqpChannel {
     connection: Connection,
     channel: Channel,
+    confirms: Arc<Mutex<PendingConfirms>>,
+}
+
+impl AmqpChannel {
+    fn is_open(&self) -> bool {
+        self.connection.is_open() && self.channel.is_open()
+    }
+}
+
+struct ConnectionState {
+    amqp: Option<AmqpChannel>,
+    failed_attempts: u32,
+    retry_at: Option<Instant>,
+    generation: u64,
+}
+
+/// Delay before the next connection attempt after `failed_attempts`
+/// consecutive failures: doubles from one second up to `max`.
+/// The bound routing keys that are not expected anymore, once each.
+fn stale_routing_keys(bound_keys: Vec<String>, routing_keys: &HashSet<String>) -> BTreeSet<String> {
+    bound_keys
+        .into_iter()
+        .filter(|key| !routing_keys.contains(key))
+        .collect()
+}
+
+fn reconnect_backoff(failed_attempts: u32, max: Duration) -> Duration {
+    let exponent = failed_attempts.saturating_sub(1).min(16);
+    Duration::from_secs(1u64 << exponent).min(max)
+}
+
+/// Publishes session notifications to the session exchange.
+///
+/// The connection is opened lazily and re-established with exponential
+/// backoff whenever it is found closed. Clones share the same connection.
+pub struct SessionNotifier {
+    rabbitmq_config: RabbitMQConfig,
+    state: Arc<tokio::sync::Mutex<ConnectionState>>,
 }
 
 impl SessionNotifier {
+    pub fn new(rabbitmq_config: RabbitMQConfig) -> Self {
+        Self {
+            rabbitmq_config,
+            state: Arc::new(tokio::sync::Mutex::new(ConnectionState {
+                amqp: None,
+                failed_attempts: 0,
+                retry_at: None,
+                generation: 0,
+            })),
+        }
+    }
+
     pub async fn create(rabbitmq_config: RabbitMQConfig) -> Result<Self, SessionNotifierError> {
+        let notifier = Self::new(rabbitmq_config);
+        notifier.ensure_connected().await?;
+        Ok(notifier)
+    }
+
+    /// Connects if there is no open connection and returns the connection
+    /// generation, which increases every time a new connection is established.
+    pub async fn ensure_connected(&self) -> Result<u64, SessionNotifierError> {
+        let mut state = self.state.lock().await;
+        self.open_channel(&mut state).await?;
+        Ok(state.generation)
+    }
+
+    async fn open_channel<'a>(
+        &self,
+        state: &'a mut ConnectionState,
+    ) -> Result<&'a AmqpChannel, SessionNotifierError> {
+        if state.amqp.as_ref().is_some_and(AmqpChannel::is_open) {
+            return Ok(state.amqp.as_ref().unwrap());
+        }
+
+        if state.amqp.take().is_some() {
+            log::warn!("Lost connection to RabbitMQ, reconnecting");
+        }
+
+        if let Some(retry_at) = state.retry_at {
+            let now = Instant::now();
+            if now < retry_at {
+                return Err(SessionNotifierError::Unavailable(retry_at - now));
+            }
+        }
+
+        match self.connect().await {
+            Ok(amqp) => {
+                state.failed_attempts = 0;
+                state.retry_at = None;
+                state.generation += 1;
+                log::info!("Connected to RabbitMQ at {}", self.rabbitmq_config.host);
+                Ok(state.amqp.insert(amqp))
+            }
+            Err(e) => {
+                state.failed_attempts += 1;
+                let max_backoff = Duration::from_secs(
+                    self.rabbitmq_config
+                        .reconnect_max_backoff_seconds
+                        .unwrap_or(DEFAULT_RECONNECT_MAX_BACKOFF_SECONDS),
+                );
+                let delay = reconnect_backoff(state.failed_attempts, max_backoff);
+                state.retry_at = Some(Instant::now() + delay);
+                log::warn!(
+                    "Failed to connect to RabbitMQ (attempt {}), retrying in {:?}: {}",
+                    state.failed_attempts,
+                    delay,
+                    e
+                );
+                Err(e)
+            }
+        }
+    }
+
+    async fn connect(&self) -> Result<AmqpChannel, SessionNotifierError> {
+        let rabbitmq_config = &self.rabbitmq_config;
         let args = if rabbitmq_config.use_ssl {
             let domain = rabbitmq_config.host.clone();
             OpenConnectionArguments::new(
@@ -52,18 +287,55 @@ impl SessionNotifier {
         let connection = Connection::open(&args).await?;
         let channel = connection.open_channel(None).await?;
 
-        Ok(Self {
-            rabbitmq_config,
+        let confirms = Arc::new(Mutex::new(PendingConfirms::new()));
+        channel
+            .register_callback(ConfirmCallback {
+                confirms: confirms.clone(),
+            })
+            .await?;
+        channel
+            .confirm_select(ConfirmSelectArguments::default())
+            .await?;
+
+        self.declare_topology(&channel).await?;
+
+        Ok(AmqpChannel {
             connection,
             channel,
+            confirms,
         })
     }
 
+    async fn declare_topology(&self, channel: &Channel) -> Result<String, SessionNotifierError> {
+        let exchange_name = &self.rabbitmq_config.exchange_name;
+        let queue_name = &self.rabbitmq_config.queue_name;
+
+        let queue_declare_result = channel
+            .queue_declare(QueueDeclareArguments::durable_client_named(queue_name))
+            .await?;
+        let queue_details = queue_declare_result.ok_or(SessionNotifierError::QueueDeclareError(
+            format!("Failed to declare queue: {}", queue_name),
+        ))?;
+
+        channel
+            .exchange_declare(ExchangeDeclareArguments::new(exchange_name, "topic"))
+            .await?;
+
+        Ok(queue_details.0)
+    }
+
+    async fn channel(&self) -> Result<Channel, SessionNotifierError> {
+        let mut state = self.state.lock().await;
+        let amqp = self.open_channel(&mut state).await?;
+        Ok(amqp.channel.clone())
+    }
+
     pub async fn bind_routing_key(&self, routing_key: &str) -> Result<(), SessionNotifierError> {
         let exchange_name = &self.rabbitmq_config.exchange_name;
         let queue_name = &self.rabbitmq_config.queue_name;
 
-        self.channel
+        self.channel()
+            .await?
             .queue_bind(QueueBindArguments::new(
                 queue_name,
                 exchange_name,
@@ -74,45 +346,161 @@ impl SessionNotifier {
         Ok(())
     }
 
+    pub async fn unbind_routing_key(&self, routing_key: &str) -> Result<(), SessionNotifierError> {
+        let exchange_name = &self.rabbitmq_config.exchange_name;
+        let queue_name = &self.rabbitmq_config.queue_name;
+
+        self.channel()
+            .await?
+            .queue_unbind(QueueUnbindArguments::new(
+                queue_name,
+                exchange_name,
+                routing_key,
+            ))
+            .await?;
+
+        Ok(())
+    }
+
+    /// Lists the routing keys currently binding the server queue to the session
+    /// exchange. AMQP cannot enumerate bindings, so this goes through the
+    /// management API and returns `None` when no management port is configured.
+    pub async fn bound_routing_keys(&self) -> Result<Option<Vec<String>>, SessionNotifierError> {
+        let management_port = match self.rabbitmq_config.management_port {
+            Some(port) => port,
+            None => return Ok(None),
+        };
+
+        let scheme = if self.rabbitmq_config.use_ssl {
+            "https"
+        } else {
+            "http"
+        };
+        let mut url = reqwest::Url::parse(&format!(
+            "{}://{}:{}/",
+            scheme, self.rabbitmq_config.host, management_port
+        ))
+        .map_err(|e| SessionNotifierError::ManagementApiError(e.to_string()))?;
+        url.path_segments_mut()
+            .map_err(|_| {
+                SessionNotifierError::ManagementApiError("Invalid management URL".to_string())
+            })?
+            .clear()
+            .extend([
+                "api",
+                "queues",
+                self.rabbitmq_config.vhost_name.as_str(),
+                self.rabbitmq_config.queue_name.as_str(),
+                "bindings",
+            ]);
+
+        let bindings = reqwest::Client::new()
+            .get(url)
+            .basic_auth(
+                &self.rabbitmq_config.root_username,
+                Some(&self.rabbitmq_config.root_password),
+            )
+            .send()
+            .await
+            .and_then(|response| response.error_for_status())
+            .map_err(|e| SessionNotifierError::ManagementApiError(e.to_string()))?
+            .json::<Vec<QueueBinding>>()
+            .await
+            .map_err(|e| SessionNotifierError::ManagementApiError(e.to_string()))?;
+
+        Ok(Some(
+            bindings
+                .into_iter()
+                .filter(|binding| binding.source == self.rabbitmq_config.exchange_name)
+                .map(|binding| binding.routing_key)
+                .collect(),
+        ))
+    }
+
+    /// Makes the server queue bindings match `routing_keys` exactly: missing keys
+    /// are bound and, when the existing bindings can be listed, stale keys are
+    /// unbound.
+    pub async fn sync_routing_keys(
+        &self,
+        routing_keys: &HashSet<String>,
+    ) -> Result<(), SessionNotifierError> {
+        for routing_key in routing_keys {
+            self.bind_routing_key(routing_key).await?;
+        }
+
+        match self.bound_routing_keys().await? {
+            Some(bound_keys) => {
+                for stale_key in stale_routing_keys(bound_keys, routing_keys) {
+                    log::info!("Unbinding stale routing key {}", stale_key);
+                    self.unbind_routing_key(&stale_key).await?;
+                }
+            }
+            None => {
+                log::warn!(
+                    "RabbitMQ management port not configured, stale routing keys were not unbound"
+                );
+            }
+        }
+
+        Ok(())
+    }
+
+    /// Publishes a persistent message and waits for the broker to confirm it.
     pub async fn publish(
         &self,
         routing_key: &str,
         message: Vec<u8>,
     ) -> Result<(), SessionNotifierError> {
         let exchange_name = &self.rabbitmq_config.exchange_name;
-
         let args = BasicPublishArguments::new(exchange_name, routing_key);
+        let mut properties = BasicProperties::default();
+        properties.with_persistence(true);
 
-        self.channel
-            .basic_publish(BasicProperties::default(), message, args)
-            .await?;
+        // Holding the state lock keeps delivery tags in publish order
+        let confirmation = {
+            let mut state = self.state.lock().await;
+            let amqp = self.open_channel(&mut state).await?;
+            let (delivery_tag, confirmation) = amqp.confirms.lock().unwrap().register();
+            if let Err(e) = amqp.channel.basic_publish(properties, message, args).await {
+                amqp.confirms.lock().unwrap().waiting.remove(&delivery_tag);
+                return Err(e.into());
+            }
+            confirmation
+        };
 
-        Ok(())
+        let confirm_timeout = Duration::from_secs(
+            self.rabbitmq_config
+                .confirm_timeout_seconds
+                .unwrap_or(DEFAULT_CONFIRM_TIMEOUT_SECONDS),
+        );
+
+        match tokio::time::timeout(confirm_timeout, confirmation).await {
+            Ok(Ok(true)) => Ok(()),
+            Ok(Ok(false)) => Err(SessionNotifierError::PublishNotConfirmed(
+                "message was nacked".to_string(),
+            )),
+            Ok(Err(_)) => Err(SessionNotifierError::PublishNotConfirmed(
+                "channel closed before the confirm arrived".to_string(),
+            )),
+            Err(_) => Err(SessionNotifierError::PublishNotConfirmed(format!(
+                "no confirm within {:?}",
+                confirm_timeout
+            ))),
+        }
     }
 
     pub async fn close(self) -> Result<(), SessionNotifierError> {
-        self.channel.clone().close().await?;
-        self.connection.clone().close().await?;
+        let amqp = self.state.lock().await.amqp.take();
+        if let Some(amqp) = amqp {
+            amqp.channel.close().await?;
+            amqp.connection.close().await?;
+        }
         Ok(())
     }
 
     pub async fn initialize(&self) -> Result<String, SessionNotifierError> {
-        let exchange_name = &self.rabbitmq_config.exchange_name;
-        let queue_name = &self.rabbitmq_config.queue_name;
-
-        let queue_declare_result = self
-            .channel
-            .queue_declare(QueueDeclareArguments::durable_client_named(queue_name))
-            .await?;
-        let queue_details = queue_declare_result.ok_or(SessionNotifierError::QueueDeclareError(
-            format!("Failed to declare queue: {}", queue_name),
-        ))?;
-
-        self.channel
-            .exchange_declare(ExchangeDeclareArguments::new(exchange_name, "topic"))
-            .await?;
-
-        Ok(queue_details.0)
+        let channel = self.channel().await?;
+        self.declare_topology(&channel).await
     }
 }
 
@@ -120,8 +508,83 @@ impl Clone for SessionNotifier {
     fn clone(&self) -> Self {
         Self {
             rabbitmq_config: self.rabbitmq_config.clone(),
-            connection: self.connection.clone(),
-            channel: self.channel.clone(),
+            state: self.state.clone(),
         }
     }
 }
+
+#[async_trait]
+impl Notifier for SessionNotifier {
+    async fn initialize(&self) -> Result<String, NotifierError> {
+        Ok(SessionNotifier::initialize(self).await?)
+    }
+
+    async fn ensure_connected(&self) -> Result<u64, NotifierError> {
+        Ok(SessionNotifier::ensure_connected(self).await?)
+    }
+
+    async fn bind_routing_key(&self, routing_key: &str) -> Result<(), NotifierError> {
+        Ok(SessionNotifier::bind_routing_key(self, routing_key).await?)
+    }
+
+    async fn unbind_routing_key(&self, routing_key: &str) -> Result<(), NotifierError> {
+        Ok(SessionNotifier::unbind_routing_key(self, routing_key).await?)
+    }
+
+    async fn sync_routing_keys(&self, routing_keys: &HashSet<String>) -> Result<(), NotifierError> {
+        Ok(SessionNotifier::sync_routing_keys(self, routing_keys).await?)
+    }
+
+    async fn publish(&self, routing_key: &str, message: Vec<u8>) -> Result<(), NotifierError> {
+        Ok(SessionNotifier::publish(self, routing_key, message).await?)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_reconnect_backoff() {
+        let max = Duration::from_secs(30);
+        assert_eq!(reconnect_backoff(1, max), Duration::from_secs(1));
+        assert_eq!(reconnect_backoff(2, max), Duration::from_secs(2));
+        assert_eq!(reconnect_backoff(4, max), Duration::from_secs(8));
+        assert_eq!(reconnect_backoff(10, max), max);
+        assert_eq!(reconnect_backoff(u32::MAX, max), max);
+    }
+
+    #[test]
+    fn test_stale_routing_keys() {
+        let expected = HashSet::from(["p.kept".to_string(), "p.added".to_string()]);
+        let bound = vec![
+            "p.kept".to_string(),
+            "p.deleted".to_string(),
+            "p.deleted".to_string(),
+            "other.group".to_string(),
+        ];
+
+        assert_eq!(
+            stale_routing_keys(bound, &expected),
+            BTreeSet::from(["other.group".to_string(), "p.deleted".to_string()])
+        );
+        assert!(stale_routing_keys(Vec::new(), &expected).is_empty());
+    }
+
+    #[test]
+    fn test_pending_confirms_resolve() {
+        let mut confirms = PendingConfirms::new();
+        let (first_tag, mut first) = confirms.register();
+        let (_, mut second) = confirms.register();
+        let (third_tag, mut third) = confirms.register();
+        assert_eq!(first_tag, 1);
+
+        confirms.resolve(third_tag, false, false);
+        assert_eq!(third.try_recv(), Ok(false));
+
+        confirms.resolve(2, true, true);
+        assert_eq!(first.try_recv(), Ok(true));
+        assert_eq!(second.try_recv(), Ok(true));
+        assert!(confirms.waiting.is_empty());
+    }
+}
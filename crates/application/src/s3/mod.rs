@@ -1 +0,0 @@
-pub mod storage_service;
@@ -0,0 +1,329 @@
+use std::path::{Path, PathBuf};
+use std::time::Duration;
+
+use async_trait::async_trait;
+use sha2::{Digest, Sha256};
+use uuid::Uuid;
+
+use super::{ObjectInfo, Storage, StorageError, UploadedPart};
+
+/// Directory under the root holding the parts of unfinished multipart uploads.
+const MULTIPART_DIR: &str = ".multipart";
+
+/// Checks that a key stays under the root: relative, without empty, `.` or
+/// `..` segments, and outside the multipart directory.
+fn validate_key(key: &str) -> Result<(), StorageError> {
+    let valid = !key.is_empty()
+        && !key.starts_with(MULTIPART_DIR)
+        && key
+            .split('/')
+            .all(|segment| !segment.is_empty() && segment != "." && segment != "..");
+
+    if valid {
+        Ok(())
+    } else {
+        Err(StorageError::InvalidKey(key.to_string()))
+    }
+}
+
+fn not_found(key: &str, error: std::io::Error) -> StorageError {
+    match error.kind() {
+        std::io::ErrorKind::NotFound => StorageError::NotFound(key.to_string()),
+        _ => error.into(),
+    }
+}
+
+/// Stores recordings as files under a directory, as LiveKit does when it
+/// writes to a local recording root. Files cannot be presigned.
+pub struct LocalStorage {
+    root: PathBuf,
+}
+
+impl LocalStorage {
+    pub fn new(root: impl AsRef<Path>) -> Self {
+        LocalStorage {
+            root: root.as_ref().to_path_buf(),
+        }
+    }
+
+    fn path(&self, key: &str) -> Result<PathBuf, StorageError> {
+        validate_key(key)?;
+        Ok(self.root.join(key))
+    }
+
+    fn upload_dir(&self, upload_id: &str) -> Result<PathBuf, StorageError> {
+        Uuid::parse_str(upload_id)
+            .map_err(|_| StorageError::UnknownUpload(upload_id.to_string()))?;
+        Ok(self.root.join(MULTIPART_DIR).join(upload_id))
+    }
+
+    async fn object_info(key: String, path: &Path) -> Result<ObjectInfo, std::io::Error> {
+        let metadata = tokio::fs::metadata(path).await?;
+
+        Ok(ObjectInfo {
+            key,
+            size: metadata.len(),
+            last_modified: metadata
+                .modified()
+                .ok()
+                .map(|time| chrono::DateTime::<chrono::Utc>::from(time).naive_utc()),
+        })
+    }
+
+    async fn write(path: &Path, body: &[u8]) -> Result<(), std::io::Error> {
+        if let Some(parent) = path.parent() {
+            tokio::fs::create_dir_all(parent).await?;
+        }
+        tokio::fs::write(path, body).await
+    }
+}
+
+#[async_trait]
+impl Storage for LocalStorage {
+    async fn probe(&self) -> Result<(), StorageError> {
+        let metadata = tokio::fs::metadata(&self.root).await?;
+        if metadata.is_dir() {
+            Ok(())
+        } else {
+            Err(std::io::Error::new(
+                std::io::ErrorKind::Other,
+                format!("{} is not a directory", self.root.display()),
+            )
+            .into())
+        }
+    }
+
+    async fn put(&self, key: &str, body: Vec<u8>) -> Result<(), StorageError> {
+        Self::write(&self.path(key)?, &body).await?;
+        Ok(())
+    }
+
+    async fn get(&self, key: &str) -> Result<Vec<u8>, StorageError> {
+        tokio::fs::read(self.path(key)?)
+            .await
+            .map_err(|e| not_found(key, e))
+    }
+
+    async fn head(&self, key: &str) -> Result<ObjectInfo, StorageError> {
+        Self::object_info(key.to_string(), &self.path(key)?)
+            .await
+            .map_err(|e| not_found(key, e))
+    }
+
+    async fn list(&self, prefix: &str) -> Result<Vec<ObjectInfo>, StorageError> {
+        // Only the directory the prefix ends in needs walking
+        let start = match prefix.rsplit_once('/') {
+            Some((dir, _)) if !dir.is_empty() => dir,
+            _ => "",
+        };
+        let mut pending = vec![(start.to_string(), self.root.join(start))];
+        let mut objects = Vec::new();
+
+        while let Some((dir_key, dir)) = pending.pop() {
+            let mut entries = match tokio::fs::read_dir(&dir).await {
+                Ok(entries) => entries,
+                Err(e) if e.kind() == std::io::ErrorKind::NotFound => continue,
+                Err(e) => return Err(e.into()),
+            };
+
+            while let Some(entry) = entries.next_entry().await? {
+                let name = entry.file_name().to_string_lossy().to_string();
+                let key = if dir_key.is_empty() {
+                    name
+                } else {
+                    format!("{}/{}", dir_key, name)
+                };
+                if key == MULTIPART_DIR {
+                    continue;
+                }
+
+                if entry.file_type().await?.is_dir() {
+                    pending.push((key, entry.path()));
+                } else if key.starts_with(prefix) {
+                    objects.push(Self::object_info(key, &entry.path()).await?);
+                }
+            }
+        }
+
+        objects.sort_by(|a, b| a.key.cmp(&b.key));
+        Ok(objects)
+    }
+
+    async fn delete(&self, key: &str) -> Result<(), StorageError> {
+        match tokio::fs::remove_file(self.path(key)?).await {
+            Err(e) if e.kind() != std::io::ErrorKind::NotFound => Err(e.into()),
+            _ => Ok(()),
+        }
+    }
+
+    async fn copy(&self, from: &str, to: &str) -> Result<(), StorageError> {
+        let destination = self.path(to)?;
+        if let Some(parent) = destination.parent() {
+            tokio::fs::create_dir_all(parent).await?;
+        }
+        tokio::fs::copy(self.path(from)?, destination)
+            .await
+            .map_err(|e| not_found(from, e))?;
+
+        Ok(())
+    }
+
+    async fn presign_get(&self, _key: &str, _expires_in: Duration) -> Result<String, StorageError> {
+        Err(StorageError::Unsupported("local", "presigned URLs"))
+    }
+
+    async fn presign_put(&self, _key: &str, _expires_in: Duration) -> Result<String, StorageError> {
+        Err(StorageError::Unsupported("local", "presigned URLs"))
+    }
+
+    async fn create_multipart_upload(&self, key: &str) -> Result<String, StorageError> {
+        validate_key(key)?;
+        let upload_id = Uuid::new_v4().to_string();
+        tokio::fs::create_dir_all(self.upload_dir(&upload_id)?).await?;
+
+        Ok(upload_id)
+    }
+
+    async fn upload_part(
+        &self,
+        key: &str,
+        upload_id: &str,
+        part_number: i32,
+        body: Vec<u8>,
+    ) -> Result<UploadedPart, StorageError> {
+        validate_key(key)?;
+        let upload_dir = self.upload_dir(upload_id)?;
+        if !tokio::fs::try_exists(&upload_dir).await? {
+            return Err(StorageError::UnknownUpload(upload_id.to_string()));
+        }
+
+        tokio::fs::write(upload_dir.join(part_number.to_string()), &body).await?;
+
+        Ok(UploadedPart {
+            part_number,
+            etag: format!("{:x}", Sha256::digest(&body)),
+        })
+    }
+
+    async fn complete_multipart_upload(
+        &self,
+        key: &str,
+        upload_id: &str,
+        parts: Vec<UploadedPart>,
+    ) -> Result<(), StorageError> {
+        let path = self.path(key)?;
+        let upload_dir = self.upload_dir(upload_id)?;
+
+        let mut body = Vec::new();
+        for part in parts {
+            let part_path = upload_dir.join(part.part_number.to_string());
+            body.extend(
+                tokio::fs::read(&part_path)
+                    .await
+                    .map_err(|_| StorageError::UnknownUpload(upload_id.to_string()))?,
+            );
+        }
+
+        Self::write(&path, &body).await?;
+        tokio::fs::remove_dir_all(upload_dir).await?;
+
+        Ok(())
+    }
+
+    async fn abort_multipart_upload(&self, key: &str, upload_id: &str) -> Result<(), StorageError> {
+        validate_key(key)?;
+        match tokio::fs::remove_dir_all(self.upload_dir(upload_id)?).await {
+            Err(e) if e.kind() != std::io::ErrorKind::NotFound => Err(e.into()),
+            _ => Ok(()),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn storage() -> (LocalStorage, PathBuf) {
+        let root = std::env::temp_dir().join(format!("syncflow-storage-{}", Uuid::new_v4()));
+        std::fs::create_dir_all(&root).unwrap();
+        (LocalStorage::new(&root), root)
+    }
+
+    #[test]
+    fn test_validate_key() {
+        assert!(validate_key("room/tracks/alice/TR_1.mp4").is_ok());
+        assert!(validate_key("").is_err());
+        assert!(validate_key("/etc/passwd").is_err());
+        assert!(validate_key("room/../../etc/passwd").is_err());
+        assert!(validate_key(".multipart/upload/1").is_err());
+    }
+
+    #[tokio::test]
+    async fn test_put_list_and_delete() {
+        let (storage, root) = storage();
+        storage.probe().await.unwrap();
+
+        storage
+            .put("room/tracks/a.mp4", b"abc".to_vec())
+            .await
+            .unwrap();
+        storage
+            .put("room/tracks/b.mp4", b"de".to_vec())
+            .await
+            .unwrap();
+        storage.put("other/c.mp4", b"f".to_vec()).await.unwrap();
+
+        let listed = storage.list("room/tr").await.unwrap();
+        assert_eq!(
+            listed
+                .iter()
+                .map(|o| (o.key.as_str(), o.size))
+                .collect::<Vec<_>>(),
+            vec![("room/tracks/a.mp4", 3), ("room/tracks/b.mp4", 2)]
+        );
+        assert_eq!(storage.list("").await.unwrap().len(), 3);
+
+        storage
+            .copy("room/tracks/a.mp4", "archive/a.mp4")
+            .await
+            .unwrap();
+        assert_eq!(storage.get("archive/a.mp4").await.unwrap(), b"abc");
+
+        storage.delete("room/tracks/a.mp4").await.unwrap();
+        storage.delete("room/tracks/a.mp4").await.unwrap();
+        assert!(matches!(
+            storage.head("room/tracks/a.mp4").await,
+            Err(StorageError::NotFound(_))
+        ));
+
+        std::fs::remove_dir_all(root).unwrap();
+    }
+
+    #[tokio::test]
+    async fn test_multipart_upload() {
+        let (storage, root) = storage();
+
+        let upload_id = storage.create_multipart_upload("big.mp4").await.unwrap();
+        let second = storage
+            .upload_part("big.mp4", &upload_id, 2, b"world".to_vec())
+            .await
+            .unwrap();
+        let first = storage
+            .upload_part("big.mp4", &upload_id, 1, b"hello ".to_vec())
+            .await
+            .unwrap();
+        storage
+            .complete_multipart_upload("big.mp4", &upload_id, vec![first, second])
+            .await
+            .unwrap();
+
+        assert_eq!(storage.get("big.mp4").await.unwrap(), b"hello world");
+        assert_eq!(
+            storage.list("").await.unwrap().len(),
+            1,
+            "no parts are left behind"
+        );
+
+        std::fs::remove_dir_all(root).unwrap();
+    }
+}
@@ -0,0 +1,246 @@
+use std::collections::{BTreeMap, HashMap};
+use std::sync::Mutex;
+use std::time::Duration;
+
+use async_trait::async_trait;
+use sha2::{Digest, Sha256};
+use uuid::Uuid;
+
+use super::{ObjectInfo, Storage, StorageError, UploadedPart};
+
+struct StoredObject {
+    body: Vec<u8>,
+    last_modified: chrono::NaiveDateTime,
+}
+
+struct MultipartUpload {
+    key: String,
+    parts: BTreeMap<i32, Vec<u8>>,
+}
+
+/// Keeps objects in memory, for tests. Presigned URLs use the `memory`
+/// scheme and cannot be fetched.
+#[derive(Default)]
+pub struct InMemoryStorage {
+    objects: Mutex<BTreeMap<String, StoredObject>>,
+    uploads: Mutex<HashMap<String, MultipartUpload>>,
+}
+
+impl InMemoryStorage {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    fn info(key: &str, object: &StoredObject) -> ObjectInfo {
+        ObjectInfo {
+            key: key.to_string(),
+            size: object.body.len() as u64,
+            last_modified: Some(object.last_modified),
+        }
+    }
+
+    fn store(&self, key: &str, body: Vec<u8>) {
+        self.objects.lock().unwrap().insert(
+            key.to_string(),
+            StoredObject {
+                body,
+                last_modified: chrono::Utc::now().naive_utc(),
+            },
+        );
+    }
+}
+
+#[async_trait]
+impl Storage for InMemoryStorage {
+    async fn probe(&self) -> Result<(), StorageError> {
+        Ok(())
+    }
+
+    async fn put(&self, key: &str, body: Vec<u8>) -> Result<(), StorageError> {
+        self.store(key, body);
+        Ok(())
+    }
+
+    async fn get(&self, key: &str) -> Result<Vec<u8>, StorageError> {
+        self.objects
+            .lock()
+            .unwrap()
+            .get(key)
+            .map(|object| object.body.clone())
+            .ok_or_else(|| StorageError::NotFound(key.to_string()))
+    }
+
+    async fn head(&self, key: &str) -> Result<ObjectInfo, StorageError> {
+        self.objects
+            .lock()
+            .unwrap()
+            .get(key)
+            .map(|object| Self::info(key, object))
+            .ok_or_else(|| StorageError::NotFound(key.to_string()))
+    }
+
+    async fn list(&self, prefix: &str) -> Result<Vec<ObjectInfo>, StorageError> {
+        Ok(self
+            .objects
+            .lock()
+            .unwrap()
+            .range(prefix.to_string()..)
+            .take_while(|(key, _)| key.starts_with(prefix))
+            .map(|(key, object)| Self::info(key, object))
+            .collect())
+    }
+
+    async fn delete(&self, key: &str) -> Result<(), StorageError> {
+        self.objects.lock().unwrap().remove(key);
+        Ok(())
+    }
+
+    async fn copy(&self, from: &str, to: &str) -> Result<(), StorageError> {
+        let body = self.get(from).await?;
+        self.store(to, body);
+        Ok(())
+    }
+
+    async fn presign_get(&self, key: &str, expires_in: Duration) -> Result<String, StorageError> {
+        Ok(format!(
+            "memory:///{}?method=GET&expires_in={}",
+            key,
+            expires_in.as_secs()
+        ))
+    }
+
+    async fn presign_put(&self, key: &str, expires_in: Duration) -> Result<String, StorageError> {
+        Ok(format!(
+            "memory:///{}?method=PUT&expires_in={}",
+            key,
+            expires_in.as_secs()
+        ))
+    }
+
+    async fn create_multipart_upload(&self, key: &str) -> Result<String, StorageError> {
+        let upload_id = Uuid::new_v4().to_string();
+        self.uploads.lock().unwrap().insert(
+            upload_id.clone(),
+            MultipartUpload {
+                key: key.to_string(),
+                parts: BTreeMap::new(),
+            },
+        );
+
+        Ok(upload_id)
+    }
+
+    async fn upload_part(
+        &self,
+        key: &str,
+        upload_id: &str,
+        part_number: i32,
+        body: Vec<u8>,
+    ) -> Result<UploadedPart, StorageError> {
+        let mut uploads = self.uploads.lock().unwrap();
+        let upload = uploads
+            .get_mut(upload_id)
+            .filter(|upload| upload.key == key)
+            .ok_or_else(|| StorageError::UnknownUpload(upload_id.to_string()))?;
+
+        let etag = format!("{:x}", Sha256::digest(&body));
+        upload.parts.insert(part_number, body);
+
+        Ok(UploadedPart { part_number, etag })
+    }
+
+    async fn complete_multipart_upload(
+        &self,
+        key: &str,
+        upload_id: &str,
+        parts: Vec<UploadedPart>,
+    ) -> Result<(), StorageError> {
+        let upload = {
+            let mut uploads = self.uploads.lock().unwrap();
+            match uploads.get(upload_id) {
+                Some(upload) if upload.key == key => uploads.remove(upload_id).unwrap(),
+                _ => return Err(StorageError::UnknownUpload(upload_id.to_string())),
+            }
+        };
+
+        let mut body = Vec::new();
+        for part in parts {
+            let uploaded = upload
+                .parts
+                .get(&part.part_number)
+                .ok_or_else(|| StorageError::UnknownUpload(upload_id.to_string()))?;
+            body.extend_from_slice(uploaded);
+        }
+        self.store(key, body);
+
+        Ok(())
+    }
+
+    async fn abort_multipart_upload(
+        &self,
+        _key: &str,
+        upload_id: &str,
+    ) -> Result<(), StorageError> {
+        self.uploads.lock().unwrap().remove(upload_id);
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[tokio::test]
+    async fn test_list_only_returns_the_prefix() {
+        let storage = InMemoryStorage::new();
+        storage.put("room-a/1.mp4", vec![1, 2]).await.unwrap();
+        storage.put("room-a/2.mp4", vec![3]).await.unwrap();
+        storage.put("room-b/1.mp4", vec![4]).await.unwrap();
+
+        let listed = storage.list("room-a/").await.unwrap();
+        assert_eq!(
+            listed
+                .iter()
+                .map(|o| (o.key.as_str(), o.size))
+                .collect::<Vec<_>>(),
+            vec![("room-a/1.mp4", 2), ("room-a/2.mp4", 1)]
+        );
+
+        storage.delete("room-a/1.mp4").await.unwrap();
+        assert!(matches!(
+            storage.get("room-a/1.mp4").await,
+            Err(StorageError::NotFound(_))
+        ));
+    }
+
+    #[tokio::test]
+    async fn test_multipart_upload_joins_parts_in_order() {
+        let storage = InMemoryStorage::new();
+        let upload_id = storage.create_multipart_upload("big.mp4").await.unwrap();
+
+        let second = storage
+            .upload_part("big.mp4", &upload_id, 2, b"world".to_vec())
+            .await
+            .unwrap();
+        let first = storage
+            .upload_part("big.mp4", &upload_id, 1, b"hello ".to_vec())
+            .await
+            .unwrap();
+        assert!(matches!(
+            storage
+                .upload_part("other.mp4", &upload_id, 3, vec![])
+                .await,
+            Err(StorageError::UnknownUpload(_))
+        ));
+
+        storage
+            .complete_multipart_upload("big.mp4", &upload_id, vec![first, second])
+            .await
+            .unwrap();
+        assert_eq!(storage.get("big.mp4").await.unwrap(), b"hello world");
+        assert!(storage
+            .complete_multipart_upload("big.mp4", &upload_id, vec![])
+            .await
+            .is_err());
+    }
+}
@@ -0,0 +1,104 @@
+use std::time::Duration;
+
+use async_trait::async_trait;
+use shared::deployment_config::StorageConfig;
+use thiserror::Error;
+
+pub mod local;
+pub mod memory;
+pub mod s3;
+
+#[derive(Debug, Error)]
+pub enum StorageError {
+    #[error("Object not found: {0}")]
+    NotFound(String),
+
+    #[error("Invalid key: {0}")]
+    InvalidKey(String),
+
+    #[error("Unknown multipart upload: {0}")]
+    UnknownUpload(String),
+
+    #[error("The {0} storage does not support {1}")]
+    Unsupported(&'static str, &'static str),
+
+    #[error("S3 request failed: {0}")]
+    S3(String),
+
+    #[error("I/O Error: {0}")]
+    Io(#[from] std::io::Error),
+}
+
+/// An object in a bucket, or a file under the local recording root.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ObjectInfo {
+    pub key: String,
+    pub size: u64,
+    pub last_modified: Option<chrono::NaiveDateTime>,
+}
+
+/// A part uploaded to a multipart upload, handed back when completing it.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct UploadedPart {
+    /// Starts at 1
+    pub part_number: i32,
+    pub etag: String,
+}
+
+/// A backend that stores recordings. Keys are `/` separated paths relative to
+/// the bucket or the recording root.
+#[async_trait]
+pub trait Storage: Send + Sync {
+    /// Checks that the bucket or recording root exists and can be accessed.
+    async fn probe(&self) -> Result<(), StorageError>;
+
+    async fn put(&self, key: &str, body: Vec<u8>) -> Result<(), StorageError>;
+
+    async fn get(&self, key: &str) -> Result<Vec<u8>, StorageError>;
+
+    async fn head(&self, key: &str) -> Result<ObjectInfo, StorageError>;
+
+    /// Every object under `prefix`, ordered by key.
+    async fn list(&self, prefix: &str) -> Result<Vec<ObjectInfo>, StorageError>;
+
+    /// Removes the object at `key`. Removing a missing object succeeds.
+    async fn delete(&self, key: &str) -> Result<(), StorageError>;
+
+    async fn copy(&self, from: &str, to: &str) -> Result<(), StorageError>;
+
+    /// A URL the object can be downloaded from until it expires.
+    async fn presign_get(&self, key: &str, expires_in: Duration) -> Result<String, StorageError>;
+
+    /// A URL the object can be uploaded to until it expires.
+    async fn presign_put(&self, key: &str, expires_in: Duration) -> Result<String, StorageError>;
+
+    /// Starts a multipart upload to `key` and returns its id.
+    async fn create_multipart_upload(&self, key: &str) -> Result<String, StorageError>;
+
+    async fn upload_part(
+        &self,
+        key: &str,
+        upload_id: &str,
+        part_number: i32,
+        body: Vec<u8>,
+    ) -> Result<UploadedPart, StorageError>;
+
+    /// Assembles the parts, in the order given, into the object at `key`.
+    async fn complete_multipart_upload(
+        &self,
+        key: &str,
+        upload_id: &str,
+        parts: Vec<UploadedPart>,
+    ) -> Result<(), StorageError>;
+
+    async fn abort_multipart_upload(&self, key: &str, upload_id: &str) -> Result<(), StorageError>;
+}
+
+pub fn from_config(config: &StorageConfig) -> Box<dyn Storage> {
+    match config {
+        StorageConfig::S3(s3_config) => Box::new(s3::S3Storage::new(s3_config)),
+        StorageConfig::Local(local_config) => {
+            Box::new(local::LocalStorage::new(&local_config.recording_root_path))
+        }
+    }
+}
@@ -0,0 +1,360 @@
+use std::time::Duration;
+
+use async_trait::async_trait;
+use aws_sdk_s3::config::{BehaviorVersion, Credentials, Region};
+use aws_sdk_s3::error::{DisplayErrorContext, SdkError};
+use aws_sdk_s3::presigning::PresigningConfig;
+use aws_sdk_s3::primitives::{ByteStream, DateTime};
+use aws_sdk_s3::types::{CompletedMultipartUpload, CompletedPart};
+use aws_sdk_s3::Client;
+use shared::deployment_config::S3Config;
+
+use super::{ObjectInfo, Storage, StorageError, UploadedPart};
+
+/// Used when the project has no region, MinIO accepts any.
+const DEFAULT_REGION: &str = "us-east-1";
+
+fn s3_error<E: std::error::Error + 'static, R: std::fmt::Debug>(
+    error: SdkError<E, R>,
+) -> StorageError {
+    StorageError::S3(DisplayErrorContext(&error).to_string())
+}
+
+/// The endpoint as a URL, endpoints stored without a scheme are served over
+/// HTTPS.
+fn endpoint_url(endpoint: &str) -> String {
+    if endpoint.contains("://") {
+        endpoint.to_string()
+    } else {
+        format!("https://{}", endpoint)
+    }
+}
+
+/// The `bucket/key` source of a copy, with the key percent-encoded as S3
+/// expects.
+fn copy_source(bucket: &str, key: &str) -> String {
+    let mut source = format!("{}/", bucket);
+    for byte in key.bytes() {
+        match byte {
+            b'A'..=b'Z' | b'a'..=b'z' | b'0'..=b'9' | b'-' | b'_' | b'.' | b'~' | b'/' => {
+                source.push(byte as char)
+            }
+            _ => source.push_str(&format!("%{:02X}", byte)),
+        }
+    }
+    source
+}
+
+fn naive_time(time: Option<&DateTime>) -> Option<chrono::NaiveDateTime> {
+    time.and_then(|time| chrono::DateTime::from_timestamp(time.secs(), time.subsec_nanos()))
+        .map(|time| time.naive_utc())
+}
+
+/// Stores recordings in an S3 compatible bucket. Requests use path style
+/// addressing, so that MinIO works without DNS for bucket subdomains.
+pub struct S3Storage {
+    bucket: String,
+    client: Client,
+}
+
+impl S3Storage {
+    pub fn new(config: &S3Config) -> Self {
+        let credentials = Credentials::new(
+            config.access_key.to_string(),
+            config.secret_key.to_string(),
+            None,
+            None,
+            "syncflow",
+        );
+        let region = if config.region.is_empty() {
+            DEFAULT_REGION.to_string()
+        } else {
+            config.region.to_string()
+        };
+
+        let client_config = aws_sdk_s3::Config::builder()
+            .behavior_version(BehaviorVersion::latest())
+            .credentials_provider(credentials)
+            .region(Region::new(region))
+            .endpoint_url(endpoint_url(&config.endpoint))
+            .force_path_style(true)
+            .build();
+
+        S3Storage {
+            bucket: config.bucket.to_string(),
+            client: Client::from_conf(client_config),
+        }
+    }
+
+    fn presigning_config(expires_in: Duration) -> Result<PresigningConfig, StorageError> {
+        PresigningConfig::expires_in(expires_in).map_err(|e| StorageError::S3(e.to_string()))
+    }
+}
+
+#[async_trait]
+impl Storage for S3Storage {
+    async fn probe(&self) -> Result<(), StorageError> {
+        self.client
+            .head_bucket()
+            .bucket(&self.bucket)
+            .send()
+            .await
+            .map_err(s3_error)?;
+
+        Ok(())
+    }
+
+    async fn put(&self, key: &str, body: Vec<u8>) -> Result<(), StorageError> {
+        self.client
+            .put_object()
+            .bucket(&self.bucket)
+            .key(key)
+            .body(ByteStream::from(body))
+            .send()
+            .await
+            .map_err(s3_error)?;
+
+        Ok(())
+    }
+
+    async fn get(&self, key: &str) -> Result<Vec<u8>, StorageError> {
+        let object = self
+            .client
+            .get_object()
+            .bucket(&self.bucket)
+            .key(key)
+            .send()
+            .await
+            .map_err(|e| match e.as_service_error() {
+                Some(service_error) if service_error.is_no_such_key() => {
+                    StorageError::NotFound(key.to_string())
+                }
+                _ => s3_error(e),
+            })?;
+
+        let body = object
+            .body
+            .collect()
+            .await
+            .map_err(|e| StorageError::S3(e.to_string()))?;
+
+        Ok(body.into_bytes().to_vec())
+    }
+
+    async fn head(&self, key: &str) -> Result<ObjectInfo, StorageError> {
+        let object = self
+            .client
+            .head_object()
+            .bucket(&self.bucket)
+            .key(key)
+            .send()
+            .await
+            .map_err(|e| match e.as_service_error() {
+                Some(service_error) if service_error.is_not_found() => {
+                    StorageError::NotFound(key.to_string())
+                }
+                _ => s3_error(e),
+            })?;
+
+        Ok(ObjectInfo {
+            key: key.to_string(),
+            size: object.content_length().unwrap_or_default().max(0) as u64,
+            last_modified: naive_time(object.last_modified()),
+        })
+    }
+
+    async fn list(&self, prefix: &str) -> Result<Vec<ObjectInfo>, StorageError> {
+        let mut objects = Vec::new();
+        let mut continuation_token = None;
+
+        loop {
+            let page = self
+                .client
+                .list_objects_v2()
+                .bucket(&self.bucket)
+                .prefix(prefix)
+                .set_continuation_token(continuation_token)
+                .send()
+                .await
+                .map_err(s3_error)?;
+
+            objects.extend(page.contents().iter().filter_map(|object| {
+                object.key().map(|key| ObjectInfo {
+                    key: key.to_string(),
+                    size: object.size().unwrap_or_default().max(0) as u64,
+                    last_modified: naive_time(object.last_modified()),
+                })
+            }));
+
+            match page.next_continuation_token() {
+                Some(token) if page.is_truncated().unwrap_or(false) => {
+                    continuation_token = Some(token.to_string())
+                }
+                _ => break,
+            }
+        }
+
+        Ok(objects)
+    }
+
+    async fn delete(&self, key: &str) -> Result<(), StorageError> {
+        self.client
+            .delete_object()
+            .bucket(&self.bucket)
+            .key(key)
+            .send()
+            .await
+            .map_err(s3_error)?;
+
+        Ok(())
+    }
+
+    async fn copy(&self, from: &str, to: &str) -> Result<(), StorageError> {
+        self.client
+            .copy_object()
+            .bucket(&self.bucket)
+            .copy_source(copy_source(&self.bucket, from))
+            .key(to)
+            .send()
+            .await
+            .map_err(s3_error)?;
+
+        Ok(())
+    }
+
+    async fn presign_get(&self, key: &str, expires_in: Duration) -> Result<String, StorageError> {
+        let request = self
+            .client
+            .get_object()
+            .bucket(&self.bucket)
+            .key(key)
+            .response_content_disposition("attachment")
+            .presigned(Self::presigning_config(expires_in)?)
+            .await
+            .map_err(s3_error)?;
+
+        Ok(request.uri().to_string())
+    }
+
+    async fn presign_put(&self, key: &str, expires_in: Duration) -> Result<String, StorageError> {
+        let request = self
+            .client
+            .put_object()
+            .bucket(&self.bucket)
+            .key(key)
+            .presigned(Self::presigning_config(expires_in)?)
+            .await
+            .map_err(s3_error)?;
+
+        Ok(request.uri().to_string())
+    }
+
+    async fn create_multipart_upload(&self, key: &str) -> Result<String, StorageError> {
+        let upload = self
+            .client
+            .create_multipart_upload()
+            .bucket(&self.bucket)
+            .key(key)
+            .send()
+            .await
+            .map_err(s3_error)?;
+
+        upload
+            .upload_id()
+            .map(str::to_string)
+            .ok_or_else(|| StorageError::S3("The multipart upload has no id".to_string()))
+    }
+
+    async fn upload_part(
+        &self,
+        key: &str,
+        upload_id: &str,
+        part_number: i32,
+        body: Vec<u8>,
+    ) -> Result<UploadedPart, StorageError> {
+        let part = self
+            .client
+            .upload_part()
+            .bucket(&self.bucket)
+            .key(key)
+            .upload_id(upload_id)
+            .part_number(part_number)
+            .body(ByteStream::from(body))
+            .send()
+            .await
+            .map_err(s3_error)?;
+
+        Ok(UploadedPart {
+            part_number,
+            etag: part.e_tag().unwrap_or_default().to_string(),
+        })
+    }
+
+    async fn complete_multipart_upload(
+        &self,
+        key: &str,
+        upload_id: &str,
+        parts: Vec<UploadedPart>,
+    ) -> Result<(), StorageError> {
+        let parts = parts
+            .into_iter()
+            .map(|part| {
+                CompletedPart::builder()
+                    .part_number(part.part_number)
+                    .e_tag(part.etag)
+                    .build()
+            })
+            .collect::<Vec<_>>();
+
+        self.client
+            .complete_multipart_upload()
+            .bucket(&self.bucket)
+            .key(key)
+            .upload_id(upload_id)
+            .multipart_upload(
+                CompletedMultipartUpload::builder()
+                    .set_parts(Some(parts))
+                    .build(),
+            )
+            .send()
+            .await
+            .map_err(s3_error)?;
+
+        Ok(())
+    }
+
+    async fn abort_multipart_upload(&self, key: &str, upload_id: &str) -> Result<(), StorageError> {
+        self.client
+            .abort_multipart_upload()
+            .bucket(&self.bucket)
+            .key(key)
+            .upload_id(upload_id)
+            .send()
+            .await
+            .map_err(s3_error)?;
+
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_endpoint_url_defaults_to_https() {
+        assert_eq!(endpoint_url("minio.local:9000"), "https://minio.local:9000");
+        assert_eq!(
+            endpoint_url("http://localhost:9000"),
+            "http://localhost:9000"
+        );
+    }
+
+    #[test]
+    fn test_copy_source_encodes_key() {
+        assert_eq!(
+            copy_source("recordings", "room/tracks/alice smith/TR_1+a.mp4"),
+            "recordings/room/tracks/alice%20smith/TR_1%2Ba.mp4"
+        );
+    }
+}
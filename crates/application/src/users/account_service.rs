@@ -1,16 +1,29 @@
-use super::{secret, tokens_manager, user};
+use super::{account_tokens, mfa, secret, signing_keys, tokens_manager, user};
+use crate::audit::audit_service::AuditService;
+use crate::audit::{AuditAction, AuditActor, AuditTarget, AuditTargetType};
+use crate::mailer::{self, Email, Mailer};
+use crate::project::deletions::deletion_crud::{self, DeletionRequest};
 use crate::project::{self, project_crud};
+use crate::rate_limit::AccountLockout;
 use crate::users::oauth::github::{fetch_github_user, verify_user_token, GithubUser};
+use crate::users::oauth::oidc::{OidcError, OidcProvider, GOOGLE_PROVIDER};
 use crate::users::tokens_manager::TokenInfo;
-use crate::users::user::UserError;
-use domain::models::User;
+use crate::users::user::{ClientInfo, LoginOutcome, LoginSessionInfo, UserError};
+use diesel::PgConnection;
+use domain::models::{AccountTokenPurpose, User};
 use infrastructure::DbPool;
+use jsonwebtoken::jwk::JwkSet;
 use shared::claims::TokenTypes;
+use shared::deletion_models::DeletionQuery;
 use shared::deployment_config::DeploymentConfig;
-use shared::project_models::{ProjectSummary, ProjectsSummary};
+use shared::project_models::{ProjectSummary, ProjectValidationReport, ProjectsSummary};
 use shared::user_models::{
-    ApiKeyRequest, ApiKeyResponse, ApiKeyResponseWithoutSecret, ProjectInfo, ProjectRequest,
-    RefreshTokenRequest, TokenResponse, UserProfile,
+    AccountDeletionRequest, ApiKeyRequest, ApiKeyResponse, ApiKeyResponseWithoutSecret,
+    EmailVerificationConfirmRequest, LoginResponse, LoginSessionResponse, MfaChallengeResponse,
+    MfaLoginRequest, MfaStatusResponse, OidcLoginRequest, PasswordChangeRequest,
+    PasswordResetConfirmRequest, PasswordResetRequest, ProfileUpdateRequest, ProjectApiKeyRequest,
+    ProjectInfo, ProjectRequest, ProjectSecretsRequest, ProjectUpdateRequest,
+    RecoveryCodesResponse, RefreshTokenRequest, TokenResponse, TotpEnrollmentResponse, UserProfile,
 };
 use shared::user_models::{LoginRequest, SignUpRequest};
 use std::sync::Arc;
@@ -20,58 +33,272 @@ pub struct AccountService {
     pool: Arc<DbPool>,
     config: DeploymentConfig,
     tokens_manager: tokens_manager::JWTTokensManager,
+    google_provider: Option<Arc<OidcProvider>>,
+    oidc_provider: Option<Arc<OidcProvider>>,
+    mailer: Arc<dyn Mailer>,
+    audit: AuditService,
 }
 
 impl AccountService {
     pub fn new(pool: Arc<DbPool>, config: DeploymentConfig) -> Self {
-        let encryption_key = &config.encryption_key.clone();
-        let jwt_expiration = config.jwt_expiration;
-        let jwt_refresh_expiration = config.jwt_refresh_expiration;
+        let tokens_manager = tokens_manager::JWTTokensManager::new(&config);
+        let google_provider = config.google_client_id.as_deref().map(|client_id| {
+            Arc::new(OidcProvider::google(
+                client_id,
+                config.google_client_secret.as_deref(),
+            ))
+        });
+        let oidc_provider = config
+            .oidc_config
+            .as_ref()
+            .map(|oidc_config| Arc::new(OidcProvider::from_config(oidc_config)));
+        let mailer = mailer::from_config(&config)
+            .unwrap_or_else(|e| panic!("Failed to set up the mailer: {}", e));
         AccountService {
+            audit: AuditService::new(pool.clone()),
             pool,
             config,
-            tokens_manager: tokens_manager::JWTTokensManager::new(
-                encryption_key,
-                jwt_expiration,
-                jwt_refresh_expiration,
-            ),
+            tokens_manager,
+            google_provider,
+            oidc_provider,
+            mailer,
         }
     }
 
-    pub fn signup(&self, request: SignUpRequest) -> Result<(), UserError> {
-        user::signup(
+    /// Creates the account and mails a verification link. The account is
+    /// usable right away, so a failed delivery is only logged.
+    pub async fn signup(&self, request: SignUpRequest) -> Result<(), UserError> {
+        let user = user::signup(
             &request,
             &mut self.pool.get().unwrap(),
             &self.config.encryption_key,
-        )
+        )?;
+
+        if let Err(e) = self.send_verification_email(&user).await {
+            log::warn!(
+                "Failed to send the verification email to {}: {}",
+                user.id,
+                e
+            );
+        }
+        Ok(())
     }
 
-    /// Logs in a user
-    pub fn login(&self, request: LoginRequest) -> Result<TokenResponse, UserError> {
-        let session_info = user::login(
+    /// Logs in a user. Users with two-factor authentication get an mfa token
+    /// to complete the login with `verify_login_mfa` instead of tokens.
+    pub fn login(
+        &self,
+        request: LoginRequest,
+        client: &ClientInfo,
+    ) -> Result<LoginResponse, UserError> {
+        let username_or_email = request.username_or_email.clone();
+        let outcome = match user::login(
             request,
+            client,
+            self.session_lifetime(),
+            &AccountLockout::from_config(&self.config),
             &mut self.pool.get().unwrap(),
             &self.config.encryption_key,
-        )?;
+        ) {
+            Ok(outcome) => outcome,
+            Err(e) => {
+                let failed = Err(e);
+                self.audit_login(client, None, username_or_email, &failed);
+                return failed;
+            }
+        };
+        let user_id = match &outcome {
+            LoginOutcome::Session(session_info) => Some(session_info.user_id),
+            LoginOutcome::MfaRequired { .. } => None,
+        };
+        let response = self.login_response(outcome, &mut self.pool.get().unwrap());
+        if let Some(user_id) = user_id {
+            self.audit_login(client, Some(user_id), user_id.to_string(), &response);
+        }
+        response
+    }
+
+    /// Tokens for a login that got a session, or an mfa token to complete a
+    /// login that requires the second factor.
+    fn login_response(
+        &self,
+        outcome: LoginOutcome,
+        conn: &mut PgConnection,
+    ) -> Result<LoginResponse, UserError> {
+        match outcome {
+            LoginOutcome::Session(session_info) => self
+                .tokens_manager
+                .generate_login_token_pairs(&session_info, conn)
+                .map(|t| LoginResponse::Tokens(TokenResponse::bearer(t.0, t.1))),
+            LoginOutcome::MfaRequired { user_id, .. } => {
+                let mfa_token = self.tokens_manager.generate_mfa_token(user_id, conn)?;
+                Ok(LoginResponse::MfaRequired(MfaChallengeResponse::new(
+                    mfa_token,
+                    tokens_manager::MFA_TOKEN_EXPIRATION,
+                )))
+            }
+        }
+    }
+
+    /// Completes a login with the second factor. Wrong codes count towards the
+    /// account lockout like wrong passwords.
+    pub fn verify_login_mfa(
+        &self,
+        request: &MfaLoginRequest,
+        client: &ClientInfo,
+    ) -> Result<TokenResponse, UserError> {
         let conn = &mut self.pool.get().unwrap();
-        let tokens = self
+        let user_id = self
             .tokens_manager
-            .generate_login_token_pairs(&session_info, conn)?;
-        Ok(TokenResponse::bearer(tokens.0, tokens.1))
+            .verify_mfa_token(&request.mfa_token, conn)?;
+        let tokens = self.complete_mfa_login(user_id, &request.code, client, conn);
+        self.audit_login(client, Some(user_id), user_id.to_string(), &tokens);
+        tokens
     }
 
-    pub fn refresh_token(&self, request: RefreshTokenRequest) -> Result<TokenResponse, UserError> {
-        let user_info = self
-            .tokens_manager
-            .verify_token(&request.refresh_token, &mut self.pool.get().unwrap())?;
-        let sid = user_info
-            .login_session
-            .ok_or(UserError::LoginSessionNotFound(format!(
-                "No login session found for the refresh token, {:?}",
-                &request.refresh_token
-            )))?;
+    fn complete_mfa_login(
+        &self,
+        user_id: i32,
+        code: &str,
+        client: &ClientInfo,
+        conn: &mut PgConnection,
+    ) -> Result<TokenResponse, UserError> {
+        let user = user::get_user(user_id, conn)?;
+        if let Some(seconds) = user.locked_for() {
+            return Err(UserError::AccountLocked(seconds));
+        }
+
+        match mfa::verify_second_factor(user_id, code, &self.config.encryption_key, conn) {
+            Ok(()) => user::reset_login_failures(user_id, conn)?,
+            Err(UserError::InvalidMfaCode(e)) => {
+                user::record_login_failure(
+                    user_id,
+                    &AccountLockout::from_config(&self.config),
+                    conn,
+                )?;
+                return Err(UserError::InvalidMfaCode(e));
+            }
+            Err(e) => return Err(e),
+        }
+
+        let session = user::new_login_session(user_id, client, self.session_lifetime(), conn)?;
+        let session_info = LoginSessionInfo::new(&session, user.username);
+        self.tokens_manager
+            .generate_login_token_pairs(&session_info, conn)
+            .map(|t| TokenResponse::bearer(t.0, t.1))
+    }
+
+    /// Records a login attempt. Failed attempts of unknown users name the
+    /// username or email that was tried.
+    fn audit_login<T>(
+        &self,
+        client: &ClientInfo,
+        user_id: Option<i32>,
+        target_id: String,
+        outcome: &Result<T, UserError>,
+    ) {
+        let actor = AuditActor {
+            user_id,
+            api_key_id: None,
+            ip_address: client.ip_address.clone(),
+        };
+        self.audit.record(
+            &actor,
+            AuditAction::Login,
+            AuditTarget::new(AuditTargetType::User, Some(target_id)),
+            outcome,
+        );
+    }
+
+    pub fn mfa_status(&self, user_id: i32) -> Result<MfaStatusResponse, UserError> {
+        let conn = &mut self.pool.get().unwrap();
+        let totp_enabled = mfa::is_enabled(user_id, conn)?;
+        let recovery_codes_remaining = if totp_enabled {
+            mfa::remaining_recovery_codes(user_id, conn)? as usize
+        } else {
+            0
+        };
+        Ok(MfaStatusResponse {
+            totp_enabled,
+            recovery_codes_remaining,
+        })
+    }
+
+    /// Starts a TOTP enrollment, which is active once confirmed with a code.
+    pub fn enroll_totp(&self, user_id: i32) -> Result<TotpEnrollmentResponse, UserError> {
         let conn = &mut self.pool.get().unwrap();
-        let login_session_info = user::get_login_session_info(user_info.user_id, &sid, conn)?;
+        let user = user::get_user(user_id, conn)?;
+        let enrollment =
+            mfa::start_enrollment(user_id, &user.email, &self.config.encryption_key, conn)?;
+        Ok(TotpEnrollmentResponse {
+            secret: enrollment.secret,
+            provisioning_uri: enrollment.provisioning_uri,
+        })
+    }
+
+    /// Confirms a TOTP enrollment and returns the recovery codes.
+    pub fn confirm_totp(
+        &self,
+        user_id: i32,
+        code: &str,
+    ) -> Result<RecoveryCodesResponse, UserError> {
+        mfa::confirm_enrollment(
+            user_id,
+            code,
+            &self.config.encryption_key,
+            &mut self.pool.get().unwrap(),
+        )
+        .map(|recovery_codes| RecoveryCodesResponse { recovery_codes })
+    }
+
+    pub fn disable_totp(&self, user_id: i32, code: &str) -> Result<(), UserError> {
+        mfa::disable(
+            user_id,
+            code,
+            &self.config.encryption_key,
+            &mut self.pool.get().unwrap(),
+        )
+    }
+
+    pub fn regenerate_recovery_codes(
+        &self,
+        user_id: i32,
+        code: &str,
+    ) -> Result<RecoveryCodesResponse, UserError> {
+        mfa::regenerate_recovery_codes(
+            user_id,
+            code,
+            &self.config.encryption_key,
+            &mut self.pool.get().unwrap(),
+        )
+        .map(|recovery_codes| RecoveryCodesResponse { recovery_codes })
+    }
+
+    /// Trades a refresh token for a new token pair. The presented refresh
+    /// token stops working, and presenting it again revokes the session.
+    pub fn refresh_token(
+        &self,
+        request: RefreshTokenRequest,
+        client: &ClientInfo,
+    ) -> Result<TokenResponse, UserError> {
+        let conn = &mut self.pool.get().unwrap();
+        let refresh_token = self
+            .tokens_manager
+            .verify_refresh_token(&request.refresh_token, conn)?;
+        let session = user::rotate_refresh_token(
+            &refresh_token.login_session,
+            refresh_token.jti.as_deref(),
+            client,
+            conn,
+        )
+        .map_err(|e| {
+            // A replayed refresh token revokes the session
+            self.tokens_manager
+                .forget_login_session(&refresh_token.login_session);
+            e
+        })?;
+        let user = user::get_user(session.user_id, conn)?;
+        let login_session_info = LoginSessionInfo::new(&session, user.username);
         self.tokens_manager
             .generate_login_token_pairs(&login_session_info, conn)
             .map(|t| TokenResponse::bearer(t.0, t.1))
@@ -85,9 +312,89 @@ impl AccountService {
             .login_session
             .ok_or(UserError::TokenError("No login session found".to_string()))?;
         let _ = user::delete_login_session(&session_id, conn);
+        self.tokens_manager.forget_login_session(&session_id);
         Ok(())
     }
 
+    /// The active login sessions of the user, flagging the one of `token_info`.
+    pub fn list_login_sessions(
+        &self,
+        token_info: &TokenInfo,
+    ) -> Result<Vec<LoginSessionResponse>, UserError> {
+        let sessions =
+            user::list_login_sessions(token_info.user_id, &mut self.pool.get().unwrap())?;
+        Ok(sessions
+            .into_iter()
+            .map(|session| {
+                let current =
+                    token_info.login_session.as_deref() == Some(&session.session_id.to_string());
+                LoginSessionResponse {
+                    current,
+                    ..session.into()
+                }
+            })
+            .collect())
+    }
+
+    pub fn revoke_login_session(
+        &self,
+        actor: &AuditActor,
+        user_id: i32,
+        session_id: &str,
+    ) -> Result<(), UserError> {
+        let revoked =
+            user::revoke_login_session(user_id, session_id, &mut self.pool.get().unwrap());
+        self.audit.record(
+            actor,
+            AuditAction::LoginSessionRevoked,
+            AuditTarget::new(AuditTargetType::LoginSession, Some(session_id.to_string())),
+            &revoked,
+        );
+        revoked?;
+        self.tokens_manager.forget_login_session(session_id);
+        Ok(())
+    }
+
+    /// Revokes the login sessions of the user, keeping the one of `token_info`
+    /// unless `include_current` is set. Returns how many were revoked.
+    pub fn revoke_login_sessions(
+        &self,
+        actor: &AuditActor,
+        token_info: &TokenInfo,
+        include_current: bool,
+    ) -> Result<usize, UserError> {
+        let keep = if include_current {
+            None
+        } else {
+            token_info.login_session.as_deref()
+        };
+        let revoked =
+            user::delete_login_sessions(token_info.user_id, keep, &mut self.pool.get().unwrap());
+        self.audit.record(
+            actor,
+            AuditAction::LoginSessionRevoked,
+            AuditTarget::new(AuditTargetType::User, Some(token_info.user_id.to_string())),
+            &revoked,
+        );
+        let revoked = revoked?;
+        self.tokens_manager.forget_user(token_info.user_id);
+        Ok(revoked)
+    }
+
+    fn session_lifetime(&self) -> chrono::Duration {
+        let lifetime = self
+            .config
+            .login_session_lifetime
+            .unwrap_or(user::DEFAULT_LOGIN_SESSION_LIFETIME);
+        chrono::Duration::seconds(lifetime as i64)
+    }
+
+    /// The public keys login tokens are signed with, for other services to
+    /// verify them. Empty unless a token signing algorithm is configured.
+    pub fn jwks(&self) -> Result<JwkSet, UserError> {
+        signing_keys::jwks(&mut self.pool.get().unwrap())
+    }
+
     pub fn get_user(&self, user_id: i32) -> Result<UserProfile, UserError> {
         user::get_user(user_id, &mut self.pool.get().unwrap()).map(Into::into)
     }
@@ -107,16 +414,26 @@ impl AccountService {
 
     pub fn generate_api_keys(
         &self,
+        actor: &AuditActor,
         user_id: i32,
         request: &ApiKeyRequest,
     ) -> Result<ApiKeyResponse, UserError> {
-        let mut api_key: ApiKeyResponse = user::generate_non_login_api_key(
+        let created = user::generate_non_login_api_key(
             user_id,
             &self.config.encryption_key,
             Some(request.comment.clone()),
             &mut self.pool.get().unwrap(),
-        )
-        .map(Into::into)?;
+        );
+        self.audit.record(
+            actor,
+            AuditAction::ApiKeyCreated,
+            AuditTarget::new(
+                AuditTargetType::ApiKey,
+                created.as_ref().ok().map(|api_key| api_key.key.clone()),
+            ),
+            &created,
+        );
+        let mut api_key: ApiKeyResponse = created?.into();
 
         api_key.secret = self.decrypt_secret(&api_key.secret)?;
 
@@ -134,26 +451,49 @@ impl AccountService {
 
     pub fn delete_api_key(
         &self,
+        actor: &AuditActor,
         user_id: i32,
         key_id: &str,
     ) -> Result<ApiKeyResponseWithoutSecret, UserError> {
-        user::delete_api_key(user_id, key_id, &mut self.pool.get().unwrap()).map(Into::into)
+        let deleted = user::delete_api_key(user_id, key_id, &mut self.pool.get().unwrap());
+        self.audit.record(
+            actor,
+            AuditAction::ApiKeyDeleted,
+            AuditTarget::new(AuditTargetType::ApiKey, Some(key_id.to_string())),
+            &deleted,
+        );
+        let api_key = deleted?;
+        self.tokens_manager
+            .forget_api_key(&api_key.key, &api_key.secret);
+        Ok(api_key.into())
     }
 
     pub fn create_project_api_key(
         &self,
+        actor: &AuditActor,
         user_id: i32,
         project_id: &str,
-        request: &ApiKeyRequest,
+        request: &ProjectApiKeyRequest,
     ) -> Result<ApiKeyResponse, UserError> {
         use project_crud::Encryptable;
-        let mut api_key = project_crud::create_api_key(
+        let created = project_crud::create_api_key(
             user_id,
             project_id,
-            Some(request.comment.clone()),
+            request,
             &self.config.encryption_key,
             &mut self.pool.get().unwrap(),
-        )?;
+        );
+        self.audit.record(
+            actor,
+            AuditAction::ApiKeyCreated,
+            AuditTarget::in_project(
+                project_id,
+                AuditTargetType::ProjectApiKey,
+                created.as_ref().ok().map(|api_key| api_key.id.to_string()),
+            ),
+            &created,
+        );
+        let mut api_key = created?;
 
         api_key
             .decrypt(&self.config.encryption_key)
@@ -164,42 +504,73 @@ impl AccountService {
 
     pub fn list_project_api_keys(
         &self,
-        user_id: i32,
         project_id: &str,
     ) -> Result<Vec<ApiKeyResponseWithoutSecret>, UserError> {
-        let keys =
-            project_crud::list_all_api_keys(user_id, project_id, &mut self.pool.get().unwrap())?;
+        let keys = project_crud::list_all_api_keys(project_id, &mut self.pool.get().unwrap())?;
         Ok(keys.into_iter().map(Into::into).collect())
     }
 
     pub fn delete_project_api_key(
         &self,
-        user_id: i32,
+        actor: &AuditActor,
         project_id: &str,
         key_id: i32,
     ) -> Result<ApiKeyResponseWithoutSecret, UserError> {
-        let key = project_crud::delete_api_key(
-            user_id,
-            project_id,
-            key_id,
-            &mut self.pool.get().unwrap(),
-        )?;
+        let deleted =
+            project_crud::delete_api_key(project_id, key_id, &mut self.pool.get().unwrap());
+        self.audit.record(
+            actor,
+            AuditAction::ApiKeyDeleted,
+            AuditTarget::in_project(
+                project_id,
+                AuditTargetType::ProjectApiKey,
+                Some(key_id.to_string()),
+            ),
+            &deleted,
+        );
+        let key = deleted?;
+        self.tokens_manager
+            .forget_api_key(&key.api_key, &key.api_secret);
         Ok(key.into())
     }
 
-    pub fn create_project(
+    pub async fn create_project(
         &self,
+        actor: &AuditActor,
         user_id: i32,
         new_project_request: &ProjectRequest,
     ) -> Result<ProjectInfo, UserError> {
-        let project = project::project_crud::create_project(
+        let created = project::project_crud::create_project(
             user_id,
             new_project_request,
             &self.config.encryption_key,
             &mut self.pool.get().unwrap(),
-        )?;
+        )
+        .await;
+        let target = match &created {
+            Ok(project) => AuditTarget::project(&project.id.to_string()),
+            Err(_) => AuditTarget::new(AuditTargetType::Project, None),
+        };
+        self.audit
+            .record(actor, AuditAction::ProjectCreated, target, &created);
+
+        Ok(created?.into())
+    }
 
-        Ok(project.into())
+    pub async fn validate_project(
+        &self,
+        user_id: i32,
+        project_request: &ProjectRequest,
+    ) -> Result<ProjectValidationReport, UserError> {
+        let report = project::project_crud::validate_project_request(
+            user_id,
+            project_request,
+            &self.config.encryption_key,
+            &mut self.pool.get().unwrap(),
+        )
+        .await?;
+
+        Ok(report)
     }
 
     pub fn get_projects(&self, user_id: i32) -> Result<Vec<ProjectInfo>, UserError> {
@@ -214,12 +585,36 @@ impl AccountService {
         Ok(project.into())
     }
 
-    pub fn delete_project(&self, user_id: i32, project_id: &str) -> Result<ProjectInfo, UserError> {
-        let project = project::project_crud::delete_project(
-            user_id,
-            project_id,
-            &mut self.pool.get().unwrap(),
-        )?;
+    /// Deletes the project softly, it can be restored until the grace period
+    /// ends and its recordings are handled as `deletion` asks.
+    pub fn delete_project(
+        &self,
+        actor: &AuditActor,
+        user_id: i32,
+        project_id: &str,
+        deletion: &DeletionQuery,
+    ) -> Result<ProjectInfo, UserError> {
+        let deleted = DeletionRequest::from_query(
+            deletion,
+            actor.user_id,
+            deletion_crud::grace_period(&self.config),
+        )
+        .and_then(|deletion| {
+            project::project_crud::delete_project(
+                user_id,
+                project_id,
+                &deletion,
+                &mut self.pool.get().unwrap(),
+            )
+        });
+        self.audit.record(
+            actor,
+            AuditAction::ProjectDeleted,
+            AuditTarget::project(project_id),
+            &deleted,
+        );
+        let project = deleted?;
+        self.tokens_manager.forget_project(project_id);
         Ok(project.into())
     }
 
@@ -256,6 +651,54 @@ impl AccountService {
         Ok(project.into())
     }
 
+    pub async fn patch_project(
+        &self,
+        actor: &AuditActor,
+        user_id: i32,
+        project_id: &str,
+        request: &ProjectUpdateRequest,
+    ) -> Result<ProjectInfo, UserError> {
+        let updated = project::project_crud::patch_project(
+            user_id,
+            project_id,
+            request,
+            &self.config.encryption_key,
+            &mut self.pool.get().unwrap(),
+        )
+        .await;
+        self.audit.record(
+            actor,
+            AuditAction::ProjectUpdated,
+            AuditTarget::project(project_id),
+            &updated,
+        );
+        Ok(updated?.into())
+    }
+
+    pub async fn rotate_project_secrets(
+        &self,
+        actor: &AuditActor,
+        user_id: i32,
+        project_id: &str,
+        request: &ProjectSecretsRequest,
+    ) -> Result<ProjectInfo, UserError> {
+        let rotated = project::project_crud::rotate_project_secrets(
+            user_id,
+            project_id,
+            request,
+            &self.config.encryption_key,
+            &mut self.pool.get().unwrap(),
+        )
+        .await;
+        self.audit.record(
+            actor,
+            AuditAction::ProjectSecretsRotated,
+            AuditTarget::project(project_id),
+            &rotated,
+        );
+        Ok(rotated?.into())
+    }
+
     pub fn create_user(
         &self,
         username: &str,
@@ -278,7 +721,8 @@ impl AccountService {
         &self,
         auth_token: &str,
         user_to_verify: &GithubUser,
-    ) -> Result<(String, String), UserError> {
+        client: &ClientInfo,
+    ) -> Result<LoginResponse, UserError> {
         log::info!("Attempting logging in with Github");
         let (client_id, client_secret) = self.get_github_credentials()?;
         log::debug!("Verifying user token");
@@ -288,11 +732,251 @@ impl AccountService {
         let github_user = fetch_github_user(auth_token).await?;
 
         let conn = &mut self.pool.get().unwrap();
-        let login_session =
-            user::login_with_github(&github_user, conn, &self.config.encryption_key)?;
+        let outcome = user::login_with_github(
+            &github_user,
+            client,
+            self.session_lifetime(),
+            conn,
+            &self.config.encryption_key,
+        )?;
 
-        self.tokens_manager
-            .generate_login_token_pairs(&login_session, conn)
+        self.login_response(outcome, conn)
+    }
+
+    pub async fn login_with_google(
+        &self,
+        request: &OidcLoginRequest,
+        client: &ClientInfo,
+    ) -> Result<LoginResponse, UserError> {
+        let provider = self
+            .google_provider
+            .clone()
+            .ok_or_else(|| OidcError::NotConfigured(GOOGLE_PROVIDER.to_string()))?;
+        self.login_with_oidc_provider(&provider, request, client)
+            .await
+    }
+
+    /// Logs in through the generic OpenID Connect provider, e.g. a university SSO.
+    pub async fn login_with_oidc(
+        &self,
+        request: &OidcLoginRequest,
+        client: &ClientInfo,
+    ) -> Result<LoginResponse, UserError> {
+        let provider = self
+            .oidc_provider
+            .clone()
+            .ok_or_else(|| OidcError::NotConfigured("OpenID Connect".to_string()))?;
+        self.login_with_oidc_provider(&provider, request, client)
+            .await
+    }
+
+    async fn login_with_oidc_provider(
+        &self,
+        provider: &OidcProvider,
+        request: &OidcLoginRequest,
+        client: &ClientInfo,
+    ) -> Result<LoginResponse, UserError> {
+        log::info!("Attempting logging in with {}", provider.name());
+        let claims = provider.authenticate(request).await?;
+
+        let conn = &mut self.pool.get().unwrap();
+        let outcome = user::login_with_oidc(
+            provider.name(),
+            &claims,
+            client,
+            self.session_lifetime(),
+            conn,
+            &self.config.encryption_key,
+        )?;
+
+        self.login_response(outcome, conn)
+    }
+
+    pub fn change_password(
+        &self,
+        actor: &AuditActor,
+        token_info: &TokenInfo,
+        request: &PasswordChangeRequest,
+    ) -> Result<(), UserError> {
+        let changed = user::change_password(
+            token_info.user_id,
+            request.current_password.as_deref(),
+            &request.new_password,
+            token_info.login_session.as_deref(),
+            &mut self.pool.get().unwrap(),
+        );
+        self.audit.record(
+            actor,
+            AuditAction::PasswordChanged,
+            AuditTarget::new(AuditTargetType::User, Some(token_info.user_id.to_string())),
+            &changed,
+        );
+        changed?;
+        self.tokens_manager.forget_user(token_info.user_id);
+        Ok(())
+    }
+
+    /// Mails a reset link if an account uses the email. Succeeds either way,
+    /// so the endpoint does not reveal which emails have accounts.
+    pub async fn request_password_reset(
+        &self,
+        request: &PasswordResetRequest,
+    ) -> Result<(), UserError> {
+        let conn = &mut self.pool.get().unwrap();
+        let user = match user::get_user_by_email(request.email.trim(), conn) {
+            Ok(user) => user,
+            Err(UserError::UserNotFound(_)) => return Ok(()),
+            Err(e) => return Err(e),
+        };
+
+        let token = account_tokens::issue_token(
+            user.id,
+            &user.email,
+            AccountTokenPurpose::PasswordReset,
+            chrono::Duration::minutes(account_tokens::PASSWORD_RESET_TTL_MINUTES),
+            &self.config.jwt_secret,
+            conn,
+        )?;
+
+        let email = Email {
+            to: user.email.clone(),
+            subject: "Reset your SyncFlow password".to_string(),
+            body: format!(
+                "Hi {},\n\nUse the link below to choose a new password. It expires in {} minutes and works once.\n\n{}\n\nIf you did not ask for this, you can ignore this email.\n",
+                user.username,
+                account_tokens::PASSWORD_RESET_TTL_MINUTES,
+                self.account_link("reset-password", &token)
+            ),
+        };
+        if let Err(e) = self.mailer.send(email).await {
+            log::error!(
+                "Failed to send the password reset email to {}: {}",
+                user.id,
+                e
+            );
+        }
+        Ok(())
+    }
+
+    /// Sets the new password and logs out every session. Following the link
+    /// also proves the user reads the mailbox, so the email counts as verified.
+    pub fn confirm_password_reset(
+        &self,
+        request: &PasswordResetConfirmRequest,
+    ) -> Result<(), UserError> {
+        let conn = &mut self.pool.get().unwrap();
+        user::validate_password(&request.new_password)?;
+        let token = account_tokens::redeem_token(
+            &request.token,
+            AccountTokenPurpose::PasswordReset,
+            &self.config.jwt_secret,
+            conn,
+        )?;
+
+        user::set_password(token.user_id, &request.new_password, None, conn)?;
+        self.tokens_manager.forget_user(token.user_id);
+        let _ = user::mark_email_verified(token.user_id, &token.email, conn);
+        Ok(())
+    }
+
+    pub async fn request_email_verification(&self, user_id: i32) -> Result<(), UserError> {
+        let user = user::get_user(user_id, &mut self.pool.get().unwrap())?;
+        if user.email_verified_at.is_some() {
+            return Err(UserError::Conflict(
+                "The email address is already verified".to_string(),
+            ));
+        }
+
+        self.send_verification_email(&user).await
+    }
+
+    pub fn confirm_email_verification(
+        &self,
+        request: &EmailVerificationConfirmRequest,
+    ) -> Result<UserProfile, UserError> {
+        let conn = &mut self.pool.get().unwrap();
+        let token = account_tokens::redeem_token(
+            &request.token,
+            AccountTokenPurpose::EmailVerification,
+            &self.config.jwt_secret,
+            conn,
+        )?;
+
+        user::mark_email_verified(token.user_id, &token.email, conn).map(Into::into)
+    }
+
+    async fn send_verification_email(&self, user: &User) -> Result<(), UserError> {
+        if user.email.is_empty() {
+            return Err(UserError::Conflict(
+                "The account has no email address".to_string(),
+            ));
+        }
+
+        let token = account_tokens::issue_token(
+            user.id,
+            &user.email,
+            AccountTokenPurpose::EmailVerification,
+            chrono::Duration::hours(account_tokens::EMAIL_VERIFICATION_TTL_HOURS),
+            &self.config.jwt_secret,
+            &mut self.pool.get().unwrap(),
+        )?;
+
+        let email = Email {
+            to: user.email.clone(),
+            subject: "Verify your SyncFlow email address".to_string(),
+            body: format!(
+                "Hi {},\n\nConfirm that this is your email address with the link below. It expires in {} hours.\n\n{}\n",
+                user.username,
+                account_tokens::EMAIL_VERIFICATION_TTL_HOURS,
+                self.account_link("verify-email", &token)
+            ),
+        };
+        self.mailer.send(email).await?;
+        Ok(())
+    }
+
+    /// Link to a dashboard page handling a mailed token, or the bare token
+    /// when no dashboard URL is configured.
+    fn account_link(&self, page: &str, token: &str) -> String {
+        match &self.config.dashboard_url {
+            Some(dashboard_url) => format!(
+                "{}/{}?token={}",
+                dashboard_url.trim_end_matches('/'),
+                page,
+                token
+            ),
+            None => format!("Token: {}", token),
+        }
+    }
+
+    pub fn update_profile(
+        &self,
+        user_id: i32,
+        request: &ProfileUpdateRequest,
+    ) -> Result<UserProfile, UserError> {
+        user::update_profile(user_id, request, &mut self.pool.get().unwrap()).map(Into::into)
+    }
+
+    pub fn delete_account(
+        &self,
+        actor: &AuditActor,
+        user_id: i32,
+        request: &AccountDeletionRequest,
+    ) -> Result<(), UserError> {
+        let deleted = user::delete_account(
+            user_id,
+            request.password.as_deref(),
+            &mut self.pool.get().unwrap(),
+        );
+        self.audit.record(
+            actor,
+            AuditAction::AccountDeleted,
+            AuditTarget::new(AuditTargetType::User, Some(user_id.to_string())),
+            &deleted,
+        );
+        deleted?;
+        self.tokens_manager.forget_user(user_id);
+        Ok(())
     }
 
     fn get_github_credentials(&self) -> Result<(String, String), UserError> {
@@ -309,11 +993,11 @@ impl Clone for AccountService {
         AccountService {
             pool: self.pool.clone(),
             config: self.config.clone(),
-            tokens_manager: tokens_manager::JWTTokensManager::new(
-                &self.config.encryption_key.clone(),
-                self.config.jwt_expiration,
-                self.config.jwt_refresh_expiration,
-            ),
+            tokens_manager: self.tokens_manager.clone(),
+            google_provider: self.google_provider.clone(),
+            oidc_provider: self.oidc_provider.clone(),
+            mailer: self.mailer.clone(),
+            audit: self.audit.clone(),
         }
     }
 }
@@ -0,0 +1,186 @@
+use chrono::{Duration, Utc};
+use diesel::prelude::*;
+use diesel::PgConnection;
+use domain::models::{AccountToken, AccountTokenPurpose, NewAccountToken};
+use hmac::{Hmac, Mac};
+use rand::{distributions::Alphanumeric, thread_rng, Rng};
+use sha2::{Digest, Sha256};
+use uuid::Uuid;
+
+use crate::users::user::UserError;
+
+pub const PASSWORD_RESET_TTL_MINUTES: i64 = 60;
+pub const EMAIL_VERIFICATION_TTL_HOURS: i64 = 48;
+
+const SECRET_LENGTH: usize = 32;
+const TOKEN_SEPARATOR: char = '.';
+
+fn hex(bytes: &[u8]) -> String {
+    bytes.iter().map(|b| format!("{:02x}", b)).collect()
+}
+
+fn mac(signing_key: &str, purpose: AccountTokenPurpose, id: Uuid, secret: &str) -> Hmac<Sha256> {
+    let mut mac = Hmac::<Sha256>::new_from_slice(signing_key.as_bytes())
+        .expect("HMAC accepts keys of any length");
+    mac.update(purpose.as_str().as_bytes());
+    mac.update(b".");
+    mac.update(id.to_string().as_bytes());
+    mac.update(b".");
+    mac.update(secret.as_bytes());
+    mac
+}
+
+/// Builds the `{id}.{secret}.{signature}` token handed to the user. The
+/// signature binds the token to its purpose, so a verification link can not
+/// be replayed as a reset link, and lets forged tokens be rejected without a
+/// database lookup.
+pub fn sign_token(
+    signing_key: &str,
+    purpose: AccountTokenPurpose,
+    id: Uuid,
+    secret: &str,
+) -> String {
+    let signature = hex(&mac(signing_key, purpose, id, secret)
+        .finalize()
+        .into_bytes());
+    format!("{id}{sep}{secret}{sep}{signature}", sep = TOKEN_SEPARATOR)
+}
+
+/// Returns the id and secret of a token whose signature is valid for `purpose`.
+pub fn verify_token_signature<'a>(
+    signing_key: &str,
+    purpose: AccountTokenPurpose,
+    token: &'a str,
+) -> Option<(Uuid, &'a str)> {
+    let mut parts = token.trim().split(TOKEN_SEPARATOR);
+    let (id, secret, signature) = match (parts.next(), parts.next(), parts.next(), parts.next()) {
+        (Some(id), Some(secret), Some(signature), None) => (id, secret, signature),
+        _ => return None,
+    };
+
+    let id = Uuid::parse_str(id).ok()?;
+    let signature = (0..signature.len())
+        .step_by(2)
+        .map(|i| {
+            signature
+                .get(i..i + 2)
+                .and_then(|b| u8::from_str_radix(b, 16).ok())
+        })
+        .collect::<Option<Vec<u8>>>()?;
+
+    mac(signing_key, purpose, id, secret)
+        .verify_slice(&signature)
+        .ok()
+        .map(|_| (id, secret))
+}
+
+fn hash_secret(secret: &str) -> String {
+    hex(&Sha256::digest(secret.as_bytes()))
+}
+
+/// Issues a token for `email`, superseding the unused tokens of the same
+/// purpose the user still has.
+pub fn issue_token(
+    uid: i32,
+    email: &str,
+    purpose: AccountTokenPurpose,
+    ttl: Duration,
+    signing_key: &str,
+    conn: &mut PgConnection,
+) -> Result<String, UserError> {
+    use domain::schema::syncflow::account_tokens::dsl;
+
+    let secret: String = thread_rng()
+        .sample_iter(&Alphanumeric)
+        .take(SECRET_LENGTH)
+        .map(char::from)
+        .collect();
+    let now = Utc::now().naive_utc();
+
+    let token = conn.transaction(|conn| {
+        diesel::update(
+            dsl::account_tokens
+                .filter(dsl::user_id.eq(uid))
+                .filter(dsl::purpose.eq(purpose))
+                .filter(dsl::used_at.is_null()),
+        )
+        .set(dsl::used_at.eq(now))
+        .execute(conn)?;
+
+        diesel::insert_into(dsl::account_tokens)
+            .values(&NewAccountToken {
+                purpose,
+                secret_hash: hash_secret(&secret),
+                email: email.to_string(),
+                expires_at: now + ttl,
+                user_id: uid,
+            })
+            .get_result::<AccountToken>(conn)
+    })?;
+
+    Ok(sign_token(signing_key, purpose, token.id, &secret))
+}
+
+/// Marks the token used and returns it. Claiming happens in a single
+/// conditional update, so concurrent attempts can not use a token twice.
+pub fn redeem_token(
+    token: &str,
+    purpose: AccountTokenPurpose,
+    signing_key: &str,
+    conn: &mut PgConnection,
+) -> Result<AccountToken, UserError> {
+    use domain::schema::syncflow::account_tokens::dsl;
+
+    let invalid =
+        || UserError::InvalidAccountToken("The link is invalid or has expired".to_string());
+    let (token_id, secret) =
+        verify_token_signature(signing_key, purpose, token).ok_or_else(invalid)?;
+    let now = Utc::now().naive_utc();
+
+    diesel::update(
+        dsl::account_tokens
+            .find(token_id)
+            .filter(dsl::purpose.eq(purpose))
+            .filter(dsl::secret_hash.eq(hash_secret(secret)))
+            .filter(dsl::used_at.is_null())
+            .filter(dsl::expires_at.gt(now)),
+    )
+    .set(dsl::used_at.eq(now))
+    .get_result::<AccountToken>(conn)
+    .optional()?
+    .ok_or_else(invalid)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_token_signature() {
+        let id = Uuid::new_v4();
+        let token = sign_token("key", AccountTokenPurpose::PasswordReset, id, "secret");
+
+        assert_eq!(
+            verify_token_signature("key", AccountTokenPurpose::PasswordReset, &token),
+            Some((id, "secret"))
+        );
+        assert_eq!(
+            verify_token_signature("key", AccountTokenPurpose::EmailVerification, &token),
+            None
+        );
+        assert_eq!(
+            verify_token_signature("other-key", AccountTokenPurpose::PasswordReset, &token),
+            None
+        );
+
+        let tampered = token.replacen("secret", "secreT", 1);
+        assert_eq!(
+            verify_token_signature("key", AccountTokenPurpose::PasswordReset, &tampered),
+            None
+        );
+        assert_eq!(
+            verify_token_signature("key", AccountTokenPurpose::PasswordReset, "not-a-token"),
+            None
+        );
+    }
+}
@@ -0,0 +1,137 @@
+use std::time::Duration;
+
+use aws_config::{BehaviorVersion, Region};
+use aws_sdk_kms::error::{DisplayErrorContext, SdkError};
+use aws_sdk_kms::primitives::Blob;
+use aws_sdk_kms::{config::Credentials, Client};
+use shared::deployment_config::AwsKmsConfig;
+
+use crate::cache::TtlCache;
+use crate::users::secret::{decode_base64, encode_base64, SecretError};
+
+use super::runtime::ProviderRuntime;
+use super::KeyProvider;
+
+pub const PROVIDER_NAME: &str = "awskms";
+
+const UNWRAPPED_KEY_TTL_SECONDS: u64 = 300;
+const UNWRAPPED_KEY_CAPACITY: usize = 1024;
+
+fn kms_error<E: std::error::Error + 'static, R: std::fmt::Debug>(
+    error: SdkError<E, R>,
+) -> SecretError {
+    SecretError::KeyProviderError(format!("KMS: {}", DisplayErrorContext(&error)))
+}
+
+/// Wraps data keys with an AWS KMS key, or a key of any service speaking the
+/// KMS API (e.g. LocalStack) when an endpoint is configured.
+///
+/// KMS ciphertexts name the key version they were made with, so rotating the
+/// key in KMS never makes wrapped keys stale on our side.
+pub struct AwsKmsProvider {
+    client: Client,
+    key_id: String,
+    runtime: ProviderRuntime,
+    unwrapped_keys: TtlCache<String, Vec<u8>>,
+}
+
+impl AwsKmsProvider {
+    pub fn new(kms_config: AwsKmsConfig) -> Result<Self, SecretError> {
+        let mut loader = aws_config::defaults(BehaviorVersion::latest())
+            .region(Region::new(kms_config.region.clone()));
+
+        if let Some(endpoint) = &kms_config.endpoint {
+            loader = loader.endpoint_url(endpoint);
+        }
+
+        match (&kms_config.access_key, &kms_config.secret_key) {
+            (Some(access_key), Some(secret_key)) => {
+                loader = loader.credentials_provider(Credentials::new(
+                    access_key, secret_key, None, None, "syncflow",
+                ));
+            }
+            (None, None) => {}
+            _ => {
+                return Err(SecretError::KeyProviderError(
+                    "the KMS access key and secret key must be set together".to_string(),
+                ))
+            }
+        }
+
+        // The default credential chain may query the instance metadata
+        // service, so the config is loaded on the provider runtime too
+        let runtime = ProviderRuntime::new()?;
+        let sdk_config = runtime.run(async move { Ok(loader.load().await) })?;
+
+        Ok(Self {
+            client: Client::new(&sdk_config),
+            key_id: kms_config.key_id,
+            runtime,
+            unwrapped_keys: TtlCache::new(
+                Duration::from_secs(UNWRAPPED_KEY_TTL_SECONDS),
+                UNWRAPPED_KEY_CAPACITY,
+            ),
+        })
+    }
+}
+
+impl KeyProvider for AwsKmsProvider {
+    fn name(&self) -> &'static str {
+        PROVIDER_NAME
+    }
+
+    fn wrap_key(&self, data_key: &[u8]) -> Result<String, SecretError> {
+        let request = self
+            .client
+            .encrypt()
+            .key_id(&self.key_id)
+            .plaintext(Blob::new(data_key));
+
+        let ciphertext = self.runtime.run(async move {
+            request
+                .send()
+                .await
+                .map_err(kms_error)?
+                .ciphertext_blob
+                .ok_or_else(|| {
+                    SecretError::KeyProviderError("KMS returned no ciphertext".to_string())
+                })
+        })?;
+
+        Ok(encode_base64(ciphertext.as_ref()))
+    }
+
+    fn unwrap_key(&self, wrapped_key: &str) -> Result<Vec<u8>, SecretError> {
+        if let Some(data_key) = self.unwrapped_keys.get(&wrapped_key.to_string()) {
+            return Ok(data_key);
+        }
+
+        let ciphertext = decode_base64(wrapped_key)
+            .map_err(|e| SecretError::Base64DecodeError(e.to_string()))?;
+        let request = self
+            .client
+            .decrypt()
+            .ciphertext_blob(Blob::new(ciphertext))
+            .key_id(&self.key_id);
+
+        let plaintext = self.runtime.run(async move {
+            request
+                .send()
+                .await
+                .map_err(kms_error)?
+                .plaintext
+                .ok_or_else(|| {
+                    SecretError::KeyProviderError("KMS returned no plaintext".to_string())
+                })
+        })?;
+
+        let data_key = plaintext.into_inner();
+        self.unwrapped_keys
+            .insert(wrapped_key.to_string(), data_key.clone());
+        Ok(data_key)
+    }
+
+    fn is_stale(&self, _wrapped_key: &str) -> bool {
+        false
+    }
+}
@@ -0,0 +1,40 @@
+use crate::users::secret::{decode_base64, encode_base64, Keyring, SecretError};
+
+use super::KeyProvider;
+
+pub const PROVIDER_NAME: &str = "local";
+
+/// Wraps data keys with the keyring from the `encryption_key` setting. This
+/// is the default provider and needs nothing but the environment.
+pub struct LocalKeyProvider {
+    keyring: Keyring,
+}
+
+impl LocalKeyProvider {
+    pub fn new(keyring: Keyring) -> Self {
+        Self { keyring }
+    }
+
+    pub fn parse(encryption_key: &str) -> Result<Self, SecretError> {
+        Keyring::parse(encryption_key).map(Self::new)
+    }
+}
+
+impl KeyProvider for LocalKeyProvider {
+    fn name(&self) -> &'static str {
+        PROVIDER_NAME
+    }
+
+    fn wrap_key(&self, data_key: &[u8]) -> Result<String, SecretError> {
+        self.keyring.encrypt(&encode_base64(data_key))
+    }
+
+    fn unwrap_key(&self, wrapped_key: &str) -> Result<Vec<u8>, SecretError> {
+        decode_base64(&self.keyring.decrypt(wrapped_key)?)
+            .map_err(|e| SecretError::Base64DecodeError(e.to_string()))
+    }
+
+    fn is_stale(&self, wrapped_key: &str) -> bool {
+        self.keyring.is_stale(wrapped_key)
+    }
+}
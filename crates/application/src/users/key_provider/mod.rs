@@ -0,0 +1,227 @@
+use std::sync::{Arc, OnceLock};
+
+use shared::deployment_config::{DeploymentConfig, KeyProviderConfig};
+
+use crate::users::secret::{
+    decode_base64, decrypt_aes_256_gcm, encode_base64, encrypt_aes_256_gcm, SecretError,
+};
+
+pub mod local;
+
+#[cfg(feature = "aws-kms")]
+pub mod aws_kms;
+#[cfg(feature = "vault")]
+pub mod vault;
+
+#[cfg(any(feature = "vault", feature = "aws-kms"))]
+mod runtime;
+
+const ENVELOPE_PREFIX: &str = "env";
+const ENVELOPE_SEPARATOR: char = '$';
+const DATA_KEY_LENGTH: usize = 32;
+
+/// Wraps and unwraps the data keys that encrypt individual secrets.
+///
+/// Every stored secret is encrypted with a data key of its own, and only the
+/// wrapped data key is stored next to it. The key encryption key never
+/// leaves the provider. Calls are synchronous because secrets are encrypted
+/// inside database code; remote providers block the calling thread.
+pub trait KeyProvider: Send + Sync {
+    /// Name recorded in envelopes, so values can be routed back to the
+    /// provider that wrapped their data key.
+    fn name(&self) -> &'static str;
+
+    fn wrap_key(&self, data_key: &[u8]) -> Result<String, SecretError>;
+
+    fn unwrap_key(&self, wrapped_key: &str) -> Result<Vec<u8>, SecretError>;
+
+    /// Whether the data key was wrapped with a key encryption key that is no
+    /// longer the current one.
+    fn is_stale(&self, wrapped_key: &str) -> bool;
+}
+
+static INSTALLED_PROVIDER: OnceLock<Arc<dyn KeyProvider>> = OnceLock::new();
+
+/// Builds the configured remote provider, or `None` for the local keyring.
+pub fn from_config(config: &DeploymentConfig) -> Result<Option<Arc<dyn KeyProvider>>, SecretError> {
+    match &config.key_provider_config {
+        None | Some(KeyProviderConfig::Local) => Ok(None),
+        #[cfg(feature = "vault")]
+        Some(KeyProviderConfig::Vault(vault_config)) => Ok(Some(Arc::new(
+            vault::VaultTransitProvider::new(vault_config.clone())?,
+        ))),
+        #[cfg(not(feature = "vault"))]
+        Some(KeyProviderConfig::Vault(_)) => Err(SecretError::KeyProviderError(
+            "the Vault key provider requires the vault feature".to_string(),
+        )),
+        #[cfg(feature = "aws-kms")]
+        Some(KeyProviderConfig::AwsKms(kms_config)) => Ok(Some(Arc::new(
+            aws_kms::AwsKmsProvider::new(kms_config.clone())?,
+        ))),
+        #[cfg(not(feature = "aws-kms"))]
+        Some(KeyProviderConfig::AwsKms(_)) => Err(SecretError::KeyProviderError(
+            "the AWS KMS key provider requires the aws-kms feature".to_string(),
+        )),
+    }
+}
+
+/// Installs the configured provider for the lifetime of the process and
+/// returns its name. Must run before the first secret is encrypted.
+pub fn install_from_config(config: &DeploymentConfig) -> Result<&'static str, SecretError> {
+    match from_config(config)? {
+        Some(provider) => {
+            let name = provider.name();
+            INSTALLED_PROVIDER.get_or_init(|| provider);
+            Ok(name)
+        }
+        None => Ok(local::PROVIDER_NAME),
+    }
+}
+
+/// The provider wrapping new data keys: the installed one, or the keyring in
+/// `encryption_key`.
+pub fn active_provider(encryption_key: &str) -> Result<Arc<dyn KeyProvider>, SecretError> {
+    match INSTALLED_PROVIDER.get() {
+        Some(provider) => Ok(provider.clone()),
+        None => Ok(Arc::new(local::LocalKeyProvider::parse(encryption_key)?)),
+    }
+}
+
+/// The provider that can unwrap data keys wrapped by `name`. Values wrapped
+/// by the local keyring stay readable after switching to a remote provider,
+/// as long as their key is still configured in `encryption_key`.
+pub fn provider_named(
+    name: &str,
+    encryption_key: &str,
+) -> Result<Arc<dyn KeyProvider>, SecretError> {
+    match INSTALLED_PROVIDER.get() {
+        Some(provider) if provider.name() == name => Ok(provider.clone()),
+        _ if name == local::PROVIDER_NAME => {
+            Ok(Arc::new(local::LocalKeyProvider::parse(encryption_key)?))
+        }
+        _ => Err(SecretError::UnknownKeyProvider(name.to_string())),
+    }
+}
+
+/// An encrypted value along with its wrapped data key, stored as
+/// `env$<provider>$<wrapped data key>$<base64(nonce || ciphertext)>`.
+/// Neither base64 nor the wrapped keys of any provider contain `$`.
+#[derive(Debug, PartialEq, Eq)]
+pub struct Envelope<'a> {
+    pub provider: &'a str,
+    pub wrapped_key: &'a str,
+    pub ciphertext: &'a str,
+}
+
+impl<'a> Envelope<'a> {
+    /// Returns `None` for values encrypted directly with the keyring.
+    pub fn parse(encrypted: &'a str) -> Option<Self> {
+        let mut parts = encrypted.splitn(4, ENVELOPE_SEPARATOR);
+        if parts.next() != Some(ENVELOPE_PREFIX) {
+            return None;
+        }
+
+        match (parts.next(), parts.next(), parts.next()) {
+            (Some(provider), Some(wrapped_key), Some(ciphertext)) => Some(Envelope {
+                provider,
+                wrapped_key,
+                ciphertext,
+            }),
+            _ => None,
+        }
+    }
+}
+
+/// Encrypts `input` with a fresh data key wrapped by `provider`.
+pub fn seal(input: &str, provider: &dyn KeyProvider) -> Result<String, SecretError> {
+    let data_key: [u8; DATA_KEY_LENGTH] = rand::random();
+    let encrypted = encrypt_aes_256_gcm(input.as_bytes(), &data_key)
+        .map_err(|e| SecretError::EncryptionError(e.to_string()))?;
+    let wrapped_key = provider.wrap_key(&data_key)?;
+
+    Ok(format!(
+        "{prefix}{sep}{provider}{sep}{wrapped_key}{sep}{ciphertext}",
+        prefix = ENVELOPE_PREFIX,
+        sep = ENVELOPE_SEPARATOR,
+        provider = provider.name(),
+        wrapped_key = wrapped_key,
+        ciphertext = encode_base64(&encrypted)
+    ))
+}
+
+/// Decrypts an envelope whose data key `provider` can unwrap.
+pub fn open(envelope: &Envelope, provider: &dyn KeyProvider) -> Result<String, SecretError> {
+    let data_key = provider.unwrap_key(envelope.wrapped_key)?;
+    if data_key.len() != DATA_KEY_LENGTH {
+        return Err(SecretError::DecryptionError(
+            "the unwrapped data key is not 256 bits long".to_string(),
+        ));
+    }
+
+    let encrypted = decode_base64(envelope.ciphertext)
+        .map_err(|e| SecretError::Base64DecodeError(e.to_string()))?;
+    let decrypted = decrypt_aes_256_gcm(&encrypted, &data_key)
+        .map_err(|e| SecretError::DecryptionError(e.to_string()))?;
+
+    String::from_utf8(decrypted).map_err(|e| SecretError::DecryptionError(e.to_string()))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn local_provider() -> local::LocalKeyProvider {
+        let key: [u8; 32] = rand::random();
+        local::LocalKeyProvider::parse(&format!("1:{}", encode_base64(&key))).unwrap()
+    }
+
+    #[test]
+    fn test_seal_and_open() {
+        let provider = local_provider();
+        let sealed = seal("Hello World", &provider).unwrap();
+        assert!(sealed.starts_with("env$local$v1:"));
+
+        let envelope = Envelope::parse(&sealed).unwrap();
+        assert_eq!(envelope.provider, "local");
+        assert_eq!(open(&envelope, &provider).unwrap(), "Hello World");
+
+        // Every value gets a data key of its own
+        let other = seal("Hello World", &provider).unwrap();
+        assert_ne!(
+            Envelope::parse(&other).unwrap().wrapped_key,
+            envelope.wrapped_key
+        );
+    }
+
+    #[test]
+    fn test_open_with_another_key_fails() {
+        let sealed = seal("secret", &local_provider()).unwrap();
+        let envelope = Envelope::parse(&sealed).unwrap();
+        assert!(open(&envelope, &local_provider()).is_err());
+    }
+
+    #[test]
+    fn test_parse_envelope() {
+        assert_eq!(
+            Envelope::parse("env$vault$vault:v1:abc$Zm9v"),
+            Some(Envelope {
+                provider: "vault",
+                wrapped_key: "vault:v1:abc",
+                ciphertext: "Zm9v",
+            })
+        );
+        assert_eq!(Envelope::parse("v1:Zm9v"), None);
+        assert_eq!(Envelope::parse("Zm9vYmFy"), None);
+        assert_eq!(Envelope::parse("env$local$v1:abc"), None);
+    }
+
+    #[test]
+    fn test_unknown_provider() {
+        let key: [u8; 32] = rand::random();
+        match provider_named("vault", &encode_base64(&key)) {
+            Err(SecretError::UnknownKeyProvider(name)) => assert_eq!(name, "vault"),
+            Err(e) => panic!("unexpected error {}", e),
+            Ok(_) => panic!("no vault provider is installed"),
+        }
+    }
+}
@@ -0,0 +1,42 @@
+use std::future::Future;
+use std::sync::mpsc;
+
+use tokio::runtime::{Builder, Runtime};
+
+use crate::users::secret::SecretError;
+
+/// A runtime owned by a remote key provider.
+///
+/// Secrets are encrypted from synchronous code that may itself run inside an
+/// async runtime, where blocking on a future would panic. Provider requests
+/// are spawned here instead and their results awaited over a channel.
+pub(crate) struct ProviderRuntime {
+    runtime: Runtime,
+}
+
+impl ProviderRuntime {
+    pub fn new() -> Result<Self, SecretError> {
+        Builder::new_multi_thread()
+            .worker_threads(1)
+            .thread_name("key-provider")
+            .enable_all()
+            .build()
+            .map(|runtime| Self { runtime })
+            .map_err(|e| SecretError::KeyProviderError(e.to_string()))
+    }
+
+    pub fn run<F, T>(&self, future: F) -> Result<T, SecretError>
+    where
+        F: Future<Output = Result<T, SecretError>> + Send + 'static,
+        T: Send + 'static,
+    {
+        let (sender, receiver) = mpsc::channel();
+        self.runtime.spawn(async move {
+            let _ = sender.send(future.await);
+        });
+
+        receiver.recv().map_err(|_| {
+            SecretError::KeyProviderError("the key provider request was dropped".to_string())
+        })?
+    }
+}
@@ -0,0 +1,221 @@
+use std::time::Duration;
+
+use serde::{Deserialize, Serialize};
+use shared::deployment_config::VaultTransitConfig;
+
+use crate::cache::TtlCache;
+use crate::users::secret::{decode_base64, encode_base64, SecretError};
+
+use super::runtime::ProviderRuntime;
+use super::KeyProvider;
+
+pub const PROVIDER_NAME: &str = "vault";
+
+const DEFAULT_MOUNT_PATH: &str = "transit";
+const DEFAULT_TIMEOUT_SECONDS: u64 = 10;
+const UNWRAPPED_KEY_TTL_SECONDS: u64 = 300;
+const UNWRAPPED_KEY_CAPACITY: usize = 1024;
+
+#[derive(Serialize)]
+struct EncryptRequest {
+    plaintext: String,
+}
+
+#[derive(Serialize)]
+struct DecryptRequest {
+    ciphertext: String,
+}
+
+#[derive(Deserialize)]
+struct TransitResponse<T> {
+    data: T,
+}
+
+#[derive(Deserialize)]
+struct EncryptData {
+    ciphertext: String,
+}
+
+#[derive(Deserialize)]
+struct DecryptData {
+    plaintext: String,
+}
+
+/// Wraps data keys with a key of the HashiCorp Vault transit secrets engine.
+///
+/// Rotating the transit key keeps older versions able to decrypt, so wrapped
+/// keys never turn stale on our side. Unwrapped keys are cached briefly to
+/// spare Vault a round trip for every secret read.
+pub struct VaultTransitProvider {
+    client: reqwest::Client,
+    vault_config: VaultTransitConfig,
+    runtime: ProviderRuntime,
+    unwrapped_keys: TtlCache<String, Vec<u8>>,
+}
+
+impl VaultTransitProvider {
+    pub fn new(vault_config: VaultTransitConfig) -> Result<Self, SecretError> {
+        let timeout = Duration::from_secs(
+            vault_config
+                .timeout_seconds
+                .unwrap_or(DEFAULT_TIMEOUT_SECONDS),
+        );
+        let client = reqwest::Client::builder()
+            .timeout(timeout)
+            .build()
+            .map_err(|e| SecretError::KeyProviderError(e.to_string()))?;
+
+        Ok(Self {
+            client,
+            vault_config,
+            runtime: ProviderRuntime::new()?,
+            unwrapped_keys: TtlCache::new(
+                Duration::from_secs(UNWRAPPED_KEY_TTL_SECONDS),
+                UNWRAPPED_KEY_CAPACITY,
+            ),
+        })
+    }
+
+    fn url(&self, operation: &str) -> String {
+        format!(
+            "{}/v1/{}/{}/{}",
+            self.vault_config.address.trim_end_matches('/'),
+            self.vault_config
+                .mount_path
+                .as_deref()
+                .unwrap_or(DEFAULT_MOUNT_PATH)
+                .trim_matches('/'),
+            operation,
+            self.vault_config.key_name
+        )
+    }
+
+    fn call<B, T>(&self, operation: &str, body: B) -> Result<T, SecretError>
+    where
+        B: Serialize + Send + 'static,
+        T: for<'de> Deserialize<'de> + Send + 'static,
+    {
+        let request = self
+            .client
+            .post(self.url(operation))
+            .header("X-Vault-Token", &self.vault_config.token)
+            .json(&body);
+
+        self.runtime.run(async move {
+            let response = request
+                .send()
+                .await
+                .and_then(|response| response.error_for_status())
+                .map_err(|e| SecretError::KeyProviderError(format!("Vault: {}", e)))?;
+
+            response
+                .json::<TransitResponse<T>>()
+                .await
+                .map(|response| response.data)
+                .map_err(|e| SecretError::KeyProviderError(format!("Vault: {}", e)))
+        })
+    }
+}
+
+impl KeyProvider for VaultTransitProvider {
+    fn name(&self) -> &'static str {
+        PROVIDER_NAME
+    }
+
+    fn wrap_key(&self, data_key: &[u8]) -> Result<String, SecretError> {
+        let data: EncryptData = self.call(
+            "encrypt",
+            EncryptRequest {
+                plaintext: encode_base64(data_key),
+            },
+        )?;
+
+        Ok(data.ciphertext)
+    }
+
+    fn unwrap_key(&self, wrapped_key: &str) -> Result<Vec<u8>, SecretError> {
+        if let Some(data_key) = self.unwrapped_keys.get(&wrapped_key.to_string()) {
+            return Ok(data_key);
+        }
+
+        let data: DecryptData = self.call(
+            "decrypt",
+            DecryptRequest {
+                ciphertext: wrapped_key.to_string(),
+            },
+        )?;
+        let data_key = decode_base64(&data.plaintext)
+            .map_err(|e| SecretError::Base64DecodeError(e.to_string()))?;
+
+        self.unwrapped_keys
+            .insert(wrapped_key.to_string(), data_key.clone());
+        Ok(data_key)
+    }
+
+    fn is_stale(&self, _wrapped_key: &str) -> bool {
+        false
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::users::key_provider::{open, seal, Envelope};
+
+    /// Connects to a Vault dev server, e.g. the one in
+    /// `docker/docker-compose.vault.yaml`, and prepares a transit key.
+    fn dev_provider() -> VaultTransitProvider {
+        let address =
+            std::env::var("VAULT_ADDR").unwrap_or_else(|_| "http://127.0.0.1:8200".to_string());
+        let token = std::env::var("VAULT_TOKEN").unwrap_or_else(|_| "root".to_string());
+        let key_name = "syncflow-test".to_string();
+
+        tokio::runtime::Runtime::new().unwrap().block_on(async {
+            let client = reqwest::Client::new();
+            // Fails harmlessly when the engine is already mounted
+            let _ = client
+                .post(format!("{}/v1/sys/mounts/transit", address))
+                .header("X-Vault-Token", &token)
+                .json(&serde_json::json!({ "type": "transit" }))
+                .send()
+                .await;
+            client
+                .post(format!("{}/v1/transit/keys/{}", address, key_name))
+                .header("X-Vault-Token", &token)
+                .send()
+                .await
+                .and_then(|response| response.error_for_status())
+                .expect("failed to create the transit key");
+        });
+
+        VaultTransitProvider::new(VaultTransitConfig {
+            address,
+            token,
+            key_name,
+            mount_path: None,
+            timeout_seconds: None,
+        })
+        .unwrap()
+    }
+
+    #[test]
+    #[ignore = "requires a Vault dev server"]
+    fn test_vault_round_trip() {
+        let provider = dev_provider();
+        let sealed = seal("Hello World", &provider).unwrap();
+        assert!(sealed.starts_with("env$vault$vault:v"));
+
+        let envelope = Envelope::parse(&sealed).unwrap();
+        assert_eq!(open(&envelope, &provider).unwrap(), "Hello World");
+
+        // A fresh provider has nothing cached and has to ask Vault
+        assert_eq!(open(&envelope, &dev_provider()).unwrap(), "Hello World");
+    }
+
+    #[test]
+    #[ignore = "requires a Vault dev server"]
+    fn test_vault_rejects_tampered_keys() {
+        let provider = dev_provider();
+        assert!(provider.unwrap_key("vault:v1:bm90IGEga2V5").is_err());
+    }
+}
@@ -0,0 +1,327 @@
+use diesel::prelude::*;
+use diesel::PgConnection;
+use domain::models::{
+    ApiKey, JwtSigningKey, LivekitCredential, Project, ProjectAPIKey, StorageCredential, UserTotp,
+};
+use thiserror::Error;
+
+use crate::project::project_crud::Encryptable;
+use crate::users::secret::{self, Keyring, SecretError};
+
+#[derive(Debug, Error)]
+pub enum KeyRotationError {
+    #[error("Database Error: {0}")]
+    DatabaseError(#[from] diesel::result::Error),
+
+    #[error("Encryption Error: {0}")]
+    EncryptionError(#[from] SecretError),
+
+    #[error("Failed to re-encrypt {table} row {id}: {source}")]
+    RowError {
+        table: &'static str,
+        id: String,
+        source: SecretError,
+    },
+}
+
+/// Rows of one table and how many of them held values that were not sealed
+/// by the active key provider with its current key.
+#[derive(Debug, Clone)]
+pub struct ReencryptionSummary {
+    pub table: &'static str,
+    pub rows: usize,
+    pub stale: usize,
+}
+
+fn is_stale(encryption_key: &str, ciphertexts: &[&str]) -> Result<bool, KeyRotationError> {
+    for ciphertext in ciphertexts {
+        if secret::is_stale(ciphertext, encryption_key)? {
+            return Ok(true);
+        }
+    }
+    Ok(false)
+}
+
+fn rewrap<T: Encryptable>(
+    row: &mut T,
+    table: &'static str,
+    id: impl ToString,
+    encryption_key: &str,
+) -> Result<(), KeyRotationError> {
+    row.decrypt(encryption_key)
+        .and_then(|_| row.encrypt(encryption_key))
+        .map_err(|source| KeyRotationError::RowError {
+            table,
+            id: id.to_string(),
+            source,
+        })
+}
+
+/// Re-encrypts every stored secret that is not an envelope sealed by the
+/// active key provider with its current key. This moves values to a new
+/// provider as well as off rotated keyring keys. With `dry_run` set, stale
+/// rows are only counted.
+/// Rows are updated one by one, so an interrupted run can simply be repeated.
+pub fn reencrypt_all(
+    encryption_key: &str,
+    dry_run: bool,
+    conn: &mut PgConnection,
+) -> Result<Vec<ReencryptionSummary>, KeyRotationError> {
+    // Values are still decrypted with the keyring until they are re-encrypted
+    Keyring::parse(encryption_key)?;
+
+    Ok(vec![
+        reencrypt_user_api_keys(encryption_key, dry_run, conn)?,
+        reencrypt_user_totp(encryption_key, dry_run, conn)?,
+        reencrypt_jwt_signing_keys(encryption_key, dry_run, conn)?,
+        reencrypt_project_api_keys(encryption_key, dry_run, conn)?,
+        reencrypt_projects(encryption_key, dry_run, conn)?,
+        reencrypt_livekit_credentials(encryption_key, dry_run, conn)?,
+        reencrypt_storage_credentials(encryption_key, dry_run, conn)?,
+    ])
+}
+
+fn reencrypt_user_api_keys(
+    encryption_key: &str,
+    dry_run: bool,
+    conn: &mut PgConnection,
+) -> Result<ReencryptionSummary, KeyRotationError> {
+    use domain::schema::syncflow::api_keys::dsl::*;
+
+    let rows = api_keys.load::<ApiKey>(conn)?;
+    let mut summary = ReencryptionSummary {
+        table: "api_keys",
+        rows: rows.len(),
+        stale: 0,
+    };
+
+    for mut row in rows {
+        if !is_stale(encryption_key, &[&row.secret])? {
+            continue;
+        }
+        summary.stale += 1;
+        if dry_run {
+            continue;
+        }
+
+        let row_id = row.id;
+        rewrap(&mut row, summary.table, row_id, encryption_key)?;
+        diesel::update(api_keys.find(row_id))
+            .set(secret.eq(&row.secret))
+            .execute(conn)?;
+    }
+
+    Ok(summary)
+}
+
+fn reencrypt_user_totp(
+    encryption_key: &str,
+    dry_run: bool,
+    conn: &mut PgConnection,
+) -> Result<ReencryptionSummary, KeyRotationError> {
+    use domain::schema::syncflow::user_totp::dsl::*;
+
+    let rows = user_totp.load::<UserTotp>(conn)?;
+    let mut summary = ReencryptionSummary {
+        table: "user_totp",
+        rows: rows.len(),
+        stale: 0,
+    };
+
+    for mut row in rows {
+        if !is_stale(encryption_key, &[&row.secret])? {
+            continue;
+        }
+        summary.stale += 1;
+        if dry_run {
+            continue;
+        }
+
+        let row_id = row.user_id;
+        rewrap(&mut row, summary.table, row_id, encryption_key)?;
+        diesel::update(user_totp.find(row_id))
+            .set(secret.eq(&row.secret))
+            .execute(conn)?;
+    }
+
+    Ok(summary)
+}
+
+fn reencrypt_jwt_signing_keys(
+    encryption_key: &str,
+    dry_run: bool,
+    conn: &mut PgConnection,
+) -> Result<ReencryptionSummary, KeyRotationError> {
+    use domain::schema::syncflow::jwt_signing_keys::dsl::*;
+
+    let rows = jwt_signing_keys.load::<JwtSigningKey>(conn)?;
+    let mut summary = ReencryptionSummary {
+        table: "jwt_signing_keys",
+        rows: rows.len(),
+        stale: 0,
+    };
+
+    for mut row in rows {
+        if !is_stale(encryption_key, &[&row.private_key])? {
+            continue;
+        }
+        summary.stale += 1;
+        if dry_run {
+            continue;
+        }
+
+        let row_id = row.kid.clone();
+        rewrap(&mut row, summary.table, &row_id, encryption_key)?;
+        diesel::update(jwt_signing_keys.find(&row_id))
+            .set(private_key.eq(&row.private_key))
+            .execute(conn)?;
+    }
+
+    Ok(summary)
+}
+
+fn reencrypt_project_api_keys(
+    encryption_key: &str,
+    dry_run: bool,
+    conn: &mut PgConnection,
+) -> Result<ReencryptionSummary, KeyRotationError> {
+    use domain::schema::syncflow::project_api_keys::dsl::*;
+
+    let rows = project_api_keys.load::<ProjectAPIKey>(conn)?;
+    let mut summary = ReencryptionSummary {
+        table: "project_api_keys",
+        rows: rows.len(),
+        stale: 0,
+    };
+
+    for mut row in rows {
+        if !is_stale(encryption_key, &[&row.api_secret])? {
+            continue;
+        }
+        summary.stale += 1;
+        if dry_run {
+            continue;
+        }
+
+        let row_id = row.id;
+        rewrap(&mut row, summary.table, row_id, encryption_key)?;
+        diesel::update(project_api_keys.find(row_id))
+            .set(api_secret.eq(&row.api_secret))
+            .execute(conn)?;
+    }
+
+    Ok(summary)
+}
+
+fn reencrypt_projects(
+    encryption_key: &str,
+    dry_run: bool,
+    conn: &mut PgConnection,
+) -> Result<ReencryptionSummary, KeyRotationError> {
+    use domain::schema::syncflow::projects::dsl::*;
+
+    let rows = projects.load::<Project>(conn)?;
+    let mut summary = ReencryptionSummary {
+        table: "projects",
+        rows: rows.len(),
+        stale: 0,
+    };
+
+    for mut row in rows {
+        let ciphertexts = [
+            row.livekit_server_api_key.as_str(),
+            row.livekit_server_api_secret.as_str(),
+            row.access_key.as_str(),
+            row.secret_key.as_str(),
+        ];
+        if !is_stale(encryption_key, &ciphertexts)? {
+            continue;
+        }
+        summary.stale += 1;
+        if dry_run {
+            continue;
+        }
+
+        let row_id = row.id;
+        rewrap(&mut row, summary.table, row_id, encryption_key)?;
+        diesel::update(projects.find(row_id))
+            .set((
+                livekit_server_api_key.eq(&row.livekit_server_api_key),
+                livekit_server_api_secret.eq(&row.livekit_server_api_secret),
+                access_key.eq(&row.access_key),
+                secret_key.eq(&row.secret_key),
+            ))
+            .execute(conn)?;
+    }
+
+    Ok(summary)
+}
+
+fn reencrypt_livekit_credentials(
+    encryption_key: &str,
+    dry_run: bool,
+    conn: &mut PgConnection,
+) -> Result<ReencryptionSummary, KeyRotationError> {
+    use domain::schema::syncflow::organization_livekit_credentials::dsl::*;
+
+    let rows = organization_livekit_credentials.load::<LivekitCredential>(conn)?;
+    let mut summary = ReencryptionSummary {
+        table: "organization_livekit_credentials",
+        rows: rows.len(),
+        stale: 0,
+    };
+
+    for mut row in rows {
+        if !is_stale(encryption_key, &[&row.api_key, &row.api_secret])? {
+            continue;
+        }
+        summary.stale += 1;
+        if dry_run {
+            continue;
+        }
+
+        let row_id = row.id;
+        rewrap(&mut row, summary.table, row_id, encryption_key)?;
+        diesel::update(organization_livekit_credentials.find(row_id))
+            .set((api_key.eq(&row.api_key), api_secret.eq(&row.api_secret)))
+            .execute(conn)?;
+    }
+
+    Ok(summary)
+}
+
+fn reencrypt_storage_credentials(
+    encryption_key: &str,
+    dry_run: bool,
+    conn: &mut PgConnection,
+) -> Result<ReencryptionSummary, KeyRotationError> {
+    use domain::schema::syncflow::organization_storage_credentials::dsl::*;
+
+    let rows = organization_storage_credentials.load::<StorageCredential>(conn)?;
+    let mut summary = ReencryptionSummary {
+        table: "organization_storage_credentials",
+        rows: rows.len(),
+        stale: 0,
+    };
+
+    for mut row in rows {
+        if !is_stale(encryption_key, &[&row.access_key, &row.secret_key])? {
+            continue;
+        }
+        summary.stale += 1;
+        if dry_run {
+            continue;
+        }
+
+        let row_id = row.id;
+        rewrap(&mut row, summary.table, row_id, encryption_key)?;
+        diesel::update(organization_storage_credentials.find(row_id))
+            .set((
+                access_key.eq(&row.access_key),
+                secret_key.eq(&row.secret_key),
+            ))
+            .execute(conn)?;
+    }
+
+    Ok(summary)
+}
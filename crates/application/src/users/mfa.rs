@@ -0,0 +1,314 @@
+use chrono::Utc;
+use diesel::prelude::*;
+use diesel::PgConnection;
+use domain::models::{MfaRecoveryCode, NewMfaRecoveryCode, NewUserTotp, UserTotp};
+use rand::{distributions::Slice, thread_rng, Rng};
+use sha2::{Digest, Sha256};
+
+use crate::project::project_crud::Encryptable;
+use crate::users::secret::{decrypt_string, encrypt_string, SecretError};
+use crate::users::totp;
+use crate::users::user::UserError;
+
+/// Issuer shown next to the account in authenticator apps.
+pub const TOTP_ISSUER: &str = "SyncFlow";
+pub const RECOVERY_CODE_COUNT: usize = 10;
+
+const RECOVERY_CODE_LENGTH: usize = 10;
+/// Lowercase letters and digits without the easily confused `0`, `1`, `l` and `o`.
+const RECOVERY_CODE_ALPHABET: &[u8] = b"23456789abcdefghijkmnpqrstuvwxyz";
+
+/// A secret to add to an authenticator app, active once a first code from it
+/// was confirmed.
+#[derive(Debug, Clone)]
+pub struct TotpEnrollment {
+    pub secret: String,
+    pub provisioning_uri: String,
+}
+
+impl Encryptable for UserTotp {
+    fn encrypt(&mut self, key: &str) -> Result<(), SecretError> {
+        self.secret = encrypt_string(&self.secret, key)?;
+        Ok(())
+    }
+
+    fn decrypt(&mut self, key: &str) -> Result<(), SecretError> {
+        self.secret = decrypt_string(&self.secret, key)?;
+        Ok(())
+    }
+}
+
+fn invalid_code() -> UserError {
+    UserError::InvalidMfaCode("The code is invalid or was already used".to_string())
+}
+
+fn get_totp(uid: i32, conn: &mut PgConnection) -> Result<Option<UserTotp>, UserError> {
+    use domain::schema::syncflow::user_totp::dsl::*;
+
+    Ok(user_totp.find(uid).first::<UserTotp>(conn).optional()?)
+}
+
+fn get_confirmed_totp(uid: i32, conn: &mut PgConnection) -> Result<UserTotp, UserError> {
+    get_totp(uid, conn)?
+        .filter(|totp| totp.confirmed_at.is_some())
+        .ok_or_else(|| UserError::Conflict("Two-factor authentication is not enabled".to_string()))
+}
+
+fn totp_secret(enrollment: &UserTotp, encryption_key: &str) -> Result<Vec<u8>, UserError> {
+    let encoded = decrypt_string(&enrollment.secret, encryption_key)
+        .map_err(|e| UserError::SecretError(e.to_string()))?;
+    totp::decode_base32(&encoded)
+        .ok_or_else(|| UserError::SecretError("Malformed TOTP secret".to_string()))
+}
+
+pub fn is_enabled(uid: i32, conn: &mut PgConnection) -> Result<bool, UserError> {
+    Ok(get_totp(uid, conn)?.is_some_and(|totp| totp.confirmed_at.is_some()))
+}
+
+pub fn remaining_recovery_codes(uid: i32, conn: &mut PgConnection) -> Result<i64, UserError> {
+    use domain::schema::syncflow::mfa_recovery_codes::dsl::*;
+
+    Ok(mfa_recovery_codes
+        .filter(user_id.eq(uid).and(used_at.is_null()))
+        .count()
+        .get_result(conn)?)
+}
+
+/// Generates a new TOTP secret for the user, replacing an unconfirmed one.
+pub fn start_enrollment(
+    uid: i32,
+    account_name: &str,
+    encryption_key: &str,
+    conn: &mut PgConnection,
+) -> Result<TotpEnrollment, UserError> {
+    use domain::schema::syncflow::user_totp::dsl::*;
+
+    if is_enabled(uid, conn)? {
+        return Err(UserError::Conflict(
+            "Two-factor authentication is already enabled".to_string(),
+        ));
+    }
+
+    let raw_secret = totp::generate_secret();
+    let encoded_secret = totp::encode_base32(&raw_secret);
+    let encrypted_secret = encrypt_string(&encoded_secret, encryption_key)
+        .map_err(|e| UserError::SecretError(e.to_string()))?;
+
+    diesel::insert_into(user_totp)
+        .values(&NewUserTotp {
+            user_id: uid,
+            secret: encrypted_secret.clone(),
+        })
+        .on_conflict(user_id)
+        .do_update()
+        .set((
+            secret.eq(&encrypted_secret),
+            confirmed_at.eq(None::<chrono::NaiveDateTime>),
+            last_used_step.eq(None::<i64>),
+            created_at.eq(Utc::now().naive_utc()),
+        ))
+        .execute(conn)?;
+
+    Ok(TotpEnrollment {
+        provisioning_uri: totp::provisioning_uri(TOTP_ISSUER, account_name, &raw_secret),
+        secret: encoded_secret,
+    })
+}
+
+/// Activates the pending TOTP secret with a code from the authenticator and
+/// returns a fresh set of recovery codes.
+pub fn confirm_enrollment(
+    uid: i32,
+    code: &str,
+    encryption_key: &str,
+    conn: &mut PgConnection,
+) -> Result<Vec<String>, UserError> {
+    use domain::schema::syncflow::user_totp::dsl::*;
+
+    let pending = get_totp(uid, conn)?
+        .filter(|totp| totp.confirmed_at.is_none())
+        .ok_or_else(|| UserError::Conflict("No two-factor enrollment is pending".to_string()))?;
+
+    let step = totp::verify(
+        &totp_secret(&pending, encryption_key)?,
+        code,
+        Utc::now().timestamp() as u64,
+    )
+    .ok_or_else(invalid_code)?;
+
+    conn.transaction(|conn| {
+        diesel::update(user_totp.find(uid))
+            .set((
+                confirmed_at.eq(Some(Utc::now().naive_utc())),
+                last_used_step.eq(Some(step as i64)),
+            ))
+            .execute(conn)?;
+        replace_recovery_codes(uid, conn)
+    })
+}
+
+/// Checks a TOTP code or an unused recovery code of a user with two-factor
+/// authentication, consuming it.
+pub fn verify_second_factor(
+    uid: i32,
+    code: &str,
+    encryption_key: &str,
+    conn: &mut PgConnection,
+) -> Result<(), UserError> {
+    let enrollment = get_confirmed_totp(uid, conn)?;
+    let code = code.trim();
+
+    if code.len() == totp::DIGITS as usize && code.chars().all(|c| c.is_ascii_digit()) {
+        verify_totp_code(&enrollment, code, encryption_key, conn)
+    } else {
+        use_recovery_code(uid, code, conn)
+    }
+}
+
+fn verify_totp_code(
+    enrollment: &UserTotp,
+    code: &str,
+    encryption_key: &str,
+    conn: &mut PgConnection,
+) -> Result<(), UserError> {
+    use domain::schema::syncflow::user_totp::dsl::*;
+
+    let step = totp::verify(
+        &totp_secret(enrollment, encryption_key)?,
+        code,
+        Utc::now().timestamp() as u64,
+    )
+    .ok_or_else(invalid_code)? as i64;
+
+    // Claiming the step in one conditional update makes each code single use,
+    // even for concurrent attempts
+    let claimed = diesel::update(
+        user_totp
+            .find(enrollment.user_id)
+            .filter(last_used_step.is_null().or(last_used_step.lt(step))),
+    )
+    .set(last_used_step.eq(Some(step)))
+    .execute(conn)?;
+
+    if claimed == 0 {
+        return Err(invalid_code());
+    }
+    Ok(())
+}
+
+fn normalize_recovery_code(code: &str) -> String {
+    code.chars()
+        .filter(|c| c.is_ascii_alphanumeric())
+        .map(|c| c.to_ascii_lowercase())
+        .collect()
+}
+
+fn hash_recovery_code(code: &str) -> String {
+    Sha256::digest(normalize_recovery_code(code).as_bytes())
+        .iter()
+        .map(|b| format!("{:02x}", b))
+        .collect()
+}
+
+fn use_recovery_code(uid: i32, code: &str, conn: &mut PgConnection) -> Result<(), UserError> {
+    use domain::schema::syncflow::mfa_recovery_codes::dsl::*;
+
+    let used = diesel::update(
+        mfa_recovery_codes
+            .filter(user_id.eq(uid))
+            .filter(code_hash.eq(hash_recovery_code(code)))
+            .filter(used_at.is_null()),
+    )
+    .set(used_at.eq(Some(Utc::now().naive_utc())))
+    .get_results::<MfaRecoveryCode>(conn)?;
+
+    if used.is_empty() {
+        return Err(invalid_code());
+    }
+    log::info!("User {} logged in with a recovery code", uid);
+    Ok(())
+}
+
+/// Replaces the recovery codes of a user, returning the new codes in the
+/// `xxxxx-xxxxx` form shown to the user.
+fn replace_recovery_codes(uid: i32, conn: &mut PgConnection) -> Result<Vec<String>, UserError> {
+    use domain::schema::syncflow::mfa_recovery_codes::dsl::*;
+
+    let alphabet = Slice::new(RECOVERY_CODE_ALPHABET).expect("The alphabet is not empty");
+    let codes = (0..RECOVERY_CODE_COUNT)
+        .map(|_| {
+            let code: String = thread_rng()
+                .sample_iter(&alphabet)
+                .take(RECOVERY_CODE_LENGTH)
+                .map(|b| *b as char)
+                .collect();
+            format!(
+                "{}-{}",
+                &code[..RECOVERY_CODE_LENGTH / 2],
+                &code[RECOVERY_CODE_LENGTH / 2..]
+            )
+        })
+        .collect::<Vec<_>>();
+
+    diesel::delete(mfa_recovery_codes.filter(user_id.eq(uid))).execute(conn)?;
+    diesel::insert_into(mfa_recovery_codes)
+        .values(
+            codes
+                .iter()
+                .map(|code| NewMfaRecoveryCode {
+                    code_hash: hash_recovery_code(code),
+                    user_id: uid,
+                })
+                .collect::<Vec<_>>(),
+        )
+        .execute(conn)?;
+
+    Ok(codes)
+}
+
+/// Replaces the recovery codes after checking a second factor code.
+pub fn regenerate_recovery_codes(
+    uid: i32,
+    code: &str,
+    encryption_key: &str,
+    conn: &mut PgConnection,
+) -> Result<Vec<String>, UserError> {
+    verify_second_factor(uid, code, encryption_key, conn)?;
+    conn.transaction(|conn| replace_recovery_codes(uid, conn))
+}
+
+/// Turns two-factor authentication off after checking a second factor code.
+pub fn disable(
+    uid: i32,
+    code: &str,
+    encryption_key: &str,
+    conn: &mut PgConnection,
+) -> Result<(), UserError> {
+    verify_second_factor(uid, code, encryption_key, conn)?;
+
+    conn.transaction(|conn| {
+        use domain::schema::syncflow::{mfa_recovery_codes, user_totp};
+
+        diesel::delete(user_totp::table.find(uid)).execute(conn)?;
+        diesel::delete(mfa_recovery_codes::table.filter(mfa_recovery_codes::user_id.eq(uid)))
+            .execute(conn)?;
+        Ok::<_, UserError>(())
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_recovery_code_normalization() {
+        assert_eq!(
+            hash_recovery_code("ab2cd-ef3gh"),
+            hash_recovery_code(" AB2CDEF3GH ")
+        );
+        assert_ne!(
+            hash_recovery_code("ab2cd-ef3gh"),
+            hash_recovery_code("ab2cd-ef3gk")
+        );
+    }
+}
@@ -1,5 +1,11 @@
 pub mod account_service;
+pub mod account_tokens;
+pub mod key_provider;
+pub mod key_rotation;
+pub mod mfa;
 pub mod oauth;
 pub mod secret;
+pub mod signing_keys;
 pub mod tokens_manager;
+pub mod totp;
 pub mod user;
@@ -0,0 +1,578 @@
+use std::sync::Mutex;
+use std::time::{Duration, Instant};
+
+use jsonwebtoken::jwk::JwkSet;
+use jsonwebtoken::{decode, decode_header, Algorithm, DecodingKey, Validation};
+use reqwest::Client;
+use serde::{Deserialize, Deserializer};
+use shared::constants::APPLICATION_NAME;
+use shared::deployment_config::OidcConfig;
+use shared::user_models::OidcLoginRequest;
+use thiserror::Error;
+
+pub const GOOGLE_PROVIDER: &str = "google";
+const GOOGLE_ISSUER: &str = "https://accounts.google.com";
+/// Google still issues some ID tokens with the scheme-less issuer
+const GOOGLE_LEGACY_ISSUER: &str = "accounts.google.com";
+
+const DISCOVERY_PATH: &str = "/.well-known/openid-configuration";
+const DISCOVERY_TTL_SECONDS: u64 = 24 * 60 * 60;
+const JWKS_TTL_SECONDS: u64 = 60 * 60;
+/// Unknown key ids trigger a JWKS refresh at most this often, so forged
+/// tokens can not make us hammer the provider
+const JWKS_MIN_REFRESH_SECONDS: u64 = 60;
+const CLOCK_SKEW_SECONDS: u64 = 60;
+const HTTP_TIMEOUT_SECONDS: u64 = 10;
+
+const ALLOWED_ALGORITHMS: [Algorithm; 9] = [
+    Algorithm::RS256,
+    Algorithm::RS384,
+    Algorithm::RS512,
+    Algorithm::PS256,
+    Algorithm::PS384,
+    Algorithm::PS512,
+    Algorithm::ES256,
+    Algorithm::ES384,
+    Algorithm::EdDSA,
+];
+
+#[derive(Debug, Error)]
+pub enum OidcError {
+    #[error("The {0} login is not configured")]
+    NotConfigured(String),
+    #[error("Discovery error: {0}")]
+    DiscoveryError(String),
+    #[error("Invalid ID token: {0}")]
+    InvalidToken(String),
+    #[error("Code exchange failed: {0}")]
+    CodeExchangeError(String),
+    #[error("Invalid login request: {0}")]
+    InvalidRequest(String),
+}
+
+#[derive(Deserialize, Debug, Clone)]
+pub struct DiscoveryDocument {
+    pub issuer: String,
+    pub jwks_uri: String,
+    pub token_endpoint: Option<String>,
+    pub authorization_endpoint: Option<String>,
+}
+
+#[derive(Deserialize, Debug, Clone)]
+pub struct IdTokenClaims {
+    pub sub: String,
+    pub email: Option<String>,
+    #[serde(default, deserialize_with = "deserialize_flag")]
+    pub email_verified: bool,
+    pub name: Option<String>,
+    pub given_name: Option<String>,
+    pub family_name: Option<String>,
+    pub preferred_username: Option<String>,
+    pub nonce: Option<String>,
+}
+
+impl IdTokenClaims {
+    /// The email, if the provider vouches that it belongs to the subject.
+    pub fn verified_email(&self) -> Option<&str> {
+        self.email
+            .as_deref()
+            .filter(|email| self.email_verified && !email.is_empty())
+    }
+}
+
+/// Some providers send `email_verified` as a string.
+fn deserialize_flag<'de, D: Deserializer<'de>>(deserializer: D) -> Result<bool, D::Error> {
+    #[derive(Deserialize)]
+    #[serde(untagged)]
+    enum Flag {
+        Bool(bool),
+        String(String),
+    }
+
+    Ok(match Option::<Flag>::deserialize(deserializer)? {
+        Some(Flag::Bool(flag)) => flag,
+        Some(Flag::String(flag)) => flag.eq_ignore_ascii_case("true"),
+        None => false,
+    })
+}
+
+#[derive(Deserialize)]
+struct TokenEndpointResponse {
+    id_token: Option<String>,
+}
+
+/// A cached value along with the time it was fetched.
+type Cached<T> = Mutex<Option<(Instant, T)>>;
+
+/// An OpenID Connect provider whose ID tokens we accept.
+///
+/// The discovery document and the signing keys are fetched lazily and cached;
+/// a token signed with a key we do not know yet refreshes the keys, so
+/// provider key rotation needs no restart.
+pub struct OidcProvider {
+    name: String,
+    issuer: String,
+    accepted_issuers: Vec<String>,
+    client_id: String,
+    client_secret: Option<String>,
+    client: Client,
+    discovery: Cached<DiscoveryDocument>,
+    jwks: Cached<JwkSet>,
+}
+
+impl OidcProvider {
+    /// `name` is recorded with the accounts linked through this provider.
+    pub fn new(name: &str, issuer: &str, client_id: &str, client_secret: Option<&str>) -> Self {
+        let issuer = issuer.trim_end_matches('/').to_string();
+        let client = Client::builder()
+            .timeout(Duration::from_secs(HTTP_TIMEOUT_SECONDS))
+            .user_agent(APPLICATION_NAME)
+            .build()
+            .unwrap_or_default();
+
+        Self {
+            name: name.to_string(),
+            accepted_issuers: vec![issuer.clone()],
+            issuer,
+            client_id: client_id.to_string(),
+            client_secret: client_secret.map(str::to_string),
+            client,
+            discovery: Mutex::new(None),
+            jwks: Mutex::new(None),
+        }
+    }
+
+    pub fn google(client_id: &str, client_secret: Option<&str>) -> Self {
+        let mut provider = Self::new(GOOGLE_PROVIDER, GOOGLE_ISSUER, client_id, client_secret);
+        provider
+            .accepted_issuers
+            .push(GOOGLE_LEGACY_ISSUER.to_string());
+        provider
+    }
+
+    /// Generic providers are recorded as `oidc:<name>`, so they can never
+    /// collide with the built in ones.
+    pub fn from_config(config: &OidcConfig) -> Self {
+        Self::new(
+            &format!("oidc:{}", config.name),
+            &config.issuer,
+            &config.client_id,
+            config.client_secret.as_deref(),
+        )
+    }
+
+    pub fn name(&self) -> &str {
+        &self.name
+    }
+
+    /// Verifies the ID token of the request, exchanging its authorization
+    /// code first if it carries one instead.
+    pub async fn authenticate(
+        &self,
+        request: &OidcLoginRequest,
+    ) -> Result<IdTokenClaims, OidcError> {
+        let id_token = match (&request.id_token, &request.code) {
+            (Some(id_token), None) => id_token.clone(),
+            (None, Some(code)) => {
+                let redirect_uri = request.redirect_uri.as_deref().ok_or_else(|| {
+                    OidcError::InvalidRequest("redirectUri is required with a code".to_string())
+                })?;
+                self.exchange_code(code, redirect_uri, request.code_verifier.as_deref())
+                    .await?
+            }
+            _ => {
+                return Err(OidcError::InvalidRequest(
+                    "Provide either an idToken or a code".to_string(),
+                ))
+            }
+        };
+
+        self.verify_id_token(&id_token, request.nonce.as_deref())
+            .await
+    }
+
+    pub async fn verify_id_token(
+        &self,
+        id_token: &str,
+        nonce: Option<&str>,
+    ) -> Result<IdTokenClaims, OidcError> {
+        let header = decode_header(id_token).map_err(|e| OidcError::InvalidToken(e.to_string()))?;
+        if !ALLOWED_ALGORITHMS.contains(&header.alg) {
+            return Err(OidcError::InvalidToken(format!(
+                "{:?} signed tokens are not accepted",
+                header.alg
+            )));
+        }
+
+        let key = self.decoding_key(header.kid.as_deref()).await?;
+
+        let mut validation = Validation::new(header.alg);
+        validation.set_issuer(&self.accepted_issuers);
+        validation.set_audience(&[&self.client_id]);
+        validation.set_required_spec_claims(&["exp", "iss", "aud", "sub"]);
+        validation.leeway = CLOCK_SKEW_SECONDS;
+
+        let claims = decode::<IdTokenClaims>(id_token, &key, &validation)
+            .map_err(|e| OidcError::InvalidToken(e.to_string()))?
+            .claims;
+
+        if let Some(nonce) = nonce {
+            if claims.nonce.as_deref() != Some(nonce) {
+                return Err(OidcError::InvalidToken("nonce mismatch".to_string()));
+            }
+        }
+
+        Ok(claims)
+    }
+
+    async fn exchange_code(
+        &self,
+        code: &str,
+        redirect_uri: &str,
+        code_verifier: Option<&str>,
+    ) -> Result<String, OidcError> {
+        let token_endpoint = self.discovery().await?.token_endpoint.ok_or_else(|| {
+            OidcError::DiscoveryError("The provider has no token endpoint".to_string())
+        })?;
+
+        let mut form = vec![
+            ("grant_type", "authorization_code"),
+            ("code", code),
+            ("redirect_uri", redirect_uri),
+            ("client_id", self.client_id.as_str()),
+        ];
+        if let Some(client_secret) = &self.client_secret {
+            form.push(("client_secret", client_secret.as_str()));
+        }
+        if let Some(code_verifier) = code_verifier {
+            form.push(("code_verifier", code_verifier));
+        }
+
+        let response = self
+            .client
+            .post(&token_endpoint)
+            .form(&form)
+            .send()
+            .await
+            .map_err(|e| OidcError::CodeExchangeError(e.to_string()))?;
+
+        if !response.status().is_success() {
+            let error_text = response.text().await.unwrap_or_default();
+            return Err(OidcError::CodeExchangeError(error_text));
+        }
+
+        response
+            .json::<TokenEndpointResponse>()
+            .await
+            .map_err(|e| OidcError::CodeExchangeError(e.to_string()))?
+            .id_token
+            .ok_or_else(|| {
+                OidcError::CodeExchangeError("The provider returned no ID token".to_string())
+            })
+    }
+
+    pub async fn discovery(&self) -> Result<DiscoveryDocument, OidcError> {
+        if let Some((fetched_at, document)) = self.discovery.lock().unwrap().as_ref() {
+            if fetched_at.elapsed() < Duration::from_secs(DISCOVERY_TTL_SECONDS) {
+                return Ok(document.clone());
+            }
+        }
+
+        let url = format!("{}{}", self.issuer, DISCOVERY_PATH);
+        let document: DiscoveryDocument = self
+            .client
+            .get(&url)
+            .send()
+            .await
+            .and_then(|response| response.error_for_status())
+            .map_err(|e| OidcError::DiscoveryError(e.to_string()))?
+            .json()
+            .await
+            .map_err(|e| OidcError::DiscoveryError(e.to_string()))?;
+
+        if document.issuer.trim_end_matches('/') != self.issuer {
+            return Err(OidcError::DiscoveryError(format!(
+                "The discovery document is for issuer {}",
+                document.issuer
+            )));
+        }
+
+        *self.discovery.lock().unwrap() = Some((Instant::now(), document.clone()));
+        Ok(document)
+    }
+
+    async fn decoding_key(&self, kid: Option<&str>) -> Result<DecodingKey, OidcError> {
+        let cached = self.jwks.lock().unwrap().clone();
+
+        if let Some((fetched_at, jwks)) = &cached {
+            if fetched_at.elapsed() < Duration::from_secs(JWKS_TTL_SECONDS) {
+                if let Some(key) = find_key(jwks, kid)? {
+                    return Ok(key);
+                }
+                if fetched_at.elapsed() < Duration::from_secs(JWKS_MIN_REFRESH_SECONDS) {
+                    return Err(OidcError::InvalidToken(
+                        "The token is signed with an unknown key".to_string(),
+                    ));
+                }
+            }
+        }
+
+        let jwks_uri = self.discovery().await?.jwks_uri;
+        let jwks: JwkSet = self
+            .client
+            .get(&jwks_uri)
+            .send()
+            .await
+            .and_then(|response| response.error_for_status())
+            .map_err(|e| OidcError::DiscoveryError(e.to_string()))?
+            .json()
+            .await
+            .map_err(|e| OidcError::DiscoveryError(e.to_string()))?;
+
+        *self.jwks.lock().unwrap() = Some((Instant::now(), jwks.clone()));
+
+        find_key(&jwks, kid)?.ok_or_else(|| {
+            OidcError::InvalidToken("The token is signed with an unknown key".to_string())
+        })
+    }
+}
+
+/// Finds the key a token names. Tokens without a key id are only accepted
+/// from providers publishing a single key.
+fn find_key(jwks: &JwkSet, kid: Option<&str>) -> Result<Option<DecodingKey>, OidcError> {
+    let jwk = match kid {
+        Some(kid) => jwks.find(kid),
+        None if jwks.keys.len() == 1 => jwks.keys.first(),
+        None => None,
+    };
+
+    jwk.map(|jwk| DecodingKey::from_jwk(jwk).map_err(|e| OidcError::InvalidToken(e.to_string())))
+        .transpose()
+}
+
+#[cfg(test)]
+mod tests {
+    use std::sync::atomic::{AtomicUsize, Ordering};
+    use std::sync::Arc;
+    use std::time::{SystemTime, UNIX_EPOCH};
+
+    use jsonwebtoken::{encode, EncodingKey, Header};
+    use serde_json::{json, Value};
+    use tokio::io::{AsyncReadExt, AsyncWriteExt};
+    use tokio::net::TcpListener;
+
+    use super::*;
+
+    const MOCK_KEY_PEM: &str = include_str!("testdata/mock_idp_key.pem");
+    const MOCK_KEY_ID: &str = "mock-key";
+    const MOCK_KEY_MODULUS: &str = "vLQZLF_HtJHwdR8UegHj8BcJh1eNaK0Ilf5P1AJgrBWs5FMO38mAtuWbwr7uaQ9__GNIeFWgXmxQcRPnFRasIM2rK0SvsV4omcqMDWbY9BJnIOQCXvJoXAz4bnjclUzbBJXl_yUm6TPv7okY0guM7LQQCJk3punCtZvkKgaThNjhb2QQwp4htQEO8mGe2QHW4x14yPQbmejr9lq_I01F4Uc5f1Mkec7g3xdphL0iWMxxwsMjzyCAFPCJvDno6YGR2M35CcsthhV1ihcLFrx9sHc2Yq_dzQpaqJ89kpVH490DW6N0Ro6Pri2EiWAk0iHo3yJSCZys9-R_vAp5Dtwvew";
+    const CLIENT_ID: &str = "syncflow-test";
+
+    /// A minimal identity provider serving discovery, JWKS and a token
+    /// endpoint that hands out an ID token for any code.
+    struct MockIdp {
+        issuer: String,
+        jwks_requests: Arc<AtomicUsize>,
+    }
+
+    impl MockIdp {
+        async fn start() -> Self {
+            let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+            let issuer = format!("http://{}", listener.local_addr().unwrap());
+            let jwks_requests = Arc::new(AtomicUsize::new(0));
+
+            let discovery = json!({
+                "issuer": issuer,
+                "jwks_uri": format!("{}/jwks", issuer),
+                "token_endpoint": format!("{}/token", issuer),
+            });
+            let jwks = json!({
+                "keys": [{
+                    "kty": "RSA",
+                    "use": "sig",
+                    "alg": "RS256",
+                    "kid": MOCK_KEY_ID,
+                    "n": MOCK_KEY_MODULUS,
+                    "e": "AQAB",
+                }]
+            });
+            let token = json!({
+                "access_token": "mock",
+                "token_type": "Bearer",
+                "id_token": sign(&claims(&issuer)),
+            });
+
+            let counter = jwks_requests.clone();
+            tokio::spawn(async move {
+                while let Ok((mut stream, _)) = listener.accept().await {
+                    let request = read_request(&mut stream).await;
+                    let path = request.split_whitespace().nth(1).unwrap_or_default();
+                    let body = match path {
+                        DISCOVERY_PATH => discovery.to_string(),
+                        "/jwks" => {
+                            counter.fetch_add(1, Ordering::SeqCst);
+                            jwks.to_string()
+                        }
+                        "/token" => token.to_string(),
+                        _ => "{}".to_string(),
+                    };
+                    let response = format!(
+                        "HTTP/1.1 200 OK\r\nContent-Type: application/json\r\nContent-Length: {}\r\nConnection: close\r\n\r\n{}",
+                        body.len(),
+                        body
+                    );
+                    let _ = stream.write_all(response.as_bytes()).await;
+                }
+            });
+
+            Self {
+                issuer,
+                jwks_requests,
+            }
+        }
+
+        fn provider(&self) -> OidcProvider {
+            OidcProvider::new("oidc:mock", &self.issuer, CLIENT_ID, Some("secret"))
+        }
+    }
+
+    async fn read_request(stream: &mut tokio::net::TcpStream) -> String {
+        let mut request = Vec::new();
+        let mut buffer = [0u8; 4096];
+        loop {
+            let read = stream.read(&mut buffer).await.unwrap_or(0);
+            request.extend_from_slice(&buffer[..read]);
+            let text = String::from_utf8_lossy(&request).to_string();
+            if let Some(header_end) = text.find("\r\n\r\n") {
+                let content_length = text
+                    .lines()
+                    .find_map(|line| {
+                        line.to_ascii_lowercase()
+                            .strip_prefix("content-length:")
+                            .and_then(|length| length.trim().parse::<usize>().ok())
+                    })
+                    .unwrap_or(0);
+                if read == 0 || request.len() >= header_end + 4 + content_length {
+                    return text;
+                }
+            } else if read == 0 {
+                return text;
+            }
+        }
+    }
+
+    fn now() -> u64 {
+        SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .unwrap()
+            .as_secs()
+    }
+
+    fn claims(issuer: &str) -> Value {
+        json!({
+            "iss": issuer,
+            "aud": CLIENT_ID,
+            "sub": "248289761001",
+            "exp": now() + 300,
+            "iat": now(),
+            "email": "jane@example.edu",
+            "email_verified": "true",
+            "preferred_username": "jane",
+            "nonce": "n-0S6_WzA2Mj",
+        })
+    }
+
+    fn sign(claims: &Value) -> String {
+        let mut header = Header::new(Algorithm::RS256);
+        header.kid = Some(MOCK_KEY_ID.to_string());
+        encode(
+            &header,
+            claims,
+            &EncodingKey::from_rsa_pem(MOCK_KEY_PEM.as_bytes()).unwrap(),
+        )
+        .unwrap()
+    }
+
+    #[tokio::test]
+    async fn test_verify_id_token() {
+        let idp = MockIdp::start().await;
+        let provider = idp.provider();
+
+        let claims = provider
+            .verify_id_token(&sign(&claims(&idp.issuer)), Some("n-0S6_WzA2Mj"))
+            .await
+            .unwrap();
+        assert_eq!(claims.sub, "248289761001");
+        assert_eq!(claims.verified_email(), Some("jane@example.edu"));
+
+        // The keys are cached between tokens
+        provider
+            .verify_id_token(&sign(&claims_without_nonce(&idp.issuer)), None)
+            .await
+            .unwrap();
+        assert_eq!(idp.jwks_requests.load(Ordering::SeqCst), 1);
+    }
+
+    fn claims_without_nonce(issuer: &str) -> Value {
+        let mut claims = claims(issuer);
+        claims.as_object_mut().unwrap().remove("nonce");
+        claims
+    }
+
+    #[tokio::test]
+    async fn test_reject_invalid_id_tokens() {
+        let idp = MockIdp::start().await;
+        let provider = idp.provider();
+
+        let mut cases = Vec::new();
+        for (claim, value) in [
+            ("aud", json!("another-client")),
+            ("iss", json!("https://evil.example.com")),
+            ("exp", json!(now() - 600)),
+        ] {
+            let mut claims = claims(&idp.issuer);
+            claims[claim] = value;
+            cases.push((claim, sign(&claims), None));
+        }
+        cases.push(("nonce", sign(&claims(&idp.issuer)), Some("another-nonce")));
+
+        let symmetric = encode(
+            &Header::new(Algorithm::HS256),
+            &claims(&idp.issuer),
+            &EncodingKey::from_secret(b"secret"),
+        )
+        .unwrap();
+        cases.push(("alg", symmetric, None));
+
+        for (case, token, nonce) in cases {
+            match provider.verify_id_token(&token, nonce).await {
+                Err(OidcError::InvalidToken(_)) => {}
+                other => panic!("{} should be rejected, got {:?}", case, other),
+            }
+        }
+    }
+
+    #[tokio::test]
+    async fn test_exchange_code() {
+        let idp = MockIdp::start().await;
+        let provider = idp.provider();
+
+        let request = OidcLoginRequest {
+            code: Some("authorization-code".to_string()),
+            redirect_uri: Some("http://localhost:3000/callback".to_string()),
+            code_verifier: Some("verifier".to_string()),
+            ..Default::default()
+        };
+        let claims = provider.authenticate(&request).await.unwrap();
+        assert_eq!(claims.preferred_username.as_deref(), Some("jane"));
+
+        let missing_redirect = OidcLoginRequest {
+            code: Some("authorization-code".to_string()),
+            ..Default::default()
+        };
+        assert!(matches!(
+            provider.authenticate(&missing_redirect).await,
+            Err(OidcError::InvalidRequest(_))
+        ));
+    }
+}
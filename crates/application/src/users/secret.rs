@@ -9,11 +9,25 @@ use std::fmt::Display;
 use std::string::String;
 use thiserror::Error;
 
+use crate::users::key_provider::{self, Envelope};
+
+/// Version of ciphertexts written before keys were versioned. They carry no
+/// version prefix, and neither does a key configured without a version.
+pub const LEGACY_KEY_VERSION: u32 = 0;
+
+const VERSION_PREFIX: &str = "v";
+const VERSION_SEPARATOR: char = ':';
+const KEY_SEPARATOR: char = ',';
+
 #[derive(Debug, Clone, Error)]
 pub enum SecretError {
     Base64DecodeError(String),
     EncryptionError(String),
     DecryptionError(String),
+    InvalidKeyring(String),
+    UnknownKeyVersion(u32),
+    UnknownKeyProvider(String),
+    KeyProviderError(String),
 }
 
 impl Display for SecretError {
@@ -22,10 +36,136 @@ impl Display for SecretError {
             SecretError::Base64DecodeError(e) => write!(f, "Base64 decode error: {}", e),
             SecretError::EncryptionError(e) => write!(f, "Encryption error: {}", e),
             SecretError::DecryptionError(e) => write!(f, "Decryption error: {}", e),
+            SecretError::InvalidKeyring(e) => write!(f, "Invalid encryption keyring: {}", e),
+            SecretError::UnknownKeyVersion(v) => {
+                write!(f, "No encryption key with version {} is configured", v)
+            }
+            SecretError::UnknownKeyProvider(name) => {
+                write!(f, "The {} key provider is not configured", name)
+            }
+            SecretError::KeyProviderError(e) => write!(f, "Key provider error: {}", e),
         }
     }
 }
 
+/// The encryption keys in use, parsed from the `encryption_key` setting.
+///
+/// The setting holds comma separated `<version>:<base64 key>` entries. The
+/// first entry encrypts new values, every entry can decrypt. A key without a
+/// version is the legacy key, so a single bare key keeps working as before.
+/// Rotating means prepending a new version, running `reencrypt_secrets` and
+/// dropping the old entry once it reports nothing left to re-encrypt.
+#[derive(Debug, Clone)]
+pub struct Keyring {
+    primary_version: u32,
+    keys: Vec<(u32, Vec<u8>)>,
+}
+
+impl Keyring {
+    pub fn parse(spec: &str) -> Result<Self, SecretError> {
+        let mut keys: Vec<(u32, Vec<u8>)> = Vec::new();
+
+        for entry in spec.split(KEY_SEPARATOR).map(str::trim) {
+            let (version, encoded_key) = match entry.split_once(VERSION_SEPARATOR) {
+                Some((version, encoded_key)) => (
+                    version.trim().parse::<u32>().map_err(|_| {
+                        SecretError::InvalidKeyring(format!("invalid key version {}", version))
+                    })?,
+                    encoded_key.trim(),
+                ),
+                None => (LEGACY_KEY_VERSION, entry),
+            };
+
+            let key = decode_base64(encoded_key)
+                .map_err(|e| SecretError::Base64DecodeError(e.to_string()))?;
+            if key.len() != 32 {
+                return Err(SecretError::InvalidKeyring(format!(
+                    "key version {} is not 256 bits long",
+                    version
+                )));
+            }
+
+            if keys.iter().any(|(existing, _)| *existing == version) {
+                return Err(SecretError::InvalidKeyring(format!(
+                    "key version {} is configured twice",
+                    version
+                )));
+            }
+
+            keys.push((version, key));
+        }
+
+        Ok(Keyring {
+            primary_version: keys[0].0,
+            keys,
+        })
+    }
+
+    pub fn primary_version(&self) -> u32 {
+        self.primary_version
+    }
+
+    pub fn versions(&self) -> Vec<u32> {
+        self.keys.iter().map(|(version, _)| *version).collect()
+    }
+
+    fn key(&self, version: u32) -> Result<&[u8], SecretError> {
+        self.keys
+            .iter()
+            .find(|(candidate, _)| *candidate == version)
+            .map(|(_, key)| key.as_slice())
+            .ok_or(SecretError::UnknownKeyVersion(version))
+    }
+
+    pub fn encrypt(&self, input: &str) -> Result<String, SecretError> {
+        let encrypted = encrypt_aes_256_gcm(input.as_bytes(), self.key(self.primary_version)?)
+            .map_err(|e| SecretError::EncryptionError(e.to_string()))?;
+
+        if self.primary_version == LEGACY_KEY_VERSION {
+            Ok(encode_base64(&encrypted))
+        } else {
+            Ok(format!(
+                "{}{}{}{}",
+                VERSION_PREFIX,
+                self.primary_version,
+                VERSION_SEPARATOR,
+                encode_base64(&encrypted)
+            ))
+        }
+    }
+
+    pub fn decrypt(&self, encrypted: &str) -> Result<String, SecretError> {
+        let (version, ciphertext) = split_ciphertext(encrypted);
+        let encrypted_bytes =
+            decode_base64(ciphertext).map_err(|e| SecretError::Base64DecodeError(e.to_string()))?;
+        let decrypted_bytes = decrypt_aes_256_gcm(&encrypted_bytes, self.key(version)?)
+            .map_err(|e| SecretError::DecryptionError(e.to_string()))?;
+
+        String::from_utf8(decrypted_bytes).map_err(|e| SecretError::DecryptionError(e.to_string()))
+    }
+
+    /// Whether the value was encrypted with a key other than the primary one.
+    pub fn is_stale(&self, encrypted: &str) -> bool {
+        split_ciphertext(encrypted).0 != self.primary_version
+    }
+}
+
+/// Splits a ciphertext into its key version and base64 payload. The base64
+/// alphabet has no `:`, so legacy ciphertexts can not be mistaken for
+/// versioned ones.
+fn split_ciphertext(encrypted: &str) -> (u32, &str) {
+    encrypted
+        .strip_prefix(VERSION_PREFIX)
+        .and_then(|rest| rest.split_once(VERSION_SEPARATOR))
+        .and_then(|(version, ciphertext)| {
+            version
+                .parse::<u32>()
+                .ok()
+                .map(|version| (version, ciphertext))
+        })
+        .unwrap_or((LEGACY_KEY_VERSION, encrypted))
+}
+
 #[derive(Debug, Clone)]
 pub struct KeySecretPair {
     pub key: String,
@@ -59,25 +199,36 @@ pub fn encode_base64(input: &[u8]) -> String {
     engine.encode(input)
 }
 
+/// Encrypts with a fresh data key, wrapped by the installed key provider or,
+/// without one, by the primary key of the keyring in `encryption_key`.
 pub fn encrypt_string(input: &str, encryption_key: &str) -> Result<String, SecretError> {
-    let key =
-        decode_base64(encryption_key).map_err(|e| SecretError::Base64DecodeError(e.to_string()))?;
-    let encrypted = encrypt_aes_256_gcm(input.as_bytes(), &key)
-        .map_err(|e| SecretError::EncryptionError(e.to_string()))?;
-    Ok(encode_base64(&encrypted))
+    let provider = key_provider::active_provider(encryption_key)?;
+    key_provider::seal(input, provider.as_ref())
 }
 
+/// Decrypts an envelope with the provider that wrapped its data key, and
+/// values encrypted before envelopes with the keyring in `encryption_key`.
 pub fn decrypt_string(encrypted: &str, encryption_key: &str) -> Result<String, SecretError> {
-    let key =
-        decode_base64(encryption_key).map_err(|e| SecretError::Base64DecodeError(e.to_string()))?;
-    let encrypted_bytes =
-        decode_base64(encrypted).map_err(|e| SecretError::Base64DecodeError(e.to_string()))?;
-    let decrypted_bytes = decrypt_aes_256_gcm(&encrypted_bytes, &key)
-        .map_err(|e| SecretError::DecryptionError(e.to_string()))?;
-    let decrypted_string = String::from_utf8(decrypted_bytes)
-        .map_err(|e| SecretError::DecryptionError(e.to_string()))?;
-
-    Ok(decrypted_string)
+    match Envelope::parse(encrypted) {
+        Some(envelope) => {
+            let provider = key_provider::provider_named(envelope.provider, encryption_key)?;
+            key_provider::open(&envelope, provider.as_ref())
+        }
+        None => Keyring::parse(encryption_key)?.decrypt(encrypted),
+    }
+}
+
+/// Whether `reencrypt_secrets` should re-encrypt the value: it is not an
+/// envelope yet, or its data key was not wrapped by the active provider with
+/// its current key.
+pub fn is_stale(encrypted: &str, encryption_key: &str) -> Result<bool, SecretError> {
+    let provider = key_provider::active_provider(encryption_key)?;
+    Ok(match Envelope::parse(encrypted) {
+        Some(envelope) => {
+            envelope.provider != provider.name() || provider.is_stale(envelope.wrapped_key)
+        }
+        None => true,
+    })
 }
 
 pub fn encrypt_aes_256_gcm(input: &[u8], key: &[u8]) -> Result<Vec<u8>, aes_gcm::Error> {
@@ -148,4 +299,71 @@ mod tests {
         assert!(decrypted.is_ok());
         assert_eq!(decrypted.unwrap(), "Hello World");
     }
+
+    fn random_key() -> String {
+        let key: [u8; 32] = rand::random();
+        encode_base64(&key)
+    }
+
+    #[test]
+    fn test_legacy_ciphertexts_stay_readable() {
+        let legacy_key = random_key();
+        let legacy = encrypt_string("Hello World", &legacy_key).unwrap();
+        assert!(!legacy.starts_with("v"));
+
+        let keyring = format!("1:{}, {}", random_key(), legacy_key);
+        assert_eq!(decrypt_string(&legacy, &keyring).unwrap(), "Hello World");
+
+        let rotated = encrypt_string("Hello World", &keyring).unwrap();
+        assert!(rotated.starts_with("env$local$v1:"));
+        assert_eq!(decrypt_string(&rotated, &keyring).unwrap(), "Hello World");
+
+        assert!(is_stale(&legacy, &keyring).unwrap());
+        assert!(!is_stale(&rotated, &keyring).unwrap());
+
+        // Values encrypted directly with a versioned key predate envelopes
+        let direct = Keyring::parse(&keyring)
+            .unwrap()
+            .encrypt("Hello World")
+            .unwrap();
+        assert!(direct.starts_with("v1:"));
+        assert_eq!(decrypt_string(&direct, &keyring).unwrap(), "Hello World");
+        assert!(is_stale(&direct, &keyring).unwrap());
+    }
+
+    #[test]
+    fn test_data_keys_wrapped_by_an_old_key_are_stale() {
+        let old_key = random_key();
+        let encrypted = encrypt_string("secret", &format!("1:{}", old_key)).unwrap();
+
+        let rotated = format!("2:{},1:{}", random_key(), old_key);
+        assert!(is_stale(&encrypted, &rotated).unwrap());
+        assert_eq!(decrypt_string(&encrypted, &rotated).unwrap(), "secret");
+    }
+
+    #[test]
+    fn test_removed_key_can_not_decrypt() {
+        let old_key = random_key();
+        let new_key = random_key();
+        let encrypted = encrypt_string("secret", &format!("1:{}", old_key)).unwrap();
+
+        match decrypt_string(&encrypted, &format!("2:{}", new_key)) {
+            Err(SecretError::UnknownKeyVersion(1)) => {}
+            other => panic!("unexpected result {:?}", other),
+        }
+    }
+
+    #[test]
+    fn test_invalid_keyrings() {
+        let key = random_key();
+        assert!(Keyring::parse(&format!("1:{},1:{}", key, random_key())).is_err());
+        assert!(Keyring::parse(&format!("one:{}", key)).is_err());
+        assert!(Keyring::parse("1:c2hvcnQ=").is_err());
+        assert_eq!(
+            Keyring::parse(&format!("3:{},2:{}", key, random_key()))
+                .unwrap()
+                .versions(),
+            vec![3, 2]
+        );
+    }
 }
@@ -0,0 +1,272 @@
+use base64::engine::general_purpose::URL_SAFE_NO_PAD;
+use base64::Engine;
+use chrono::{Duration, Utc};
+use diesel::prelude::*;
+use diesel::PgConnection;
+use domain::models::{JwtSigningAlgorithm, JwtSigningKey, NewJwtSigningKey};
+use jsonwebtoken::jwk::{
+    AlgorithmParameters, CommonParameters, EllipticCurve, Jwk, JwkSet, KeyAlgorithm,
+    OctetKeyPairParameters, OctetKeyPairType, PublicKeyUse, RSAKeyParameters, RSAKeyType,
+};
+use jsonwebtoken::{Algorithm, DecodingKey, EncodingKey};
+use ring::rand::SystemRandom;
+use ring::signature::{Ed25519KeyPair, KeyPair};
+use rsa::pkcs1::EncodeRsaPrivateKey;
+use rsa::traits::PublicKeyParts;
+use rsa::RsaPrivateKey;
+use shared::deployment_config::{TokenSigningAlgorithm, TokenSigningConfig};
+use uuid::Uuid;
+
+use crate::project::project_crud::Encryptable;
+use crate::users::secret::{
+    decode_base64, decrypt_string, encode_base64, encrypt_string, SecretError,
+};
+use crate::users::user::UserError;
+
+pub const DEFAULT_ROTATION_DAYS: u32 = 90;
+const RSA_KEY_BITS: usize = 2048;
+
+/// The key pair new tokens are signed with.
+pub struct SigningKey {
+    pub kid: String,
+    pub algorithm: Algorithm,
+    pub encoding_key: EncodingKey,
+}
+
+impl Encryptable for JwtSigningKey {
+    fn encrypt(&mut self, key: &str) -> Result<(), SecretError> {
+        self.private_key = encrypt_string(&self.private_key, key)?;
+        Ok(())
+    }
+
+    fn decrypt(&mut self, key: &str) -> Result<(), SecretError> {
+        self.private_key = decrypt_string(&self.private_key, key)?;
+        Ok(())
+    }
+}
+
+fn stored_algorithm(algorithm: TokenSigningAlgorithm) -> JwtSigningAlgorithm {
+    match algorithm {
+        TokenSigningAlgorithm::RS256 => JwtSigningAlgorithm::Rs256,
+        TokenSigningAlgorithm::EdDSA => JwtSigningAlgorithm::EdDsa,
+    }
+}
+
+fn jwt_algorithm(algorithm: JwtSigningAlgorithm) -> Algorithm {
+    match algorithm {
+        JwtSigningAlgorithm::Rs256 => Algorithm::RS256,
+        JwtSigningAlgorithm::EdDsa => Algorithm::EdDSA,
+    }
+}
+
+fn key_error(e: impl ToString) -> UserError {
+    UserError::SecretError(format!("Signing key error: {}", e.to_string()))
+}
+
+/// Generates a key pair, returning the DER encoded private key (PKCS#1 for
+/// RSA, PKCS#8 for Ed25519, as `EncodingKey` expects them) and the public JWK.
+fn generate_key_pair(
+    algorithm: JwtSigningAlgorithm,
+    kid: &str,
+) -> Result<(Vec<u8>, Jwk), UserError> {
+    let (private_der, key_algorithm, parameters) = match algorithm {
+        JwtSigningAlgorithm::Rs256 => {
+            let private_key =
+                RsaPrivateKey::new(&mut rand::thread_rng(), RSA_KEY_BITS).map_err(key_error)?;
+            let private_der = private_key.to_pkcs1_der().map_err(key_error)?;
+            (
+                private_der.as_bytes().to_vec(),
+                KeyAlgorithm::RS256,
+                AlgorithmParameters::RSA(RSAKeyParameters {
+                    key_type: RSAKeyType::RSA,
+                    n: URL_SAFE_NO_PAD.encode(private_key.n().to_bytes_be()),
+                    e: URL_SAFE_NO_PAD.encode(private_key.e().to_bytes_be()),
+                }),
+            )
+        }
+        JwtSigningAlgorithm::EdDsa => {
+            let pkcs8 = Ed25519KeyPair::generate_pkcs8(&SystemRandom::new()).map_err(key_error)?;
+            let key_pair = Ed25519KeyPair::from_pkcs8(pkcs8.as_ref()).map_err(key_error)?;
+            (
+                pkcs8.as_ref().to_vec(),
+                KeyAlgorithm::EdDSA,
+                AlgorithmParameters::OctetKeyPair(OctetKeyPairParameters {
+                    key_type: OctetKeyPairType::OctetKeyPair,
+                    curve: EllipticCurve::Ed25519,
+                    x: URL_SAFE_NO_PAD.encode(key_pair.public_key().as_ref()),
+                }),
+            )
+        }
+    };
+
+    let jwk = Jwk {
+        common: CommonParameters {
+            public_key_use: Some(PublicKeyUse::Signature),
+            key_algorithm: Some(key_algorithm),
+            key_id: Some(kid.to_string()),
+            ..Default::default()
+        },
+        algorithm: parameters,
+    };
+
+    Ok((private_der, jwk))
+}
+
+fn encoding_key(algorithm: JwtSigningAlgorithm, private_der: &[u8]) -> EncodingKey {
+    match algorithm {
+        JwtSigningAlgorithm::Rs256 => EncodingKey::from_rsa_der(private_der),
+        JwtSigningAlgorithm::EdDsa => EncodingKey::from_ed_der(private_der),
+    }
+}
+
+/// Generates a key pair that signs from now on and retires the current keys.
+/// Keys retired for longer than `retention` are deleted, as every token they
+/// signed has expired.
+pub fn rotate(
+    algorithm: JwtSigningAlgorithm,
+    retention: Duration,
+    encryption_key: &str,
+    conn: &mut PgConnection,
+) -> Result<JwtSigningKey, UserError> {
+    use domain::schema::syncflow::jwt_signing_keys::dsl;
+
+    let kid = Uuid::new_v4().simple().to_string();
+    let (private_der, jwk) = generate_key_pair(algorithm, &kid)?;
+    let new_key = NewJwtSigningKey {
+        kid,
+        algorithm,
+        private_key: encrypt_string(&encode_base64(&private_der), encryption_key)
+            .map_err(|e| UserError::SecretError(e.to_string()))?,
+        public_jwk: serde_json::to_string(&jwk).map_err(key_error)?,
+    };
+
+    let key = conn.transaction(|conn| {
+        let now = Utc::now().naive_utc();
+        diesel::update(dsl::jwt_signing_keys.filter(dsl::retired_at.is_null()))
+            .set(dsl::retired_at.eq(Some(now)))
+            .execute(conn)?;
+        diesel::delete(dsl::jwt_signing_keys.filter(dsl::retired_at.lt(now - retention)))
+            .execute(conn)?;
+        diesel::insert_into(dsl::jwt_signing_keys)
+            .values(&new_key)
+            .get_result::<JwtSigningKey>(conn)
+    })?;
+
+    log::info!("Rotated the token signing key, new key {}", key.kid);
+    Ok(key)
+}
+
+/// The key to sign with, rotating first when there is none for the configured
+/// algorithm or it is older than the rotation interval.
+pub fn active_signing_key(
+    config: &TokenSigningConfig,
+    retention: Duration,
+    encryption_key: &str,
+    conn: &mut PgConnection,
+) -> Result<SigningKey, UserError> {
+    use domain::schema::syncflow::jwt_signing_keys::dsl;
+
+    let algorithm = stored_algorithm(config.algorithm);
+    let rotation_interval =
+        Duration::days(config.rotation_days.unwrap_or(DEFAULT_ROTATION_DAYS) as i64);
+
+    let current = dsl::jwt_signing_keys
+        .filter(dsl::retired_at.is_null())
+        .filter(dsl::algorithm.eq(algorithm))
+        .order(dsl::created_at.desc())
+        .first::<JwtSigningKey>(conn)
+        .optional()?;
+
+    let mut key = match current {
+        Some(key) if key.created_at + rotation_interval > Utc::now().naive_utc() => key,
+        _ => rotate(algorithm, retention, encryption_key, conn)?,
+    };
+
+    key.decrypt(encryption_key)
+        .map_err(|e| UserError::SecretError(e.to_string()))?;
+    let private_der = decode_base64(&key.private_key).map_err(key_error)?;
+
+    Ok(SigningKey {
+        encoding_key: encoding_key(key.algorithm, &private_der),
+        algorithm: jwt_algorithm(key.algorithm),
+        kid: key.kid,
+    })
+}
+
+/// The public key named by the `kid` header of a token.
+pub fn decoding_key(
+    kid: &str,
+    conn: &mut PgConnection,
+) -> Result<(Algorithm, DecodingKey), UserError> {
+    use domain::schema::syncflow::jwt_signing_keys::dsl;
+
+    let key = dsl::jwt_signing_keys
+        .find(kid)
+        .first::<JwtSigningKey>(conn)
+        .optional()?
+        .ok_or_else(|| UserError::TokenError("Unknown signing key".to_string()))?;
+    let jwk: Jwk = serde_json::from_str(&key.public_jwk).map_err(key_error)?;
+    let decoding_key = DecodingKey::from_jwk(&jwk).map_err(key_error)?;
+
+    Ok((jwt_algorithm(key.algorithm), decoding_key))
+}
+
+/// The public keys of all current and retired keys, newest first.
+pub fn jwks(conn: &mut PgConnection) -> Result<JwkSet, UserError> {
+    use domain::schema::syncflow::jwt_signing_keys::dsl;
+
+    let keys = dsl::jwt_signing_keys
+        .order(dsl::created_at.desc())
+        .load::<JwtSigningKey>(conn)?
+        .iter()
+        .map(|key| serde_json::from_str::<Jwk>(&key.public_jwk).map_err(key_error))
+        .collect::<Result<Vec<_>, _>>()?;
+
+    Ok(JwkSet { keys })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde::{Deserialize, Serialize};
+    use shared::signed_token::{decode_jwt_header, sign_jwt_with_key, verify_jwt_with_key};
+
+    #[derive(Debug, Serialize, Deserialize, PartialEq)]
+    struct TestClaims {
+        exp: usize,
+        sub: String,
+    }
+
+    #[test]
+    fn test_ed25519_key_pair_signs_and_verifies_through_jwk() {
+        let (private_der, jwk) = generate_key_pair(JwtSigningAlgorithm::EdDsa, "kid-1").unwrap();
+        let claims = TestClaims {
+            exp: 10000000000,
+            sub: "user".to_string(),
+        };
+
+        let token = sign_jwt_with_key(
+            &claims,
+            Algorithm::EdDSA,
+            "kid-1",
+            &encoding_key(JwtSigningAlgorithm::EdDsa, &private_der),
+        )
+        .unwrap();
+        assert_eq!(
+            decode_jwt_header(&token).unwrap().kid.as_deref(),
+            Some("kid-1")
+        );
+
+        // The JWK survives the round trip through the database column
+        let stored: Jwk = serde_json::from_str(&serde_json::to_string(&jwk).unwrap()).unwrap();
+        let decoding_key = DecodingKey::from_jwk(&stored).unwrap();
+        let decoded: TestClaims =
+            verify_jwt_with_key(&token, Algorithm::EdDSA, &decoding_key).unwrap();
+        assert_eq!(decoded, claims);
+
+        // A key of another pair does not verify it
+        let (_, other_jwk) = generate_key_pair(JwtSigningAlgorithm::EdDsa, "kid-2").unwrap();
+        let other_key = DecodingKey::from_jwk(&other_jwk).unwrap();
+        assert!(verify_jwt_with_key::<TestClaims>(&token, Algorithm::EdDSA, &other_key).is_err());
+    }
+}
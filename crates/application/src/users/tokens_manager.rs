@@ -1,15 +1,36 @@
+use std::sync::Arc;
+use std::time::Duration;
+
+use crate::cache::TtlCache;
 use crate::project::project_crud;
 use crate::users::secret::decrypt_string;
+use crate::users::signing_keys;
 use crate::users::user;
 use crate::users::user::{LoginSessionInfo, UserError};
 use diesel::PgConnection;
 use domain::models::ApiKey;
+use serde::de::DeserializeOwned;
 use serde::{Deserialize, Serialize};
-use shared::claims::{ApiToken, LoginToken, ProjectToken, RefreshToken, TokenTypes};
-use shared::signed_token::{decode_jwt_unsafe, generate_and_sign_jwt, verify_and_decode_jwt};
+use sha2::{Digest, Sha256};
+use shared::api_key_scopes::{ApiKeyScope, IpRange};
+use shared::claims::{
+    ApiToken, LoginToken, MfaPendingToken, ProjectToken, RefreshToken, TokenKind, TokenTypes,
+    TypedClaims,
+};
+use shared::deployment_config::{DeploymentConfig, TokenSigningConfig};
+use shared::signed_token::{
+    decode_jwt_header, decode_jwt_unsafe, generate_and_sign_jwt, sign_jwt_with_key,
+    verify_and_decode_jwt, verify_jwt_with_key,
+};
 
 pub type UserTokenType = TokenTypes;
 
+/// Seconds a user has to enter the second factor after the password.
+pub const MFA_TOKEN_EXPIRATION: usize = 5 * 60;
+
+const DEFAULT_TOKEN_CACHE_TTL_SECONDS: u64 = 30;
+const DEFAULT_TOKEN_CACHE_CAPACITY: usize = 10_000;
+
 #[derive(Debug, Serialize, Deserialize, Clone)]
 #[serde(rename_all = "camelCase")]
 pub struct TokenInfo {
@@ -17,38 +38,125 @@ pub struct TokenInfo {
     pub user_name: String,
     pub login_session: Option<String>,
     pub project_id: Option<String>,
+    pub api_key: Option<ApiKeyGrant>,
 }
 
+/// Restrictions of the project API key a token was signed with.
+#[derive(Debug, Serialize, Deserialize, Clone)]
+#[serde(rename_all = "camelCase")]
+pub struct ApiKeyGrant {
+    pub key_id: i32,
+    pub scopes: Vec<ApiKeyScope>,
+    pub allowed_ips: Vec<String>,
+}
+
+impl ApiKeyGrant {
+    pub fn allows_scope(&self, required: ApiKeyScope) -> bool {
+        ApiKeyScope::granted(&self.scopes, required)
+    }
+
+    pub fn allows_ip(&self, ip: std::net::IpAddr) -> bool {
+        let allow_list = self
+            .allowed_ips
+            .iter()
+            .filter_map(|range| range.parse::<IpRange>().ok())
+            .collect::<Vec<_>>();
+
+        // A list that no longer parses must not turn into "allow everything"
+        if allow_list.len() != self.allowed_ips.len() {
+            return false;
+        }
+
+        IpRange::allowed(&allow_list, ip)
+    }
+}
+
+/// A verified token, kept until it expires or its session or key is revoked.
+#[derive(Debug, Clone)]
+struct VerifiedToken {
+    info: TokenInfo,
+    /// The key the token was signed with
+    issuer: String,
+    valid_until: usize,
+}
+
+/// Issues and verifies tokens. Clones share the caches of verified tokens and
+/// decrypted key secrets, which the owner has to invalidate when sessions or
+/// keys are revoked.
+#[derive(Clone)]
 pub struct JWTTokensManager {
     pub encryption_key: String,
     pub access_token_expiration: usize,
     pub refresh_token_expiration: usize,
+    /// Server-managed key pairs signing the tokens of login sessions, the
+    /// login secrets of the users sign when unset
+    pub signing_config: Option<TokenSigningConfig>,
+    verified_tokens: Arc<TtlCache<String, VerifiedToken>>,
+    /// Plaintext key secrets by ciphertext, so a changed secret is never
+    /// served from the cache
+    decrypted_secrets: Arc<TtlCache<String, String>>,
 }
 
 impl JWTTokensManager {
-    pub fn new(
-        encryption_key: &str,
-        access_token_expiration: usize,
-        refresh_token_expiration: usize,
-    ) -> Self {
+    pub fn new(config: &DeploymentConfig) -> Self {
+        let ttl = Duration::from_secs(
+            config
+                .token_cache_ttl_seconds
+                .unwrap_or(DEFAULT_TOKEN_CACHE_TTL_SECONDS),
+        );
+        let capacity = config
+            .token_cache_capacity
+            .unwrap_or(DEFAULT_TOKEN_CACHE_CAPACITY);
+
         JWTTokensManager {
-            encryption_key: encryption_key.to_owned(),
-            access_token_expiration,
-            refresh_token_expiration,
+            encryption_key: config.encryption_key.to_owned(),
+            access_token_expiration: config.jwt_expiration,
+            refresh_token_expiration: config.jwt_refresh_expiration,
+            signing_config: config.token_signing_config.clone(),
+            verified_tokens: Arc::new(TtlCache::new(ttl, capacity)),
+            decrypted_secrets: Arc::new(TtlCache::new(ttl, capacity)),
         }
     }
 
+    /// Drops the cached tokens of a login session, e.g. on logout.
+    pub fn forget_login_session(&self, session_id: &str) {
+        self.verified_tokens.invalidate_where(|_, verified| {
+            verified.info.login_session.as_deref() == Some(session_id)
+        });
+    }
+
+    /// Drops every cached token of the user, e.g. when their sessions are revoked.
+    pub fn forget_user(&self, user_id: i32) {
+        self.verified_tokens
+            .invalidate_where(|_, verified| verified.info.user_id == user_id);
+    }
+
+    /// Drops the cached tokens of a project, e.g. when it is deleted.
+    pub fn forget_project(&self, project_id: &str) {
+        self.verified_tokens.invalidate_where(|_, verified| {
+            verified.info.project_id.as_deref() == Some(project_id)
+        });
+    }
+
+    /// Drops the cached tokens signed with a deleted key, and its secret.
+    pub fn forget_api_key(&self, key: &str, encrypted_secret: &str) {
+        self.verified_tokens
+            .invalidate_where(|_, verified| verified.issuer == key);
+        self.decrypted_secrets.remove(&encrypted_secret.to_string());
+    }
+
     pub fn generate_login_token(
         &self,
         login_session_info: &LoginSessionInfo,
         conn: &mut PgConnection,
     ) -> Result<String, UserError> {
         let api_key = user::fetch_login_key(login_session_info.user_id, conn)?;
-        let decrypted_secret = self.decrypt_user_secret(&api_key)?;
 
-        let exp = chrono::Utc::now().timestamp() as usize + self.access_token_expiration;
+        let exp = (chrono::Utc::now().timestamp() as usize + self.access_token_expiration)
+            .min(session_expiry(login_session_info));
 
         let user_token = LoginToken {
+            typ: TokenKind::Login,
             iat: chrono::Utc::now().timestamp() as usize,
             exp,
             iss: api_key.key.to_owned(),
@@ -57,8 +165,7 @@ impl JWTTokensManager {
             login_session: login_session_info.session_id.to_owned(),
         };
 
-        generate_and_sign_jwt::<LoginToken>(&user_token, &decrypted_secret)
-            .map_err(|e| UserError::TokenError(e.to_string()))
+        self.sign_user_token(&user_token, &api_key, conn)
     }
 
     pub fn generate_login_token_pairs(
@@ -67,13 +174,15 @@ impl JWTTokensManager {
         conn: &mut PgConnection,
     ) -> Result<(String, String), UserError> {
         let api_key = user::fetch_login_key(login_session_info.user_id, conn)?;
-        let decrypted_secret = self.decrypt_user_secret(&api_key)?;
 
-        //ToDo: Make this configurable
-        let login_token_expiry =
-            chrono::Utc::now().timestamp() as usize + self.access_token_expiration;
-        let refresh_token_expiry = login_token_expiry + self.refresh_token_expiration;
+        let (login_token_expiry, refresh_token_expiry) = token_pair_expiry(
+            chrono::Utc::now().timestamp() as usize,
+            self.access_token_expiration,
+            self.refresh_token_expiration,
+            session_expiry(login_session_info),
+        );
         let user_token = LoginToken {
+            typ: TokenKind::Login,
             iat: chrono::Utc::now().timestamp() as usize,
             exp: login_token_expiry,
             iss: api_key.key.to_owned(),
@@ -83,29 +192,152 @@ impl JWTTokensManager {
         };
 
         let refresh_token: RefreshToken = RefreshToken {
+            typ: TokenKind::Refresh,
             iat: chrono::Utc::now().timestamp() as usize,
             exp: refresh_token_expiry,
             iss: api_key.key.to_owned(),
             login_session: login_session_info.session_id.to_owned(),
+            jti: login_session_info.refresh_token_id.to_owned(),
         };
 
-        let login_token = generate_and_sign_jwt::<LoginToken>(&user_token, &decrypted_secret)
-            .map_err(|e| UserError::TokenError(e.to_string()))?;
-
-        let refresh_token =
-            generate_and_sign_jwt::<RefreshToken>(&refresh_token, &decrypted_secret)
-                .map_err(|e| UserError::TokenError(e.to_string()))?;
+        let login_token = self.sign_user_token(&user_token, &api_key, conn)?;
+        let refresh_token = self.sign_user_token(&refresh_token, &api_key, conn)?;
 
         Ok((login_token, refresh_token))
     }
 
+    /// Issues the token a user with two-factor authentication trades for a
+    /// token pair together with a valid code.
+    pub fn generate_mfa_token(
+        &self,
+        user_id: i32,
+        conn: &mut PgConnection,
+    ) -> Result<String, UserError> {
+        let api_key = user::fetch_login_key(user_id, conn)?;
+        let now = chrono::Utc::now().timestamp() as usize;
+
+        let mfa_token = MfaPendingToken {
+            typ: TokenKind::MfaPending,
+            iat: now,
+            exp: now + MFA_TOKEN_EXPIRATION,
+            iss: api_key.key.to_owned(),
+            user_id,
+            mfa_pending: true,
+        };
+
+        self.sign_user_token(&mfa_token, &api_key, conn)
+    }
+
+    /// Verifies an mfa pending token, returning the id of its user.
+    pub fn verify_mfa_token(&self, token: &str, conn: &mut PgConnection) -> Result<i32, UserError> {
+        let unverified = decode_jwt_unsafe::<MfaPendingToken>(token)
+            .map_err(|_| UserError::TokenError("Not an mfa token".to_string()))?;
+        // Only the login key of the user the token names can have signed it
+        let api_key = user::fetch_login_key(unverified.user_id, conn)?;
+        let token_data = self.verify_user_token::<MfaPendingToken>(token, &api_key, conn)?;
+
+        if token_data.iss != api_key.key
+            || token_data.typ != TokenKind::MfaPending
+            || !token_data.mfa_pending
+            || token_data.exp < chrono::Utc::now().timestamp() as usize
+        {
+            return Err(UserError::TokenError("Invalid token".to_string()));
+        }
+
+        Ok(token_data.user_id)
+    }
+
+    /// Signs a token of a login session with the active server key pair when
+    /// one is configured, and with the login secret in `api_key` otherwise.
+    fn sign_user_token<T: Serialize>(
+        &self,
+        claims: &T,
+        api_key: &ApiKey,
+        conn: &mut PgConnection,
+    ) -> Result<String, UserError> {
+        match &self.signing_config {
+            Some(signing_config) => {
+                let key = signing_keys::active_signing_key(
+                    signing_config,
+                    self.signing_key_retention(),
+                    &self.encryption_key,
+                    conn,
+                )?;
+                sign_jwt_with_key(claims, key.algorithm, &key.kid, &key.encoding_key)
+            }
+            None => generate_and_sign_jwt(claims, &self.decrypt_user_secret(api_key)?),
+        }
+        .map_err(|e| UserError::TokenError(e.to_string()))
+    }
+
+    /// Checks the signature of a token of a login session. Tokens naming a
+    /// `kid` were signed by a server key pair, the others with the login
+    /// secret in `api_key`. Both are accepted regardless of the configuration,
+    /// so switching does not log anybody out.
+    fn verify_user_token<T: DeserializeOwned>(
+        &self,
+        token: &str,
+        api_key: &ApiKey,
+        conn: &mut PgConnection,
+    ) -> Result<T, UserError> {
+        let header = decode_jwt_header(token).map_err(|e| UserError::TokenError(e.to_string()))?;
+        match header.kid {
+            Some(kid) => {
+                let (algorithm, decoding_key) = signing_keys::decoding_key(&kid, conn)?;
+                verify_jwt_with_key(token, algorithm, &decoding_key)
+            }
+            None => verify_and_decode_jwt(token, &self.decrypt_user_secret(api_key)?),
+        }
+        .map_err(|e| UserError::TokenError(e.to_string()))
+    }
+
+    /// How long a retired signing key may still have signed tokens in use.
+    fn signing_key_retention(&self) -> chrono::Duration {
+        chrono::Duration::seconds(
+            (self.access_token_expiration + self.refresh_token_expiration) as i64,
+        )
+    }
+
     fn decrypt_user_secret(&self, api_key: &ApiKey) -> Result<String, UserError> {
-        let encrypted_secret = &api_key.secret;
-        decrypt_string(encrypted_secret, &self.encryption_key)
-            .map_err(|e| UserError::SecretError(e.to_string()))
+        self.decrypt_secret(&api_key.secret)
+    }
+
+    fn decrypt_secret(&self, encrypted_secret: &str) -> Result<String, UserError> {
+        let cache_key = encrypted_secret.to_string();
+        if let Some(secret) = self.decrypted_secrets.get(&cache_key) {
+            return Ok(secret);
+        }
+
+        let secret = decrypt_string(encrypted_secret, &self.encryption_key)
+            .map_err(|e| UserError::SecretError(e.to_string()))?;
+        self.decrypted_secrets.insert(cache_key, secret.clone());
+        Ok(secret)
     }
 
+    /// Decodes a token without checking its signature. The `typ` claim names
+    /// the kind, tokens issued without it are told apart by their claims.
     pub fn decode_token_unsafe(&self, token: &str) -> Result<TokenTypes, UserError> {
+        let invalid = |_| UserError::TokenError("Invalid token".to_string());
+        let kind = decode_jwt_unsafe::<TypedClaims>(token)
+            .map_err(invalid)?
+            .typ;
+
+        match kind {
+            Some(TokenKind::Login) => decode_jwt_unsafe(token).map(TokenTypes::LoginToken),
+            Some(TokenKind::Refresh) => decode_jwt_unsafe(token).map(TokenTypes::RefreshToken),
+            Some(TokenKind::Api) => decode_jwt_unsafe(token).map(TokenTypes::ApiToken),
+            Some(TokenKind::Project) => decode_jwt_unsafe(token).map(TokenTypes::ProjectToken),
+            Some(TokenKind::MfaPending) => {
+                return Err(UserError::TokenError(
+                    "An mfa token is not a bearer token".to_string(),
+                ))
+            }
+            None => return self.decode_untyped_token(token),
+        }
+        .map_err(invalid)
+    }
+
+    fn decode_untyped_token(&self, token: &str) -> Result<TokenTypes, UserError> {
         if let Ok(token_data) = decode_jwt_unsafe::<LoginToken>(token) {
             Ok(TokenTypes::LoginToken(token_data))
         } else if let Ok(token_data) = decode_jwt_unsafe::<RefreshToken>(token) {
@@ -113,24 +345,27 @@ impl JWTTokensManager {
         } else if let Ok(token_data) = decode_jwt_unsafe::<ProjectToken>(token) {
             Ok(TokenTypes::ProjectToken(token_data))
         } else {
-            return Err(UserError::TokenError("Invalid token".to_string()));
+            Err(UserError::TokenError("Invalid token".to_string()))
         }
     }
 
+    /// Verifies a token, answering from the cache for tokens verified
+    /// recently. Refresh tokens are always checked against the database.
     pub fn verify_token(
         &self,
         token: &str,
         conn: &mut PgConnection,
     ) -> Result<TokenInfo, UserError> {
+        let cache_key = token_cache_key(token);
+        if let Some(info) = self.recall(&cache_key) {
+            return Ok(info);
+        }
+
         let parsed_token = self.decode_token_unsafe(token)?;
         match parsed_token {
             TokenTypes::LoginToken(token_data) => {
                 let api_key = user::fetch_api_key_by_id(token_data.iss.as_str(), conn)?;
-                let encrypted_secret = api_key.secret;
-                let decrypted_secret = decrypt_string(&encrypted_secret, &self.encryption_key)
-                    .map_err(|e| UserError::SecretError(e.to_string()))?;
-                let token_data = verify_and_decode_jwt::<LoginToken>(token, &decrypted_secret)
-                    .map_err(|e| UserError::TokenError(e.to_string()))?;
+                let token_data = self.verify_user_token::<LoginToken>(token, &api_key, conn)?;
 
                 // Verify that the token is valid
                 if token_data.iss != api_key.key {
@@ -141,45 +376,38 @@ impl JWTTokensManager {
                     return Err(UserError::TokenError("Invalid token".to_string()));
                 }
 
-                Ok(TokenInfo {
-                    user_id: token_data.user_id,
-                    user_name: token_data.user_name.to_owned(),
-                    login_session: Some(token_data.login_session.to_owned()),
-                    project_id: None,
-                })
+                Ok(self.remember(
+                    cache_key,
+                    VerifiedToken {
+                        info: TokenInfo {
+                            user_id: token_data.user_id,
+                            user_name: token_data.user_name.to_owned(),
+                            login_session: Some(token_data.login_session.to_owned()),
+                            project_id: None,
+                            api_key: None,
+                        },
+                        issuer: api_key.key,
+                        valid_until: token_data.exp,
+                    },
+                ))
             }
 
             TokenTypes::RefreshToken(token_data) => {
-                let api_key = user::fetch_api_key_by_id(token_data.iss.as_str(), conn)?;
-                let encrypted_secret = api_key.secret;
-                let decrypted_secret = decrypt_string(&encrypted_secret, &self.encryption_key)
-                    .map_err(|e| UserError::SecretError(e.to_string()))?;
-                let token_data = verify_and_decode_jwt::<RefreshToken>(token, &decrypted_secret)
-                    .map_err(|e| UserError::TokenError(e.to_string()))?;
-
-                if token_data.iss != api_key.key {
-                    return Err(UserError::TokenError("Invalid token".to_string()));
-                }
-
-                if !self.is_refresh_token_valid(&token_data, conn) {
-                    return Err(UserError::TokenError("Invalid token".to_string()));
-                }
-
-                let user = user::get_user(api_key.user_id, conn)?;
+                let (token_data, user_id) = self.verify_refresh_claims(token, &token_data, conn)?;
+                let user = user::get_user(user_id, conn)?;
 
                 Ok(TokenInfo {
                     user_id: user.id,
                     user_name: user.username.to_owned(),
                     login_session: Some(token_data.login_session.to_owned()),
                     project_id: None,
+                    api_key: None,
                 })
             }
 
             TokenTypes::ApiToken(token_data) => {
                 let api_key = user::fetch_api_key_by_key(token_data.iss.as_str(), conn)?;
-                let encrypted_secret = api_key.secret;
-                let decrypted_secret = decrypt_string(&encrypted_secret, &self.encryption_key)
-                    .map_err(|e| UserError::SecretError(e.to_string()))?;
+                let decrypted_secret = self.decrypt_user_secret(&api_key)?;
                 let token_data = verify_and_decode_jwt::<ApiToken>(token, &decrypted_secret)
                     .map_err(|e| UserError::TokenError(e.to_string()))?;
 
@@ -193,12 +421,20 @@ impl JWTTokensManager {
                 }
 
                 let user = user::get_user(api_key.user_id, conn)?;
-                Ok(TokenInfo {
-                    user_id: user.id,
-                    user_name: user.username.to_owned(),
-                    login_session: None,
-                    project_id: None,
-                })
+                Ok(self.remember(
+                    cache_key,
+                    VerifiedToken {
+                        info: TokenInfo {
+                            user_id: user.id,
+                            user_name: user.username.to_owned(),
+                            login_session: None,
+                            project_id: None,
+                            api_key: None,
+                        },
+                        issuer: api_key.key,
+                        valid_until: token_data.exp,
+                    },
+                ))
             }
 
             TokenTypes::ProjectToken(token_data) => {
@@ -209,9 +445,7 @@ impl JWTTokensManager {
                 )
                 .map_err(|e| UserError::TokenError(e.to_string()))?;
 
-                let encrypted_secret = api_key.api_secret;
-                let decrypted_secret = decrypt_string(&encrypted_secret, &self.encryption_key)
-                    .map_err(|e| UserError::SecretError(e.to_string()))?;
+                let decrypted_secret = self.decrypt_secret(&api_key.api_secret)?;
 
                 let token_data = verify_and_decode_jwt::<ProjectToken>(token, &decrypted_secret)
                     .map_err(|e| UserError::TokenError(e.to_string()))?;
@@ -224,17 +458,99 @@ impl JWTTokensManager {
                     return Err(UserError::TokenError("Invalid token".to_string()));
                 }
 
+                if api_key.is_expired() {
+                    return Err(UserError::TokenError("API key has expired".to_string()));
+                }
+
+                let scopes = ApiKeyScope::parse_list(&api_key.scopes)
+                    .map_err(|e| UserError::TokenError(e.to_string()))?;
+
+                if let Err(e) = project_crud::touch_api_key(api_key.id, conn) {
+                    log::warn!("Failed to record the use of API key {}: {}", api_key.id, e);
+                }
+
                 let user = user::get_user(api_key.user_id, conn)?;
-                Ok(TokenInfo {
-                    user_id: user.id,
-                    user_name: user.username.to_owned(),
-                    login_session: None,
-                    project_id: Some(token_data.project_id.to_owned()),
-                })
+                // Cached use does not outlive the key either
+                let valid_until = api_key
+                    .expires_at
+                    .map(|expires_at| expires_at.and_utc().timestamp().max(0) as usize)
+                    .map_or(token_data.exp, |expires_at| expires_at.min(token_data.exp));
+                Ok(self.remember(
+                    cache_key,
+                    VerifiedToken {
+                        info: TokenInfo {
+                            user_id: user.id,
+                            user_name: user.username.to_owned(),
+                            login_session: None,
+                            project_id: Some(token_data.project_id.to_owned()),
+                            api_key: Some(ApiKeyGrant {
+                                key_id: api_key.id,
+                                allowed_ips: api_key.allowed_ip_list(),
+                                scopes,
+                            }),
+                        },
+                        issuer: api_key.api_key,
+                        valid_until,
+                    },
+                ))
             }
         }
     }
 
+    /// The cached verification of a token, unless the token has expired since.
+    fn recall(&self, cache_key: &str) -> Option<TokenInfo> {
+        let cache_key = cache_key.to_string();
+        let verified = self.verified_tokens.get(&cache_key)?;
+        if verified.valid_until >= chrono::Utc::now().timestamp() as usize {
+            return Some(verified.info);
+        }
+
+        self.verified_tokens.remove(&cache_key);
+        None
+    }
+
+    fn remember(&self, cache_key: String, verified: VerifiedToken) -> TokenInfo {
+        let info = verified.info.clone();
+        self.verified_tokens.insert(cache_key, verified);
+        info
+    }
+
+    /// Verifies a refresh token, rejecting every other kind of token.
+    pub fn verify_refresh_token(
+        &self,
+        token: &str,
+        conn: &mut PgConnection,
+    ) -> Result<RefreshToken, UserError> {
+        match self.decode_token_unsafe(token)? {
+            TokenTypes::RefreshToken(token_data) => self
+                .verify_refresh_claims(token, &token_data, conn)
+                .map(|(token_data, _)| token_data),
+            _ => Err(UserError::TokenError("Not a refresh token".to_string())),
+        }
+    }
+
+    /// Checks the signature of a refresh token, returning its claims and the
+    /// id of the user it was issued to.
+    fn verify_refresh_claims(
+        &self,
+        token: &str,
+        unverified: &RefreshToken,
+        conn: &mut PgConnection,
+    ) -> Result<(RefreshToken, i32), UserError> {
+        let api_key = user::fetch_api_key_by_id(unverified.iss.as_str(), conn)?;
+        let token_data = self.verify_user_token::<RefreshToken>(token, &api_key, conn)?;
+
+        if token_data.iss != api_key.key {
+            return Err(UserError::TokenError("Invalid token".to_string()));
+        }
+
+        if !self.is_refresh_token_valid(&token_data, conn) {
+            return Err(UserError::TokenError("Invalid token".to_string()));
+        }
+
+        Ok((token_data, api_key.user_id))
+    }
+
     pub fn is_login_token_valid(&self, token_data: &LoginToken, conn: &mut PgConnection) -> bool {
         let login_session_id = token_data.login_session.as_str();
 
@@ -261,6 +577,10 @@ impl JWTTokensManager {
             return false;
         }
 
+        if token_data.exp < (chrono::Utc::now().timestamp() as usize) {
+            return false;
+        }
+
         true
     }
 
@@ -281,3 +601,271 @@ impl JWTTokensManager {
         true
     }
 }
+
+/// Tokens are cached by digest, so the cache holds no usable credentials.
+fn token_cache_key(token: &str) -> String {
+    Sha256::digest(token.as_bytes())
+        .iter()
+        .map(|b| format!("{:02x}", b))
+        .collect()
+}
+
+fn session_expiry(login_session_info: &LoginSessionInfo) -> usize {
+    login_session_info.expires_at.and_utc().timestamp().max(0) as usize
+}
+
+/// Expiries of a login and refresh token pair issued at `now`. Neither token
+/// outlives the login session.
+fn token_pair_expiry(
+    now: usize,
+    access_token_expiration: usize,
+    refresh_token_expiration: usize,
+    session_expiry: usize,
+) -> (usize, usize) {
+    let login_token_expiry = (now + access_token_expiration).min(session_expiry);
+    let refresh_token_expiry = (login_token_expiry + refresh_token_expiration).min(session_expiry);
+
+    (login_token_expiry, refresh_token_expiry)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use shared::signed_token::generate_and_sign_jwt;
+
+    fn tokens_manager() -> JWTTokensManager {
+        JWTTokensManager {
+            encryption_key: "encryption-key".to_string(),
+            access_token_expiration: 60,
+            refresh_token_expiration: 600,
+            signing_config: None,
+            verified_tokens: Arc::new(TtlCache::new(Duration::from_secs(60), 100)),
+            decrypted_secrets: Arc::new(TtlCache::new(Duration::from_secs(60), 100)),
+        }
+    }
+
+    fn verified_token(
+        user_id: i32,
+        login_session: Option<&str>,
+        project_id: Option<&str>,
+        issuer: &str,
+    ) -> VerifiedToken {
+        VerifiedToken {
+            info: TokenInfo {
+                user_id,
+                user_name: format!("user-{}", user_id),
+                login_session: login_session.map(str::to_string),
+                project_id: project_id.map(str::to_string),
+                api_key: None,
+            },
+            issuer: issuer.to_string(),
+            valid_until: chrono::Utc::now().timestamp() as usize + 60,
+        }
+    }
+
+    /// A manager caching a login token of user 1, a project token of user 1
+    /// and a login token of user 2, each signed with its own key.
+    fn manager_with_cached_tokens() -> JWTTokensManager {
+        let manager = tokens_manager();
+        manager.remember(
+            "login-1".to_string(),
+            verified_token(1, Some("session-1"), None, "key-1"),
+        );
+        manager.remember(
+            "project-1".to_string(),
+            verified_token(1, None, Some("project-1"), "project-key-1"),
+        );
+        manager.remember(
+            "login-2".to_string(),
+            verified_token(2, Some("session-2"), None, "key-2"),
+        );
+        manager
+    }
+
+    fn cached(manager: &JWTTokensManager) -> Vec<&'static str> {
+        ["login-1", "project-1", "login-2"]
+            .into_iter()
+            .filter(|key| manager.recall(key).is_some())
+            .collect()
+    }
+
+    /// Signs `claims` issued now, decoding checks the expiry even without
+    /// checking the signature.
+    fn sign(mut claims: serde_json::Value) -> String {
+        let now = chrono::Utc::now().timestamp();
+        let timestamps = serde_json::json!({"iat": now, "exp": now + 60, "iss": "key"});
+        claims
+            .as_object_mut()
+            .unwrap()
+            .extend(timestamps.as_object().unwrap().clone());
+        generate_and_sign_jwt(&claims, "secret").unwrap()
+    }
+
+    #[test]
+    fn test_forget_cached_tokens() {
+        let manager = manager_with_cached_tokens();
+        manager.forget_login_session("session-1");
+        assert_eq!(cached(&manager), vec!["project-1", "login-2"]);
+
+        let manager = manager_with_cached_tokens();
+        manager.forget_user(1);
+        assert_eq!(cached(&manager), vec!["login-2"]);
+
+        let manager = manager_with_cached_tokens();
+        manager.forget_project("project-1");
+        assert_eq!(cached(&manager), vec!["login-1", "login-2"]);
+
+        let manager = manager_with_cached_tokens();
+        manager
+            .decrypted_secrets
+            .insert("encrypted-2".to_string(), "secret-2".to_string());
+        manager
+            .decrypted_secrets
+            .insert("encrypted-1".to_string(), "secret-1".to_string());
+        manager.forget_api_key("key-2", "encrypted-2");
+        assert_eq!(cached(&manager), vec!["login-1", "project-1"]);
+        assert!(manager
+            .decrypted_secrets
+            .get(&"encrypted-2".to_string())
+            .is_none());
+        assert!(manager
+            .decrypted_secrets
+            .get(&"encrypted-1".to_string())
+            .is_some());
+    }
+
+    #[test]
+    fn test_recall_expired_token() {
+        let manager = tokens_manager();
+        let mut expired = verified_token(1, Some("session-1"), None, "key-1");
+        expired.valid_until = chrono::Utc::now().timestamp() as usize - 1;
+        manager.remember("expired".to_string(), expired);
+
+        assert!(manager.recall("expired").is_none());
+        // The stale entry is dropped rather than kept around until its TTL
+        assert!(manager
+            .verified_tokens
+            .get(&"expired".to_string())
+            .is_none());
+    }
+
+    #[test]
+    fn test_decode_typed_tokens() {
+        let manager = tokens_manager();
+        let claims = |typ: &str, mut data: serde_json::Value| {
+            data["typ"] = serde_json::json!(typ);
+            sign(data)
+        };
+
+        let login = claims(
+            "Login",
+            serde_json::json!({"userName": "user", "userId": 1, "loginSession": "session"}),
+        );
+        assert!(matches!(
+            manager.decode_token_unsafe(&login),
+            Ok(TokenTypes::LoginToken(_))
+        ));
+
+        let refresh = claims(
+            "Refresh",
+            serde_json::json!({"loginSession": "session", "jti": "id"}),
+        );
+        assert!(matches!(
+            manager.decode_token_unsafe(&refresh),
+            Ok(TokenTypes::RefreshToken(token)) if token.jti.as_deref() == Some("id")
+        ));
+
+        let api = claims("Api", serde_json::json!({"project": null}));
+        assert!(matches!(
+            manager.decode_token_unsafe(&api),
+            Ok(TokenTypes::ApiToken(_))
+        ));
+
+        let project = claims("Project", serde_json::json!({"projectId": "project"}));
+        assert!(matches!(
+            manager.decode_token_unsafe(&project),
+            Ok(TokenTypes::ProjectToken(_))
+        ));
+
+        // The claims of a login token do not make it one when typed otherwise
+        let mislabeled = claims(
+            "Project",
+            serde_json::json!({"userName": "user", "userId": 1, "loginSession": "session"}),
+        );
+        assert!(manager.decode_token_unsafe(&mislabeled).is_err());
+
+        let mfa = claims(
+            "MfaPending",
+            serde_json::json!({"userId": 1, "mfaPending": true}),
+        );
+        assert!(matches!(
+            manager.decode_token_unsafe(&mfa),
+            Err(UserError::TokenError(_))
+        ));
+    }
+
+    #[test]
+    fn test_decode_untyped_tokens() {
+        let manager = tokens_manager();
+
+        let login = sign(serde_json::json!({
+            "userName": "user",
+            "userId": 1,
+            "loginSession": "session",
+        }));
+        assert!(matches!(
+            manager.decode_token_unsafe(&login),
+            Ok(TokenTypes::LoginToken(token)) if token.typ == TokenKind::Login
+        ));
+
+        let refresh = sign(serde_json::json!({
+            "loginSession": "session",
+        }));
+        assert!(matches!(
+            manager.decode_token_unsafe(&refresh),
+            Ok(TokenTypes::RefreshToken(token)) if token.jti.is_none()
+        ));
+
+        let project = sign(serde_json::json!({
+            "projectId": "project",
+        }));
+        assert!(matches!(
+            manager.decode_token_unsafe(&project),
+            Ok(TokenTypes::ProjectToken(_))
+        ));
+
+        let unknown = sign(serde_json::json!({}));
+        assert!(manager.decode_token_unsafe(&unknown).is_err());
+        assert!(manager.decode_token_unsafe("not-a-token").is_err());
+    }
+
+    #[test]
+    fn test_token_pair_expiry() {
+        // A long lived session leaves both expirations untouched
+        assert_eq!(token_pair_expiry(1_000, 60, 600, 1_000_000), (1_060, 1_660));
+        // The refresh token is clamped first
+        assert_eq!(token_pair_expiry(1_000, 60, 600, 1_300), (1_060, 1_300));
+        // Then both, once the session ends before the login token would
+        assert_eq!(token_pair_expiry(1_000, 60, 600, 1_030), (1_030, 1_030));
+        // An expired session yields tokens that are already expired
+        assert_eq!(token_pair_expiry(1_000, 60, 600, 0), (0, 0));
+    }
+
+    #[test]
+    fn test_session_expiry() {
+        let mut session = LoginSessionInfo {
+            session_id: "session".to_string(),
+            user_id: 1,
+            user_name: "user".to_string(),
+            refresh_token_id: None,
+            expires_at: chrono::DateTime::from_timestamp(1_300, 0)
+                .unwrap()
+                .naive_utc(),
+        };
+        assert_eq!(session_expiry(&session), 1_300);
+
+        // Timestamps before the epoch must not wrap around
+        session.expires_at = chrono::DateTime::from_timestamp(-1, 0).unwrap().naive_utc();
+        assert_eq!(session_expiry(&session), 0);
+    }
+}
@@ -0,0 +1,182 @@
+use hmac::{Hmac, Mac};
+use rand::{thread_rng, RngCore};
+use sha1::Sha1;
+
+/// Parameters understood by every authenticator app: SHA-1, six digits and
+/// 30 second steps (RFC 6238 defaults).
+pub const DIGITS: u32 = 6;
+pub const STEP_SECONDS: u64 = 30;
+pub const SECRET_LENGTH: usize = 20;
+
+/// Steps before and after the current one a code is still accepted for, to
+/// allow for clock drift.
+const ALLOWED_DRIFT: u64 = 1;
+
+const BASE32_ALPHABET: &[u8; 32] = b"ABCDEFGHIJKLMNOPQRSTUVWXYZ234567";
+
+pub fn generate_secret() -> Vec<u8> {
+    let mut secret = vec![0u8; SECRET_LENGTH];
+    thread_rng().fill_bytes(&mut secret);
+    secret
+}
+
+/// The code for a time step (RFC 4226 with the step as counter).
+pub fn code_at_step(secret: &[u8], step: u64) -> String {
+    let mut mac = Hmac::<Sha1>::new_from_slice(secret).expect("HMAC accepts keys of any length");
+    mac.update(&step.to_be_bytes());
+    let digest = mac.finalize().into_bytes();
+
+    let offset = (digest[digest.len() - 1] & 0x0f) as usize;
+    let binary = u32::from_be_bytes([
+        digest[offset] & 0x7f,
+        digest[offset + 1],
+        digest[offset + 2],
+        digest[offset + 3],
+    ]);
+
+    format!(
+        "{:0width$}",
+        binary % 10u32.pow(DIGITS),
+        width = DIGITS as usize
+    )
+}
+
+/// Returns the time step `code` is valid for at `unix_time`, if any.
+pub fn verify(secret: &[u8], code: &str, unix_time: u64) -> Option<u64> {
+    let code = code.trim().replace(' ', "");
+    if code.len() != DIGITS as usize || !code.chars().all(|c| c.is_ascii_digit()) {
+        return None;
+    }
+
+    let current = unix_time / STEP_SECONDS;
+    (current.saturating_sub(ALLOWED_DRIFT)..=current + ALLOWED_DRIFT)
+        .find(|step| constant_time_eq(code_at_step(secret, *step).as_bytes(), code.as_bytes()))
+}
+
+fn constant_time_eq(a: &[u8], b: &[u8]) -> bool {
+    a.len() == b.len() && a.iter().zip(b).fold(0u8, |acc, (x, y)| acc | (x ^ y)) == 0
+}
+
+/// The `otpauth://` URI authenticator apps import, usually shown as a QR code.
+pub fn provisioning_uri(issuer: &str, account: &str, secret: &[u8]) -> String {
+    format!(
+        "otpauth://totp/{}:{}?secret={}&issuer={}&algorithm=SHA1&digits={}&period={}",
+        percent_encode(issuer),
+        percent_encode(account),
+        encode_base32(secret),
+        percent_encode(issuer),
+        DIGITS,
+        STEP_SECONDS
+    )
+}
+
+fn percent_encode(value: &str) -> String {
+    value
+        .bytes()
+        .map(|b| match b {
+            b'A'..=b'Z' | b'a'..=b'z' | b'0'..=b'9' | b'-' | b'.' | b'_' | b'~' => {
+                (b as char).to_string()
+            }
+            _ => format!("%{:02X}", b),
+        })
+        .collect()
+}
+
+/// Unpadded RFC 4648 base32, the format authenticator apps expect.
+pub fn encode_base32(data: &[u8]) -> String {
+    let mut encoded = String::with_capacity(data.len().div_ceil(5) * 8);
+    let (mut buffer, mut bits) = (0u32, 0u32);
+
+    for byte in data {
+        buffer = (buffer << 8) | *byte as u32;
+        bits += 8;
+        while bits >= 5 {
+            bits -= 5;
+            encoded.push(BASE32_ALPHABET[((buffer >> bits) & 0x1f) as usize] as char);
+        }
+    }
+    if bits > 0 {
+        encoded.push(BASE32_ALPHABET[((buffer << (5 - bits)) & 0x1f) as usize] as char);
+    }
+
+    encoded
+}
+
+pub fn decode_base32(encoded: &str) -> Option<Vec<u8>> {
+    let mut decoded = Vec::with_capacity(encoded.len() * 5 / 8);
+    let (mut buffer, mut bits) = (0u32, 0u32);
+
+    for c in encoded.trim_end_matches('=').chars() {
+        let value = BASE32_ALPHABET
+            .iter()
+            .position(|a| *a as char == c.to_ascii_uppercase())? as u32;
+        buffer = (buffer << 5) | value;
+        bits += 5;
+        if bits >= 8 {
+            bits -= 8;
+            decoded.push((buffer >> bits) as u8);
+        }
+    }
+
+    Some(decoded)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const RFC_SECRET: &[u8] = b"12345678901234567890";
+
+    #[test]
+    fn test_rfc_6238_vectors() {
+        // The SHA-1 vectors of RFC 6238, truncated to six digits
+        for (time, code) in [
+            (59, "287082"),
+            (1111111109, "081804"),
+            (1111111111, "050471"),
+            (1234567890, "005924"),
+            (2000000000, "279037"),
+        ] {
+            assert_eq!(code_at_step(RFC_SECRET, time / STEP_SECONDS), code);
+        }
+    }
+
+    #[test]
+    fn test_verify_allows_drift_of_one_step() {
+        let now = 1111111111;
+        let previous = code_at_step(RFC_SECRET, now / STEP_SECONDS - 1);
+        let stale = code_at_step(RFC_SECRET, now / STEP_SECONDS - 2);
+
+        assert_eq!(verify(RFC_SECRET, "050471", now), Some(now / STEP_SECONDS));
+        assert_eq!(
+            verify(RFC_SECRET, &previous, now),
+            Some(now / STEP_SECONDS - 1)
+        );
+        assert_eq!(verify(RFC_SECRET, &stale, now), None);
+        assert_eq!(verify(RFC_SECRET, "05047", now), None);
+    }
+
+    #[test]
+    fn test_base32_round_trip() {
+        assert_eq!(
+            encode_base32(RFC_SECRET),
+            "GEZDGNBVGY3TQOJQGEZDGNBVGY3TQOJQ"
+        );
+        assert_eq!(
+            decode_base32("gezdgnbvgy3tqojqgezdgnbvgy3tqojq").unwrap(),
+            RFC_SECRET
+        );
+        assert_eq!(decode_base32("not base32!"), None);
+
+        let secret = generate_secret();
+        assert_eq!(decode_base32(&encode_base32(&secret)).unwrap(), secret);
+    }
+
+    #[test]
+    fn test_provisioning_uri() {
+        assert_eq!(
+            provisioning_uri("SyncFlow", "jane doe", RFC_SECRET),
+            "otpauth://totp/SyncFlow:jane%20doe?secret=GEZDGNBVGY3TQOJQGEZDGNBVGY3TQOJQ&issuer=SyncFlow&algorithm=SHA1&digits=6&period=30"
+        );
+    }
+}
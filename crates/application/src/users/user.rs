@@ -2,14 +2,22 @@ use bcrypt::verify;
 use diesel::prelude::*;
 use diesel::result::Error as DieselError;
 use diesel::PgConnection;
-use domain::models::{ApiKey, KeyType, LoginSession, NewApiKey, NewLoginSession, NewUser, User};
+use domain::models::{
+    ApiKey, KeyType, LoginSession, NewApiKey, NewLoginSession, NewUser, NewUserIdentity,
+    OrganizationRole, User, UserIdentity, UserProfileChangeset,
+};
+use domain::schema::syncflow::login_sessions;
 
 use super::oauth::github::{GithubOAuthError, GithubUser};
-use crate::project::project_crud::ProjectError;
-use crate::users::secret::{encrypt_string, key_secret_pair};
+use super::oauth::oidc::{IdTokenClaims, OidcError};
+use crate::mailer::MailerError;
+use crate::project::project_crud::{Encryptable, ProjectError};
+use crate::rate_limit::AccountLockout;
+use crate::users::mfa;
+use crate::users::secret::{decrypt_string, encrypt_string, key_secret_pair, SecretError};
 use serde::{Deserialize, Serialize};
 use shared::response_models::Response;
-use shared::user_models::{LoginRequest, SignUpRequest};
+use shared::user_models::{LoginRequest, ProfileUpdateRequest, SignUpRequest};
 use thiserror::Error;
 use uuid::Uuid;
 
@@ -18,8 +26,42 @@ pub struct LoginSessionInfo {
     pub session_id: String,
     pub user_id: i32,
     pub user_name: String,
+    pub refresh_token_id: Option<String>,
+    pub expires_at: chrono::NaiveDateTime,
 }
 
+impl LoginSessionInfo {
+    pub fn new(session: &LoginSession, user_name: String) -> Self {
+        LoginSessionInfo {
+            session_id: session.session_id.to_string(),
+            user_id: session.user_id,
+            user_name,
+            refresh_token_id: session.refresh_token_id.map(|id| id.to_string()),
+            expires_at: session.expires_at,
+        }
+    }
+}
+
+/// The result of a password or identity provider login. Users with two-factor
+/// authentication get a session only after `mfa::verify_second_factor`
+/// accepted their code.
+#[derive(Debug)]
+pub enum LoginOutcome {
+    Session(LoginSessionInfo),
+    MfaRequired { user_id: i32, user_name: String },
+}
+
+/// The device a login or refresh came from, shown in the session list.
+#[derive(Debug, Clone, Default)]
+pub struct ClientInfo {
+    pub user_agent: Option<String>,
+    pub ip_address: Option<String>,
+}
+
+pub const DEFAULT_LOGIN_SESSION_LIFETIME: usize = 30 * 24 * 60 * 60;
+
+const MAX_USER_AGENT_LENGTH: usize = 512;
+
 #[derive(Debug, Error)]
 pub enum UserError {
     #[error("User not found: {0}")]
@@ -44,6 +86,20 @@ pub enum UserError {
     HashError(String),
     #[error("OAuth error: {0}")]
     GithubOAuthError(#[from] GithubOAuthError),
+    #[error("OpenID Connect error: {0}")]
+    OidcError(#[from] OidcError),
+    #[error("Invalid account token: {0}")]
+    InvalidAccountToken(String),
+    #[error("Invalid password: {0}")]
+    InvalidPassword(String),
+    #[error("Conflict: {0}")]
+    Conflict(String),
+    #[error("Account locked after repeated login failures, retry in {0} seconds")]
+    AccountLocked(u64),
+    #[error("Invalid two-factor code: {0}")]
+    InvalidMfaCode(String),
+    #[error("Mailer error: {0}")]
+    MailerError(#[from] MailerError),
     #[error("Project error: {0}")]
     ProjectError(#[from] ProjectError),
 }
@@ -95,11 +151,68 @@ impl From<UserError> for Response {
                 status: 500,
                 message: e.to_string(),
             },
+            UserError::OidcError(e) => {
+                let status = match e {
+                    OidcError::NotConfigured(_) => 404,
+                    OidcError::InvalidRequest(_) => 400,
+                    OidcError::InvalidToken(_) | OidcError::CodeExchangeError(_) => 401,
+                    OidcError::DiscoveryError(_) => 502,
+                };
+                Response {
+                    status,
+                    message: e.to_string(),
+                }
+            }
+            UserError::InvalidAccountToken(e) => Response {
+                status: 400,
+                message: e,
+            },
+            UserError::InvalidPassword(e) => Response {
+                status: 400,
+                message: e,
+            },
+            UserError::Conflict(e) => Response {
+                status: 409,
+                message: e,
+            },
+            UserError::AccountLocked(seconds) => Response {
+                status: 429,
+                message: format!(
+                    "Account locked after repeated login failures, retry in {} seconds",
+                    seconds
+                ),
+            },
+            UserError::InvalidMfaCode(e) => Response {
+                status: 401,
+                message: e,
+            },
+            UserError::MailerError(e) => Response {
+                status: 502,
+                message: e.to_string(),
+            },
             UserError::ProjectError(e) => e.into(),
         }
     }
 }
 
+impl From<DieselError> for UserError {
+    fn from(e: DieselError) -> Self {
+        UserError::DatabaseError(e.to_string())
+    }
+}
+
+impl Encryptable for ApiKey {
+    fn encrypt(&mut self, key: &str) -> Result<(), SecretError> {
+        self.secret = encrypt_string(&self.secret, key)?;
+        Ok(())
+    }
+
+    fn decrypt(&mut self, key: &str) -> Result<(), SecretError> {
+        self.secret = decrypt_string(&self.secret, key)?;
+        Ok(())
+    }
+}
+
 fn verify_passwd(password: &str, hash: &str) -> bool {
     let password_match = verify(password, hash);
     password_match.unwrap_or(false)
@@ -111,9 +224,12 @@ fn generate_hash(password: &str) -> Result<String, bcrypt::BcryptError> {
 
 pub fn login(
     login_request: LoginRequest,
+    client: &ClientInfo,
+    session_lifetime: chrono::Duration,
+    lockout: &AccountLockout,
     conn: &mut PgConnection,
     encryption_secret: &str,
-) -> Result<LoginSessionInfo, UserError> {
+) -> Result<LoginOutcome, UserError> {
     use domain::schema::syncflow::users::dsl::*;
 
     let (uname, passwd) = (login_request.username_or_email, login_request.password);
@@ -124,6 +240,10 @@ pub fn login(
 
     match user_to_verify {
         Ok(usr) => {
+            if let Some(seconds) = usr.locked_for() {
+                return Err(UserError::AccountLocked(seconds));
+            }
+
             if usr.password.is_none() {
                 return Err(UserError::PasswordMismatch("Password Mismatch".to_string()));
             }
@@ -132,8 +252,6 @@ pub fn login(
 
             let password_match = verify_passwd(&passwd, user_db_password);
             if password_match {
-                let new_session = new_login_session(usr.id, conn);
-
                 let login_key = fetch_login_key(usr.id, conn);
 
                 match login_key {
@@ -143,18 +261,30 @@ pub fn login(
                     }
                 }
 
+                // The failure count is kept until the second factor was verified
+                // too, otherwise a known password would reset it between guesses
+                if mfa::is_enabled(usr.id, conn)? {
+                    return Ok(LoginOutcome::MfaRequired {
+                        user_id: usr.id,
+                        user_name: usr.username,
+                    });
+                }
+
+                if usr.failed_login_attempts > 0 || usr.locked_until.is_some() {
+                    reset_login_failures(usr.id, conn)?;
+                }
+
+                let new_session = new_login_session(usr.id, client, session_lifetime, conn);
+
                 match new_session {
-                    Ok(session) => {
-                        let session_info = LoginSessionInfo {
-                            session_id: session.session_id.to_string(),
-                            user_id: session.user_id,
-                            user_name: usr.username,
-                        };
-                        Ok(session_info)
-                    }
+                    Ok(session) => Ok(LoginOutcome::Session(LoginSessionInfo::new(
+                        &session,
+                        usr.username,
+                    ))),
                     Err(e) => Err(e),
                 }
             } else {
+                record_login_failure(usr.id, lockout, conn)?;
                 Err(UserError::PasswordMismatch("Password Mismatch".to_string()))
             }
         }
@@ -162,11 +292,53 @@ pub fn login(
     }
 }
 
+/// Counts a wrong password or second factor code, locking the account once the
+/// failures reach the lockout threshold.
+pub fn record_login_failure(
+    uid: i32,
+    lockout: &AccountLockout,
+    conn: &mut PgConnection,
+) -> Result<(), UserError> {
+    use domain::schema::syncflow::users::dsl::*;
+
+    let failures = diesel::update(users.find(uid))
+        .set(failed_login_attempts.eq(failed_login_attempts + 1))
+        .returning(failed_login_attempts)
+        .get_result::<i32>(conn)?;
+
+    if lockout.threshold > 0 && failures >= lockout.threshold as i32 {
+        log::warn!(
+            "Locking user {} after {} failed login attempts",
+            uid,
+            failures
+        );
+        diesel::update(users.find(uid))
+            .set((
+                failed_login_attempts.eq(0),
+                locked_until.eq(Some(chrono::Utc::now().naive_utc() + lockout.duration)),
+            ))
+            .execute(conn)?;
+    }
+    Ok(())
+}
+
+pub fn reset_login_failures(uid: i32, conn: &mut PgConnection) -> Result<(), UserError> {
+    use domain::schema::syncflow::users::dsl::*;
+
+    diesel::update(users.find(uid))
+        .set((
+            failed_login_attempts.eq(0),
+            locked_until.eq(None::<chrono::NaiveDateTime>),
+        ))
+        .execute(conn)?;
+    Ok(())
+}
+
 pub fn signup(
     signup_request: &SignUpRequest,
     conn: &mut PgConnection,
     encryption_key: &str,
-) -> Result<(), UserError> {
+) -> Result<User, UserError> {
     let user_exists = username_exists(&signup_request.username, conn);
 
     if user_exists {
@@ -197,6 +369,7 @@ pub fn signup(
         last_name: signup_request.last_name.clone(),
         organization: signup_request.organization.clone(),
         job_role: signup_request.job_role.clone(),
+        email_verified_at: None,
     };
 
     let created_user = diesel::insert_into(domain::schema::syncflow::users::table)
@@ -206,12 +379,30 @@ pub fn signup(
 
     generate_login_key(created_user.id, encryption_key, conn)?;
 
-    Ok(())
+    Ok(created_user)
 }
 
-pub fn new_login_session(uid: i32, conn: &mut PgConnection) -> Result<LoginSession, UserError> {
+/// Starts a login session lasting `lifetime`, clearing the expired sessions
+/// of the user on the way.
+pub fn new_login_session(
+    uid: i32,
+    client: &ClientInfo,
+    lifetime: chrono::Duration,
+    conn: &mut PgConnection,
+) -> Result<LoginSession, UserError> {
     use domain::schema::syncflow::login_sessions::dsl::*;
-    let new_login_session = NewLoginSession { user_id: uid };
+    let now = chrono::Utc::now().naive_utc();
+
+    diesel::delete(login_sessions.filter(user_id.eq(uid).and(expires_at.le(now)))).execute(conn)?;
+
+    let new_login_session = NewLoginSession {
+        user_id: uid,
+        user_agent: client.user_agent.as_deref().map(truncate_user_agent),
+        ip_address: client.ip_address.clone(),
+        last_used_at: Some(now),
+        expires_at: now + lifetime,
+        refresh_token_id: Some(Uuid::new_v4()),
+    };
 
     diesel::insert_into(login_sessions)
         .values(&new_login_session)
@@ -219,6 +410,10 @@ pub fn new_login_session(uid: i32, conn: &mut PgConnection) -> Result<LoginSessi
         .map_err(|err| UserError::DatabaseError(err.to_string()))
 }
 
+fn truncate_user_agent(agent: &str) -> String {
+    agent.chars().take(MAX_USER_AGENT_LENGTH).collect()
+}
+
 pub fn get_login_session_info(
     uid: i32,
     sid: &str,
@@ -227,11 +422,113 @@ pub fn get_login_session_info(
     let user_info = get_user(uid, conn)?;
     let login_session_info = get_login_session(sid, conn)?;
 
-    Ok(LoginSessionInfo {
-        session_id: login_session_info.session_id.to_string(),
-        user_id: login_session_info.user_id,
-        user_name: user_info.username,
-    })
+    Ok(LoginSessionInfo::new(
+        &login_session_info,
+        user_info.username,
+    ))
+}
+
+type RotatableSession = diesel::dsl::Filter<
+    diesel::dsl::Find<login_sessions::table, Uuid>,
+    diesel::dsl::IsNotDistinctFrom<login_sessions::refresh_token_id, Option<Uuid>>,
+>;
+
+/// The session a refresh token with the id `presented` may rotate. Tokens
+/// from before rotation carry no id and match sessions that have none.
+fn rotatable_session(suuid: Uuid, presented: Option<Uuid>) -> RotatableSession {
+    login_sessions::table
+        .find(suuid)
+        .filter(login_sessions::refresh_token_id.is_not_distinct_from(presented))
+}
+
+fn parse_refresh_token_id(token_id: Option<&str>) -> Result<Option<Uuid>, UserError> {
+    token_id
+        .map(Uuid::parse_str)
+        .transpose()
+        .map_err(|_| UserError::TokenError("Invalid token".to_string()))
+}
+
+/// Swaps the refresh token of a session for a new one. Each refresh token
+/// works once: presenting one that was already swapped means it leaked, so
+/// the whole session is revoked, along with every token issued from it.
+pub fn rotate_refresh_token(
+    sid: &str,
+    presented_token_id: Option<&str>,
+    client: &ClientInfo,
+    conn: &mut PgConnection,
+) -> Result<LoginSession, UserError> {
+    use domain::schema::syncflow::login_sessions::dsl::*;
+
+    let suuid = Uuid::parse_str(sid).map_err(|e| UserError::LoginSessionNotFound(e.to_string()))?;
+    let presented = parse_refresh_token_id(presented_token_id)?;
+    let now = chrono::Utc::now().naive_utc();
+
+    let session = login_sessions
+        .find(suuid)
+        .filter(expires_at.gt(now))
+        .first::<LoginSession>(conn)
+        .optional()?
+        .ok_or_else(|| {
+            UserError::LoginSessionNotFound("The login session has expired".to_string())
+        })?;
+
+    let rotated = diesel::update(rotatable_session(suuid, presented))
+        .set((
+            refresh_token_id.eq(Some(Uuid::new_v4())),
+            last_used_at.eq(Some(now)),
+            user_agent.eq(client
+                .user_agent
+                .as_deref()
+                .map(truncate_user_agent)
+                .or(session.user_agent)),
+            ip_address.eq(client.ip_address.clone().or(session.ip_address)),
+        ))
+        .get_result::<LoginSession>(conn)
+        .optional()?;
+
+    match rotated {
+        Some(rotated) => Ok(rotated),
+        None => {
+            log::warn!(
+                "Refresh token reuse detected for login session {} of user {}, revoking it",
+                suuid,
+                session.user_id
+            );
+            diesel::delete(login_sessions.find(suuid)).execute(conn)?;
+            Err(UserError::TokenError(
+                "The refresh token was already used, the login session has been revoked"
+                    .to_string(),
+            ))
+        }
+    }
+}
+
+/// The login sessions of a user that have not expired, most recently used first.
+pub fn list_login_sessions(
+    uid: i32,
+    conn: &mut PgConnection,
+) -> Result<Vec<LoginSession>, UserError> {
+    use domain::schema::syncflow::login_sessions::dsl::*;
+
+    Ok(login_sessions
+        .filter(user_id.eq(uid))
+        .filter(expires_at.gt(chrono::Utc::now().naive_utc()))
+        .order((last_used_at.desc().nulls_last(), created_at.desc()))
+        .load::<LoginSession>(conn)?)
+}
+
+/// Ends one login session of a user.
+pub fn revoke_login_session(uid: i32, sid: &str, conn: &mut PgConnection) -> Result<(), UserError> {
+    use domain::schema::syncflow::login_sessions::dsl::*;
+
+    let suuid = Uuid::parse_str(sid).map_err(|e| UserError::LoginSessionNotFound(e.to_string()))?;
+    let deleted = diesel::delete(login_sessions.filter(session_id.eq(suuid).and(user_id.eq(uid))))
+        .execute(conn)?;
+
+    if deleted == 0 {
+        return Err(UserError::LoginSessionNotFound(sid.to_string()));
+    }
+    Ok(())
 }
 
 pub fn delete_login_session(sid: &str, conn: &mut PgConnection) -> Result<bool, UserError> {
@@ -272,7 +569,7 @@ pub fn is_valid_login_session(sid: &str, conn: &mut PgConnection) -> bool {
                 .filter(session_id.eq(suuid))
                 .first::<LoginSession>(conn);
             match session_result {
-                Ok(_session_info) => true,
+                Ok(session_info) => !session_info.is_expired(),
                 Err(_) => false,
             }
         }
@@ -354,29 +651,175 @@ pub fn create_or_get_github_user(
 
 pub fn login_with_github(
     github_user: &GithubUser,
+    client: &ClientInfo,
+    session_lifetime: chrono::Duration,
     conn: &mut PgConnection,
     encryption_secret: &str,
-) -> Result<LoginSessionInfo, UserError> {
+) -> Result<LoginOutcome, UserError> {
     let user = create_or_get_github_user(github_user, conn)?;
 
-    let new_session = new_login_session(user.id, conn)?;
+    provider_login(user, client, session_lifetime, conn, encryption_secret)
+}
 
-    let login_key = fetch_login_key(user.id, conn);
+/// Logs in a user an identity provider vouched for. Like password logins,
+/// users with two-factor authentication still have to enter their code.
+fn provider_login(
+    user: User,
+    client: &ClientInfo,
+    session_lifetime: chrono::Duration,
+    conn: &mut PgConnection,
+    encryption_secret: &str,
+) -> Result<LoginOutcome, UserError> {
+    if fetch_login_key(user.id, conn).is_err() {
+        let _ = generate_login_key(user.id, encryption_secret, conn)?;
+    }
 
-    match login_key {
-        Ok(_) => (),
-        Err(_) => {
-            let _ = generate_login_key(user.id, encryption_secret, conn)?;
-        }
+    if mfa::is_enabled(user.id, conn)? {
+        return Ok(LoginOutcome::MfaRequired {
+            user_id: user.id,
+            user_name: user.username,
+        });
     }
 
-    Ok(LoginSessionInfo {
-        session_id: new_session.session_id.to_string(),
-        user_id: new_session.user_id,
-        user_name: user.username,
+    let new_session = new_login_session(user.id, client, session_lifetime, conn)?;
+
+    Ok(LoginOutcome::Session(LoginSessionInfo::new(
+        &new_session,
+        user.username,
+    )))
+}
+
+/// Longest username derived from an identity, leaving room for a suffix.
+const MAX_DERIVED_USERNAME_LENGTH: usize = 240;
+
+/// Username for a new account created through an identity provider, from
+/// its preferred username, the local part of its email or its subject.
+fn derived_username(claims: &IdTokenClaims) -> String {
+    let candidate = claims
+        .preferred_username
+        .as_deref()
+        .or_else(|| claims.email.as_deref().and_then(|e| e.split('@').next()))
+        .unwrap_or(&claims.sub);
+
+    let username: String = candidate
+        .chars()
+        .filter(|c| c.is_ascii_alphanumeric() || matches!(c, '.' | '_' | '-'))
+        .take(MAX_DERIVED_USERNAME_LENGTH)
+        .collect();
+
+    if username.is_empty() {
+        "user".to_string()
+    } else {
+        username
+    }
+}
+
+fn unique_username(base: &str, conn: &mut PgConnection) -> String {
+    let mut candidate = base.to_string();
+    let mut suffix = 1;
+    while username_exists(&candidate, conn) {
+        suffix += 1;
+        candidate = format!("{}-{}", base, suffix);
+    }
+    candidate
+}
+
+/// Finds the user an identity belongs to. Unknown identities are linked to
+/// the account with the same email if the provider verified it, and get a
+/// new account otherwise.
+pub fn create_or_link_oidc_user(
+    provider: &str,
+    claims: &IdTokenClaims,
+    conn: &mut PgConnection,
+) -> Result<User, UserError> {
+    use domain::schema::syncflow::user_identities::dsl as identities;
+    use domain::schema::syncflow::users::dsl::*;
+
+    conn.transaction(|conn| {
+        let identity = identities::user_identities
+            .filter(identities::provider.eq(provider))
+            .filter(identities::subject.eq(&claims.sub))
+            .first::<UserIdentity>(conn)
+            .optional()?;
+
+        if let Some(identity) = identity {
+            diesel::update(identities::user_identities.find(identity.id))
+                .set((
+                    identities::last_login_at.eq(chrono::Utc::now().naive_utc()),
+                    identities::email.eq(&claims.email),
+                ))
+                .execute(conn)?;
+            return users.find(identity.user_id).first::<User>(conn);
+        }
+
+        // Only link accounts whose email both the provider and the local
+        // account verified, or anyone could claim an unverified sign-up.
+        let existing = match claims.verified_email() {
+            Some(verified_email) => users
+                .filter(email.eq(verified_email))
+                .filter(email_verified_at.is_not_null())
+                .first::<User>(conn)
+                .optional()?,
+            None => None,
+        };
+
+        let user = match existing {
+            Some(user) => user,
+            None => {
+                let new_email = claims.email.clone().unwrap_or_default();
+                if !new_email.is_empty() && email_exists(&new_email, conn) {
+                    return Err(UserError::UserEmailAlreadyExists(
+                        "An account with this email exists and cannot be linked until both \
+                         the provider and the account have verified it"
+                            .to_string(),
+                    ));
+                }
+
+                let new_user = NewUser {
+                    username: unique_username(&derived_username(claims), conn),
+                    email: new_email,
+                    password: None,
+                    oauth_provider: Some(provider.to_string()),
+                    oauth_provider_user_id: Some(claims.sub.clone()),
+                    first_name: claims.given_name.clone(),
+                    last_name: claims.family_name.clone(),
+                    email_verified_at: claims
+                        .verified_email()
+                        .map(|_| chrono::Utc::now().naive_utc()),
+                    ..Default::default()
+                };
+                diesel::insert_into(users)
+                    .values(&new_user)
+                    .get_result::<User>(conn)?
+            }
+        };
+
+        diesel::insert_into(identities::user_identities)
+            .values(&NewUserIdentity {
+                provider: provider.to_string(),
+                subject: claims.sub.clone(),
+                email: claims.email.clone(),
+                user_id: user.id,
+            })
+            .execute(conn)?;
+
+        Ok(user)
     })
 }
 
+pub fn login_with_oidc(
+    provider: &str,
+    claims: &IdTokenClaims,
+    client: &ClientInfo,
+    session_lifetime: chrono::Duration,
+    conn: &mut PgConnection,
+    encryption_secret: &str,
+) -> Result<LoginOutcome, UserError> {
+    let user = create_or_link_oidc_user(provider, claims, conn)?;
+
+    provider_login(user, client, session_lifetime, conn, encryption_secret)
+}
+
 pub fn generate_login_key(
     uid: i32,
     encryption_key: &str,
@@ -464,6 +907,30 @@ pub fn get_user(uid: i32, conn: &mut PgConnection) -> Result<User, UserError> {
         .map_err(|e| UserError::DatabaseError(e.to_string()))
 }
 
+pub fn get_user_by_username(uname: &str, conn: &mut PgConnection) -> Result<User, UserError> {
+    use domain::schema::syncflow::users::dsl::*;
+
+    users
+        .filter(username.eq(uname))
+        .first::<User>(conn)
+        .map_err(|e| match e {
+            diesel::result::Error::NotFound => UserError::UserNotFound(uname.to_string()),
+            _ => UserError::DatabaseError(e.to_string()),
+        })
+}
+
+pub fn get_user_by_email(em: &str, conn: &mut PgConnection) -> Result<User, UserError> {
+    use domain::schema::syncflow::users::dsl::*;
+
+    users
+        .filter(email.eq(em))
+        .first::<User>(conn)
+        .map_err(|e| match e {
+            diesel::result::Error::NotFound => UserError::UserNotFound(em.to_string()),
+            _ => UserError::DatabaseError(e.to_string()),
+        })
+}
+
 pub fn get_login_session(sid: &str, conn: &mut PgConnection) -> Result<LoginSession, UserError> {
     use domain::schema::syncflow::login_sessions::dsl::*;
     let session_uuid = Uuid::parse_str(sid);
@@ -496,3 +963,212 @@ pub fn delete_api_key(
         .get_result::<ApiKey>(conn)
         .map_err(|e| UserError::DatabaseError(e.to_string()))
 }
+
+pub const MIN_PASSWORD_LENGTH: usize = 8;
+
+pub fn validate_password(password: &str) -> Result<(), UserError> {
+    if password.chars().count() < MIN_PASSWORD_LENGTH {
+        return Err(UserError::InvalidPassword(format!(
+            "The password must be at least {} characters long",
+            MIN_PASSWORD_LENGTH
+        )));
+    }
+    Ok(())
+}
+
+/// Ends the login sessions of a user, except `keep` if given.
+pub fn delete_login_sessions(
+    uid: i32,
+    keep: Option<&str>,
+    conn: &mut PgConnection,
+) -> Result<usize, UserError> {
+    use domain::schema::syncflow::login_sessions::dsl::*;
+
+    let keep = keep.and_then(|sid| Uuid::parse_str(sid).ok());
+    let deleted = match keep {
+        Some(keep) => {
+            diesel::delete(login_sessions.filter(user_id.eq(uid).and(session_id.ne(keep))))
+                .execute(conn)?
+        }
+        None => diesel::delete(login_sessions.filter(user_id.eq(uid))).execute(conn)?,
+    };
+
+    Ok(deleted)
+}
+
+/// Replaces the password and ends every other login session. Accounts created
+/// through an identity provider may set a first password without a current one.
+pub fn change_password(
+    uid: i32,
+    current_password: Option<&str>,
+    new_password: &str,
+    current_session: Option<&str>,
+    conn: &mut PgConnection,
+) -> Result<(), UserError> {
+    let user = get_user(uid, conn)?;
+    if let Some(hash) = &user.password {
+        let matches = current_password.is_some_and(|current| verify_passwd(current, hash));
+        if !matches {
+            return Err(UserError::PasswordMismatch("Password Mismatch".to_string()));
+        }
+    }
+
+    set_password(uid, new_password, current_session, conn)
+}
+
+/// Sets a new password and ends the login sessions of the user, except
+/// `keep_session`.
+pub fn set_password(
+    uid: i32,
+    new_password: &str,
+    keep_session: Option<&str>,
+    conn: &mut PgConnection,
+) -> Result<(), UserError> {
+    use domain::schema::syncflow::users::dsl::*;
+
+    validate_password(new_password)?;
+    let hashed_password =
+        generate_hash(new_password).map_err(|e| UserError::HashError(e.to_string()))?;
+
+    conn.transaction(|conn| {
+        diesel::update(users.find(uid))
+            .set((
+                password.eq(Some(hashed_password)),
+                updatedAt.eq(chrono::Utc::now().naive_utc()),
+            ))
+            .execute(conn)?;
+        reset_login_failures(uid, conn)?;
+        delete_login_sessions(uid, keep_session, conn)?;
+        Ok::<_, UserError>(())
+    })
+}
+
+/// Marks the email of the user verified, provided it is still `verified_email`.
+pub fn mark_email_verified(
+    uid: i32,
+    verified_email: &str,
+    conn: &mut PgConnection,
+) -> Result<User, UserError> {
+    use domain::schema::syncflow::users::dsl::*;
+
+    diesel::update(users.find(uid).filter(email.eq(verified_email)))
+        .set(email_verified_at.eq(chrono::Utc::now().naive_utc()))
+        .get_result::<User>(conn)
+        .optional()?
+        .ok_or_else(|| {
+            UserError::InvalidAccountToken(
+                "The email address changed after the link was sent".to_string(),
+            )
+        })
+}
+
+pub fn update_profile(
+    uid: i32,
+    request: &ProfileUpdateRequest,
+    conn: &mut PgConnection,
+) -> Result<User, UserError> {
+    use domain::schema::syncflow::users::dsl::*;
+
+    diesel::update(users.find(uid))
+        .set((
+            UserProfileChangeset::from(request),
+            updatedAt.eq(chrono::Utc::now().naive_utc()),
+        ))
+        .get_result::<User>(conn)
+        .map_err(|e| match e {
+            DieselError::NotFound => UserError::UserNotFound(uid.to_string()),
+            _ => UserError::DatabaseError(e.to_string()),
+        })
+}
+
+/// Deletes the account along with everything that cascades from it. Refused
+/// while the user owns projects or is the last owner of an organization,
+/// since those would silently disappear for their other members.
+pub fn delete_account(
+    uid: i32,
+    confirm_password: Option<&str>,
+    conn: &mut PgConnection,
+) -> Result<(), UserError> {
+    use domain::schema::syncflow::organization_members::dsl as members;
+    use domain::schema::syncflow::{api_keys, projects, users};
+
+    let user = get_user(uid, conn)?;
+    if let Some(hash) = &user.password {
+        if !confirm_password.is_some_and(|confirm| verify_passwd(confirm, hash)) {
+            return Err(UserError::PasswordMismatch("Password Mismatch".to_string()));
+        }
+    }
+
+    let owned_projects = projects::table
+        .filter(projects::user_id.eq(uid))
+        .count()
+        .get_result::<i64>(conn)?;
+    if owned_projects > 0 {
+        return Err(UserError::Conflict(format!(
+            "Delete your {} project(s) before deleting the account",
+            owned_projects
+        )));
+    }
+
+    let owned_organizations = members::organization_members
+        .filter(members::user_id.eq(uid))
+        .filter(members::role.eq(OrganizationRole::Owner))
+        .select(members::organization_id)
+        .load::<Uuid>(conn)?;
+    for organization in owned_organizations {
+        let owners = members::organization_members
+            .filter(members::organization_id.eq(organization))
+            .filter(members::role.eq(OrganizationRole::Owner))
+            .count()
+            .get_result::<i64>(conn)?;
+        if owners <= 1 {
+            return Err(UserError::Conflict(
+                "Hand the ownership of your organizations over before deleting the account"
+                    .to_string(),
+            ));
+        }
+    }
+
+    conn.transaction(|conn| {
+        // The only reference to users without ON DELETE CASCADE
+        diesel::delete(api_keys::table.filter(api_keys::user_id.eq(uid))).execute(conn)?;
+        diesel::delete(users::table.find(uid)).execute(conn)?;
+        Ok::<_, UserError>(())
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use diesel::pg::Pg;
+
+    #[test]
+    fn test_rotatable_session() {
+        let sid = Uuid::new_v4();
+        let jti = Uuid::new_v4();
+
+        // NULL ids must match each other, which `=` would never do
+        let legacy = diesel::debug_query::<Pg, _>(&rotatable_session(sid, None)).to_string();
+        assert!(legacy.contains("\"refresh_token_id\" IS NOT DISTINCT FROM $2"));
+        assert!(legacy.ends_with(&format!("-- binds: [{:?}, None]", sid)));
+
+        let rotated = diesel::debug_query::<Pg, _>(&rotatable_session(sid, Some(jti))).to_string();
+        assert!(rotated.contains("\"refresh_token_id\" IS NOT DISTINCT FROM $2"));
+        assert!(rotated.ends_with(&format!("-- binds: [{:?}, Some({:?})]", sid, jti)));
+    }
+
+    #[test]
+    fn test_parse_refresh_token_id() {
+        let jti = Uuid::new_v4();
+
+        assert_eq!(parse_refresh_token_id(None).unwrap(), None);
+        assert_eq!(
+            parse_refresh_token_id(Some(&jti.to_string())).unwrap(),
+            Some(jti)
+        );
+        assert!(matches!(
+            parse_refresh_token_id(Some("not-a-uuid")),
+            Err(UserError::TokenError(_))
+        ));
+    }
+}
@@ -3,8 +3,11 @@ use reqwest::Client;
 use shared::livekit_models::TokenResponse;
 use shared::signed_token::SignedTokenError;
 use shared::{
-    claims::ProjectToken,
-    device_models::{DeviceRegisterRequest, DeviceResponse},
+    claims::{ProjectToken, TokenKind},
+    device_models::{
+        BulkDeviceRegisterResponse, DeviceGroupRequest, DeviceGroupResponse,
+        DeviceGroupUpdateRequest, DeviceRegisterRequest, DeviceResponse, DeviceUpdateRequest,
+    },
     livekit_models::TokenRequest,
     project_models::{NewSessionRequest, ProjectSessionResponse, ProjectSummary},
     signed_token::{generate_and_sign_jwt, verify_and_decode_jwt},
@@ -71,6 +74,7 @@ impl ProjectClient {
             .unwrap()
             .as_secs() as usize;
         let claims = ProjectToken {
+            typ: TokenKind::Project,
             iat: now,
             exp: now + 3600, // 1 hour expiration
             iss: self.api_key.clone(),
@@ -195,6 +199,36 @@ impl ProjectClient {
             .await
     }
 
+    pub async fn get_devices_in_group(
+        &self,
+        group: &str,
+    ) -> Result<Vec<DeviceResponse>, ProjectClientError> {
+        let path = format!("projects/{}/devices", self.project_id);
+
+        self.authenticated_get_with_query(&path, &[("group", group)])
+            .await
+    }
+
+    pub async fn register_devices(
+        &self,
+        device_register_requests: &[DeviceRegisterRequest],
+    ) -> Result<BulkDeviceRegisterResponse, ProjectClientError> {
+        let path = format!("projects/{}/devices/register-bulk", self.project_id);
+
+        self.authenticated_post(&path, &device_register_requests)
+            .await
+    }
+
+    pub async fn update_device(
+        &self,
+        device_id: &str,
+        device_update_request: &DeviceUpdateRequest,
+    ) -> Result<DeviceResponse, ProjectClientError> {
+        let path = format!("projects/{}/devices/{}", self.project_id, device_id);
+
+        self.authenticated_patch(&path, device_update_request).await
+    }
+
     pub async fn delete_device(
         &self,
         device_id: &str,
@@ -204,15 +238,64 @@ impl ProjectClient {
         self.authenticated_delete(&path).await
     }
 
+    pub async fn get_device_groups(&self) -> Result<Vec<DeviceGroupResponse>, ProjectClientError> {
+        let path = format!("projects/{}/device-groups", self.project_id);
+
+        self.authenticated_get(&path).await
+    }
+
+    pub async fn create_device_group(
+        &self,
+        device_group_request: &DeviceGroupRequest,
+    ) -> Result<DeviceGroupResponse, ProjectClientError> {
+        let path = format!("projects/{}/device-groups", self.project_id);
+
+        self.authenticated_post(&path, device_group_request).await
+    }
+
+    pub async fn update_device_group(
+        &self,
+        group_id: &str,
+        device_group_update_request: &DeviceGroupUpdateRequest,
+    ) -> Result<DeviceGroupResponse, ProjectClientError> {
+        let path = format!("projects/{}/device-groups/{}", self.project_id, group_id);
+
+        self.authenticated_patch(&path, device_group_update_request)
+            .await
+    }
+
+    pub async fn delete_device_group(
+        &self,
+        group_id: &str,
+    ) -> Result<DeviceGroupResponse, ProjectClientError> {
+        let path = format!("projects/{}/device-groups/{}", self.project_id, group_id);
+
+        self.authenticated_delete(&path).await
+    }
+
     pub async fn authenticated_get<T: serde::de::DeserializeOwned>(
         &self,
         path: &str,
+    ) -> Result<T, ProjectClientError> {
+        self.authenticated_get_with_query(path, &[] as &[(&str, &str)])
+            .await
+    }
+
+    /// Like `authenticated_get`, with `query` encoded into the query string.
+    pub async fn authenticated_get_with_query<
+        T: serde::de::DeserializeOwned,
+        Q: serde::Serialize + ?Sized,
+    >(
+        &self,
+        path: &str,
+        query: &Q,
     ) -> Result<T, ProjectClientError> {
         let token = self.get_api_token().await?;
         let url = format!("{}/{}", self.base_url, path);
         let response = self
             .client
             .get(&url)
+            .query(query)
             .header("Authorization", format!("Bearer {}", token))
             .header("User-Agent", "SyncFlow Project Client/ V0.1.0")
             .send()
@@ -245,6 +328,28 @@ impl ProjectClient {
         Ok(response_json)
     }
 
+    pub async fn authenticated_patch<T: serde::de::DeserializeOwned, E: serde::Serialize>(
+        &self,
+        path: &str,
+        body: &E,
+    ) -> Result<T, ProjectClientError> {
+        let token = self.get_api_token().await?;
+        let url = format!("{}/{}", self.base_url, path);
+        let response = self
+            .client
+            .patch(&url)
+            .header("Authorization", format!("Bearer {}", token))
+            .header("Content-Type", "application/json")
+            .header("User-Agent", "SyncFlow Project Client/ V0.1.0")
+            .json(body)
+            .send()
+            .await?;
+
+        let response_json = response.json::<T>().await?;
+
+        Ok(response_json)
+    }
+
     pub async fn authenticated_delete<T: serde::de::DeserializeOwned>(
         &self,
         path: &str,
@@ -1,17 +1,31 @@
 use crate::schema::syncflow::{
-    api_keys, login_sessions, participant_tracks, project_api_keys, project_devices,
-    project_sessions, projects, session_egresses, session_participants, users,
+    account_tokens, api_keys, audit_events, device_groups, jwt_signing_keys, login_sessions,
+    mfa_recovery_codes, notification_outbox, organization_invitations,
+    organization_livekit_credentials, organization_members, organization_storage_credentials,
+    organizations, participant_consents, participant_tracks, project_api_keys, project_devices,
+    project_invitations, project_members, project_sessions, projects, recording_retention_policies,
+    scheduled_deletions, session_egresses, session_participants, user_identities, user_totp, users,
 };
 use diesel::prelude::*;
 use diesel_derive_enum::DbEnum;
 use serde::{Deserialize, Serialize};
 use shared::{
-    device_models::DeviceResponse,
+    audit_models::AuditEventResponse,
+    deletion_models::ScheduledDeletionResponse,
+    device_models::{DeviceGroupResponse, DeviceResponse},
+    member_models::ProjectInvitationResponse,
+    organization_models::{
+        LivekitCredentialResponse, OrganizationInvitationResponse, StorageCredentialResponse,
+    },
     project_models::{
         EgressResponse, ParticipantTrackResponse, ProjectSessionResponse,
         SessionParticipantResponse,
     },
-    user_models::{ApiKeyResponse, ApiKeyResponseWithoutSecret, ProjectInfo, UserProfile},
+    recording_models::{ParticipantConsentResponse, RetentionPolicyResponse},
+    user_models::{
+        ApiKeyResponse, ApiKeyResponseWithoutSecret, LoginSessionResponse, ProfileUpdateRequest,
+        ProjectInfo, UserProfile,
+    },
 };
 use utoipa::ToSchema;
 use uuid::Uuid;
@@ -37,6 +51,17 @@ pub struct User {
     pub last_name: Option<String>,
     pub organization: Option<String>,
     pub job_role: Option<String>,
+    pub email_verified_at: Option<chrono::NaiveDateTime>,
+    pub failed_login_attempts: i32,
+    pub locked_until: Option<chrono::NaiveDateTime>,
+}
+
+impl User {
+    /// Seconds until a locked account can log in again.
+    pub fn locked_for(&self) -> Option<u64> {
+        let remaining = self.locked_until? - chrono::Utc::now().naive_utc();
+        (remaining > chrono::Duration::zero()).then(|| remaining.num_seconds().max(1) as u64)
+    }
 }
 
 impl From<User> for UserProfile {
@@ -49,6 +74,7 @@ impl From<User> for UserProfile {
             organization: value.organization,
             job_role: value.job_role,
             middle_name: value.middle_name,
+            email_verified: value.email_verified_at.is_some(),
         }
     }
 }
@@ -66,6 +92,224 @@ pub struct NewUser {
     pub last_name: Option<String>,
     pub organization: Option<String>,
     pub job_role: Option<String>,
+    pub email_verified_at: Option<chrono::NaiveDateTime>,
+}
+
+/// Profile fields to change, `None` keeps the current value.
+#[derive(Debug, Clone, Default, AsChangeset)]
+#[diesel(table_name = users)]
+pub struct UserProfileChangeset {
+    pub first_name: Option<String>,
+    pub middle_name: Option<String>,
+    pub last_name: Option<String>,
+    pub organization: Option<String>,
+    pub job_role: Option<String>,
+}
+
+impl From<&ProfileUpdateRequest> for UserProfileChangeset {
+    fn from(value: &ProfileUpdateRequest) -> Self {
+        UserProfileChangeset {
+            first_name: value.first_name.clone(),
+            middle_name: value.middle_name.clone(),
+            last_name: value.last_name.clone(),
+            organization: value.organization.clone(),
+            job_role: value.job_role.clone(),
+        }
+    }
+}
+
+#[derive(Debug, Serialize, Deserialize, DbEnum, Clone, Copy, Eq, PartialEq)]
+#[ExistingTypePath = "crate::schema::syncflow::sql_types::AccountTokenPurpose"]
+#[DbValueStyle = "snake_case"]
+pub enum AccountTokenPurpose {
+    PasswordReset,
+    EmailVerification,
+}
+
+impl AccountTokenPurpose {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            AccountTokenPurpose::PasswordReset => "password_reset",
+            AccountTokenPurpose::EmailVerification => "email_verification",
+        }
+    }
+}
+
+/// A single use token mailed to a user, e.g. to reset their password.
+#[derive(Debug, Clone, Queryable)]
+#[diesel(table_name = account_tokens)]
+pub struct AccountToken {
+    pub id: Uuid,
+    pub purpose: AccountTokenPurpose,
+    pub secret_hash: String,
+    pub email: String,
+    pub created_at: chrono::NaiveDateTime,
+    pub expires_at: chrono::NaiveDateTime,
+    pub used_at: Option<chrono::NaiveDateTime>,
+    pub user_id: i32,
+}
+
+#[derive(Debug, Clone, Insertable)]
+#[diesel(table_name = account_tokens)]
+pub struct NewAccountToken {
+    pub purpose: AccountTokenPurpose,
+    pub secret_hash: String,
+    pub email: String,
+    pub expires_at: chrono::NaiveDateTime,
+    pub user_id: i32,
+}
+
+#[derive(Debug, Serialize, Deserialize, DbEnum, Clone, Copy, Eq, PartialEq)]
+#[ExistingTypePath = "crate::schema::syncflow::sql_types::AuditResult"]
+#[DbValueStyle = "snake_case"]
+pub enum AuditResult {
+    Success,
+    Failure,
+}
+
+impl AuditResult {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            AuditResult::Success => "success",
+            AuditResult::Failure => "failure",
+        }
+    }
+}
+
+/// An entry of the append-only audit log.
+#[derive(Debug, Clone, Queryable)]
+#[diesel(table_name = audit_events)]
+pub struct AuditEvent {
+    pub id: i64,
+    pub occurred_at: chrono::NaiveDateTime,
+    pub actor_user_id: Option<i32>,
+    pub actor_api_key_id: Option<i32>,
+    pub ip_address: Option<String>,
+    pub action: String,
+    pub target_type: String,
+    pub target_id: Option<String>,
+    pub project_id: Option<Uuid>,
+    pub result: AuditResult,
+    pub error_message: Option<String>,
+}
+
+#[derive(Debug, Clone, Insertable)]
+#[diesel(table_name = audit_events)]
+pub struct NewAuditEvent {
+    pub actor_user_id: Option<i32>,
+    pub actor_api_key_id: Option<i32>,
+    pub ip_address: Option<String>,
+    pub action: String,
+    pub target_type: String,
+    pub target_id: Option<String>,
+    pub project_id: Option<Uuid>,
+    pub result: AuditResult,
+    pub error_message: Option<String>,
+}
+
+impl From<AuditEvent> for AuditEventResponse {
+    fn from(value: AuditEvent) -> Self {
+        AuditEventResponse {
+            id: value.id,
+            occurred_at: value.occurred_at.and_utc().timestamp() as usize,
+            actor_user_id: value.actor_user_id,
+            actor_api_key_id: value.actor_api_key_id,
+            ip_address: value.ip_address,
+            action: value.action,
+            target_type: value.target_type,
+            target_id: value.target_id,
+            project_id: value.project_id.map(|id| id.to_string()),
+            result: value.result.as_str().to_string(),
+            error_message: value.error_message,
+        }
+    }
+}
+
+#[derive(Debug, Serialize, Deserialize, DbEnum, Clone, Copy, Eq, PartialEq)]
+#[ExistingTypePath = "crate::schema::syncflow::sql_types::JwtSigningAlgorithm"]
+#[DbValueStyle = "snake_case"]
+pub enum JwtSigningAlgorithm {
+    Rs256,
+    EdDsa,
+}
+
+/// A server-managed key pair signing login tokens. The private key is stored
+/// encrypted, the public key as a JWK.
+#[derive(Debug, Clone, Queryable)]
+#[diesel(table_name = jwt_signing_keys)]
+pub struct JwtSigningKey {
+    pub kid: String,
+    pub algorithm: JwtSigningAlgorithm,
+    pub private_key: String,
+    pub public_jwk: String,
+    pub created_at: chrono::NaiveDateTime,
+    pub retired_at: Option<chrono::NaiveDateTime>,
+}
+
+#[derive(Debug, Clone, Insertable)]
+#[diesel(table_name = jwt_signing_keys)]
+pub struct NewJwtSigningKey {
+    pub kid: String,
+    pub algorithm: JwtSigningAlgorithm,
+    pub private_key: String,
+    pub public_jwk: String,
+}
+
+/// The TOTP second factor of a user. The secret is stored encrypted.
+#[derive(Debug, Clone, Queryable)]
+#[diesel(table_name = user_totp)]
+pub struct UserTotp {
+    pub user_id: i32,
+    pub secret: String,
+    pub confirmed_at: Option<chrono::NaiveDateTime>,
+    pub last_used_step: Option<i64>,
+    pub created_at: chrono::NaiveDateTime,
+}
+
+#[derive(Debug, Clone, Insertable)]
+#[diesel(table_name = user_totp)]
+pub struct NewUserTotp {
+    pub user_id: i32,
+    pub secret: String,
+}
+
+#[derive(Debug, Clone, Queryable)]
+#[diesel(table_name = mfa_recovery_codes)]
+pub struct MfaRecoveryCode {
+    pub id: i32,
+    pub code_hash: String,
+    pub used_at: Option<chrono::NaiveDateTime>,
+    pub created_at: chrono::NaiveDateTime,
+    pub user_id: i32,
+}
+
+#[derive(Debug, Clone, Insertable)]
+#[diesel(table_name = mfa_recovery_codes)]
+pub struct NewMfaRecoveryCode {
+    pub code_hash: String,
+    pub user_id: i32,
+}
+
+/// An account of an OpenID Connect provider linked to a user.
+#[derive(Debug, Serialize, Deserialize, Clone, Queryable)]
+#[diesel(table_name = user_identities)]
+pub struct UserIdentity {
+    pub id: Uuid,
+    pub provider: String,
+    pub subject: String,
+    pub email: Option<String>,
+    pub created_at: chrono::NaiveDateTime,
+    pub last_login_at: chrono::NaiveDateTime,
+    pub user_id: i32,
+}
+
+#[derive(Debug, Clone, Insertable)]
+#[diesel(table_name = user_identities)]
+pub struct NewUserIdentity {
+    pub provider: String,
+    pub subject: String,
+    pub email: Option<String>,
+    pub user_id: i32,
 }
 
 #[derive(Debug, Serialize, Deserialize, ToSchema, Clone, Insertable, Queryable, AsChangeset)]
@@ -74,12 +318,45 @@ pub struct LoginSession {
     pub session_id: Uuid,
     pub user_id: i32,
     pub created_at: Option<chrono::NaiveDateTime>,
+    pub user_agent: Option<String>,
+    pub ip_address: Option<String>,
+    pub last_used_at: Option<chrono::NaiveDateTime>,
+    pub expires_at: chrono::NaiveDateTime,
+    pub refresh_token_id: Option<Uuid>,
+}
+
+impl LoginSession {
+    pub fn is_expired(&self) -> bool {
+        self.expires_at <= chrono::Utc::now().naive_utc()
+    }
+}
+
+impl From<LoginSession> for LoginSessionResponse {
+    fn from(value: LoginSession) -> Self {
+        LoginSessionResponse {
+            session_id: value.session_id.to_string(),
+            user_agent: value.user_agent,
+            ip_address: value.ip_address,
+            created_at: value
+                .created_at
+                .map(|c| c.and_utc().timestamp() as usize)
+                .unwrap_or_default(),
+            last_used_at: value.last_used_at.map(|c| c.and_utc().timestamp() as usize),
+            expires_at: value.expires_at.and_utc().timestamp() as usize,
+            current: false,
+        }
+    }
 }
 
 #[derive(Debug, Serialize, Deserialize, ToSchema, Insertable)]
 #[diesel(table_name = login_sessions)]
 pub struct NewLoginSession {
     pub user_id: i32,
+    pub user_agent: Option<String>,
+    pub ip_address: Option<String>,
+    pub last_used_at: Option<chrono::NaiveDateTime>,
+    pub expires_at: chrono::NaiveDateTime,
+    pub refresh_token_id: Option<Uuid>,
 }
 
 #[derive(Debug, Serialize, Deserialize, ToSchema, Clone, DbEnum, Eq, PartialEq)]
@@ -114,6 +391,9 @@ impl From<ApiKey> for ApiKeyResponse {
                 .map(|c| c.and_utc().timestamp() as usize)
                 .unwrap_or_default(),
             project_id: None,
+            scopes: None,
+            expires_at: None,
+            allowed_ips: None,
         }
     }
 }
@@ -129,6 +409,10 @@ impl From<ApiKey> for ApiKeyResponseWithoutSecret {
                 .map(|c| c.and_utc().timestamp() as usize)
                 .unwrap_or_default(),
             project_id: None,
+            scopes: None,
+            expires_at: None,
+            allowed_ips: None,
+            last_used_at: None,
         }
     }
 }
@@ -169,6 +453,9 @@ pub struct Project {
     pub region: Option<String>,
     pub created_at: Option<chrono::NaiveDateTime>,
     pub updated_at: Option<chrono::NaiveDateTime>,
+    pub organization_id: Option<Uuid>,
+    pub livekit_credential_id: Option<Uuid>,
+    pub storage_credential_id: Option<Uuid>,
 }
 
 impl Project {
@@ -193,12 +480,16 @@ impl From<Project> for ProjectInfo {
                 .updated_at
                 .map(|c| c.and_utc().timestamp() as usize)
                 .unwrap_or_default(),
+            organization_id: value.organization_id.map(|id| id.to_string()),
+            livekit_credential_id: value.livekit_credential_id.map(|id| id.to_string()),
+            storage_credential_id: value.storage_credential_id.map(|id| id.to_string()),
         }
     }
 }
 
 #[derive(Debug, Serialize, Deserialize, ToSchema, Clone, Insertable, Queryable, AsChangeset)]
 #[diesel(table_name = projects)]
+#[diesel(treat_none_as_null = true)]
 pub struct NewProject {
     pub user_id: i32,
     pub name: String,
@@ -212,6 +503,9 @@ pub struct NewProject {
     pub access_key: String,
     pub secret_key: String,
     pub region: Option<String>,
+    pub organization_id: Option<Uuid>,
+    pub livekit_credential_id: Option<Uuid>,
+    pub storage_credential_id: Option<Uuid>,
 }
 
 impl From<Project> for NewProject {
@@ -229,10 +523,352 @@ impl From<Project> for NewProject {
             access_key: value.access_key,
             secret_key: value.secret_key,
             region: value.region,
+            organization_id: value.organization_id,
+            livekit_credential_id: value.livekit_credential_id,
+            storage_credential_id: value.storage_credential_id,
+        }
+    }
+}
+
+#[derive(Debug, Serialize, Deserialize, DbEnum, Clone, Copy, Eq, PartialEq)]
+#[ExistingTypePath = "crate::schema::syncflow::sql_types::ProjectRole"]
+#[DbValueStyle = "snake_case"]
+pub enum ProjectRole {
+    Owner,
+    Admin,
+    Operator,
+    Viewer,
+}
+
+impl ProjectRole {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            ProjectRole::Owner => "owner",
+            ProjectRole::Admin => "admin",
+            ProjectRole::Operator => "operator",
+            ProjectRole::Viewer => "viewer",
+        }
+    }
+
+    fn rank(&self) -> u8 {
+        match self {
+            ProjectRole::Owner => 3,
+            ProjectRole::Admin => 2,
+            ProjectRole::Operator => 1,
+            ProjectRole::Viewer => 0,
+        }
+    }
+
+    /// Whether this role grants everything `required` grants.
+    pub fn includes(&self, required: ProjectRole) -> bool {
+        self.rank() >= required.rank()
+    }
+}
+
+impl std::str::FromStr for ProjectRole {
+    type Err = String;
+
+    fn from_str(role: &str) -> Result<Self, Self::Err> {
+        match role {
+            "owner" => Ok(ProjectRole::Owner),
+            "admin" => Ok(ProjectRole::Admin),
+            "operator" => Ok(ProjectRole::Operator),
+            "viewer" => Ok(ProjectRole::Viewer),
+            _ => Err(format!("Unknown project role: {}", role)),
         }
     }
 }
 
+#[derive(Debug, Serialize, Deserialize, Clone, Queryable)]
+#[diesel(table_name = project_members)]
+pub struct ProjectMember {
+    pub id: Uuid,
+    pub role: ProjectRole,
+    pub created_at: chrono::NaiveDateTime,
+    pub project_id: Uuid,
+    pub user_id: i32,
+}
+
+#[derive(Debug, Clone, Insertable)]
+#[diesel(table_name = project_members)]
+pub struct NewProjectMember {
+    pub role: ProjectRole,
+    pub project_id: Uuid,
+    pub user_id: i32,
+}
+
+#[derive(Debug, Serialize, Deserialize, Clone, Queryable)]
+#[diesel(table_name = project_invitations)]
+pub struct ProjectInvitation {
+    pub id: Uuid,
+    pub email: String,
+    pub role: ProjectRole,
+    pub created_at: chrono::NaiveDateTime,
+    pub expires_at: chrono::NaiveDateTime,
+    pub project_id: Uuid,
+    pub invited_by: i32,
+}
+
+impl From<ProjectInvitation> for ProjectInvitationResponse {
+    fn from(value: ProjectInvitation) -> Self {
+        ProjectInvitationResponse {
+            id: value.id.to_string(),
+            email: value.email,
+            role: value.role.as_str().to_string(),
+            created_at: value.created_at.and_utc().timestamp() as usize,
+            expires_at: value.expires_at.and_utc().timestamp() as usize,
+            project_id: value.project_id.to_string(),
+            invited_by: value.invited_by,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Insertable)]
+#[diesel(table_name = project_invitations)]
+pub struct NewProjectInvitation {
+    pub email: String,
+    pub role: ProjectRole,
+    pub expires_at: chrono::NaiveDateTime,
+    pub project_id: Uuid,
+    pub invited_by: i32,
+}
+
+#[derive(Debug, Serialize, Deserialize, DbEnum, Clone, Copy, Eq, PartialEq)]
+#[ExistingTypePath = "crate::schema::syncflow::sql_types::OrganizationRole"]
+#[DbValueStyle = "snake_case"]
+pub enum OrganizationRole {
+    Owner,
+    Admin,
+    Member,
+}
+
+impl OrganizationRole {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            OrganizationRole::Owner => "owner",
+            OrganizationRole::Admin => "admin",
+            OrganizationRole::Member => "member",
+        }
+    }
+
+    fn rank(&self) -> u8 {
+        match self {
+            OrganizationRole::Owner => 2,
+            OrganizationRole::Admin => 1,
+            OrganizationRole::Member => 0,
+        }
+    }
+
+    /// Whether this role grants everything `required` grants.
+    pub fn includes(&self, required: OrganizationRole) -> bool {
+        self.rank() >= required.rank()
+    }
+
+    /// Role the organization member gets in projects owned by the organization.
+    pub fn project_role(&self) -> ProjectRole {
+        match self {
+            OrganizationRole::Owner => ProjectRole::Owner,
+            OrganizationRole::Admin => ProjectRole::Admin,
+            OrganizationRole::Member => ProjectRole::Viewer,
+        }
+    }
+}
+
+impl std::str::FromStr for OrganizationRole {
+    type Err = String;
+
+    fn from_str(role: &str) -> Result<Self, Self::Err> {
+        match role {
+            "owner" => Ok(OrganizationRole::Owner),
+            "admin" => Ok(OrganizationRole::Admin),
+            "member" => Ok(OrganizationRole::Member),
+            _ => Err(format!("Unknown organization role: {}", role)),
+        }
+    }
+}
+
+#[derive(Debug, Serialize, Deserialize, Clone, Queryable)]
+#[diesel(table_name = organizations)]
+pub struct Organization {
+    pub id: Uuid,
+    pub name: String,
+    pub description: Option<String>,
+    pub created_at: chrono::NaiveDateTime,
+    pub updated_at: chrono::NaiveDateTime,
+}
+
+#[derive(Debug, Clone, Insertable)]
+#[diesel(table_name = organizations)]
+pub struct NewOrganization {
+    pub name: String,
+    pub description: Option<String>,
+}
+
+#[derive(Debug, Clone, AsChangeset)]
+#[diesel(table_name = organizations)]
+pub struct OrganizationChangeset {
+    pub name: Option<String>,
+    pub description: Option<String>,
+    pub updated_at: chrono::NaiveDateTime,
+}
+
+#[derive(Debug, Serialize, Deserialize, Clone, Queryable)]
+#[diesel(table_name = organization_members)]
+pub struct OrganizationMember {
+    pub id: Uuid,
+    pub role: OrganizationRole,
+    pub created_at: chrono::NaiveDateTime,
+    pub organization_id: Uuid,
+    pub user_id: i32,
+}
+
+#[derive(Debug, Clone, Insertable)]
+#[diesel(table_name = organization_members)]
+pub struct NewOrganizationMember {
+    pub role: OrganizationRole,
+    pub organization_id: Uuid,
+    pub user_id: i32,
+}
+
+#[derive(Debug, Serialize, Deserialize, Clone, Queryable)]
+#[diesel(table_name = organization_invitations)]
+pub struct OrganizationInvitation {
+    pub id: Uuid,
+    pub email: String,
+    pub role: OrganizationRole,
+    pub created_at: chrono::NaiveDateTime,
+    pub expires_at: chrono::NaiveDateTime,
+    pub organization_id: Uuid,
+    pub invited_by: i32,
+}
+
+impl From<OrganizationInvitation> for OrganizationInvitationResponse {
+    fn from(value: OrganizationInvitation) -> Self {
+        OrganizationInvitationResponse {
+            id: value.id.to_string(),
+            email: value.email,
+            role: value.role.as_str().to_string(),
+            created_at: value.created_at.and_utc().timestamp() as usize,
+            expires_at: value.expires_at.and_utc().timestamp() as usize,
+            organization_id: value.organization_id.to_string(),
+            invited_by: value.invited_by,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Insertable)]
+#[diesel(table_name = organization_invitations)]
+pub struct NewOrganizationInvitation {
+    pub email: String,
+    pub role: OrganizationRole,
+    pub expires_at: chrono::NaiveDateTime,
+    pub organization_id: Uuid,
+    pub invited_by: i32,
+}
+
+#[derive(Debug, Serialize, Deserialize, Clone, Queryable)]
+#[diesel(table_name = organization_livekit_credentials)]
+pub struct LivekitCredential {
+    pub id: Uuid,
+    pub name: String,
+    pub server_url: String,
+    pub api_key: String,
+    pub api_secret: String,
+    pub created_at: chrono::NaiveDateTime,
+    pub updated_at: chrono::NaiveDateTime,
+    pub organization_id: Uuid,
+}
+
+impl From<LivekitCredential> for LivekitCredentialResponse {
+    fn from(value: LivekitCredential) -> Self {
+        LivekitCredentialResponse {
+            id: value.id.to_string(),
+            name: value.name,
+            server_url: value.server_url,
+            organization_id: value.organization_id.to_string(),
+            last_updated: value.updated_at.and_utc().timestamp() as usize,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Insertable)]
+#[diesel(table_name = organization_livekit_credentials)]
+pub struct NewLivekitCredential {
+    pub name: String,
+    pub server_url: String,
+    pub api_key: String,
+    pub api_secret: String,
+    pub organization_id: Uuid,
+}
+
+#[derive(Debug, Clone, AsChangeset)]
+#[diesel(table_name = organization_livekit_credentials)]
+pub struct LivekitCredentialChangeset {
+    pub name: Option<String>,
+    pub server_url: Option<String>,
+    pub api_key: Option<String>,
+    pub api_secret: Option<String>,
+    pub updated_at: chrono::NaiveDateTime,
+}
+
+#[derive(Debug, Serialize, Deserialize, Clone, Queryable)]
+#[diesel(table_name = organization_storage_credentials)]
+pub struct StorageCredential {
+    pub id: Uuid,
+    pub name: String,
+    pub storage_type: StorageType,
+    pub bucket_name: String,
+    pub endpoint: String,
+    pub access_key: String,
+    pub secret_key: String,
+    pub region: Option<String>,
+    pub created_at: chrono::NaiveDateTime,
+    pub updated_at: chrono::NaiveDateTime,
+    pub organization_id: Uuid,
+}
+
+impl From<StorageCredential> for StorageCredentialResponse {
+    fn from(value: StorageCredential) -> Self {
+        StorageCredentialResponse {
+            id: value.id.to_string(),
+            name: value.name,
+            storage_type: match value.storage_type {
+                StorageType::S3 => "s3".to_string(),
+            },
+            bucket_name: value.bucket_name,
+            endpoint: value.endpoint,
+            region: value.region,
+            organization_id: value.organization_id.to_string(),
+            last_updated: value.updated_at.and_utc().timestamp() as usize,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Insertable)]
+#[diesel(table_name = organization_storage_credentials)]
+pub struct NewStorageCredential {
+    pub name: String,
+    pub storage_type: StorageType,
+    pub bucket_name: String,
+    pub endpoint: String,
+    pub access_key: String,
+    pub secret_key: String,
+    pub region: Option<String>,
+    pub organization_id: Uuid,
+}
+
+#[derive(Debug, Clone, AsChangeset)]
+#[diesel(table_name = organization_storage_credentials)]
+pub struct StorageCredentialChangeset {
+    pub name: Option<String>,
+    pub bucket_name: Option<String>,
+    pub endpoint: Option<String>,
+    pub access_key: Option<String>,
+    pub secret_key: Option<String>,
+    pub region: Option<String>,
+    pub updated_at: chrono::NaiveDateTime,
+}
+
 #[derive(Debug, Serialize, Deserialize, DbEnum, Clone, Eq, PartialEq)]
 #[ExistingTypePath = "crate::schema::syncflow::sql_types::ProjectSessionStatus"]
 #[DbValueStyle = "PascalCase"]
@@ -320,6 +956,110 @@ impl From<ProjectSession> for ProjectSessionResponse {
     }
 }
 
+#[derive(Debug, Serialize, Deserialize, DbEnum, Clone, Copy, Eq, PartialEq)]
+#[ExistingTypePath = "crate::schema::syncflow::sql_types::RetentionMode"]
+#[DbValueStyle = "snake_case"]
+pub enum RetentionMode {
+    KeepMedia,
+    /// Recordings are removed a number of days after their session stopped
+    DeleteMedia,
+    /// Recordings are removed as soon as their session stopped
+    MetadataOnly,
+}
+
+impl RetentionMode {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            RetentionMode::KeepMedia => "keep_media",
+            RetentionMode::DeleteMedia => "delete_media",
+            RetentionMode::MetadataOnly => "metadata_only",
+        }
+    }
+}
+
+impl std::str::FromStr for RetentionMode {
+    type Err = String;
+
+    fn from_str(mode: &str) -> Result<Self, Self::Err> {
+        match mode {
+            "keep_media" => Ok(RetentionMode::KeepMedia),
+            "delete_media" => Ok(RetentionMode::DeleteMedia),
+            "metadata_only" => Ok(RetentionMode::MetadataOnly),
+            _ => Err(format!("Unknown retention mode: {}", mode)),
+        }
+    }
+}
+
+#[derive(Debug, Serialize, Deserialize, Clone, Queryable, Insertable, AsChangeset)]
+#[diesel(table_name = recording_retention_policies)]
+#[diesel(primary_key(project_id))]
+#[diesel(treat_none_as_null = true)]
+pub struct RetentionPolicy {
+    pub project_id: Uuid,
+    pub mode: RetentionMode,
+    pub media_retention_days: Option<i32>,
+    pub updated_at: chrono::NaiveDateTime,
+    pub updated_by: Option<i32>,
+}
+
+impl RetentionPolicy {
+    /// Sessions that stopped before the returned time have expired recordings,
+    /// none when recordings are kept.
+    pub fn media_cutoff(&self, now: chrono::NaiveDateTime) -> Option<chrono::NaiveDateTime> {
+        match self.mode {
+            RetentionMode::KeepMedia => None,
+            RetentionMode::DeleteMedia => self
+                .media_retention_days
+                .map(|days| now - chrono::Duration::days(days as i64)),
+            RetentionMode::MetadataOnly => Some(now),
+        }
+    }
+}
+
+impl From<RetentionPolicy> for RetentionPolicyResponse {
+    fn from(value: RetentionPolicy) -> Self {
+        RetentionPolicyResponse {
+            project_id: value.project_id.to_string(),
+            mode: value.mode.as_str().to_string(),
+            media_retention_days: value.media_retention_days,
+            updated_at: Some(value.updated_at.and_utc().timestamp() as usize),
+            updated_by: value.updated_by,
+        }
+    }
+}
+
+#[derive(Debug, Serialize, Deserialize, Clone, Queryable, Identifiable)]
+#[diesel(table_name = participant_consents)]
+pub struct ParticipantConsent {
+    pub id: Uuid,
+    pub participant_identity: String,
+    pub granted_at: chrono::NaiveDateTime,
+    pub withdrawn_at: Option<chrono::NaiveDateTime>,
+    pub recorded_by: Option<i32>,
+    pub session_id: Uuid,
+}
+
+impl From<ParticipantConsent> for ParticipantConsentResponse {
+    fn from(value: ParticipantConsent) -> Self {
+        ParticipantConsentResponse {
+            id: value.id.to_string(),
+            participant_identity: value.participant_identity,
+            granted_at: value.granted_at.and_utc().timestamp() as usize,
+            withdrawn_at: value.withdrawn_at.map(|w| w.and_utc().timestamp() as usize),
+            recorded_by: value.recorded_by,
+            session_id: value.session_id.to_string(),
+        }
+    }
+}
+
+#[derive(Debug, Clone, Insertable)]
+#[diesel(table_name = participant_consents)]
+pub struct NewParticipantConsent {
+    pub participant_identity: String,
+    pub recorded_by: Option<i32>,
+    pub session_id: Uuid,
+}
+
 #[derive(Debug, Serialize, Deserialize, ToSchema, Clone, Queryable, Insertable)]
 #[diesel(table_name = project_api_keys)]
 pub struct ProjectAPIKey {
@@ -330,11 +1070,38 @@ pub struct ProjectAPIKey {
     pub created_at: chrono::NaiveDateTime,
     pub user_id: i32,
     pub project_id: Uuid,
+    pub scopes: String,
+    pub expires_at: Option<chrono::NaiveDateTime>,
+    pub allowed_ips: Option<String>,
+    pub last_used_at: Option<chrono::NaiveDateTime>,
+}
+
+impl ProjectAPIKey {
+    pub fn scope_list(&self) -> Vec<String> {
+        self.scopes.split_whitespace().map(String::from).collect()
+    }
+
+    pub fn allowed_ip_list(&self) -> Vec<String> {
+        self.allowed_ips
+            .as_deref()
+            .unwrap_or_default()
+            .split_whitespace()
+            .map(String::from)
+            .collect()
+    }
+
+    pub fn is_expired(&self) -> bool {
+        self.expires_at
+            .is_some_and(|expires_at| expires_at <= chrono::Utc::now().naive_utc())
+    }
 }
 
 impl From<ProjectAPIKey> for ApiKeyResponse {
     fn from(value: ProjectAPIKey) -> Self {
         ApiKeyResponse {
+            scopes: Some(value.scope_list()),
+            allowed_ips: Some(value.allowed_ip_list()),
+            expires_at: value.expires_at.map(|e| e.and_utc().timestamp() as usize),
             key: value.api_key,
             secret: value.api_secret,
             comment: value.comments.unwrap_or_default(),
@@ -347,6 +1114,10 @@ impl From<ProjectAPIKey> for ApiKeyResponse {
 impl From<ProjectAPIKey> for ApiKeyResponseWithoutSecret {
     fn from(value: ProjectAPIKey) -> Self {
         ApiKeyResponseWithoutSecret {
+            scopes: Some(value.scope_list()),
+            allowed_ips: Some(value.allowed_ip_list()),
+            expires_at: value.expires_at.map(|e| e.and_utc().timestamp() as usize),
+            last_used_at: value.last_used_at.map(|l| l.and_utc().timestamp() as usize),
             id: value.id,
             key: value.api_key,
             comment: value.comments.unwrap_or_default(),
@@ -364,6 +1135,9 @@ pub struct NewProjectAPIKey {
     pub comments: Option<String>,
     pub user_id: i32,
     pub project_id: Uuid,
+    pub scopes: String,
+    pub expires_at: Option<chrono::NaiveDateTime>,
+    pub allowed_ips: Option<String>,
 }
 
 #[derive(Debug, Serialize, Deserialize, ToSchema, Clone, Queryable, Insertable, AsChangeset)]
@@ -420,6 +1194,81 @@ pub struct NewProjectDevice {
     pub registered_by: i32,
 }
 
+#[derive(Debug, Clone, Default, AsChangeset)]
+#[diesel(table_name = project_devices)]
+pub struct ProjectDeviceChangeset {
+    pub device_name: Option<String>,
+    pub device_group: Option<String>,
+    pub comments: Option<String>,
+}
+
+#[derive(Debug, Serialize, Deserialize, ToSchema, Clone, Queryable, Identifiable)]
+#[diesel(table_name = device_groups)]
+pub struct DeviceGroup {
+    pub id: Uuid,
+    pub name: String,
+    pub description: Option<String>,
+    pub notify_on_session_start: bool,
+    pub created_at: chrono::NaiveDateTime,
+    pub updated_at: chrono::NaiveDateTime,
+    pub project_id: Uuid,
+}
+
+impl DeviceGroup {
+    pub fn into_group_response(self, num_devices: i64) -> DeviceGroupResponse {
+        DeviceGroupResponse {
+            id: self.id.to_string(),
+            name: self.name,
+            description: self.description,
+            notify_on_session_start: self.notify_on_session_start,
+            num_devices: num_devices as usize,
+            created_at: self.created_at.and_utc().timestamp() as usize,
+            updated_at: self.updated_at.and_utc().timestamp() as usize,
+            project_id: self.project_id.to_string(),
+        }
+    }
+}
+
+#[derive(Debug, Serialize, Deserialize, ToSchema, Clone, Insertable)]
+#[diesel(table_name = device_groups)]
+pub struct NewDeviceGroup {
+    pub name: String,
+    pub description: Option<String>,
+    pub notify_on_session_start: bool,
+    pub project_id: Uuid,
+}
+
+#[derive(Debug, Clone, Default, AsChangeset)]
+#[diesel(table_name = device_groups)]
+pub struct DeviceGroupChangeset {
+    pub name: Option<String>,
+    pub description: Option<String>,
+    pub notify_on_session_start: Option<bool>,
+    pub updated_at: Option<chrono::NaiveDateTime>,
+}
+
+#[derive(Debug, Clone, Queryable, Identifiable)]
+#[diesel(table_name = notification_outbox)]
+pub struct OutboxMessage {
+    pub id: Uuid,
+    pub routing_key: String,
+    pub payload: Vec<u8>,
+    pub attempts: i32,
+    pub last_error: Option<String>,
+    pub created_at: chrono::NaiveDateTime,
+    pub next_attempt_at: chrono::NaiveDateTime,
+    pub delivered_at: Option<chrono::NaiveDateTime>,
+    pub session_id: Option<Uuid>,
+}
+
+#[derive(Debug, Clone, Insertable)]
+#[diesel(table_name = notification_outbox)]
+pub struct NewOutboxMessage {
+    pub routing_key: String,
+    pub payload: Vec<u8>,
+    pub session_id: Option<Uuid>,
+}
+
 #[derive(Debug, Serialize, Deserialize, DbEnum, Clone, Eq, PartialEq)]
 #[ExistingTypePath = "crate::schema::syncflow::sql_types::SessionEgressStatus"]
 #[DbValueStyle = "SCREAMING_SNAKE_CASE"]
@@ -522,6 +1371,9 @@ pub struct SessionEgress {
     pub session_id: Uuid,
     pub participant_id: Option<Uuid>,
     pub db_track_id: Option<Uuid>,
+    pub purged_at: Option<chrono::NaiveDateTime>,
+    pub purge_failures: i32,
+    pub purge_attempted_at: Option<chrono::NaiveDateTime>,
 }
 
 impl From<SessionEgress> for EgressResponse {
@@ -538,6 +1390,7 @@ impl From<SessionEgress> for EgressResponse {
             session_id: value.session_id.to_string(),
             participant_id: value.participant_id.map(|p| p.to_string()),
             db_track_id: value.db_track_id.map(|t| t.to_string()),
+            purged_at: value.purged_at.map(|p| p.and_utc().timestamp() as usize),
         }
     }
 }
@@ -709,3 +1562,80 @@ pub struct NewParticipantTrack {
     pub source: TrackSource,
     pub participant_id: Uuid,
 }
+
+#[derive(Debug, Serialize, Deserialize, DbEnum, Clone, Copy, Eq, PartialEq)]
+#[ExistingTypePath = "crate::schema::syncflow::sql_types::MediaDeletionMode"]
+#[DbValueStyle = "snake_case"]
+pub enum MediaDeletionMode {
+    /// Recordings stay in the bucket
+    Keep,
+    Delete,
+    /// Recordings are moved under the deletion's archive prefix
+    Archive,
+}
+
+impl MediaDeletionMode {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            MediaDeletionMode::Keep => "keep",
+            MediaDeletionMode::Delete => "delete",
+            MediaDeletionMode::Archive => "archive",
+        }
+    }
+}
+
+impl std::str::FromStr for MediaDeletionMode {
+    type Err = String;
+
+    fn from_str(mode: &str) -> Result<Self, Self::Err> {
+        match mode {
+            "keep" => Ok(MediaDeletionMode::Keep),
+            "delete" => Ok(MediaDeletionMode::Delete),
+            "archive" => Ok(MediaDeletionMode::Archive),
+            _ => Err(format!("Unknown media deletion mode: {}", mode)),
+        }
+    }
+}
+
+#[derive(Debug, Serialize, Deserialize, Clone, Queryable, Identifiable)]
+#[diesel(table_name = scheduled_deletions)]
+pub struct ScheduledDeletion {
+    pub id: Uuid,
+    pub project_id: Uuid,
+    pub session_id: Option<Uuid>,
+    pub media_mode: MediaDeletionMode,
+    pub archive_prefix: Option<String>,
+    pub requested_at: chrono::NaiveDateTime,
+    pub requested_by: Option<i32>,
+    pub purge_after: chrono::NaiveDateTime,
+    pub attempts: i32,
+    pub last_error: Option<String>,
+}
+
+impl From<ScheduledDeletion> for ScheduledDeletionResponse {
+    fn from(value: ScheduledDeletion) -> Self {
+        ScheduledDeletionResponse {
+            id: value.id.to_string(),
+            project_id: value.project_id.to_string(),
+            session_id: value.session_id.map(|s| s.to_string()),
+            media_mode: value.media_mode.as_str().to_string(),
+            archive_prefix: value.archive_prefix,
+            requested_at: value.requested_at.and_utc().timestamp() as usize,
+            requested_by: value.requested_by,
+            purge_after: value.purge_after.and_utc().timestamp() as usize,
+            attempts: value.attempts,
+            last_error: value.last_error,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Insertable)]
+#[diesel(table_name = scheduled_deletions)]
+pub struct NewScheduledDeletion {
+    pub project_id: Uuid,
+    pub session_id: Option<Uuid>,
+    pub media_mode: MediaDeletionMode,
+    pub archive_prefix: Option<String>,
+    pub requested_by: Option<i32>,
+    pub purge_after: chrono::NaiveDateTime,
+}
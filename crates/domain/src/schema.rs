@@ -2,14 +2,42 @@
 
 pub mod syncflow {
     pub mod sql_types {
+        #[derive(diesel::query_builder::QueryId, diesel::sql_types::SqlType)]
+        #[diesel(postgres_type(name = "account_token_purpose", schema = "syncflow"))]
+        pub struct AccountTokenPurpose;
+
+        #[derive(diesel::query_builder::QueryId, diesel::sql_types::SqlType)]
+        #[diesel(postgres_type(name = "audit_result", schema = "syncflow"))]
+        pub struct AuditResult;
+
+        #[derive(diesel::query_builder::QueryId, diesel::sql_types::SqlType)]
+        #[diesel(postgres_type(name = "jwt_signing_algorithm", schema = "syncflow"))]
+        pub struct JwtSigningAlgorithm;
+
         #[derive(diesel::query_builder::QueryId, diesel::sql_types::SqlType)]
         #[diesel(postgres_type(name = "KeyType", schema = "syncflow"))]
         pub struct KeyType;
 
+        #[derive(diesel::query_builder::QueryId, diesel::sql_types::SqlType)]
+        #[diesel(postgres_type(name = "media_deletion_mode", schema = "syncflow"))]
+        pub struct MediaDeletionMode;
+
+        #[derive(diesel::query_builder::QueryId, diesel::sql_types::SqlType)]
+        #[diesel(postgres_type(name = "organization_role", schema = "syncflow"))]
+        pub struct OrganizationRole;
+
+        #[derive(diesel::query_builder::QueryId, diesel::sql_types::SqlType)]
+        #[diesel(postgres_type(name = "project_role", schema = "syncflow"))]
+        pub struct ProjectRole;
+
         #[derive(diesel::query_builder::QueryId, diesel::sql_types::SqlType)]
         #[diesel(postgres_type(name = "project_session_status", schema = "syncflow"))]
         pub struct ProjectSessionStatus;
 
+        #[derive(diesel::query_builder::QueryId, diesel::sql_types::SqlType)]
+        #[diesel(postgres_type(name = "retention_mode", schema = "syncflow"))]
+        pub struct RetentionMode;
+
         #[derive(diesel::query_builder::QueryId, diesel::sql_types::SqlType)]
         #[diesel(postgres_type(name = "session_egress_status", schema = "syncflow"))]
         pub struct SessionEgressStatus;
@@ -31,6 +59,46 @@ pub mod syncflow {
         pub struct TrackSource;
     }
 
+    diesel::table! {
+        use diesel::sql_types::*;
+        use super::sql_types::AccountTokenPurpose;
+
+        syncflow.account_tokens (id) {
+            id -> Uuid,
+            purpose -> AccountTokenPurpose,
+            #[max_length = 64]
+            secret_hash -> Varchar,
+            #[max_length = 255]
+            email -> Varchar,
+            created_at -> Timestamp,
+            expires_at -> Timestamp,
+            used_at -> Nullable<Timestamp>,
+            user_id -> Int4,
+        }
+    }
+
+    diesel::table! {
+        use diesel::sql_types::*;
+        use super::sql_types::AuditResult;
+
+        syncflow.audit_events (id) {
+            id -> Int8,
+            occurred_at -> Timestamp,
+            actor_user_id -> Nullable<Int4>,
+            actor_api_key_id -> Nullable<Int4>,
+            #[max_length = 64]
+            ip_address -> Nullable<Varchar>,
+            #[max_length = 64]
+            action -> Varchar,
+            #[max_length = 32]
+            target_type -> Varchar,
+            target_id -> Nullable<Text>,
+            project_id -> Nullable<Uuid>,
+            result -> AuditResult,
+            error_message -> Nullable<Text>,
+        }
+    }
+
     diesel::table! {
         use diesel::sql_types::*;
         use super::sql_types::KeyType;
@@ -49,11 +117,144 @@ pub mod syncflow {
         }
     }
 
+    diesel::table! {
+        syncflow.device_groups (id) {
+            id -> Uuid,
+            #[max_length = 50]
+            name -> Varchar,
+            description -> Nullable<Text>,
+            notify_on_session_start -> Bool,
+            created_at -> Timestamp,
+            updated_at -> Timestamp,
+            project_id -> Uuid,
+        }
+    }
+
+    diesel::table! {
+        use diesel::sql_types::*;
+        use super::sql_types::JwtSigningAlgorithm;
+
+        syncflow.jwt_signing_keys (kid) {
+            #[max_length = 64]
+            kid -> Varchar,
+            algorithm -> JwtSigningAlgorithm,
+            private_key -> Text,
+            public_jwk -> Text,
+            created_at -> Timestamp,
+            retired_at -> Nullable<Timestamp>,
+        }
+    }
+
     diesel::table! {
         syncflow.login_sessions (session_id) {
             session_id -> Uuid,
             user_id -> Int4,
             created_at -> Nullable<Timestamptz>,
+            user_agent -> Nullable<Text>,
+            #[max_length = 64]
+            ip_address -> Nullable<Varchar>,
+            last_used_at -> Nullable<Timestamptz>,
+            expires_at -> Timestamptz,
+            refresh_token_id -> Nullable<Uuid>,
+        }
+    }
+
+    diesel::table! {
+        syncflow.mfa_recovery_codes (id) {
+            id -> Int4,
+            #[max_length = 64]
+            code_hash -> Varchar,
+            used_at -> Nullable<Timestamp>,
+            created_at -> Timestamp,
+            user_id -> Int4,
+        }
+    }
+
+    diesel::table! {
+        use diesel::sql_types::*;
+        use super::sql_types::OrganizationRole;
+
+        syncflow.organization_invitations (id) {
+            id -> Uuid,
+            #[max_length = 255]
+            email -> Varchar,
+            role -> OrganizationRole,
+            created_at -> Timestamp,
+            expires_at -> Timestamp,
+            organization_id -> Uuid,
+            invited_by -> Int4,
+        }
+    }
+
+    diesel::table! {
+        syncflow.organization_livekit_credentials (id) {
+            id -> Uuid,
+            #[max_length = 50]
+            name -> Varchar,
+            server_url -> Text,
+            api_key -> Text,
+            api_secret -> Text,
+            created_at -> Timestamp,
+            updated_at -> Timestamp,
+            organization_id -> Uuid,
+        }
+    }
+
+    diesel::table! {
+        use diesel::sql_types::*;
+        use super::sql_types::OrganizationRole;
+
+        syncflow.organization_members (id) {
+            id -> Uuid,
+            role -> OrganizationRole,
+            created_at -> Timestamp,
+            organization_id -> Uuid,
+            user_id -> Int4,
+        }
+    }
+
+    diesel::table! {
+        use diesel::sql_types::*;
+        use super::sql_types::StorageType;
+
+        syncflow.organization_storage_credentials (id) {
+            id -> Uuid,
+            #[max_length = 50]
+            name -> Varchar,
+            storage_type -> StorageType,
+            #[max_length = 50]
+            bucket_name -> Varchar,
+            endpoint -> Text,
+            access_key -> Text,
+            secret_key -> Text,
+            #[max_length = 50]
+            region -> Nullable<Varchar>,
+            created_at -> Timestamp,
+            updated_at -> Timestamp,
+            organization_id -> Uuid,
+        }
+    }
+
+    diesel::table! {
+        syncflow.organizations (id) {
+            id -> Uuid,
+            #[max_length = 50]
+            name -> Varchar,
+            description -> Nullable<Text>,
+            created_at -> Timestamp,
+            updated_at -> Timestamp,
+        }
+    }
+
+    diesel::table! {
+        syncflow.participant_consents (id) {
+            id -> Uuid,
+            #[max_length = 255]
+            participant_identity -> Varchar,
+            granted_at -> Timestamp,
+            withdrawn_at -> Nullable<Timestamp>,
+            recorded_by -> Nullable<Int4>,
+            session_id -> Uuid,
         }
     }
 
@@ -83,6 +284,10 @@ pub mod syncflow {
             created_at -> Timestamp,
             user_id -> Int4,
             project_id -> Uuid,
+            scopes -> Text,
+            expires_at -> Nullable<Timestamp>,
+            allowed_ips -> Nullable<Text>,
+            last_used_at -> Nullable<Timestamp>,
         }
     }
 
@@ -100,6 +305,35 @@ pub mod syncflow {
         }
     }
 
+    diesel::table! {
+        use diesel::sql_types::*;
+        use super::sql_types::ProjectRole;
+
+        syncflow.project_invitations (id) {
+            id -> Uuid,
+            #[max_length = 255]
+            email -> Varchar,
+            role -> ProjectRole,
+            created_at -> Timestamp,
+            expires_at -> Timestamp,
+            project_id -> Uuid,
+            invited_by -> Int4,
+        }
+    }
+
+    diesel::table! {
+        use diesel::sql_types::*;
+        use super::sql_types::ProjectRole;
+
+        syncflow.project_members (id) {
+            id -> Uuid,
+            role -> ProjectRole,
+            created_at -> Timestamp,
+            project_id -> Uuid,
+            user_id -> Int4,
+        }
+    }
+
     diesel::table! {
         use diesel::sql_types::*;
         use super::sql_types::ProjectSessionStatus;
@@ -144,6 +378,41 @@ pub mod syncflow {
             region -> Nullable<Varchar>,
             created_at -> Nullable<Timestamptz>,
             updated_at -> Nullable<Timestamptz>,
+            organization_id -> Nullable<Uuid>,
+            livekit_credential_id -> Nullable<Uuid>,
+            storage_credential_id -> Nullable<Uuid>,
+        }
+    }
+
+    diesel::table! {
+        use diesel::sql_types::*;
+        use super::sql_types::RetentionMode;
+
+        syncflow.recording_retention_policies (project_id) {
+            project_id -> Uuid,
+            mode -> RetentionMode,
+            media_retention_days -> Nullable<Int4>,
+            updated_at -> Timestamp,
+            updated_by -> Nullable<Int4>,
+        }
+    }
+
+    diesel::table! {
+        use diesel::sql_types::*;
+        use super::sql_types::MediaDeletionMode;
+
+        syncflow.scheduled_deletions (id) {
+            id -> Uuid,
+            project_id -> Uuid,
+            session_id -> Nullable<Uuid>,
+            media_mode -> MediaDeletionMode,
+            #[max_length = 255]
+            archive_prefix -> Nullable<Varchar>,
+            requested_at -> Timestamp,
+            requested_by -> Nullable<Int4>,
+            purge_after -> Timestamp,
+            attempts -> Int4,
+            last_error -> Nullable<Text>,
         }
     }
 
@@ -167,6 +436,9 @@ pub mod syncflow {
             session_id -> Uuid,
             participant_id -> Nullable<Uuid>,
             db_track_id -> Nullable<Uuid>,
+            purged_at -> Nullable<Timestamp>,
+            purge_failures -> Int4,
+            purge_attempted_at -> Nullable<Timestamp>,
         }
     }
 
@@ -183,6 +455,31 @@ pub mod syncflow {
         }
     }
 
+    diesel::table! {
+        syncflow.user_identities (id) {
+            id -> Uuid,
+            #[max_length = 255]
+            provider -> Varchar,
+            #[max_length = 255]
+            subject -> Varchar,
+            #[max_length = 255]
+            email -> Nullable<Varchar>,
+            created_at -> Timestamp,
+            last_login_at -> Timestamp,
+            user_id -> Int4,
+        }
+    }
+
+    diesel::table! {
+        syncflow.user_totp (user_id) {
+            user_id -> Int4,
+            secret -> Text,
+            confirmed_at -> Nullable<Timestamp>,
+            last_used_step -> Nullable<Int8>,
+            created_at -> Timestamp,
+        }
+    }
+
     diesel::table! {
         syncflow.users (id) {
             id -> Int4,
@@ -207,33 +504,80 @@ pub mod syncflow {
             organization -> Nullable<Varchar>,
             #[max_length = 255]
             job_role -> Nullable<Varchar>,
+            email_verified_at -> Nullable<Timestamptz>,
+            failed_login_attempts -> Int4,
+            locked_until -> Nullable<Timestamptz>,
         }
     }
 
+    diesel::joinable!(account_tokens -> users (user_id));
     diesel::joinable!(api_keys -> users (user_id));
+    diesel::joinable!(device_groups -> projects (project_id));
     diesel::joinable!(login_sessions -> users (user_id));
+    diesel::joinable!(mfa_recovery_codes -> users (user_id));
+    diesel::joinable!(notification_outbox -> project_sessions (session_id));
+    diesel::joinable!(organization_invitations -> organizations (organization_id));
+    diesel::joinable!(organization_invitations -> users (invited_by));
+    diesel::joinable!(organization_livekit_credentials -> organizations (organization_id));
+    diesel::joinable!(organization_members -> organizations (organization_id));
+    diesel::joinable!(organization_members -> users (user_id));
+    diesel::joinable!(organization_storage_credentials -> organizations (organization_id));
+    diesel::joinable!(participant_consents -> project_sessions (session_id));
+    diesel::joinable!(participant_consents -> users (recorded_by));
     diesel::joinable!(participant_tracks -> session_participants (participant_id));
     diesel::joinable!(project_api_keys -> projects (project_id));
     diesel::joinable!(project_api_keys -> users (user_id));
     diesel::joinable!(project_devices -> projects (project_id));
     diesel::joinable!(project_devices -> users (registered_by));
+    diesel::joinable!(project_invitations -> projects (project_id));
+    diesel::joinable!(project_invitations -> users (invited_by));
+    diesel::joinable!(project_members -> projects (project_id));
+    diesel::joinable!(project_members -> users (user_id));
     diesel::joinable!(project_sessions -> projects (project_id));
+    diesel::joinable!(projects -> organization_livekit_credentials (livekit_credential_id));
+    diesel::joinable!(projects -> organization_storage_credentials (storage_credential_id));
+    diesel::joinable!(projects -> organizations (organization_id));
     diesel::joinable!(projects -> users (user_id));
+    diesel::joinable!(recording_retention_policies -> projects (project_id));
+    diesel::joinable!(recording_retention_policies -> users (updated_by));
+    diesel::joinable!(scheduled_deletions -> project_sessions (session_id));
+    diesel::joinable!(scheduled_deletions -> projects (project_id));
+    diesel::joinable!(scheduled_deletions -> users (requested_by));
     diesel::joinable!(session_egresses -> participant_tracks (db_track_id));
     diesel::joinable!(session_egresses -> project_sessions (session_id));
     diesel::joinable!(session_egresses -> session_participants (participant_id));
     diesel::joinable!(session_participants -> project_sessions (session_id));
+    diesel::joinable!(user_identities -> users (user_id));
+    diesel::joinable!(user_totp -> users (user_id));
 
     diesel::allow_tables_to_appear_in_same_query!(
+        account_tokens,
         api_keys,
+        audit_events,
+        device_groups,
+        jwt_signing_keys,
         login_sessions,
+        mfa_recovery_codes,
+        notification_outbox,
+        organization_invitations,
+        organization_livekit_credentials,
+        organization_members,
+        organization_storage_credentials,
+        organizations,
+        participant_consents,
         participant_tracks,
         project_api_keys,
         project_devices,
+        project_invitations,
+        project_members,
         project_sessions,
         projects,
+        recording_retention_policies,
+        scheduled_deletions,
         session_egresses,
         session_participants,
+        user_identities,
+        user_totp,
         users,
     );
 }
@@ -0,0 +1,3 @@
+-- This file should undo anything in `up.sql`
+ALTER TABLE syncflow.project_devices DROP CONSTRAINT IF EXISTS project_devices_device_group_fkey;
+DROP TABLE IF EXISTS syncflow.device_groups;
@@ -0,0 +1,21 @@
+-- Your SQL goes here
+CREATE TABLE syncflow.device_groups(
+    id UUID PRIMARY KEY DEFAULT gen_random_uuid(),
+    name VARCHAR(50) NOT NULL,
+    description TEXT,
+    notify_on_session_start BOOLEAN NOT NULL DEFAULT FALSE,
+    created_at TIMESTAMP NOT NULL DEFAULT NOW(),
+    updated_at TIMESTAMP NOT NULL DEFAULT NOW(),
+    project_id UUID NOT NULL REFERENCES syncflow.projects(id) ON DELETE CASCADE,
+    UNIQUE (project_id, name)
+);
+
+INSERT INTO syncflow.device_groups (project_id, name)
+SELECT DISTINCT project_id, device_group FROM syncflow.project_devices;
+
+-- Renaming a group cascades to its devices; a group with devices cannot be deleted.
+ALTER TABLE syncflow.project_devices
+    ADD CONSTRAINT project_devices_device_group_fkey
+    FOREIGN KEY (project_id, device_group)
+    REFERENCES syncflow.device_groups(project_id, name)
+    ON UPDATE CASCADE ON DELETE RESTRICT;
@@ -0,0 +1,16 @@
+-- Your SQL goes here
+CREATE TABLE syncflow.notification_outbox(
+    id UUID PRIMARY KEY DEFAULT gen_random_uuid(),
+    routing_key VARCHAR(255) NOT NULL,
+    payload BYTEA NOT NULL,
+    attempts INT NOT NULL DEFAULT 0,
+    last_error TEXT,
+    created_at TIMESTAMP NOT NULL DEFAULT NOW(),
+    next_attempt_at TIMESTAMP NOT NULL DEFAULT NOW(),
+    delivered_at TIMESTAMP,
+    session_id UUID REFERENCES syncflow.project_sessions(id) ON DELETE CASCADE
+);
+
+CREATE INDEX notification_outbox_pending_idx
+    ON syncflow.notification_outbox(next_attempt_at)
+    WHERE delivered_at IS NULL;
@@ -0,0 +1,4 @@
+-- This file should undo anything in `up.sql`
+DROP TABLE IF EXISTS syncflow.project_invitations;
+DROP TABLE IF EXISTS syncflow.project_members;
+DROP TYPE IF EXISTS syncflow.project_role;
@@ -0,0 +1,26 @@
+-- Your SQL goes here
+CREATE TYPE syncflow.project_role AS ENUM ('owner', 'admin', 'operator', 'viewer');
+
+CREATE TABLE syncflow.project_members(
+    id UUID PRIMARY KEY DEFAULT gen_random_uuid(),
+    role syncflow.project_role NOT NULL,
+    created_at TIMESTAMP NOT NULL DEFAULT NOW(),
+    project_id UUID NOT NULL REFERENCES syncflow.projects(id) ON DELETE CASCADE,
+    user_id INT NOT NULL REFERENCES syncflow.users(id) ON DELETE CASCADE,
+    UNIQUE (project_id, user_id)
+);
+
+INSERT INTO syncflow.project_members (project_id, user_id, role)
+SELECT id, user_id, 'owner' FROM syncflow.projects;
+
+CREATE TABLE syncflow.project_invitations(
+    id UUID PRIMARY KEY DEFAULT gen_random_uuid(),
+    email VARCHAR(255) NOT NULL,
+    role syncflow.project_role NOT NULL,
+    created_at TIMESTAMP NOT NULL DEFAULT NOW(),
+    expires_at TIMESTAMP NOT NULL,
+    project_id UUID NOT NULL REFERENCES syncflow.projects(id) ON DELETE CASCADE,
+    invited_by INT NOT NULL REFERENCES syncflow.users(id) ON DELETE CASCADE
+);
+
+CREATE INDEX project_invitations_email_idx ON syncflow.project_invitations(email);
@@ -0,0 +1,14 @@
+-- This file should undo anything in `up.sql`
+DROP INDEX IF EXISTS syncflow.projects_organization_id_idx;
+
+ALTER TABLE syncflow.projects
+    DROP COLUMN IF EXISTS storage_credential_id,
+    DROP COLUMN IF EXISTS livekit_credential_id,
+    DROP COLUMN IF EXISTS organization_id;
+
+DROP TABLE IF EXISTS syncflow.organization_storage_credentials;
+DROP TABLE IF EXISTS syncflow.organization_livekit_credentials;
+DROP TABLE IF EXISTS syncflow.organization_invitations;
+DROP TABLE IF EXISTS syncflow.organization_members;
+DROP TABLE IF EXISTS syncflow.organizations;
+DROP TYPE IF EXISTS syncflow.organization_role;
@@ -0,0 +1,68 @@
+-- Your SQL goes here
+CREATE TYPE syncflow.organization_role AS ENUM ('owner', 'admin', 'member');
+
+CREATE TABLE syncflow.organizations(
+    id UUID PRIMARY KEY DEFAULT gen_random_uuid(),
+    name VARCHAR(50) NOT NULL UNIQUE,
+    description TEXT,
+    created_at TIMESTAMP NOT NULL DEFAULT NOW(),
+    updated_at TIMESTAMP NOT NULL DEFAULT NOW()
+);
+
+CREATE TABLE syncflow.organization_members(
+    id UUID PRIMARY KEY DEFAULT gen_random_uuid(),
+    role syncflow.organization_role NOT NULL,
+    created_at TIMESTAMP NOT NULL DEFAULT NOW(),
+    organization_id UUID NOT NULL REFERENCES syncflow.organizations(id) ON DELETE CASCADE,
+    user_id INT NOT NULL REFERENCES syncflow.users(id) ON DELETE CASCADE,
+    UNIQUE (organization_id, user_id)
+);
+
+-- Users join an organization only by accepting an invitation to their email
+CREATE TABLE syncflow.organization_invitations(
+    id UUID PRIMARY KEY DEFAULT gen_random_uuid(),
+    email VARCHAR(255) NOT NULL,
+    role syncflow.organization_role NOT NULL,
+    created_at TIMESTAMP NOT NULL DEFAULT NOW(),
+    expires_at TIMESTAMP NOT NULL,
+    organization_id UUID NOT NULL REFERENCES syncflow.organizations(id) ON DELETE CASCADE,
+    invited_by INT NOT NULL REFERENCES syncflow.users(id) ON DELETE CASCADE
+);
+
+CREATE INDEX organization_invitations_email_idx ON syncflow.organization_invitations(email);
+
+-- Secrets are encrypted with the deployment encryption key, like the project columns
+CREATE TABLE syncflow.organization_livekit_credentials(
+    id UUID PRIMARY KEY DEFAULT gen_random_uuid(),
+    name VARCHAR(50) NOT NULL,
+    server_url TEXT NOT NULL,
+    api_key TEXT NOT NULL,
+    api_secret TEXT NOT NULL,
+    created_at TIMESTAMP NOT NULL DEFAULT NOW(),
+    updated_at TIMESTAMP NOT NULL DEFAULT NOW(),
+    organization_id UUID NOT NULL REFERENCES syncflow.organizations(id) ON DELETE CASCADE,
+    UNIQUE (organization_id, name)
+);
+
+CREATE TABLE syncflow.organization_storage_credentials(
+    id UUID PRIMARY KEY DEFAULT gen_random_uuid(),
+    name VARCHAR(50) NOT NULL,
+    storage_type "syncflow"."StorageType" NOT NULL DEFAULT 'S3',
+    bucket_name VARCHAR(50) NOT NULL,
+    endpoint TEXT NOT NULL,
+    access_key TEXT NOT NULL,
+    secret_key TEXT NOT NULL,
+    region VARCHAR(50),
+    created_at TIMESTAMP NOT NULL DEFAULT NOW(),
+    updated_at TIMESTAMP NOT NULL DEFAULT NOW(),
+    organization_id UUID NOT NULL REFERENCES syncflow.organizations(id) ON DELETE CASCADE,
+    UNIQUE (organization_id, name)
+);
+
+-- Projects referencing a credential leave their own credential columns empty
+ALTER TABLE syncflow.projects
+    ADD COLUMN organization_id UUID REFERENCES syncflow.organizations(id) ON DELETE RESTRICT,
+    ADD COLUMN livekit_credential_id UUID REFERENCES syncflow.organization_livekit_credentials(id) ON DELETE RESTRICT,
+    ADD COLUMN storage_credential_id UUID REFERENCES syncflow.organization_storage_credentials(id) ON DELETE RESTRICT;
+
+CREATE INDEX projects_organization_id_idx ON syncflow.projects(organization_id);
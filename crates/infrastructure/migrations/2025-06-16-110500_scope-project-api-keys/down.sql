@@ -0,0 +1,6 @@
+-- This file should undo anything in `up.sql`
+ALTER TABLE syncflow.project_api_keys
+    DROP COLUMN IF EXISTS last_used_at,
+    DROP COLUMN IF EXISTS allowed_ips,
+    DROP COLUMN IF EXISTS expires_at,
+    DROP COLUMN IF EXISTS scopes;
@@ -0,0 +1,9 @@
+-- Your SQL goes here
+-- Scopes are stored space separated, existing keys keep full access
+ALTER TABLE syncflow.project_api_keys
+    ADD COLUMN scopes TEXT NOT NULL DEFAULT 'project:admin',
+    ADD COLUMN expires_at TIMESTAMP,
+    ADD COLUMN allowed_ips TEXT,
+    ADD COLUMN last_used_at TIMESTAMP;
+
+ALTER TABLE syncflow.project_api_keys ALTER COLUMN scopes DROP DEFAULT;
@@ -0,0 +1,14 @@
+-- Your SQL goes here
+-- Accounts of OpenID Connect providers linked to a user, keyed by the subject the provider asserts
+CREATE TABLE syncflow.user_identities(
+    id UUID PRIMARY KEY DEFAULT gen_random_uuid(),
+    provider VARCHAR(255) NOT NULL,
+    subject VARCHAR(255) NOT NULL,
+    email VARCHAR(255),
+    created_at TIMESTAMP NOT NULL DEFAULT NOW(),
+    last_login_at TIMESTAMP NOT NULL DEFAULT NOW(),
+    user_id INT NOT NULL REFERENCES syncflow.users(id) ON DELETE CASCADE,
+    UNIQUE (provider, subject)
+);
+
+CREATE INDEX user_identities_user_id_idx ON syncflow.user_identities(user_id);
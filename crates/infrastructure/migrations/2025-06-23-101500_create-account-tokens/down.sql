@@ -0,0 +1,4 @@
+-- This file should undo anything in `up.sql`
+DROP TABLE syncflow.account_tokens;
+DROP TYPE syncflow.account_token_purpose;
+ALTER TABLE syncflow.users DROP COLUMN email_verified_at;
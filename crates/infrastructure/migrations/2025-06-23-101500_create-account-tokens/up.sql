@@ -0,0 +1,19 @@
+-- Your SQL goes here
+ALTER TABLE syncflow.users ADD COLUMN email_verified_at TIMESTAMPTZ;
+
+CREATE TYPE syncflow.account_token_purpose AS ENUM ('password_reset', 'email_verification');
+
+-- Single use tokens mailed to users. Only a hash of the secret part is stored.
+CREATE TABLE syncflow.account_tokens(
+    id UUID PRIMARY KEY DEFAULT gen_random_uuid(),
+    purpose syncflow.account_token_purpose NOT NULL,
+    secret_hash VARCHAR(64) NOT NULL,
+    -- The address the token was sent to, verification only applies while it is unchanged
+    email VARCHAR(255) NOT NULL,
+    created_at TIMESTAMP NOT NULL DEFAULT NOW(),
+    expires_at TIMESTAMP NOT NULL,
+    used_at TIMESTAMP,
+    user_id INT NOT NULL REFERENCES syncflow.users(id) ON DELETE CASCADE
+);
+
+CREATE INDEX account_tokens_user_id_idx ON syncflow.account_tokens(user_id, purpose);
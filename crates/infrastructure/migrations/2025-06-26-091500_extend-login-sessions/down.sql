@@ -0,0 +1,9 @@
+-- This file should undo anything in `up.sql`
+DROP INDEX IF EXISTS syncflow.login_sessions_user_id_idx;
+
+ALTER TABLE syncflow.login_sessions
+    DROP COLUMN IF EXISTS refresh_token_id,
+    DROP COLUMN IF EXISTS expires_at,
+    DROP COLUMN IF EXISTS last_used_at,
+    DROP COLUMN IF EXISTS ip_address,
+    DROP COLUMN IF EXISTS user_agent;
@@ -0,0 +1,16 @@
+-- Your SQL goes here
+-- Existing sessions get the default lifetime counted from their creation
+ALTER TABLE syncflow.login_sessions
+    ADD COLUMN user_agent TEXT,
+    ADD COLUMN ip_address VARCHAR(64),
+    ADD COLUMN last_used_at TIMESTAMP WITH TIME ZONE,
+    ADD COLUMN expires_at TIMESTAMP WITH TIME ZONE,
+    -- Id of the only refresh token of the session that may still be used
+    ADD COLUMN refresh_token_id UUID;
+
+UPDATE syncflow.login_sessions
+    SET expires_at = COALESCE(created_at, CURRENT_TIMESTAMP) + INTERVAL '30 days';
+
+ALTER TABLE syncflow.login_sessions ALTER COLUMN expires_at SET NOT NULL;
+
+CREATE INDEX login_sessions_user_id_idx ON syncflow.login_sessions(user_id);
@@ -0,0 +1,4 @@
+-- This file should undo anything in `up.sql`
+ALTER TABLE syncflow.users
+    DROP COLUMN IF EXISTS locked_until,
+    DROP COLUMN IF EXISTS failed_login_attempts;
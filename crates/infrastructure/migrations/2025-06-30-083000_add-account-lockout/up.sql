@@ -0,0 +1,5 @@
+-- Your SQL goes here
+-- Consecutive password failures, reset by a successful login or a new password
+ALTER TABLE syncflow.users
+    ADD COLUMN failed_login_attempts INT NOT NULL DEFAULT 0,
+    ADD COLUMN locked_until TIMESTAMP WITH TIME ZONE;
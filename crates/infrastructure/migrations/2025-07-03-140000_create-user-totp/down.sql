@@ -0,0 +1,3 @@
+-- This file should undo anything in `up.sql`
+DROP TABLE IF EXISTS syncflow.mfa_recovery_codes;
+DROP TABLE IF EXISTS syncflow.user_totp;
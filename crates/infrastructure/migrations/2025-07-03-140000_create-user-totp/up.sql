@@ -0,0 +1,22 @@
+-- Your SQL goes here
+-- TOTP second factor of a user, active once confirmed with a first code
+CREATE TABLE syncflow.user_totp(
+    user_id INT PRIMARY KEY REFERENCES syncflow.users(id) ON DELETE CASCADE,
+    -- Encrypted like the other stored secrets
+    secret TEXT NOT NULL,
+    confirmed_at TIMESTAMP,
+    -- Time step of the last accepted code, so that a code works only once
+    last_used_step BIGINT,
+    created_at TIMESTAMP NOT NULL DEFAULT NOW()
+);
+
+-- Single use codes for when the authenticator is lost. Only hashes are stored.
+CREATE TABLE syncflow.mfa_recovery_codes(
+    id SERIAL PRIMARY KEY,
+    code_hash VARCHAR(64) NOT NULL,
+    used_at TIMESTAMP,
+    created_at TIMESTAMP NOT NULL DEFAULT NOW(),
+    user_id INT NOT NULL REFERENCES syncflow.users(id) ON DELETE CASCADE
+);
+
+CREATE INDEX mfa_recovery_codes_user_id_idx ON syncflow.mfa_recovery_codes(user_id);
@@ -0,0 +1,3 @@
+-- This file should undo anything in `up.sql`
+DROP TABLE IF EXISTS syncflow.jwt_signing_keys;
+DROP TYPE IF EXISTS syncflow.jwt_signing_algorithm;
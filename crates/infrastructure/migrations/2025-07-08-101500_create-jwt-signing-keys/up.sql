@@ -0,0 +1,16 @@
+-- Your SQL goes here
+CREATE TYPE syncflow.jwt_signing_algorithm AS ENUM ('rs256', 'ed_dsa');
+
+-- Server-managed key pairs signing login tokens. The newest unretired key of
+-- the configured algorithm signs, retired keys stay published in the key set
+-- until the tokens they signed expired.
+CREATE TABLE syncflow.jwt_signing_keys(
+    kid VARCHAR(64) PRIMARY KEY,
+    algorithm syncflow.jwt_signing_algorithm NOT NULL,
+    -- DER encoded and encrypted like the other stored secrets
+    private_key TEXT NOT NULL,
+    -- The public key as a JWK
+    public_jwk TEXT NOT NULL,
+    created_at TIMESTAMP NOT NULL DEFAULT NOW(),
+    retired_at TIMESTAMP
+);
@@ -0,0 +1,5 @@
+-- This file should undo anything in `up.sql`
+DROP TRIGGER audit_events_append_only ON syncflow.audit_events;
+DROP FUNCTION syncflow.reject_audit_event_change();
+DROP TABLE syncflow.audit_events;
+DROP TYPE syncflow.audit_result;
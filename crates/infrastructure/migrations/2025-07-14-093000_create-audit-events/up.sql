@@ -0,0 +1,32 @@
+-- Your SQL goes here
+CREATE TYPE syncflow.audit_result AS ENUM ('success', 'failure');
+
+-- Append-only record of security- and data-relevant actions. Actors, targets
+-- and projects are not foreign keys, events outlive what they name.
+CREATE TABLE syncflow.audit_events(
+    id BIGSERIAL PRIMARY KEY,
+    occurred_at TIMESTAMP NOT NULL DEFAULT NOW(),
+    actor_user_id INT,
+    -- The project API key the request was made with
+    actor_api_key_id INT,
+    ip_address VARCHAR(64),
+    action VARCHAR(64) NOT NULL,
+    target_type VARCHAR(32) NOT NULL,
+    target_id TEXT,
+    project_id UUID,
+    result syncflow.audit_result NOT NULL,
+    error_message TEXT
+);
+
+CREATE INDEX audit_events_project_id_idx ON syncflow.audit_events(project_id, occurred_at);
+CREATE INDEX audit_events_actor_user_id_idx ON syncflow.audit_events(actor_user_id, occurred_at);
+
+CREATE FUNCTION syncflow.reject_audit_event_change() RETURNS trigger AS $$
+BEGIN
+    RAISE EXCEPTION 'audit_events is append-only';
+END;
+$$ LANGUAGE plpgsql;
+
+CREATE TRIGGER audit_events_append_only
+    BEFORE UPDATE OR DELETE ON syncflow.audit_events
+    FOR EACH ROW EXECUTE FUNCTION syncflow.reject_audit_event_change();
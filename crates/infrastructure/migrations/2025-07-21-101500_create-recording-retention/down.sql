@@ -0,0 +1,8 @@
+-- This file should undo anything in `up.sql`
+ALTER TABLE syncflow.session_egresses
+    DROP COLUMN purge_attempted_at,
+    DROP COLUMN purge_failures,
+    DROP COLUMN purged_at;
+DROP TABLE IF EXISTS syncflow.participant_consents;
+DROP TABLE IF EXISTS syncflow.recording_retention_policies;
+DROP TYPE IF EXISTS syncflow.retention_mode;
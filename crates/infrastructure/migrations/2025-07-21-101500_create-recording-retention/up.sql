@@ -0,0 +1,30 @@
+-- Your SQL goes here
+CREATE TYPE syncflow.retention_mode AS ENUM ('keep_media', 'delete_media', 'metadata_only');
+
+-- Projects without a policy keep their recordings indefinitely
+CREATE TABLE syncflow.recording_retention_policies(
+    project_id UUID PRIMARY KEY REFERENCES syncflow.projects(id) ON DELETE CASCADE,
+    mode syncflow.retention_mode NOT NULL,
+    -- Days recordings of a stopped session are kept, only used by delete_media
+    media_retention_days INT CHECK (media_retention_days > 0),
+    updated_at TIMESTAMP NOT NULL DEFAULT NOW(),
+    updated_by INT REFERENCES syncflow.users(id) ON DELETE SET NULL
+);
+
+CREATE TABLE syncflow.participant_consents(
+    id UUID PRIMARY KEY DEFAULT gen_random_uuid(),
+    participant_identity VARCHAR(255) NOT NULL,
+    granted_at TIMESTAMP NOT NULL DEFAULT NOW(),
+    withdrawn_at TIMESTAMP,
+    recorded_by INT REFERENCES syncflow.users(id) ON DELETE SET NULL,
+    session_id UUID NOT NULL REFERENCES syncflow.project_sessions(id) ON DELETE CASCADE,
+    UNIQUE (session_id, participant_identity)
+);
+
+-- Set once the egress file has been removed from the bucket, the row is kept
+ALTER TABLE syncflow.session_egresses ADD COLUMN purged_at TIMESTAMP;
+
+-- Failed purges are retried after the others, and given up on after a while
+ALTER TABLE syncflow.session_egresses
+    ADD COLUMN purge_failures INT NOT NULL DEFAULT 0,
+    ADD COLUMN purge_attempted_at TIMESTAMP;
@@ -0,0 +1,3 @@
+-- This file should undo anything in `up.sql`
+DROP TABLE IF EXISTS syncflow.scheduled_deletions;
+DROP TYPE IF EXISTS syncflow.media_deletion_mode;
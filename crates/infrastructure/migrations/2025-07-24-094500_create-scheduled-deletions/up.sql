@@ -0,0 +1,26 @@
+-- Your SQL goes here
+CREATE TYPE syncflow.media_deletion_mode AS ENUM ('keep', 'delete', 'archive');
+
+-- Soft deletions of projects and sessions. A deleted project or session stays
+-- hidden but restorable until purge_after, when the purger applies the media
+-- mode to its recordings and removes the rows.
+CREATE TABLE syncflow.scheduled_deletions(
+    id UUID PRIMARY KEY DEFAULT gen_random_uuid(),
+    project_id UUID NOT NULL REFERENCES syncflow.projects(id) ON DELETE CASCADE,
+    -- Unset when the whole project is deleted
+    session_id UUID REFERENCES syncflow.project_sessions(id) ON DELETE CASCADE,
+    media_mode syncflow.media_deletion_mode NOT NULL,
+    -- Prefix recordings are moved under, only used by archive
+    archive_prefix VARCHAR(255),
+    requested_at TIMESTAMP NOT NULL DEFAULT NOW(),
+    requested_by INT REFERENCES syncflow.users(id) ON DELETE SET NULL,
+    purge_after TIMESTAMP NOT NULL,
+    attempts INT NOT NULL DEFAULT 0,
+    last_error TEXT
+);
+
+CREATE UNIQUE INDEX scheduled_deletions_project_idx
+    ON syncflow.scheduled_deletions(project_id) WHERE session_id IS NULL;
+CREATE UNIQUE INDEX scheduled_deletions_session_idx
+    ON syncflow.scheduled_deletions(session_id);
+CREATE INDEX scheduled_deletions_purge_after_idx ON syncflow.scheduled_deletions(purge_after);
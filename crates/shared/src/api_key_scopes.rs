@@ -0,0 +1,234 @@
+use std::net::IpAddr;
+use std::str::FromStr;
+
+use serde::{Deserialize, Serialize};
+use utoipa::ToSchema;
+
+/// Permissions a project API key can be restricted to.
+#[derive(Debug, Serialize, Deserialize, ToSchema, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum ApiKeyScope {
+    #[serde(rename = "project:read")]
+    ProjectRead,
+    /// Full control over the project, including its deletion
+    #[serde(rename = "project:admin")]
+    ProjectAdmin,
+    #[serde(rename = "sessions:read")]
+    SessionsRead,
+    #[serde(rename = "sessions:write")]
+    SessionsWrite,
+    #[serde(rename = "devices:read")]
+    DevicesRead,
+    #[serde(rename = "devices:register")]
+    DevicesRegister,
+    #[serde(rename = "devices:write")]
+    DevicesWrite,
+    #[serde(rename = "media:download")]
+    MediaDownload,
+    #[serde(rename = "tokens:mint")]
+    TokensMint,
+}
+
+impl ApiKeyScope {
+    pub const ALL: [ApiKeyScope; 9] = [
+        ApiKeyScope::ProjectRead,
+        ApiKeyScope::ProjectAdmin,
+        ApiKeyScope::SessionsRead,
+        ApiKeyScope::SessionsWrite,
+        ApiKeyScope::DevicesRead,
+        ApiKeyScope::DevicesRegister,
+        ApiKeyScope::DevicesWrite,
+        ApiKeyScope::MediaDownload,
+        ApiKeyScope::TokensMint,
+    ];
+
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            ApiKeyScope::ProjectRead => "project:read",
+            ApiKeyScope::ProjectAdmin => "project:admin",
+            ApiKeyScope::SessionsRead => "sessions:read",
+            ApiKeyScope::SessionsWrite => "sessions:write",
+            ApiKeyScope::DevicesRead => "devices:read",
+            ApiKeyScope::DevicesRegister => "devices:register",
+            ApiKeyScope::DevicesWrite => "devices:write",
+            ApiKeyScope::MediaDownload => "media:download",
+            ApiKeyScope::TokensMint => "tokens:mint",
+        }
+    }
+
+    /// Parses a space separated scope list, as stored in the database.
+    pub fn parse_list(scopes: &str) -> Result<Vec<ApiKeyScope>, String> {
+        scopes
+            .split_whitespace()
+            .map(ApiKeyScope::from_str)
+            .collect()
+    }
+
+    pub fn join(scopes: &[ApiKeyScope]) -> String {
+        scopes
+            .iter()
+            .map(|scope| scope.as_str())
+            .collect::<Vec<_>>()
+            .join(" ")
+    }
+
+    /// Whether the granted scopes allow an action requiring `required`.
+    /// `project:admin` allows everything.
+    pub fn granted(scopes: &[ApiKeyScope], required: ApiKeyScope) -> bool {
+        scopes.contains(&ApiKeyScope::ProjectAdmin) || scopes.contains(&required)
+    }
+}
+
+impl FromStr for ApiKeyScope {
+    type Err = String;
+
+    fn from_str(scope: &str) -> Result<Self, Self::Err> {
+        ApiKeyScope::ALL
+            .into_iter()
+            .find(|candidate| candidate.as_str() == scope)
+            .ok_or_else(|| format!("Unknown API key scope: {}", scope))
+    }
+}
+
+/// An IP address or CIDR block of an API key allow-list.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct IpRange {
+    network: IpAddr,
+    prefix_len: u8,
+}
+
+impl IpRange {
+    pub fn contains(&self, ip: IpAddr) -> bool {
+        match (self.network, ip) {
+            (IpAddr::V4(network), IpAddr::V4(ip)) => {
+                prefix_matches(&network.octets(), &ip.octets(), self.prefix_len)
+            }
+            (IpAddr::V6(network), IpAddr::V6(ip)) => {
+                prefix_matches(&network.octets(), &ip.octets(), self.prefix_len)
+            }
+            (IpAddr::V6(network), IpAddr::V4(ip)) => {
+                network.to_ipv4_mapped().is_some_and(|network| {
+                    prefix_matches(
+                        &network.octets(),
+                        &ip.octets(),
+                        self.prefix_len.saturating_sub(96),
+                    )
+                })
+            }
+            (IpAddr::V4(_), IpAddr::V6(ip)) => ip
+                .to_ipv4_mapped()
+                .is_some_and(|ip| self.contains(IpAddr::V4(ip))),
+        }
+    }
+
+    /// Whether `ip` is allowed by the list. An empty list allows every address.
+    pub fn allowed(allow_list: &[IpRange], ip: IpAddr) -> bool {
+        allow_list.is_empty() || allow_list.iter().any(|range| range.contains(ip))
+    }
+}
+
+fn prefix_matches(network: &[u8], ip: &[u8], prefix_len: u8) -> bool {
+    let full_bytes = (prefix_len / 8) as usize;
+    let remaining_bits = prefix_len % 8;
+
+    if network[..full_bytes] != ip[..full_bytes] {
+        return false;
+    }
+
+    if remaining_bits == 0 {
+        return true;
+    }
+
+    let mask = 0xffu8 << (8 - remaining_bits);
+    network[full_bytes] & mask == ip[full_bytes] & mask
+}
+
+impl FromStr for IpRange {
+    type Err = String;
+
+    fn from_str(range: &str) -> Result<Self, Self::Err> {
+        let invalid = || format!("Invalid IP address or CIDR block: {}", range);
+
+        let (address, prefix_len) = match range.split_once('/') {
+            Some((address, prefix_len)) => (address, Some(prefix_len)),
+            None => (range, None),
+        };
+
+        let network = IpAddr::from_str(address.trim()).map_err(|_| invalid())?;
+        let max_prefix_len = if network.is_ipv4() { 32 } else { 128 };
+        let prefix_len = match prefix_len {
+            Some(prefix_len) => prefix_len.trim().parse::<u8>().map_err(|_| invalid())?,
+            None => max_prefix_len,
+        };
+
+        if prefix_len > max_prefix_len {
+            return Err(invalid());
+        }
+
+        Ok(IpRange {
+            network,
+            prefix_len,
+        })
+    }
+}
+
+impl std::fmt::Display for IpRange {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{}/{}", self.network, self.prefix_len)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_scopes() {
+        assert_eq!(
+            ApiKeyScope::parse_list("sessions:read  devices:register").unwrap(),
+            vec![ApiKeyScope::SessionsRead, ApiKeyScope::DevicesRegister]
+        );
+        assert!(ApiKeyScope::parse_list("sessions:read project:delete").is_err());
+        assert_eq!(
+            ApiKeyScope::join(&[ApiKeyScope::MediaDownload, ApiKeyScope::TokensMint]),
+            "media:download tokens:mint"
+        );
+    }
+
+    #[test]
+    fn test_scope_granted() {
+        let device = [ApiKeyScope::SessionsRead, ApiKeyScope::DevicesRegister];
+        assert!(ApiKeyScope::granted(&device, ApiKeyScope::SessionsRead));
+        assert!(!ApiKeyScope::granted(&device, ApiKeyScope::ProjectAdmin));
+        assert!(!ApiKeyScope::granted(&device, ApiKeyScope::SessionsWrite));
+        assert!(ApiKeyScope::granted(
+            &[ApiKeyScope::ProjectAdmin],
+            ApiKeyScope::MediaDownload
+        ));
+    }
+
+    #[test]
+    fn test_ip_ranges() {
+        let cases = [
+            ("10.0.0.0/8", "10.20.30.40", true),
+            ("10.0.0.0/8", "11.0.0.1", false),
+            ("192.168.1.0/25", "192.168.1.127", true),
+            ("192.168.1.0/25", "192.168.1.128", false),
+            ("203.0.113.7", "203.0.113.7", true),
+            ("203.0.113.7", "203.0.113.8", false),
+            ("2001:db8::/32", "2001:db8:1::1", true),
+            ("2001:db8::/32", "2001:db9::1", false),
+            ("10.0.0.0/8", "::ffff:10.1.2.3", true),
+            ("0.0.0.0/0", "198.51.100.1", true),
+        ];
+
+        for (range, ip, allowed) in cases {
+            let range = IpRange::from_str(range).unwrap();
+            let ip = IpAddr::from_str(ip).unwrap();
+            assert_eq!(range.contains(ip), allowed, "{} {}", range, ip);
+        }
+
+        assert!(IpRange::from_str("10.0.0.0/33").is_err());
+        assert!(IpRange::from_str("not-an-ip").is_err());
+        assert!(IpRange::allowed(&[], IpAddr::from_str("1.2.3.4").unwrap()));
+    }
+}
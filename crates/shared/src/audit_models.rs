@@ -0,0 +1,38 @@
+use serde::{Deserialize, Serialize};
+use utoipa::ToSchema;
+
+#[derive(Debug, Serialize, Deserialize, ToSchema)]
+#[serde(rename_all = "camelCase")]
+pub struct AuditEventResponse {
+    pub id: i64,
+    pub occurred_at: usize,
+    pub actor_user_id: Option<i32>,
+    /// The project API key the action was performed with
+    pub actor_api_key_id: Option<i32>,
+    pub ip_address: Option<String>,
+    pub action: String,
+    pub target_type: String,
+    pub target_id: Option<String>,
+    pub project_id: Option<String>,
+    /// `success` or `failure`
+    pub result: String,
+    pub error_message: Option<String>,
+}
+
+/// Filters of the audit log, all optional. Events are returned newest first.
+#[derive(Debug, Default, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct AuditEventQuery {
+    pub action: Option<String>,
+    pub actor_user_id: Option<i32>,
+    pub actor_api_key_id: Option<i32>,
+    pub target_type: Option<String>,
+    pub target_id: Option<String>,
+    pub result: Option<String>,
+    /// Unix timestamps bounding when the events occurred, inclusive
+    pub since: Option<usize>,
+    pub until: Option<usize>,
+    /// Ignored by exports, which return every matching event
+    pub limit: Option<i64>,
+    pub offset: Option<i64>,
+}
@@ -10,9 +10,51 @@ pub enum TokenTypes {
     ProjectToken(ProjectToken),
 }
 
+/// The `typ` claim naming the kind of a token, so that verifiers do not have
+/// to guess it from the claims present. Tokens issued before it was added
+/// decode with the kind of the struct they are read as.
+#[derive(Debug, Serialize, Deserialize, Clone, Copy, PartialEq, Eq)]
+pub enum TokenKind {
+    Login,
+    Refresh,
+    Api,
+    Project,
+    MfaPending,
+}
+
+impl TokenKind {
+    fn login() -> Self {
+        TokenKind::Login
+    }
+
+    fn refresh() -> Self {
+        TokenKind::Refresh
+    }
+
+    fn api() -> Self {
+        TokenKind::Api
+    }
+
+    fn project() -> Self {
+        TokenKind::Project
+    }
+
+    fn mfa_pending() -> Self {
+        TokenKind::MfaPending
+    }
+}
+
+/// Just the `typ` claim of a token, `None` for tokens issued without it.
+#[derive(Debug, Deserialize)]
+pub struct TypedClaims {
+    pub typ: Option<TokenKind>,
+}
+
 #[derive(Debug, Serialize, Deserialize, Clone)]
 #[serde(rename_all = "camelCase")]
 pub struct LoginToken {
+    #[serde(default = "TokenKind::login")]
+    pub typ: TokenKind,
     pub iat: usize,
     pub exp: usize,
     pub iss: String,
@@ -26,17 +68,42 @@ pub struct LoginToken {
 #[derive(Debug, Serialize, Deserialize, Clone)]
 #[serde(rename_all = "camelCase")]
 pub struct RefreshToken {
+    #[serde(default = "TokenKind::refresh")]
+    pub typ: TokenKind,
     pub iat: usize,
     pub exp: usize,
     pub iss: String,
 
     // Data
     pub login_session: String,
+    /// Id of the token within its session, tokens issued before refresh
+    /// rotation have none
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub jti: Option<String>,
+}
+
+/// Proves the password of a user with two-factor authentication was checked.
+/// It only buys a try at the second factor and is never accepted as a bearer
+/// token, which is why it is not one of the `TokenTypes`.
+#[derive(Debug, Serialize, Deserialize, Clone)]
+#[serde(rename_all = "camelCase")]
+pub struct MfaPendingToken {
+    #[serde(default = "TokenKind::mfa_pending")]
+    pub typ: TokenKind,
+    pub iat: usize,
+    pub exp: usize,
+    pub iss: String,
+
+    // Data
+    pub user_id: i32,
+    pub mfa_pending: bool,
 }
 
 #[derive(Debug, Serialize, Deserialize, Clone)]
 #[serde(rename_all = "camelCase")]
 pub struct ApiToken {
+    #[serde(default = "TokenKind::api")]
+    pub typ: TokenKind,
     pub iat: usize,
     pub exp: usize,
     pub iss: String,
@@ -48,6 +115,8 @@ pub struct ApiToken {
 #[derive(Debug, Serialize, Deserialize, Clone)]
 #[serde(rename_all = "camelCase")]
 pub struct ProjectToken {
+    #[serde(default = "TokenKind::project")]
+    pub typ: TokenKind,
     pub iat: usize,
     pub exp: usize,
     pub iss: String,
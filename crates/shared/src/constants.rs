@@ -1,10 +1,14 @@
 pub const AUTHORIZATION_HEADER: &str = "Authorization";
 
-pub const IGNORE_ROUTES: [&str; 12] = [
+pub const IGNORE_ROUTES: [&str; 17] = [
     "/users/login",
     "/users/signup",
     "/users/refresh-token",
+    "/users/password-reset",
+    "/users/email-verification/confirm",
     "/oauth/github/login",
+    "/oauth/google/login",
+    "/oauth/oidc/login",
     "/rapidoc",
     "/redoc",
     "/swagger-ui",
@@ -13,10 +17,16 @@ pub const IGNORE_ROUTES: [&str; 12] = [
     "/rmq/auth/vhost",
     "/rmq/auth/resource",
     "/rmq/auth/topic",
+    "/.well-known/jwks.json",
 ];
 
-pub const IGNORE_PROJECT_OWNERSHIP_ROUTES: [&str; 3] =
-    ["/projects/create", "/projects/list", "/projects/summarize"];
+pub const IGNORE_PROJECT_OWNERSHIP_ROUTES: [&str; 5] = [
+    "/projects/create",
+    "/projects/list",
+    "/projects/summarize",
+    "/projects/invitations",
+    "/projects/validate",
+];
 
 pub const MESSAGE_INVALID_TOKEN: &str = "Invalid token, please login again";
 
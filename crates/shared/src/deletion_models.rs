@@ -0,0 +1,30 @@
+use serde::{Deserialize, Serialize};
+use utoipa::ToSchema;
+
+/// How a project or session is deleted, both optional.
+#[derive(Debug, Default, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct DeletionQuery {
+    /// What happens to the recordings once the grace period ends, `keep`
+    /// (the default), `delete` or `archive`
+    pub media: Option<String>,
+    /// Prefix the recordings are moved under by `archive`, `archive` when unset
+    pub archive_prefix: Option<String>,
+}
+
+#[derive(Debug, Serialize, Deserialize, ToSchema)]
+#[serde(rename_all = "camelCase")]
+pub struct ScheduledDeletionResponse {
+    pub id: String,
+    pub project_id: String,
+    /// Unset when the whole project is deleted
+    pub session_id: Option<String>,
+    pub media_mode: String,
+    pub archive_prefix: Option<String>,
+    pub requested_at: usize,
+    pub requested_by: Option<i32>,
+    /// Until then the deletion can be undone
+    pub purge_after: usize,
+    pub attempts: i32,
+    pub last_error: Option<String>,
+}
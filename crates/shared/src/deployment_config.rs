@@ -9,19 +9,63 @@ pub struct DeploymentConfig {
     pub num_actix_workers: usize,
     pub jwt_secret: String,
     pub database_url: String,
+    /// Keyring for stored secrets: comma separated `<version>:<base64 key>`
+    /// entries, the first encrypts. A single bare key is also accepted.
     pub encryption_key: String,
+    /// Provider wrapping the per-record data keys of stored secrets, the
+    /// keyring in `encryption_key` when unset
+    pub key_provider_config: Option<KeyProviderConfig>,
     pub jwt_expiration: usize,
     pub jwt_refresh_expiration: usize,
+    /// Seconds a login session lasts before the user has to sign in again,
+    /// regardless of refreshes. 30 days when unset
+    pub login_session_lifetime: Option<usize>,
+    /// Signing of login tokens with server-managed key pairs, published at
+    /// `/.well-known/jwks.json`. The per-user login secrets sign when unset
+    pub token_signing_config: Option<TokenSigningConfig>,
+    /// Seconds verified tokens and decrypted key secrets stay cached, 30 when
+    /// unset. Revocations on other replicas take up to this long to apply
+    pub token_cache_ttl_seconds: Option<u64>,
+    /// Maximum number of cached tokens and of cached secrets, 0 turns the
+    /// caches off
+    pub token_cache_capacity: Option<usize>,
 
     pub github_client_id: Option<String>,
     pub github_client_secret: Option<String>,
     pub google_client_id: Option<String>,
     pub google_client_secret: Option<String>,
+    /// Generic OpenID Connect provider, e.g. a university SSO
+    pub oidc_config: Option<OidcConfig>,
+
+    /// Delivery of account emails, only logged when unset
+    pub mailer_config: Option<MailerConfig>,
+    /// Base URL of the dashboard, used for the links in account emails
+    pub dashboard_url: Option<String>,
+
+    /// Throttling of the auth, broker auth and session endpoints, the
+    /// defaults of `RateLimitConfig` apply when unset
+    pub rate_limit_config: Option<RateLimitConfig>,
 
     pub root_user: Option<RootUser>,
 
+    /// Broker settings, used by the `Amqp` notifier and the broker auth endpoints
     pub rabbitmq_config: RabbitMQConfig,
 
+    /// Backend used to notify devices of new sessions, AMQP when unset
+    pub notifier_config: Option<NotifierConfig>,
+
+    /// How often recordings past their project's retention policy and those of
+    /// participants who withdrew consent are purged, in seconds. Hourly when unset
+    pub retention_interval_seconds: Option<u64>,
+
+    /// How long deleted projects and sessions can be restored before their
+    /// recordings are purged and their rows removed, in seconds. A week when unset
+    pub deletion_grace_period_seconds: Option<u64>,
+
+    /// How often deletions past their grace period are purged, in seconds.
+    /// Every ten minutes when unset
+    pub deletion_purge_interval_seconds: Option<u64>,
+
     /// Test configuration
     pub login_token: Option<String>,
     pub test_user: Option<String>,
@@ -35,12 +79,134 @@ pub struct RootUser {
     pub password: String,
 }
 
+#[derive(Deserialize, Debug, Clone)]
+pub struct OidcConfig {
+    /// Short name of the provider, recorded with the accounts linked through it
+    pub name: String,
+    /// The discovery document is read from `{issuer}/.well-known/openid-configuration`
+    pub issuer: String,
+    pub client_id: String,
+    /// Only needed to exchange authorization codes, ID tokens are accepted without it
+    pub client_secret: Option<String>,
+}
+
+#[derive(Deserialize, Debug, Clone)]
+pub enum MailerConfig {
+    Log,
+    Smtp(SmtpConfig),
+}
+
+#[derive(Deserialize, Debug, Clone)]
+pub struct SmtpConfig {
+    pub host: String,
+    /// The default port of the security mode when unset
+    pub port: Option<u16>,
+    pub username: Option<String>,
+    pub password: Option<String>,
+    /// Sender of account emails, e.g. `SyncFlow <no-reply@example.com>`
+    pub from: String,
+    /// `StartTls` when unset
+    pub security: Option<SmtpSecurity>,
+}
+
+/// Request limits, each counted per minute. Limits left out keep their default.
+#[derive(Deserialize, Debug, Clone, Default)]
+pub struct RateLimitConfig {
+    /// Turns all limits off when false
+    pub enabled: Option<bool>,
+    /// Requests from one IP to the login, signup, refresh and account email routes
+    pub auth_per_ip: Option<u32>,
+    /// Login and password reset attempts for one account
+    pub auth_per_account: Option<u32>,
+    /// Requests from one IP to the broker auth routes
+    pub rmq_auth_per_ip: Option<u32>,
+    /// Sessions created in one project
+    pub sessions_per_project: Option<u32>,
+    /// Session tokens minted in one project
+    pub tokens_per_project: Option<u32>,
+    /// Consecutive password failures that lock an account
+    pub lockout_threshold: Option<u32>,
+    /// Seconds a locked account stays locked
+    pub lockout_seconds: Option<u64>,
+    /// Take client IPs from `Forwarded`/`X-Forwarded-For`, only safe behind a
+    /// proxy that overwrites them. The peer address is used otherwise
+    pub trust_proxy_headers: Option<bool>,
+}
+
+#[derive(Deserialize, Debug, Clone)]
+pub struct TokenSigningConfig {
+    pub algorithm: TokenSigningAlgorithm,
+    /// Days a key signs tokens before the next one is generated, 90 when unset.
+    /// Retired keys stay published until the tokens they signed expired
+    pub rotation_days: Option<u32>,
+}
+
+#[derive(Deserialize, Debug, Clone, Copy, PartialEq, Eq)]
+pub enum TokenSigningAlgorithm {
+    RS256,
+    EdDSA,
+}
+
+#[derive(Deserialize, Debug, Clone, Copy)]
+pub enum SmtpSecurity {
+    StartTls,
+    Tls,
+    /// Plain text, only for local mail catchers
+    None,
+}
+
 #[derive(Deserialize, Debug, Clone)]
 pub enum StorageConfig {
     S3(S3Config),
     Local(LocalConfig),
 }
 
+#[derive(Deserialize, Debug, Clone)]
+pub enum NotifierConfig {
+    Amqp,
+    Webhook(WebhookConfig),
+    InMemory,
+}
+
+#[derive(Deserialize, Debug, Clone)]
+pub enum KeyProviderConfig {
+    Local,
+    /// Requires the `vault` feature
+    Vault(VaultTransitConfig),
+    /// Requires the `aws-kms` feature
+    AwsKms(AwsKmsConfig),
+}
+
+#[derive(Deserialize, Debug, Clone)]
+pub struct VaultTransitConfig {
+    pub address: String,
+    pub token: String,
+    pub key_name: String,
+    /// Mount path of the transit secrets engine, `transit` when unset
+    pub mount_path: Option<String>,
+    pub timeout_seconds: Option<u64>,
+}
+
+#[derive(Deserialize, Debug, Clone)]
+pub struct AwsKmsConfig {
+    /// Key id, alias or ARN of the key encryption key
+    pub key_id: String,
+    pub region: String,
+    /// Endpoint of a KMS compatible service, the AWS endpoint of the region when unset
+    pub endpoint: Option<String>,
+    /// Static credentials, the default AWS credential chain when unset
+    pub access_key: Option<String>,
+    pub secret_key: Option<String>,
+}
+
+#[derive(Deserialize, Debug, Clone)]
+pub struct WebhookConfig {
+    pub url: String,
+    /// Shared secret used to sign webhook payloads
+    pub secret: String,
+    pub timeout_seconds: Option<u64>,
+}
+
 #[derive(Deserialize, Debug, Clone)]
 pub struct S3Config {
     pub bucket: String,
@@ -65,6 +231,20 @@ pub struct RabbitMQConfig {
     pub queue_name: String,
     pub use_ssl: bool,
     pub vhost_name: String,
+    /// Port of the management HTTP API, used to list existing queue bindings
+    pub management_port: Option<u16>,
+
+    /// How long a granted broker auth decision is reused, in seconds
+    pub auth_cache_ttl_seconds: Option<u64>,
+    /// Maximum number of cached broker auth decisions
+    pub auth_cache_capacity: Option<usize>,
+
+    /// How long to wait for the broker to confirm a publish, in seconds
+    pub confirm_timeout_seconds: Option<u64>,
+    /// Upper bound of the delay between reconnection attempts, in seconds
+    pub reconnect_max_backoff_seconds: Option<u64>,
+    /// How often the outbox worker polls for undelivered notifications, in seconds
+    pub outbox_poll_interval_seconds: Option<u64>,
 }
 
 impl DeploymentConfig {
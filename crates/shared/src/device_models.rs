@@ -28,3 +28,59 @@ pub struct NewSessionMessage {
     pub session_id: String,
     pub session_name: String,
 }
+
+#[derive(Debug, Default, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct DeviceUpdateRequest {
+    pub name: Option<String>,
+    pub group: Option<String>,
+    pub comments: Option<String>,
+}
+
+impl DeviceUpdateRequest {
+    pub fn is_empty(&self) -> bool {
+        self.name.is_none() && self.group.is_none() && self.comments.is_none()
+    }
+}
+
+#[derive(Debug, Default, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct DeviceListQuery {
+    pub group: Option<String>,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct BulkDeviceRegisterResponse {
+    pub registered: Vec<DeviceResponse>,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct DeviceGroupRequest {
+    pub name: String,
+    pub description: Option<String>,
+    #[serde(default)]
+    pub notify_on_session_start: bool,
+}
+
+#[derive(Debug, Default, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct DeviceGroupUpdateRequest {
+    pub name: Option<String>,
+    pub description: Option<String>,
+    pub notify_on_session_start: Option<bool>,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct DeviceGroupResponse {
+    pub id: String,
+    pub name: String,
+    pub description: Option<String>,
+    pub notify_on_session_start: bool,
+    pub num_devices: usize,
+    pub created_at: usize,
+    pub updated_at: usize,
+    pub project_id: String,
+}
@@ -1,9 +1,15 @@
+pub mod api_key_scopes;
+pub mod audit_models;
 pub mod claims;
 pub mod constants;
+pub mod deletion_models;
 pub mod deployment_config;
 pub mod device_models;
 pub mod livekit_models;
+pub mod member_models;
+pub mod organization_models;
 pub mod project_models;
+pub mod recording_models;
 pub mod response_models;
 pub mod signed_token;
 pub mod user_models;
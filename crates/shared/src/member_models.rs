@@ -0,0 +1,37 @@
+use serde::{Deserialize, Serialize};
+use utoipa::ToSchema;
+
+#[derive(Debug, Serialize, Deserialize, ToSchema)]
+#[serde(rename_all = "camelCase")]
+pub struct ProjectInviteRequest {
+    pub username_or_email: String,
+    pub role: String,
+}
+
+#[derive(Debug, Serialize, Deserialize, ToSchema)]
+#[serde(rename_all = "camelCase")]
+pub struct MemberRoleUpdateRequest {
+    pub role: String,
+}
+
+#[derive(Debug, Serialize, Deserialize, ToSchema)]
+#[serde(rename_all = "camelCase")]
+pub struct ProjectMemberResponse {
+    pub user_id: i32,
+    pub username: String,
+    pub email: String,
+    pub role: String,
+    pub joined_at: usize,
+}
+
+#[derive(Debug, Serialize, Deserialize, ToSchema)]
+#[serde(rename_all = "camelCase")]
+pub struct ProjectInvitationResponse {
+    pub id: String,
+    pub email: String,
+    pub role: String,
+    pub created_at: usize,
+    pub expires_at: usize,
+    pub project_id: String,
+    pub invited_by: i32,
+}
@@ -0,0 +1,122 @@
+use serde::{Deserialize, Serialize};
+use utoipa::ToSchema;
+
+#[derive(Debug, Serialize, Deserialize, ToSchema)]
+#[serde(rename_all = "camelCase")]
+pub struct OrganizationRequest {
+    pub name: String,
+    pub description: Option<String>,
+}
+
+#[derive(Debug, Serialize, Deserialize, ToSchema)]
+#[serde(rename_all = "camelCase")]
+pub struct OrganizationUpdateRequest {
+    pub name: Option<String>,
+    pub description: Option<String>,
+}
+
+#[derive(Debug, Serialize, Deserialize, ToSchema)]
+#[serde(rename_all = "camelCase")]
+pub struct OrganizationResponse {
+    pub id: String,
+    pub name: String,
+    pub description: Option<String>,
+    /// Role of the requesting user in the organization
+    pub role: String,
+    pub created_at: usize,
+}
+
+#[derive(Debug, Serialize, Deserialize, ToSchema)]
+#[serde(rename_all = "camelCase")]
+pub struct OrganizationMemberRequest {
+    pub username_or_email: String,
+    pub role: String,
+}
+
+#[derive(Debug, Serialize, Deserialize, ToSchema)]
+#[serde(rename_all = "camelCase")]
+pub struct OrganizationMemberResponse {
+    pub user_id: i32,
+    pub username: String,
+    pub email: String,
+    pub role: String,
+    pub joined_at: usize,
+}
+
+#[derive(Debug, Serialize, Deserialize, ToSchema)]
+#[serde(rename_all = "camelCase")]
+pub struct OrganizationInvitationResponse {
+    pub id: String,
+    pub email: String,
+    pub role: String,
+    pub created_at: usize,
+    pub expires_at: usize,
+    pub organization_id: String,
+    pub invited_by: i32,
+}
+
+#[derive(Debug, Serialize, Deserialize, ToSchema)]
+#[serde(rename_all = "camelCase")]
+pub struct LivekitCredentialRequest {
+    pub name: String,
+    pub server_url: String,
+    pub api_key: String,
+    pub api_secret: String,
+}
+
+#[derive(Debug, Serialize, Deserialize, ToSchema)]
+#[serde(rename_all = "camelCase")]
+pub struct LivekitCredentialUpdateRequest {
+    pub name: Option<String>,
+    pub server_url: Option<String>,
+    pub api_key: Option<String>,
+    pub api_secret: Option<String>,
+}
+
+/// LiveKit credentials as returned by the API, without the key and secret
+#[derive(Debug, Serialize, Deserialize, ToSchema)]
+#[serde(rename_all = "camelCase")]
+pub struct LivekitCredentialResponse {
+    pub id: String,
+    pub name: String,
+    pub server_url: String,
+    pub organization_id: String,
+    pub last_updated: usize,
+}
+
+#[derive(Debug, Serialize, Deserialize, ToSchema)]
+#[serde(rename_all = "camelCase")]
+pub struct StorageCredentialRequest {
+    pub name: String,
+    pub storage_type: String,
+    pub bucket_name: String,
+    pub endpoint: String,
+    pub access_key: String,
+    pub secret_key: String,
+    pub region: Option<String>,
+}
+
+#[derive(Debug, Serialize, Deserialize, ToSchema)]
+#[serde(rename_all = "camelCase")]
+pub struct StorageCredentialUpdateRequest {
+    pub name: Option<String>,
+    pub bucket_name: Option<String>,
+    pub endpoint: Option<String>,
+    pub access_key: Option<String>,
+    pub secret_key: Option<String>,
+    pub region: Option<String>,
+}
+
+/// Storage credentials as returned by the API, without the access and secret keys
+#[derive(Debug, Serialize, Deserialize, ToSchema)]
+#[serde(rename_all = "camelCase")]
+pub struct StorageCredentialResponse {
+    pub id: String,
+    pub name: String,
+    pub storage_type: String,
+    pub bucket_name: String,
+    pub endpoint: String,
+    pub region: Option<String>,
+    pub organization_id: String,
+    pub last_updated: usize,
+}
@@ -121,6 +121,84 @@ pub struct ProjectSummary {
     pub num_recordings: u32,
 }
 
+#[derive(Debug, Serialize, Deserialize, ToSchema, Clone, Copy, PartialEq, Eq)]
+#[serde(rename_all = "lowercase")]
+pub enum ValidationStatus {
+    Passed,
+    Failed,
+    /// Not run because a check it depends on failed
+    Skipped,
+}
+
+#[derive(Debug, Serialize, Deserialize, ToSchema, Clone)]
+#[serde(rename_all = "camelCase")]
+pub struct ValidationCheck {
+    pub name: String,
+    pub status: ValidationStatus,
+    pub message: Option<String>,
+}
+
+impl ValidationCheck {
+    pub fn passed(name: &str) -> Self {
+        ValidationCheck {
+            name: name.to_string(),
+            status: ValidationStatus::Passed,
+            message: None,
+        }
+    }
+
+    pub fn failed(name: &str, message: impl ToString) -> Self {
+        ValidationCheck {
+            name: name.to_string(),
+            status: ValidationStatus::Failed,
+            message: Some(message.to_string()),
+        }
+    }
+
+    pub fn skipped(name: &str, message: impl ToString) -> Self {
+        ValidationCheck {
+            name: name.to_string(),
+            status: ValidationStatus::Skipped,
+            message: Some(message.to_string()),
+        }
+    }
+}
+
+/// Result of checking a project's LiveKit and storage settings.
+#[derive(Debug, Serialize, Deserialize, ToSchema, Clone)]
+#[serde(rename_all = "camelCase")]
+pub struct ProjectValidationReport {
+    pub valid: bool,
+    pub checks: Vec<ValidationCheck>,
+}
+
+impl ProjectValidationReport {
+    pub fn new(checks: Vec<ValidationCheck>) -> Self {
+        ProjectValidationReport {
+            valid: checks
+                .iter()
+                .all(|check| check.status != ValidationStatus::Failed),
+            checks,
+        }
+    }
+
+    /// The failed checks and their reasons, on one line.
+    pub fn failures(&self) -> String {
+        self.checks
+            .iter()
+            .filter(|check| check.status == ValidationStatus::Failed)
+            .map(|check| {
+                format!(
+                    "{}: {}",
+                    check.name,
+                    check.message.as_deref().unwrap_or_default()
+                )
+            })
+            .collect::<Vec<_>>()
+            .join("; ")
+    }
+}
+
 #[derive(Debug, Serialize, Deserialize, ToSchema)]
 #[serde(rename_all = "camelCase")]
 pub struct LivekitSessionInfo {
@@ -189,6 +267,7 @@ impl From<LivekitSessionInfo> for (Vec<SessionParticipantResponse>, Vec<EgressRe
                 room_name: value.room_name.clone(),
                 participant_id: None,
                 session_id: value.room_sid.clone(),
+                purged_at: None,
             })
             .collect();
 
@@ -225,6 +304,8 @@ pub struct EgressResponse {
     pub session_id: String,
     pub participant_id: Option<String>,
     pub db_track_id: Option<String>,
+    /// When the file was removed from the bucket, the metadata is kept
+    pub purged_at: Option<usize>,
 }
 
 #[derive(Debug, Clone, Serialize, Deserialize, ToSchema)]
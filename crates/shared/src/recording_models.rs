@@ -0,0 +1,89 @@
+use serde::{Deserialize, Serialize};
+use utoipa::ToSchema;
+
+#[derive(Debug, Serialize, Deserialize, ToSchema)]
+#[serde(rename_all = "camelCase")]
+pub struct RetentionPolicyRequest {
+    /// `keep_media`, `delete_media` or `metadata_only`
+    pub mode: String,
+    /// Days the recordings of a stopped session are kept, required by `delete_media`
+    pub media_retention_days: Option<i32>,
+}
+
+#[derive(Debug, Serialize, Deserialize, ToSchema)]
+#[serde(rename_all = "camelCase")]
+pub struct RetentionPolicyResponse {
+    pub project_id: String,
+    pub mode: String,
+    pub media_retention_days: Option<i32>,
+    /// Unset for projects that never had a policy
+    pub updated_at: Option<usize>,
+    pub updated_by: Option<i32>,
+}
+
+#[derive(Debug, Serialize, Deserialize, ToSchema)]
+#[serde(rename_all = "camelCase")]
+pub struct ConsentRequest {
+    pub participant_identity: String,
+}
+
+#[derive(Debug, Serialize, Deserialize, ToSchema)]
+#[serde(rename_all = "camelCase")]
+pub struct ParticipantConsentResponse {
+    pub id: String,
+    pub participant_identity: String,
+    pub granted_at: usize,
+    pub withdrawn_at: Option<usize>,
+    pub recorded_by: Option<i32>,
+    pub session_id: String,
+}
+
+#[derive(Debug, Serialize, Deserialize, ToSchema)]
+#[serde(rename_all = "camelCase")]
+pub struct ConsentWithdrawalResponse {
+    pub consent: ParticipantConsentResponse,
+    /// Paths of the recordings removed from the bucket
+    pub purged: Vec<String>,
+    /// Paths that could not be removed yet, the retention job retries them
+    pub pending: Vec<String>,
+}
+
+#[derive(Debug, Serialize, Deserialize, ToSchema, PartialEq, Eq)]
+#[serde(rename_all = "camelCase")]
+pub struct SessionMediaResponse {
+    /// Path of the file in the bucket
+    pub key: String,
+    pub file_name: String,
+    pub size: u64,
+    pub last_modified: Option<usize>,
+    /// Identity of the publisher, from the path LiveKit wrote the file to
+    pub publisher: Option<String>,
+    /// LiveKit sid of the recorded track, unset when it could not be matched
+    pub track_id: Option<String>,
+    /// Unset for files no egress of the session recorded
+    pub egress_id: Option<String>,
+}
+
+#[derive(Debug, Serialize, Deserialize, ToSchema, PartialEq, Eq)]
+#[serde(rename_all = "camelCase")]
+pub struct SessionStorageUsage {
+    pub session_id: String,
+    pub session_name: String,
+    pub livekit_room_name: String,
+    pub num_objects: u64,
+    pub total_size: u64,
+}
+
+#[derive(Debug, Serialize, Deserialize, ToSchema, PartialEq, Eq)]
+#[serde(rename_all = "camelCase")]
+pub struct StorageUsageResponse {
+    pub project_id: String,
+    pub num_objects: u64,
+    pub total_size: u64,
+    /// Sessions with at least one file, largest first
+    pub sessions: Vec<SessionStorageUsage>,
+    /// Files of no live session, such as those of deleted sessions awaiting
+    /// purge, or unrecorded files under a room several sessions reuse
+    pub unattributed_objects: u64,
+    pub unattributed_size: u64,
+}
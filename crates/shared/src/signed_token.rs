@@ -1,4 +1,6 @@
-use jsonwebtoken::{decode, encode, Algorithm, DecodingKey, EncodingKey, Header, Validation};
+use jsonwebtoken::{
+    decode, decode_header, encode, Algorithm, DecodingKey, EncodingKey, Header, Validation,
+};
 use serde::de::DeserializeOwned;
 use std::fmt::Display;
 use thiserror::Error;
@@ -46,6 +48,44 @@ pub fn verify_and_decode_jwt<T: DeserializeOwned>(
     Ok(token_data.claims)
 }
 
+/// Signs with a server-managed key pair, naming it in the `kid` header so
+/// verifiers can pick the key from the published key set.
+pub fn sign_jwt_with_key<T>(
+    claims: &T,
+    algorithm: Algorithm,
+    kid: &str,
+    key: &EncodingKey,
+) -> Result<String, SignedTokenError>
+where
+    T: serde::Serialize,
+{
+    encode(
+        &Header {
+            alg: algorithm,
+            kid: Some(kid.to_string()),
+            ..Header::default()
+        },
+        claims,
+        key,
+    )
+    .map_err(|e| SignedTokenError::JWTError(e.to_string()))
+}
+
+pub fn verify_jwt_with_key<T: DeserializeOwned>(
+    token: &str,
+    algorithm: Algorithm,
+    key: &DecodingKey,
+) -> Result<T, SignedTokenError> {
+    let token_data = decode::<T>(token, key, &Validation::new(algorithm))
+        .map_err(|e| SignedTokenError::JWTError(e.to_string()))?;
+    Ok(token_data.claims)
+}
+
+/// The unverified header, to find out how a token was signed.
+pub fn decode_jwt_header(token: &str) -> Result<Header, SignedTokenError> {
+    decode_header(token).map_err(|e| SignedTokenError::JWTError(e.to_string()))
+}
+
 pub fn decode_jwt_unsafe<T: DeserializeOwned>(token: &str) -> Result<T, SignedTokenError> {
     let key = DecodingKey::from_secret(&[]);
     let mut validation = Validation::new(Algorithm::HS256);
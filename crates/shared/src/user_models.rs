@@ -31,6 +31,33 @@ pub struct ApiKeyRequest {
     pub comment: String,
 }
 
+#[derive(Debug, Serialize, Deserialize, ToSchema)]
+#[serde(rename_all = "camelCase")]
+pub struct ProjectApiKeyRequest {
+    pub comment: String,
+    /// Scopes granted to the key, `project:admin` when omitted
+    pub scopes: Option<Vec<String>>,
+    /// Unix timestamp after which the key is rejected
+    pub expires_at: Option<usize>,
+    /// IP addresses or CIDR blocks the key may be used from, any when omitted
+    pub allowed_ips: Option<Vec<String>>,
+}
+
+/// Proof of an OpenID Connect login: either an ID token the client already
+/// obtained, or an authorization code for the server to exchange.
+#[derive(Debug, Serialize, Deserialize, ToSchema, Default)]
+#[serde(rename_all = "camelCase")]
+pub struct OidcLoginRequest {
+    pub id_token: Option<String>,
+    pub code: Option<String>,
+    /// Redirect URI the authorization code was issued for
+    pub redirect_uri: Option<String>,
+    /// PKCE verifier of the authorization request
+    pub code_verifier: Option<String>,
+    /// Nonce of the authorization request, checked against the ID token
+    pub nonce: Option<String>,
+}
+
 #[derive(Debug, Serialize, Deserialize, ToSchema)]
 #[serde(rename_all = "camelCase")]
 pub struct TokenResponse {
@@ -47,6 +74,9 @@ pub struct ApiKeyResponse {
     pub comment: String,
     pub created_at: usize,
     pub project_id: Option<String>,
+    pub scopes: Option<Vec<String>>,
+    pub expires_at: Option<usize>,
+    pub allowed_ips: Option<Vec<String>>,
 }
 
 #[derive(Debug, Serialize, Deserialize, ToSchema)]
@@ -57,6 +87,10 @@ pub struct ApiKeyResponseWithoutSecret {
     pub comment: String,
     pub created_at: usize,
     pub project_id: Option<String>,
+    pub scopes: Option<Vec<String>>,
+    pub expires_at: Option<usize>,
+    pub allowed_ips: Option<Vec<String>>,
+    pub last_used_at: Option<usize>,
 }
 
 impl TokenResponse {
@@ -87,6 +121,143 @@ pub struct UserProfile {
     pub last_name: Option<String>,
     pub organization: Option<String>,
     pub job_role: Option<String>,
+    pub email_verified: bool,
+}
+
+/// Profile fields to change, fields left out keep their value.
+#[derive(Debug, Serialize, Deserialize, ToSchema, Default)]
+#[serde(rename_all = "camelCase")]
+pub struct ProfileUpdateRequest {
+    pub first_name: Option<String>,
+    pub middle_name: Option<String>,
+    pub last_name: Option<String>,
+    pub organization: Option<String>,
+    pub job_role: Option<String>,
+}
+
+#[derive(Debug, Serialize, Deserialize, ToSchema)]
+#[serde(rename_all = "camelCase")]
+pub struct PasswordChangeRequest {
+    /// Not needed for accounts that were created through an identity provider
+    /// and have no password yet
+    pub current_password: Option<String>,
+    pub new_password: String,
+}
+
+#[derive(Debug, Serialize, Deserialize, ToSchema)]
+#[serde(rename_all = "camelCase")]
+pub struct PasswordResetRequest {
+    pub email: String,
+}
+
+#[derive(Debug, Serialize, Deserialize, ToSchema)]
+#[serde(rename_all = "camelCase")]
+pub struct PasswordResetConfirmRequest {
+    pub token: String,
+    pub new_password: String,
+}
+
+#[derive(Debug, Serialize, Deserialize, ToSchema)]
+#[serde(rename_all = "camelCase")]
+pub struct EmailVerificationConfirmRequest {
+    pub token: String,
+}
+
+#[derive(Debug, Serialize, Deserialize, ToSchema, Default)]
+#[serde(rename_all = "camelCase")]
+pub struct AccountDeletionRequest {
+    /// Required for accounts with a password
+    pub password: Option<String>,
+}
+
+/// A signed in device or browser.
+#[derive(Debug, Serialize, Deserialize, ToSchema)]
+#[serde(rename_all = "camelCase")]
+pub struct LoginSessionResponse {
+    pub session_id: String,
+    pub user_agent: Option<String>,
+    pub ip_address: Option<String>,
+    pub created_at: usize,
+    pub last_used_at: Option<usize>,
+    pub expires_at: usize,
+    /// Whether this is the session of the token used for the request
+    pub current: bool,
+}
+
+#[derive(Debug, Default, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct SessionRevocationQuery {
+    /// Also end the session making the request
+    #[serde(default)]
+    pub include_current: bool,
+}
+
+/// Second step of a login for users with two-factor authentication.
+#[derive(Debug, Serialize, Deserialize, ToSchema)]
+#[serde(rename_all = "camelCase")]
+pub struct MfaLoginRequest {
+    pub mfa_token: String,
+    /// A code from the authenticator app or an unused recovery code
+    pub code: String,
+}
+
+#[derive(Debug, Serialize, Deserialize, ToSchema)]
+#[serde(rename_all = "camelCase")]
+pub struct MfaCodeRequest {
+    /// A code from the authenticator app or, except when confirming an
+    /// enrollment, an unused recovery code
+    pub code: String,
+}
+
+/// Returned by a login when the password was right and a second factor is
+/// needed, see [`MfaLoginRequest`].
+#[derive(Debug, Serialize, Deserialize, ToSchema)]
+#[serde(rename_all = "camelCase")]
+pub struct MfaChallengeResponse {
+    pub mfa_token: String,
+    pub token_type: String,
+    /// Seconds the `mfa_token` is valid for
+    pub expires_in: usize,
+}
+
+impl MfaChallengeResponse {
+    pub fn new(mfa_token: String, expires_in: usize) -> Self {
+        Self {
+            mfa_token,
+            token_type: "MfaPending".to_string(),
+            expires_in,
+        }
+    }
+}
+
+#[derive(Debug, Serialize, Deserialize, ToSchema)]
+#[serde(untagged)]
+pub enum LoginResponse {
+    Tokens(TokenResponse),
+    MfaRequired(MfaChallengeResponse),
+}
+
+#[derive(Debug, Serialize, Deserialize, ToSchema)]
+#[serde(rename_all = "camelCase")]
+pub struct TotpEnrollmentResponse {
+    /// Base32 secret for apps that can not scan the QR code
+    pub secret: String,
+    /// `otpauth://` URI to show as a QR code
+    pub provisioning_uri: String,
+}
+
+#[derive(Debug, Serialize, Deserialize, ToSchema)]
+#[serde(rename_all = "camelCase")]
+pub struct RecoveryCodesResponse {
+    /// Single use codes, shown only once
+    pub recovery_codes: Vec<String>,
+}
+
+#[derive(Debug, Serialize, Deserialize, ToSchema)]
+#[serde(rename_all = "camelCase")]
+pub struct MfaStatusResponse {
+    pub totp_enabled: bool,
+    pub recovery_codes_remaining: usize,
 }
 
 #[derive(Debug, Serialize, Deserialize, ToSchema)]
@@ -94,15 +265,59 @@ pub struct UserProfile {
 pub struct ProjectRequest {
     pub name: String,
     pub description: Option<String>,
+    /// LiveKit settings, may be omitted when `livekit_credential_id` is set
+    #[serde(default)]
     pub livekit_server_url: String,
+    #[serde(default)]
     pub livekit_server_api_key: String,
+    #[serde(default)]
     pub livekit_server_api_secret: String,
+    /// Storage settings, may be omitted when `storage_credential_id` is set
+    #[serde(default)]
     pub storage_type: String,
+    #[serde(default)]
     pub bucket_name: String,
+    #[serde(default)]
     pub endpoint: String,
+    #[serde(default)]
     pub access_key: String,
+    #[serde(default)]
     pub secret_key: String,
     pub region: Option<String>,
+    /// Organization owning the project
+    pub organization_id: Option<String>,
+    /// Organization LiveKit credentials to use instead of the fields above
+    pub livekit_credential_id: Option<String>,
+    /// Organization storage credentials to use instead of the fields above
+    pub storage_credential_id: Option<String>,
+}
+
+/// Partial update of a project's settings, omitted fields are left unchanged.
+/// Secrets are changed with a [`ProjectSecretsRequest`].
+#[derive(Debug, Default, Serialize, Deserialize, ToSchema)]
+#[serde(rename_all = "camelCase")]
+pub struct ProjectUpdateRequest {
+    /// Must match the current name, projects cannot be renamed
+    pub name: Option<String>,
+    pub description: Option<String>,
+    pub livekit_server_url: Option<String>,
+    pub bucket_name: Option<String>,
+    pub endpoint: Option<String>,
+    pub region: Option<String>,
+    /// Switches the project to these organization LiveKit credentials
+    pub livekit_credential_id: Option<String>,
+    /// Switches the project to these organization storage credentials
+    pub storage_credential_id: Option<String>,
+}
+
+/// Rotates a project's own secrets. Keys and secrets are replaced in pairs.
+#[derive(Debug, Default, Serialize, Deserialize, ToSchema)]
+#[serde(rename_all = "camelCase")]
+pub struct ProjectSecretsRequest {
+    pub livekit_server_api_key: Option<String>,
+    pub livekit_server_api_secret: Option<String>,
+    pub access_key: Option<String>,
+    pub secret_key: Option<String>,
 }
 
 #[derive(Debug, Serialize, Deserialize, ToSchema)]
@@ -116,4 +331,7 @@ pub struct ProjectInfo {
     pub bucket_name: String,
     pub endpoint: String,
     pub last_updated: usize,
+    pub organization_id: Option<String>,
+    pub livekit_credential_id: Option<String>,
+    pub storage_credential_id: Option<String>,
 }